@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+UPDATE users
+SET phone_number = NULL
+WHERE phone_number IS NOT NULL AND phone_number NOT LIKE '+91%';
+
+UPDATE users
+SET phone_number = substring(phone_number FROM 4)
+WHERE phone_number LIKE '+91%';
+
+ALTER TABLE users
+DROP COLUMN phone_country,
+ALTER COLUMN phone_number TYPE VARCHAR(10);
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+ALTER TABLE users
+ALTER COLUMN phone_number TYPE VARCHAR(16),
+ADD COLUMN phone_country VARCHAR(2);
+
+-- Numbers stored so far were parsed as Indian national numbers, written with
+-- or without separators and the 0 trunk prefix
+UPDATE users
+SET phone_number = regexp_replace(regexp_replace(phone_number, '[^0-9]', '', 'g'), '^0', '')
+WHERE phone_number IS NOT NULL;
+
+-- Indian national numbers are 10 digits and never start with 0 or 1
+UPDATE users
+SET phone_number = '+91' || phone_number,
+    phone_country = 'IN'
+WHERE phone_number ~ '^[2-9][0-9]{9}$';
+
+-- Anything else can't be normalized to E.164 so users have to enter it again
+UPDATE users
+SET phone_number = NULL
+WHERE phone_number IS NOT NULL AND phone_country IS NULL;
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE item_attributes;
+DROP TABLE inventory_categories;
+DROP TABLE categories;
+
+ALTER TABLE inventory
+DROP COLUMN sku,
+DROP COLUMN slug,
+DROP COLUMN description,
+DROP COLUMN brand;
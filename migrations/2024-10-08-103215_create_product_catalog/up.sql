@@ -0,0 +1,42 @@
+-- Your SQL goes here
+ALTER TABLE inventory
+ADD COLUMN sku text,
+ADD COLUMN slug text,
+ADD COLUMN description text,
+ADD COLUMN brand text;
+
+-- Items created before the catalog existed are identified by their id
+UPDATE inventory
+SET sku = item_id::text,
+    slug = item_id::text;
+
+ALTER TABLE inventory
+ALTER COLUMN sku SET NOT NULL,
+ALTER COLUMN slug SET NOT NULL,
+ADD CONSTRAINT inventory_sku_key UNIQUE (sku),
+ADD CONSTRAINT inventory_slug_key UNIQUE (slug);
+
+CREATE TABLE categories(
+    category_id uuid PRIMARY KEY,
+    parent_id uuid,
+    name text NOT NULL,
+    slug text UNIQUE NOT NULL,
+    FOREIGN KEY(parent_id) REFERENCES categories(category_id) ON DELETE CASCADE,
+    CHECK (parent_id <> category_id)
+);
+
+CREATE TABLE inventory_categories(
+    item_id uuid NOT NULL,
+    category_id uuid NOT NULL,
+    PRIMARY KEY(item_id, category_id),
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    FOREIGN KEY(category_id) REFERENCES categories(category_id) ON DELETE CASCADE
+);
+
+CREATE TABLE item_attributes(
+    item_id uuid NOT NULL,
+    key text NOT NULL,
+    value text NOT NULL,
+    PRIMARY KEY(item_id, key),
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE order_items
+DROP COLUMN variant_id;
+
+DROP TABLE variant_options;
+DROP TABLE product_variants;
@@ -0,0 +1,37 @@
+-- Your SQL goes here
+CREATE TABLE product_variants(
+    variant_id uuid PRIMARY KEY,
+    item_id uuid NOT NULL,
+    sku text UNIQUE NOT NULL,
+    name text NOT NULL,
+    price float,
+    amount integer NOT NULL DEFAULT 0,
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    CHECK (amount >= 0)
+);
+
+CREATE TABLE variant_options(
+    variant_id uuid NOT NULL,
+    name text NOT NULL,
+    value text NOT NULL,
+    PRIMARY KEY(variant_id, name),
+    FOREIGN KEY(variant_id) REFERENCES product_variants(variant_id) ON DELETE CASCADE
+);
+
+-- Every existing item gets a default variant holding its stock
+INSERT INTO product_variants (variant_id, item_id, sku, name, price, amount)
+SELECT gen_random_uuid(), item_id, sku, 'Default', NULL, COALESCE(amount, 0)
+FROM inventory;
+
+ALTER TABLE order_items
+ADD COLUMN variant_id uuid;
+
+UPDATE order_items
+SET variant_id = product_variants.variant_id
+FROM product_variants
+WHERE product_variants.item_id = order_items.item_id;
+
+ALTER TABLE order_items
+ALTER COLUMN variant_id SET NOT NULL,
+ADD CONSTRAINT order_items_variant_id_fkey
+    FOREIGN KEY (variant_id) REFERENCES product_variants(variant_id);
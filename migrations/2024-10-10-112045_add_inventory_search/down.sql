@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX inventory_created_at_idx;
+DROP INDEX inventory_price_idx;
+DROP INDEX inventory_search_vector_idx;
+
+ALTER TABLE inventory
+DROP COLUMN search_vector,
+DROP COLUMN created_at;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE inventory
+ADD COLUMN created_at timestamptz NOT NULL DEFAULT now(),
+ADD COLUMN search_vector tsvector NOT NULL GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', name), 'A') ||
+    setweight(to_tsvector('english', coalesce(description, '')), 'B')
+) STORED;
+
+CREATE INDEX inventory_search_vector_idx ON inventory USING GIN (search_vector);
+CREATE INDEX inventory_price_idx ON inventory (price);
+CREATE INDEX inventory_created_at_idx ON inventory (created_at, item_id);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX orders_user_id_order_date_idx;
+DROP INDEX orders_order_date_idx;
+
+ALTER TABLE orders
+ALTER COLUMN order_date DROP NOT NULL,
+ALTER COLUMN order_date DROP DEFAULT;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+UPDATE orders SET order_date = now() WHERE order_date IS NULL;
+
+ALTER TABLE orders
+ALTER COLUMN order_date SET DEFAULT now(),
+ALTER COLUMN order_date SET NOT NULL;
+
+CREATE INDEX orders_order_date_idx ON orders (order_date, order_id);
+CREATE INDEX orders_user_id_order_date_idx ON orders (user_id, order_date, order_id);
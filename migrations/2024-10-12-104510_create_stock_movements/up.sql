@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE stock_movements(
+    movement_id uuid PRIMARY KEY,
+    item_id uuid NOT NULL,
+    variant_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    reason text NOT NULL,
+    reference_id uuid,
+    actor_id uuid,
+    note text,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    FOREIGN KEY(variant_id) REFERENCES product_variants(variant_id) ON DELETE CASCADE,
+    FOREIGN KEY(actor_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    CHECK (quantity <> 0),
+    CHECK (reason IN ('order', 'cancellation', 'restock', 'adjustment', 'return'))
+);
+
+CREATE INDEX stock_movements_item_id_idx ON stock_movements (item_id, created_at, movement_id);
+CREATE INDEX stock_movements_variant_id_idx ON stock_movements (variant_id);
+
+-- Stock held before the ledger existed is recorded as an opening balance
+INSERT INTO stock_movements (movement_id, item_id, variant_id, quantity, reason, note)
+SELECT gen_random_uuid(), item_id, variant_id, amount, 'adjustment', 'Opening balance'
+FROM product_variants
+WHERE amount <> 0;
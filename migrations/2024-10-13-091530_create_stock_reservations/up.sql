@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE stock_reservations(
+    reservation_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL,
+    item_id uuid NOT NULL,
+    variant_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    status text NOT NULL DEFAULT 'active',
+    order_id uuid,
+    expires_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    FOREIGN KEY(variant_id) REFERENCES product_variants(variant_id) ON DELETE CASCADE,
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE SET NULL,
+    CHECK (quantity > 0),
+    CHECK (status IN ('active', 'committed', 'released', 'expired'))
+);
+
+-- Only active holds are looked up by variant or swept on expiry
+CREATE INDEX stock_reservations_active_variant_idx ON stock_reservations (variant_id) WHERE status = 'active';
+CREATE INDEX stock_reservations_active_expires_at_idx ON stock_reservations (expires_at) WHERE status = 'active';
+CREATE INDEX stock_reservations_user_id_idx ON stock_reservations (user_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX inventory_reorder_threshold_idx;
+
+ALTER TABLE inventory
+    DROP COLUMN low_stock_alerted_at,
+    DROP COLUMN reorder_threshold;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE inventory
+    ADD COLUMN reorder_threshold integer CHECK (reorder_threshold >= 0),
+    ADD COLUMN low_stock_alerted_at timestamptz;
+
+-- Items at or below their threshold are found by the low stock job
+CREATE INDEX inventory_reorder_threshold_idx ON inventory (sku) WHERE reorder_threshold IS NOT NULL;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE stock_movements DROP COLUMN warehouse_id;
+
+DROP TABLE order_item_allocations;
+DROP TABLE warehouse_stock;
+DROP TABLE warehouses;
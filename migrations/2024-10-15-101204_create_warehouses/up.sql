@@ -0,0 +1,57 @@
+-- Your SQL goes here
+CREATE TABLE warehouses(
+    warehouse_id uuid PRIMARY KEY,
+    code text NOT NULL UNIQUE,
+    name text NOT NULL,
+    -- Lower values are allocated from first
+    priority integer NOT NULL DEFAULT 0,
+    latitude double precision,
+    longitude double precision,
+    is_default boolean NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (latitude BETWEEN -90 AND 90),
+    CHECK (longitude BETWEEN -180 AND 180)
+);
+
+-- Stock without a location (initial stock, imports) is held by the default warehouse
+CREATE UNIQUE INDEX warehouses_is_default_idx ON warehouses (is_default) WHERE is_default;
+
+CREATE TABLE warehouse_stock(
+    warehouse_id uuid NOT NULL,
+    variant_id uuid NOT NULL,
+    amount integer NOT NULL DEFAULT 0,
+    PRIMARY KEY(warehouse_id, variant_id),
+    FOREIGN KEY(warehouse_id) REFERENCES warehouses(warehouse_id) ON DELETE CASCADE,
+    FOREIGN KEY(variant_id) REFERENCES product_variants(variant_id) ON DELETE CASCADE,
+    CHECK (amount >= 0)
+);
+
+CREATE INDEX warehouse_stock_variant_id_idx ON warehouse_stock (variant_id);
+
+CREATE TABLE order_item_allocations(
+    allocation_id uuid PRIMARY KEY,
+    order_item_id uuid NOT NULL,
+    warehouse_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    FOREIGN KEY(order_item_id) REFERENCES order_items(order_item_id) ON DELETE CASCADE,
+    FOREIGN KEY(warehouse_id) REFERENCES warehouses(warehouse_id),
+    CHECK (quantity > 0)
+);
+
+CREATE INDEX order_item_allocations_order_item_id_idx ON order_item_allocations (order_item_id);
+
+ALTER TABLE stock_movements
+    ADD COLUMN warehouse_id uuid REFERENCES warehouses(warehouse_id) ON DELETE SET NULL;
+
+-- Stock held before warehouses existed is moved to the default warehouse
+INSERT INTO warehouses (warehouse_id, code, name, is_default)
+VALUES (gen_random_uuid(), 'MAIN', 'Main warehouse', true);
+
+INSERT INTO warehouse_stock (warehouse_id, variant_id, amount)
+SELECT warehouses.warehouse_id, product_variants.variant_id, product_variants.amount
+FROM product_variants
+CROSS JOIN warehouses
+WHERE warehouses.is_default AND product_variants.amount > 0;
+
+UPDATE stock_movements
+SET warehouse_id = (SELECT warehouse_id FROM warehouses WHERE is_default);
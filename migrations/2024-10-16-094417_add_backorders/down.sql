@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX order_items_backordered_idx;
+
+ALTER TABLE order_items
+    DROP COLUMN backordered_quantity;
+
+ALTER TABLE inventory
+    DROP COLUMN backorder_limit,
+    DROP COLUMN backorder_policy;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE inventory
+    ADD COLUMN backorder_policy text NOT NULL DEFAULT 'none'
+        CHECK (backorder_policy IN ('none', 'backorder', 'preorder')),
+    ADD COLUMN backorder_limit integer CHECK (backorder_limit >= 0);
+
+-- Part of an order line waiting for stock to arrive
+ALTER TABLE order_items
+    ADD COLUMN backordered_quantity integer NOT NULL DEFAULT 0
+        CHECK (backordered_quantity >= 0 AND backordered_quantity <= quantity);
+
+CREATE INDEX order_items_backordered_idx ON order_items (variant_id) WHERE backordered_quantity > 0;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE payments;
+
+ALTER TABLE orders DROP COLUMN paid_at;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE payments(
+    payment_id uuid PRIMARY KEY,
+    order_id uuid NOT NULL,
+    provider text NOT NULL,
+    provider_reference text NOT NULL UNIQUE,
+    amount_cents bigint NOT NULL,
+    currency text NOT NULL,
+    status text NOT NULL,
+    failure_reason text,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    captured_at timestamptz,
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE RESTRICT,
+    CHECK (amount_cents >= 0),
+    CHECK (status IN ('requires_payment', 'authorized', 'captured', 'failed'))
+);
+
+-- An order has at most one payment in flight or captured
+CREATE UNIQUE INDEX payments_open_order_idx ON payments (order_id)
+    WHERE status IN ('requires_payment', 'authorized', 'captured');
+
+-- Set once payment of the order is captured, paid orders can't be deleted
+ALTER TABLE orders ADD COLUMN paid_at timestamptz;
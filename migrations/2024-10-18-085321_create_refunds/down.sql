@@ -0,0 +1,23 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE refund_lines;
+DROP TABLE refunds;
+
+DROP INDEX payments_open_order_idx;
+CREATE UNIQUE INDEX payments_open_order_idx ON payments (order_id)
+    WHERE status IN ('requires_payment', 'authorized', 'captured');
+
+ALTER TABLE payments
+    DROP CONSTRAINT payments_status_check,
+    ADD CONSTRAINT payments_status_check
+        CHECK (status IN ('requires_payment', 'authorized', 'captured', 'failed')),
+    DROP CONSTRAINT payments_refunded_cents_check,
+    DROP COLUMN refunded_cents;
+
+UPDATE orders SET status = 'delivered' WHERE status = 'refunded';
+ALTER TABLE orders
+    DROP CONSTRAINT orders_status_check,
+    ADD CONSTRAINT orders_status_check CHECK (status IN ('pending', 'shipped', 'delivered'));
+
+ALTER TABLE order_items
+    DROP COLUMN refunded_quantity,
+    DROP COLUMN unit_price_cents;
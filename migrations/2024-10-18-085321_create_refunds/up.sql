@@ -0,0 +1,66 @@
+-- Your SQL goes here
+-- Price of an order line is fixed when ordered so totals and refunds don't move with catalog prices
+ALTER TABLE order_items
+    ADD COLUMN unit_price_cents bigint NOT NULL DEFAULT 0 CHECK (unit_price_cents >= 0),
+    ADD COLUMN refunded_quantity integer NOT NULL DEFAULT 0
+        CHECK (refunded_quantity >= 0 AND refunded_quantity <= quantity);
+
+UPDATE order_items
+SET unit_price_cents = round(coalesce(product_variants.price, inventory.price, 0) * 100)
+FROM product_variants, inventory
+WHERE product_variants.variant_id = order_items.variant_id
+    AND inventory.item_id = order_items.item_id;
+
+ALTER TABLE payments
+    ADD COLUMN refunded_cents bigint NOT NULL DEFAULT 0,
+    ADD CONSTRAINT payments_refunded_cents_check CHECK (refunded_cents >= 0 AND refunded_cents <= amount_cents),
+    DROP CONSTRAINT payments_status_check,
+    ADD CONSTRAINT payments_status_check
+        CHECK (status IN ('requires_payment', 'authorized', 'captured', 'failed', 'partially_refunded', 'refunded'));
+
+-- Orders whose every line was refunded end up refunded
+ALTER TABLE orders
+    DROP CONSTRAINT orders_status_check,
+    ADD CONSTRAINT orders_status_check CHECK (status IN ('pending', 'shipped', 'delivered', 'refunded'));
+
+DROP INDEX payments_open_order_idx;
+CREATE UNIQUE INDEX payments_open_order_idx ON payments (order_id) WHERE status <> 'failed';
+
+-- Money already given back is kept on record, so orders with refunds can't be deleted
+CREATE TABLE refunds(
+    refund_id uuid PRIMARY KEY,
+    order_id uuid NOT NULL,
+    payment_id uuid NOT NULL,
+    status text NOT NULL,
+    amount_cents bigint NOT NULL,
+    restock boolean NOT NULL,
+    reason text,
+    provider_reference text UNIQUE,
+    actor_id uuid,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE RESTRICT,
+    FOREIGN KEY(payment_id) REFERENCES payments(payment_id) ON DELETE RESTRICT,
+    FOREIGN KEY(actor_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    CHECK (amount_cents > 0),
+    CHECK (status IN ('pending', 'succeeded', 'failed'))
+);
+
+CREATE INDEX refunds_order_id_idx ON refunds (order_id, created_at);
+
+-- Quantity of an order line given back by a refund
+-- Unpicked quantity came off the line's backorder rather than from shipped stock
+CREATE TABLE refund_lines(
+    refund_line_id uuid PRIMARY KEY,
+    refund_id uuid NOT NULL,
+    order_item_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    unpicked_quantity integer NOT NULL,
+    amount_cents bigint NOT NULL,
+    FOREIGN KEY(refund_id) REFERENCES refunds(refund_id) ON DELETE CASCADE,
+    FOREIGN KEY(order_item_id) REFERENCES order_items(order_item_id) ON DELETE RESTRICT,
+    CHECK (quantity > 0),
+    CHECK (unpicked_quantity >= 0 AND unpicked_quantity <= quantity),
+    CHECK (amount_cents >= 0)
+);
+
+CREATE INDEX refund_lines_refund_id_idx ON refund_lines (refund_id);
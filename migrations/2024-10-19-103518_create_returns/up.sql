@@ -0,0 +1,37 @@
+-- Your SQL goes here
+-- Return merchandise authorization requested by a customer for lines of a delivered order
+CREATE TABLE returns(
+    return_id uuid PRIMARY KEY,
+    order_id uuid NOT NULL,
+    user_id uuid,
+    status text NOT NULL DEFAULT 'requested',
+    note text,
+    -- Refund issued for the received goods
+    refund_id uuid UNIQUE,
+    actor_id uuid,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    FOREIGN KEY(refund_id) REFERENCES refunds(refund_id) ON DELETE SET NULL,
+    FOREIGN KEY(actor_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    CHECK (status IN ('requested', 'approved', 'rejected', 'received'))
+);
+
+CREATE INDEX returns_order_id_idx ON returns (order_id, created_at);
+CREATE INDEX returns_status_idx ON returns (status, created_at);
+
+CREATE TABLE return_lines(
+    return_line_id uuid PRIMARY KEY,
+    return_id uuid NOT NULL,
+    order_item_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    reason text NOT NULL,
+    FOREIGN KEY(return_id) REFERENCES returns(return_id) ON DELETE CASCADE,
+    FOREIGN KEY(order_item_id) REFERENCES order_items(order_item_id) ON DELETE CASCADE,
+    CHECK (quantity > 0),
+    CHECK (reason IN ('damaged', 'defective', 'wrong_item', 'not_as_described', 'no_longer_needed', 'other'))
+);
+
+CREATE INDEX return_lines_return_id_idx ON return_lines (return_id);
+CREATE INDEX return_lines_order_item_id_idx ON return_lines (order_item_id);
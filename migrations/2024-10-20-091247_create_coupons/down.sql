@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders
+    DROP COLUMN discount_cents,
+    DROP COLUMN coupon_id;
+
+DROP TABLE coupon_redemptions;
+DROP TABLE coupon_categories;
+DROP TABLE coupon_items;
+DROP TABLE coupons;
@@ -0,0 +1,60 @@
+-- Your SQL goes here
+-- Discount codes customers enter when ordering
+-- Percentage coupons take discount_value percent off, fixed ones take discount_value cents off
+CREATE TABLE coupons(
+    coupon_id uuid PRIMARY KEY,
+    code text NOT NULL UNIQUE,
+    discount_type text NOT NULL,
+    discount_value bigint NOT NULL,
+    -- Order subtotal in cents needed before the coupon applies
+    min_order_cents bigint,
+    usage_limit integer,
+    per_user_limit integer,
+    starts_at timestamptz,
+    ends_at timestamptz,
+    active boolean NOT NULL DEFAULT true,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (discount_type IN ('percentage', 'fixed')),
+    CHECK (discount_value > 0),
+    CHECK (discount_type <> 'percentage' OR discount_value <= 100),
+    CHECK (min_order_cents >= 0),
+    CHECK (usage_limit > 0),
+    CHECK (per_user_limit > 0),
+    CHECK (starts_at < ends_at)
+);
+
+-- Coupons with restrictions only discount lines of listed items or items in listed categories
+CREATE TABLE coupon_items(
+    coupon_id uuid NOT NULL,
+    item_id uuid NOT NULL,
+    PRIMARY KEY(coupon_id, item_id),
+    FOREIGN KEY(coupon_id) REFERENCES coupons(coupon_id) ON DELETE CASCADE,
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE
+);
+
+CREATE TABLE coupon_categories(
+    coupon_id uuid NOT NULL,
+    category_id uuid NOT NULL,
+    PRIMARY KEY(coupon_id, category_id),
+    FOREIGN KEY(coupon_id) REFERENCES coupons(coupon_id) ON DELETE CASCADE,
+    FOREIGN KEY(category_id) REFERENCES categories(category_id) ON DELETE CASCADE
+);
+
+CREATE TABLE coupon_redemptions(
+    redemption_id uuid PRIMARY KEY,
+    coupon_id uuid NOT NULL,
+    order_id uuid NOT NULL UNIQUE,
+    user_id uuid,
+    discount_cents bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(coupon_id) REFERENCES coupons(coupon_id) ON DELETE CASCADE,
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE SET NULL
+);
+
+CREATE INDEX coupon_redemptions_coupon_id_idx ON coupon_redemptions (coupon_id, user_id);
+
+ALTER TABLE orders
+    ADD COLUMN coupon_id uuid REFERENCES coupons(coupon_id) ON DELETE SET NULL,
+    ADD COLUMN discount_cents bigint NOT NULL DEFAULT 0 CHECK (discount_cents >= 0);
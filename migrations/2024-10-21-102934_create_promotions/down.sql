@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders
+    DROP COLUMN promotion_discount_cents;
+
+DROP TABLE order_promotions;
+DROP TABLE promotion_tiers;
+DROP TABLE promotion_items;
+DROP TABLE promotions;
@@ -0,0 +1,61 @@
+-- Your SQL goes here
+-- Promotions apply to orders without a code, lower priority values are evaluated first
+-- and units discounted by one promotion aren't counted by later ones
+CREATE TABLE promotions(
+    promotion_id uuid PRIMARY KEY,
+    name text NOT NULL,
+    kind text NOT NULL,
+    priority integer NOT NULL DEFAULT 0,
+    active boolean NOT NULL DEFAULT true,
+    starts_at timestamptz,
+    ends_at timestamptz,
+    -- Buy buy_quantity get get_quantity units percent_off
+    buy_quantity integer,
+    get_quantity integer,
+    percent_off integer,
+    -- Price of a full set of bundle items
+    bundle_price_cents bigint,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (kind IN ('buy_x_get_y', 'tiered', 'bundle')),
+    CHECK (kind <> 'buy_x_get_y' OR (buy_quantity > 0 AND get_quantity > 0 AND percent_off BETWEEN 1 AND 100)),
+    CHECK (kind <> 'bundle' OR bundle_price_cents >= 0),
+    CHECK (starts_at < ends_at)
+);
+
+-- Items a promotion counts, bundles need quantity of each
+CREATE TABLE promotion_items(
+    promotion_id uuid NOT NULL,
+    item_id uuid NOT NULL,
+    quantity integer NOT NULL DEFAULT 1,
+    PRIMARY KEY(promotion_id, item_id),
+    FOREIGN KEY(promotion_id) REFERENCES promotions(promotion_id) ON DELETE CASCADE,
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    CHECK (quantity > 0)
+);
+
+CREATE TABLE promotion_tiers(
+    promotion_id uuid NOT NULL,
+    min_quantity integer NOT NULL,
+    percent_off integer NOT NULL,
+    PRIMARY KEY(promotion_id, min_quantity),
+    FOREIGN KEY(promotion_id) REFERENCES promotions(promotion_id) ON DELETE CASCADE,
+    CHECK (min_quantity > 0),
+    CHECK (percent_off BETWEEN 1 AND 100)
+);
+
+-- Promotions applied to an order, name is kept in case the promotion is deleted
+CREATE TABLE order_promotions(
+    order_promotion_id uuid PRIMARY KEY,
+    order_id uuid NOT NULL,
+    promotion_id uuid,
+    name text NOT NULL,
+    discount_cents bigint NOT NULL,
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE CASCADE,
+    FOREIGN KEY(promotion_id) REFERENCES promotions(promotion_id) ON DELETE SET NULL,
+    CHECK (discount_cents > 0)
+);
+
+CREATE INDEX order_promotions_order_id_idx ON order_promotions (order_id);
+
+ALTER TABLE orders
+    ADD COLUMN promotion_discount_cents bigint NOT NULL DEFAULT 0 CHECK (promotion_discount_cents >= 0);
@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders
+    DROP COLUMN tax_cents,
+    DROP COLUMN ship_region,
+    DROP COLUMN ship_country;
+
+ALTER TABLE order_items
+    DROP COLUMN tax_cents,
+    DROP COLUMN tax_rate_basis_points,
+    DROP COLUMN discount_cents;
+
+ALTER TABLE inventory
+    DROP COLUMN tax_class;
+
+DROP TABLE tax_rates;
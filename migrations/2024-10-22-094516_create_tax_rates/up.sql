@@ -0,0 +1,43 @@
+-- Your SQL goes here
+-- Rate for a tax class shipped to a country, or to a region within it
+-- Region specific rates take precedence over the country wide one
+CREATE TABLE tax_rates(
+    tax_rate_id uuid PRIMARY KEY,
+    country text NOT NULL,
+    region text,
+    tax_class text NOT NULL,
+    rate_basis_points integer NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (rate_basis_points BETWEEN 0 AND 10000)
+);
+
+CREATE UNIQUE INDEX tax_rates_destination_class_idx ON tax_rates (country, COALESCE(region, ''), tax_class);
+
+ALTER TABLE inventory
+    ADD COLUMN tax_class text NOT NULL DEFAULT 'standard';
+
+-- Discount is what promotions and coupon took off the line, tax is charged on the rest
+ALTER TABLE order_items
+    ADD COLUMN discount_cents bigint NOT NULL DEFAULT 0 CHECK (discount_cents >= 0),
+    ADD COLUMN tax_rate_basis_points integer NOT NULL DEFAULT 0,
+    ADD COLUMN tax_cents bigint NOT NULL DEFAULT 0 CHECK (tax_cents >= 0);
+
+-- Discounts of existing orders are spread over their lines by line subtotal
+UPDATE order_items
+SET discount_cents = order_items.unit_price_cents * order_items.quantity
+    * (orders.discount_cents + orders.promotion_discount_cents) / totals.subtotal_cents
+FROM orders, (
+    SELECT order_id, SUM(unit_price_cents * quantity) AS subtotal_cents
+    FROM order_items
+    GROUP BY order_id
+) AS totals
+WHERE orders.order_id = order_items.order_id
+    AND totals.order_id = order_items.order_id
+    AND totals.subtotal_cents > 0
+    AND orders.discount_cents + orders.promotion_discount_cents > 0;
+
+ALTER TABLE orders
+    ADD COLUMN ship_country text,
+    ADD COLUMN ship_region text,
+    ADD COLUMN tax_cents bigint NOT NULL DEFAULT 0 CHECK (tax_cents >= 0);
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders
+    DROP COLUMN shipping_cents,
+    DROP COLUMN shipping_method_name,
+    DROP COLUMN shipping_method_id;
+
+ALTER TABLE inventory
+    DROP COLUMN weight_grams;
+
+DROP TABLE shipping_method_zones;
+DROP TABLE shipping_methods;
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+-- Rate columns used depend on kind, see domain::shipping::ShippingRate
+CREATE TABLE shipping_methods(
+    shipping_method_id uuid PRIMARY KEY,
+    name text NOT NULL,
+    kind text NOT NULL,
+    rate_cents bigint NOT NULL DEFAULT 0,
+    per_kg_cents bigint,
+    threshold_cents bigint,
+    active boolean NOT NULL DEFAULT true,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (kind IN ('flat_rate', 'weight_based', 'free_over')),
+    CHECK (rate_cents >= 0),
+    CHECK (kind <> 'weight_based' OR per_kg_cents >= 0),
+    CHECK (kind <> 'free_over' OR threshold_cents >= 0)
+);
+
+-- Destinations a method ships to, methods without zones ship everywhere
+-- Zone without region covers whole country
+CREATE TABLE shipping_method_zones(
+    zone_id uuid PRIMARY KEY,
+    shipping_method_id uuid NOT NULL,
+    country text NOT NULL,
+    region text,
+    FOREIGN KEY(shipping_method_id) REFERENCES shipping_methods(shipping_method_id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX shipping_method_zones_destination_idx ON shipping_method_zones (shipping_method_id, country, COALESCE(region, ''));
+
+ALTER TABLE inventory
+    ADD COLUMN weight_grams integer NOT NULL DEFAULT 0 CHECK (weight_grams >= 0);
+
+-- Name and cost are kept in case the method is changed or deleted
+ALTER TABLE orders
+    ADD COLUMN shipping_method_id uuid REFERENCES shipping_methods(shipping_method_id) ON DELETE SET NULL,
+    ADD COLUMN shipping_method_name text,
+    ADD COLUMN shipping_cents bigint NOT NULL DEFAULT 0 CHECK (shipping_cents >= 0);
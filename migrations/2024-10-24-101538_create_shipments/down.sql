@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE shipment_lines;
+DROP TABLE shipments;
+
+UPDATE orders SET status = 'pending' WHERE status = 'partially_shipped';
+
+ALTER TABLE orders
+    DROP CONSTRAINT orders_status_check,
+    ADD CONSTRAINT orders_status_check CHECK (status IN ('pending', 'shipped', 'delivered', 'refunded'));
+
+ALTER TABLE order_items DROP COLUMN shipped_quantity;
@@ -0,0 +1,46 @@
+-- Your SQL goes here
+-- Quantity of an order line handed to a carrier so far
+ALTER TABLE order_items
+    ADD COLUMN shipped_quantity integer NOT NULL DEFAULT 0
+        CHECK (shipped_quantity >= 0 AND shipped_quantity <= quantity);
+
+-- Orders marked shipped before shipments existed count as fully shipped
+UPDATE order_items
+SET shipped_quantity = order_items.quantity - order_items.refunded_quantity
+FROM orders
+WHERE orders.order_id = order_items.order_id
+    AND orders.status IN ('shipped', 'delivered');
+
+-- Orders with only some of their lines shipped are partially shipped
+ALTER TABLE orders
+    DROP CONSTRAINT orders_status_check,
+    ADD CONSTRAINT orders_status_check
+        CHECK (status IN ('pending', 'partially_shipped', 'shipped', 'delivered', 'refunded'));
+
+CREATE TABLE shipments(
+    shipment_id uuid PRIMARY KEY,
+    order_id uuid NOT NULL,
+    carrier text NOT NULL,
+    tracking_number text NOT NULL,
+    actor_id uuid,
+    shipped_at timestamptz NOT NULL DEFAULT now(),
+    FOREIGN KEY(order_id) REFERENCES orders(order_id) ON DELETE CASCADE,
+    FOREIGN KEY(actor_id) REFERENCES users(user_id) ON DELETE SET NULL,
+    CHECK (carrier <> ''),
+    CHECK (tracking_number <> '')
+);
+
+CREATE INDEX shipments_order_id_idx ON shipments (order_id, shipped_at);
+
+-- Quantity of an order line sent with a shipment
+CREATE TABLE shipment_lines(
+    shipment_line_id uuid PRIMARY KEY,
+    shipment_id uuid NOT NULL,
+    order_item_id uuid NOT NULL,
+    quantity integer NOT NULL,
+    FOREIGN KEY(shipment_id) REFERENCES shipments(shipment_id) ON DELETE CASCADE,
+    FOREIGN KEY(order_item_id) REFERENCES order_items(order_item_id) ON DELETE CASCADE,
+    CHECK (quantity > 0)
+);
+
+CREATE INDEX shipment_lines_shipment_id_idx ON shipment_lines (shipment_id);
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE email_suppressions;
+DROP TABLE email_preferences;
+
+UPDATE email_outbox SET status = 'dead' WHERE status = 'suppressed';
+
+ALTER TABLE email_outbox
+    DROP CONSTRAINT email_outbox_status_check,
+    ADD CONSTRAINT email_outbox_status_check CHECK (status IN ('pending', 'sent', 'dead'));
+
+ALTER TABLE email_outbox DROP COLUMN category;
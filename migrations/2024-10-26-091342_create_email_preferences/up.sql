@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- Emails a user can opt out of, those without a category are always sent
+ALTER TABLE email_outbox
+    ADD COLUMN category TEXT CHECK (category IN ('order_updates', 'promotions', 'back_in_stock'));
+
+-- Emails withheld because recipient is suppressed or opted out of their category
+ALTER TABLE email_outbox
+    DROP CONSTRAINT email_outbox_status_check,
+    ADD CONSTRAINT email_outbox_status_check CHECK (status IN ('pending', 'sent', 'dead', 'suppressed'));
+
+-- Categories of emails a user wants, users without a row get all of them
+CREATE TABLE email_preferences(
+    user_id UUID PRIMARY KEY,
+    order_updates BOOLEAN NOT NULL DEFAULT TRUE,
+    promotions BOOLEAN NOT NULL DEFAULT TRUE,
+    back_in_stock BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Addresses nothing is delivered to, stored lowercased
+CREATE TABLE email_suppressions(
+    email TEXT PRIMARY KEY,
+    reason TEXT NOT NULL CHECK (reason IN ('unsubscribed', 'bounced', 'complained', 'manual')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (email = lower(email))
+);
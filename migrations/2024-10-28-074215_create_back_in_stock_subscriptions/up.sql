@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Users waiting to be emailed once an out of stock item is restocked
+CREATE TABLE back_in_stock_subscriptions(
+    subscription_id UUID PRIMARY KEY,
+    item_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY(item_id) REFERENCES inventory(item_id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    UNIQUE (item_id, user_id)
+);
+
+CREATE INDEX back_in_stock_subscriptions_user_id_idx ON back_in_stock_subscriptions (user_id, created_at);
@@ -1,16 +1,25 @@
-use std::u16;
-
 use config::{Config, Environment, File};
+use phonenumber::country;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 
+use crate::domain::allocation::AllocationStrategy;
+
 // Struct to store application config and settings
 #[derive(Deserialize, Debug)]
 pub struct Settings{
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub email: EmailSettings,
-    pub jwt: JWTSettings
+    pub jwt: JWTSettings,
+    pub phone: PhoneSettings,
+    pub reservation: ReservationSettings,
+    pub low_stock: LowStockSettings,
+    pub fulfillment: FulfillmentSettings,
+    pub payment: PaymentSettings,
+    pub email_outbox: EmailOutboxSettings,
+    pub email_templates: EmailTemplateSettings,
+    pub back_in_stock: BackInStockSettings
 }
 
 impl Settings{
@@ -54,9 +63,49 @@ pub struct DatabaseSettings{
 // Settings related to email sending service
 #[derive(Deserialize, Debug)]
 pub struct EmailSettings{
-    pub api_uri: String,
+    #[serde(default)]
+    pub transport: EmailTransportKind,
     pub sender: String,
-    pub key: String
+    // Used by postmark transport
+    pub api_uri: Option<String>,
+    pub key: Option<String>,
+    // Used by smtp transport
+    pub smtp: Option<SmtpSettings>,
+    // Used by file transport, directory emails are written to
+    pub file_directory: Option<String>,
+    // Key one-click unsubscribe links are signed with
+    pub unsubscribe_secret: SecretString
+}
+
+// Transports emails can be sent through
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransportKind{
+    #[default]
+    Postmark,
+    Smtp,
+    File
+}
+
+// Settings related to relaying emails through an SMTP server
+#[derive(Deserialize, Debug)]
+pub struct SmtpSettings{
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+    #[serde(default)]
+    pub tls: SmtpTls
+}
+
+// How connection to SMTP server is secured
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls{
+    None,
+    #[default]
+    StartTls,
+    Tls
 }
 
 // Settings related to JWT
@@ -66,6 +115,76 @@ pub struct JWTSettings{
     pub expiry_hours: u64
 }
 
+// Settings related to phone number parsing
+#[derive(Deserialize, Debug)]
+pub struct PhoneSettings{
+    pub default_region: country::Id
+}
+
+// Settings related to stock reservations placed during checkout
+#[derive(Deserialize, Debug)]
+pub struct ReservationSettings{
+    pub ttl_seconds: i64,
+    pub expiry_interval_seconds: u64
+}
+
+// Settings related to low stock alerts sent to admins
+#[derive(Deserialize, Debug)]
+pub struct LowStockSettings{
+    pub alert_recipients: Vec<String>,
+    pub check_interval_seconds: u64
+}
+
+// Settings related to choosing warehouses orders are shipped from
+#[derive(Deserialize, Debug)]
+pub struct FulfillmentSettings{
+    pub allocation_strategy: AllocationStrategy
+}
+
+// Payment providers orders can be paid through
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentProviderKind{
+    Fake
+}
+
+// Settings related to taking payments for orders
+#[derive(Deserialize, Debug)]
+pub struct PaymentSettings{
+    pub provider: PaymentProviderKind,
+    pub webhook_secret: SecretString,
+    pub currency: String
+}
+
+// Settings related to delivering queued emails from the outbox
+#[derive(Deserialize, Debug)]
+pub struct EmailOutboxSettings{
+    pub poll_interval_milliseconds: u64,
+    pub batch_size: i64,
+    // Emails are dead-lettered after this many failed attempts
+    pub max_attempts: i32,
+    // Delay before the first retry, doubled for every following one up to retry_max_seconds
+    pub retry_base_seconds: i64,
+    pub retry_max_seconds: i64,
+    // How long a claimed email is held back from other workers while it is being sent
+    pub lease_seconds: i64
+}
+
+// Settings related to templates emails are rendered from
+#[derive(Deserialize, Debug)]
+pub struct EmailTemplateSettings{
+    pub directory: String,
+    // Locale used when recipient's isn't known or has no templates
+    pub default_locale: String
+}
+
+// Settings related to emailing subscribers of restocked items
+#[derive(Deserialize, Debug)]
+pub struct BackInStockSettings{
+    pub check_interval_seconds: u64,
+    pub batch_size: i64
+}
+
 impl DatabaseSettings{
     // get database url
     pub fn get_database_url(&self) -> String{
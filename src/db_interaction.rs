@@ -6,3 +6,63 @@ pub use inventory::*;
 
 pub mod orders;
 pub use orders::*;
+
+pub mod category;
+pub use category::*;
+
+pub mod search;
+pub use search::*;
+
+pub mod catalog;
+pub use catalog::*;
+
+pub mod stock;
+pub use stock::*;
+
+pub mod reservations;
+pub use reservations::*;
+
+pub mod low_stock;
+pub use low_stock::*;
+
+pub mod warehouses;
+pub use warehouses::*;
+
+pub mod backorders;
+pub use backorders::*;
+
+pub mod payments;
+pub use payments::*;
+
+pub mod refunds;
+pub use refunds::*;
+
+pub mod returns;
+pub use returns::*;
+
+pub mod coupons;
+pub use coupons::*;
+
+pub mod promotions;
+pub use promotions::*;
+
+pub mod tax;
+pub use tax::*;
+
+pub mod shipping;
+pub use shipping::*;
+
+pub mod shipments;
+pub use shipments::*;
+
+pub mod order_notices;
+pub use order_notices::*;
+
+pub mod outbox;
+pub use outbox::*;
+
+pub mod email_preferences;
+pub use email_preferences::*;
+
+pub mod back_in_stock;
+pub use back_in_stock::*;
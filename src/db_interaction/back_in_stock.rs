@@ -0,0 +1,228 @@
+use std::{error::Error, fmt::Debug};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{enqueue_email, NewEmail}, models::BackInStockSubscription, pagination::{Page, PageRequest}, schema::{back_in_stock_subscriptions, inventory, users}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Subscription whose item is in stock again, along with what its email needs
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct RestockedSubscription {
+    pub subscription_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub sku: String
+}
+
+// Position of a subscription within listing ordered from newest to oldest
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackInStockCursor {
+    pub created_at: DateTime<Utc>,
+    pub subscription_id: Uuid
+}
+
+// Errors associated with back in stock subscriptions
+#[derive(Error)]
+pub enum BackInStockError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid),
+    #[error("item_id: {0} is in stock")]
+    InStockError(Uuid),
+    #[error("Not subscribed to item_id: {0}")]
+    NoSubscriptionError(Uuid)
+}
+
+impl Debug for BackInStockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Subscribe user to an out of stock item, subscribing again keeps the existing subscription
+#[tracing::instrument(
+    "Subscribing to back in stock email",
+    skip(conn)
+)]
+pub async fn subscribe_back_in_stock(
+    mut conn: DbConnection,
+    user_id: Uuid,
+    item_id: Uuid
+) -> Result<BackInStockSubscription, BackInStockError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, BackInStockError, _>(|conn| {
+            // Lock keeps a concurrent restock from being missed by the notification job
+            let amount = inventory::table
+                .find(item_id)
+                .select(inventory::amount)
+                .for_update()
+                .get_result::<Option<i32>>(conn)
+                .optional()?
+                .ok_or(BackInStockError::NoItemIdError(item_id))?;
+
+            if amount.unwrap_or(0) > 0 {
+                return Err(BackInStockError::InStockError(item_id));
+            }
+
+            diesel::insert_into(back_in_stock_subscriptions::table)
+                .values(BackInStockSubscription {
+                    subscription_id: Uuid::new_v4(),
+                    item_id,
+                    user_id,
+                    created_at: Utc::now()
+                })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            let subscription = back_in_stock_subscriptions::table
+                .filter(back_in_stock_subscriptions::item_id.eq(item_id))
+                .filter(back_in_stock_subscriptions::user_id.eq(user_id))
+                .get_result::<BackInStockSubscription>(conn)?;
+
+            Ok(subscription)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Unsubscribing from back in stock email",
+    skip(conn)
+)]
+pub async fn unsubscribe_back_in_stock(
+    mut conn: DbConnection,
+    user_id: Uuid,
+    item_id: Uuid
+) -> Result<(), BackInStockError> {
+    spawn_blocking_with_tracing(move || {
+        let deleted = diesel::delete(back_in_stock_subscriptions::table)
+            .filter(back_in_stock_subscriptions::item_id.eq(item_id))
+            .filter(back_in_stock_subscriptions::user_id.eq(user_id))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(BackInStockError::NoSubscriptionError(item_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting back in stock subscriptions of user",
+    skip(conn)
+)]
+pub async fn get_back_in_stock_subscriptions(
+    mut conn: DbConnection,
+    user_id: Uuid,
+    page_request: PageRequest<BackInStockCursor>
+) -> Result<Page<BackInStockSubscription>, BackInStockError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = back_in_stock_subscriptions::table
+            .filter(back_in_stock_subscriptions::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = back_in_stock_subscriptions::table
+            .filter(back_in_stock_subscriptions::user_id.eq(user_id))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                back_in_stock_subscriptions::created_at.lt(after.created_at)
+                    .or(back_in_stock_subscriptions::created_at.eq(after.created_at).and(back_in_stock_subscriptions::subscription_id.lt(after.subscription_id)))
+            );
+        }
+
+        let rows = query
+            .order((back_in_stock_subscriptions::created_at.desc(), back_in_stock_subscriptions::subscription_id.desc()))
+            .limit(page_request.fetch_limit())
+            .load::<BackInStockSubscription>(&mut conn)?;
+
+        Ok::<_, BackInStockError>(Page::new(rows, page_request.limit, total, |subscription| BackInStockCursor {
+            created_at: subscription.created_at,
+            subscription_id: subscription.subscription_id
+        }))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Users can only subscribe while an item is out of stock, so stock above zero means it was restocked since
+#[tracing::instrument(
+    "Getting subscriptions of restocked items",
+    skip(conn)
+)]
+pub async fn get_restocked_subscriptions(
+    mut conn: DbConnection,
+    limit: i64
+) -> Result<Vec<RestockedSubscription>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        back_in_stock_subscriptions::table
+            .inner_join(inventory::table)
+            .inner_join(users::table)
+            .filter(inventory::amount.gt(0))
+            .select((
+                back_in_stock_subscriptions::subscription_id,
+                users::email,
+                inventory::name,
+                inventory::sku
+            ))
+            .order((back_in_stock_subscriptions::created_at, back_in_stock_subscriptions::subscription_id))
+            .limit(limit)
+            .load::<RestockedSubscription>(&mut conn)
+    })
+    .await
+    .context("Failed due to threadpool error")?
+    .context("Failed to get subscriptions of restocked items")?;
+
+    Ok(res)
+}
+
+// Queue email of each subscription and clear it in the same transaction
+// Subscriptions already cleared, e.g. by another worker or the user, are skipped so nobody is emailed twice
+#[tracing::instrument(
+    "Queueing back in stock emails",
+    skip(conn, notifications)
+)]
+pub async fn queue_back_in_stock_emails(
+    mut conn: DbConnection,
+    notifications: Vec<(Uuid, NewEmail)>
+) -> Result<usize, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let mut queued = 0;
+
+            for (subscription_id, email) in notifications {
+                let deleted = diesel::delete(back_in_stock_subscriptions::table.find(subscription_id))
+                    .execute(conn)?;
+
+                if deleted == 1 {
+                    enqueue_email(conn, email)?;
+                    queued += 1;
+                }
+            }
+
+            Ok(queued)
+        })
+    })
+    .await
+    .context("Failed due to threadpool error")?
+    .context("Failed to queue back in stock emails")?;
+
+    Ok(res)
+}
@@ -0,0 +1,278 @@
+use std::{error::Error, fmt::Debug};
+
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{adjust_variant_stock, allocate_order_lines, enqueue_email, reserved_quantities, NewEmail, StockChange, StockMovementReason}, domain::allocation::AllocationStrategy, email_templates::EmailTemplateError, models::OrderItemAllocation, schema::{inventory, order_item_allocations, order_items, orders, product_variants, users}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing whether an item can be ordered beyond its stock
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackorderPolicy {
+    // Lines beyond stock are dropped from the order
+    None,
+    // Lines beyond stock wait for a restock
+    Backorder,
+    // Item is ordered ahead of its launch and waits for first stock
+    // No release date is kept, launch is simply the first restock, so lines are
+    // held and filled exactly like backorders and only customer emails word it differently
+    Preorder
+}
+
+impl BackorderPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackorderPolicy::None => "none",
+            BackorderPolicy::Backorder => "backorder",
+            BackorderPolicy::Preorder => "preorder"
+        }
+    }
+}
+
+// Struct representing part of a backordered order line which got stock
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilledBackorder {
+    pub order_id: Uuid,
+    pub email: String,
+    pub item_name: String,
+    pub sku: String,
+    pub policy: String,
+    pub quantity: i32,
+    // Quantity of line still waiting for stock
+    pub remaining: i32
+}
+
+// Errors associated with backorder policy of inventory items
+#[derive(Error)]
+pub enum BackorderError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for BackorderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Setting backorder policy of inventory item",
+    skip(conn)
+)]
+pub async fn set_backorder_policy(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    policy: BackorderPolicy,
+    backorder_limit: Option<i32>
+) -> Result<(), BackorderError> {
+    spawn_blocking_with_tracing(move || {
+        let affected_rows = diesel::update(inventory::table.find(item_id))
+            .set((
+                inventory::backorder_policy.eq(policy.as_str()),
+                inventory::backorder_limit.eq(backorder_limit)
+            ))
+            .execute(&mut conn)?;
+
+        if affected_rows == 0 {
+            return Err(BackorderError::NoItemIdError(item_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Lock item and get quantity which can still be backordered across its variants
+// Limit caps quantity waiting for stock over all orders, no limit means unlimited
+pub fn lock_backorder_capacity(
+    conn: &mut DbConnection,
+    item_id: Uuid
+) -> QueryResult<i64> {
+    let (policy, backorder_limit) = inventory::table
+        .find(item_id)
+        .select((inventory::backorder_policy, inventory::backorder_limit))
+        .for_update()
+        .get_result::<(String, Option<i32>)>(conn)?;
+
+    if policy == BackorderPolicy::None.as_str() {
+        return Ok(0);
+    }
+
+    let Some(backorder_limit) = backorder_limit else {
+        return Ok(i64::MAX);
+    };
+
+    // Only orders still waiting to be filled hold on to backordered quantity
+    let outstanding = order_items::table
+        .inner_join(orders::table)
+        .filter(order_items::item_id.eq(item_id))
+        .filter(orders::status.eq_any(["pending", "partially_shipped"]))
+        .select(diesel::dsl::sum(order_items::backordered_quantity))
+        .get_result::<Option<i64>>(conn)?
+        .unwrap_or(0);
+
+    Ok((backorder_limit as i64 - outstanding).max(0))
+}
+
+// Variants with stock on hand which backordered lines are still waiting for
+#[tracing::instrument(
+    "Getting backordered variants with stock",
+    skip(conn)
+)]
+pub async fn get_fillable_backorder_variants(
+    mut conn: DbConnection
+) -> Result<Vec<Uuid>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        order_items::table
+            .inner_join(orders::table)
+            .inner_join(product_variants::table)
+            .filter(order_items::backordered_quantity.gt(0))
+            .filter(orders::status.eq_any(["pending", "partially_shipped"]))
+            .filter(product_variants::amount.gt(0))
+            .select(order_items::variant_id)
+            .distinct()
+            .load::<Uuid>(&mut conn)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Customers are emailed about each filled line in the same transaction as it is allocated
+#[tracing::instrument(
+    "Allocating restocked variant to backordered order lines",
+    skip(conn, email)
+)]
+pub async fn fill_backorders(
+    mut conn: DbConnection,
+    variant_id: Uuid,
+    strategy: AllocationStrategy,
+    actor_id: Option<Uuid>,
+    email: impl Fn(&FilledBackorder) -> Result<NewEmail, EmailTemplateError> + Send + 'static
+) -> Result<Vec<FilledBackorder>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<Vec<FilledBackorder>, anyhow::Error, _>(|conn| {
+            let amount = product_variants::table
+                .find(variant_id)
+                .select(product_variants::amount)
+                .for_update()
+                .get_result::<i32>(conn)
+                .optional()?;
+
+            let Some(amount) = amount else {
+                return Ok(Vec::new());
+            };
+
+            // Stock held by checkouts stays with them
+            let reserved = reserved_quantities(conn, &[variant_id], None)?
+                .remove(&variant_id)
+                .unwrap_or(0);
+            let mut available = (amount as i64 - reserved).max(0) as i32;
+
+            // Oldest orders get stock first
+            let waiting = order_items::table
+                .inner_join(orders::table)
+                .inner_join(inventory::table)
+                .filter(order_items::variant_id.eq(variant_id))
+                .filter(order_items::backordered_quantity.gt(0))
+                .filter(orders::status.eq_any(["pending", "partially_shipped"]))
+                .select((
+                    order_items::order_item_id,
+                    order_items::order_id,
+                    orders::user_id,
+                    order_items::backordered_quantity,
+                    inventory::name,
+                    inventory::sku,
+                    inventory::backorder_policy
+                ))
+                .order((orders::order_date, order_items::order_item_id))
+                .for_update()
+                .load::<(Uuid, Uuid, Option<Uuid>, i32, String, String, String)>(conn)?;
+
+            let mut filled = Vec::new();
+
+            for (order_item_id, order_id, user_id, backordered, item_name, sku, policy) in waiting {
+                let quantity = available.min(backordered);
+                if quantity == 0 {
+                    break;
+                }
+
+                let Some(allocations) = allocate_order_lines(conn, strategy, &[(variant_id, quantity)], None)? else {
+                    break;
+                };
+
+                let change = StockChange {
+                    reference_id: Some(order_id),
+                    actor_id,
+                    ..StockChange::new(StockMovementReason::Order)
+                };
+
+                for allocation in &allocations {
+                    let change = StockChange {
+                        warehouse_id: Some(allocation.warehouse_id),
+                        ..change.clone()
+                    };
+
+                    if adjust_variant_stock(conn, variant_id, -allocation.quantity, &change)?.is_none() {
+                        return Err(diesel::result::Error::RollbackTransaction.into());
+                    }
+                }
+
+                diesel::insert_into(order_item_allocations::table)
+                    .values(allocations.iter()
+                        .map(|allocation| OrderItemAllocation {
+                            allocation_id: Uuid::new_v4(),
+                            order_item_id,
+                            warehouse_id: allocation.warehouse_id,
+                            quantity: allocation.quantity
+                        })
+                        .collect::<Vec<_>>())
+                    .execute(conn)?;
+
+                diesel::update(order_items::table.find(order_item_id))
+                    .set(order_items::backordered_quantity.eq(order_items::backordered_quantity - quantity))
+                    .execute(conn)?;
+
+                available -= quantity;
+
+                let recipient = match user_id {
+                    Some(user_id) => users::table
+                        .find(user_id)
+                        .select(users::email)
+                        .get_result::<String>(conn)
+                        .optional()?,
+                    None => None
+                };
+
+                if let Some(recipient) = recipient {
+                    let backorder = FilledBackorder {
+                        order_id,
+                        email: recipient,
+                        item_name,
+                        sku,
+                        policy,
+                        quantity,
+                        remaining: backordered - quantity
+                    };
+
+                    enqueue_email(conn, email(&backorder)?)?;
+                    filled.push(backorder);
+                }
+            }
+
+            Ok(filled)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,223 @@
+use std::{error::Error, fmt::Debug};
+
+use anyhow::Context;
+use diesel::{AsChangeset, BoolExpressionMethods, Connection, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl, SelectableHelper};
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{adjust_variant_stock, insert_item_with_default_variant, StockChange, StockMovementReason}, models::InventoryItem, schema::{inventory, product_variants}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Validated catalog row to be upserted by sku
+// Columns left empty keep their existing value when the item already exists
+#[derive(Debug, Clone)]
+pub struct CatalogRecord {
+    pub row: usize,
+    pub sku: String,
+    pub name: String,
+    pub price: Option<f64>,
+    pub amount: Option<i32>,
+    pub slug: Option<String>,
+    pub derived_slug: String,
+    pub description: Option<String>,
+    pub brand: Option<String>
+}
+
+// Changes applied to an existing item by an import row
+#[derive(AsChangeset)]
+#[diesel(table_name = inventory)]
+struct CatalogItemChanges<'a> {
+    name: &'a str,
+    price: Option<f64>,
+    slug: Option<&'a str>,
+    description: Option<&'a str>,
+    brand: Option<&'a str>
+}
+
+// Struct representing an import row which couldn't be applied
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub sku: Option<String>,
+    pub message: String
+}
+
+// Struct representing outcome of a catalog import
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub errors: Vec<ImportRowError>
+}
+
+// Errors associated with importing catalog records
+#[derive(Error)]
+pub enum CatalogImportError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    // Used to roll back the transaction while still returning the report
+    #[error("Import was rolled back")]
+    RolledBack(ImportReport)
+}
+
+impl Debug for CatalogImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Whether an import row created a new item or updated an existing one
+enum UpsertOutcome {
+    Created,
+    Updated
+}
+
+#[tracing::instrument(
+    "Upserting catalog records to db",
+    skip_all,
+    fields(records = records.len(), dry_run)
+)]
+pub async fn upsert_catalog_records(
+    mut conn: DbConnection,
+    records: Vec<CatalogRecord>,
+    dry_run: bool,
+    actor_id: Uuid
+) -> Result<ImportReport, CatalogImportError> {
+
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ImportReport, CatalogImportError, _>(|conn| {
+            let mut report = ImportReport { dry_run, ..Default::default() };
+
+            for record in records {
+                // Savepoint per row so a failing row doesn't hide errors in the rows after it
+                match conn.transaction(|conn| upsert_catalog_record(conn, &record, actor_id)) {
+                    Ok(UpsertOutcome::Created) => report.created += 1,
+                    Ok(UpsertOutcome::Updated) => report.updated += 1,
+                    Err(diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::UniqueViolation,
+                        _
+                    )) => report.errors.push(ImportRowError {
+                        row: record.row,
+                        sku: Some(record.sku.clone()),
+                        message: "sku or slug is already used by another item".to_string()
+                    }),
+                    Err(e) => return Err(e.into())
+                }
+            }
+
+            if dry_run || !report.errors.is_empty() {
+                return Err(CatalogImportError::RolledBack(report));
+            }
+
+            Ok(report)
+        })
+    })
+    .await?;
+
+    match res {
+        Err(CatalogImportError::RolledBack(report)) => Ok(report),
+        res => res
+    }
+}
+
+fn upsert_catalog_record(
+    conn: &mut DbConnection,
+    record: &CatalogRecord,
+    actor_id: Uuid
+) -> QueryResult<UpsertOutcome> {
+    let existing = inventory::table
+        .filter(inventory::sku.eq(&record.sku))
+        .select(inventory::item_id)
+        .for_update()
+        .get_result::<Uuid>(conn)
+        .optional()?;
+
+    let Some(item_id) = existing else {
+        insert_item_with_default_variant(conn, InventoryItem {
+            item_id: Uuid::new_v4(),
+            name: record.name.clone(),
+            amount: Some(record.amount.unwrap_or(0)),
+            price: record.price,
+            sku: record.sku.clone(),
+            slug: record.slug.clone().unwrap_or_else(|| record.derived_slug.clone()),
+            description: record.description.clone(),
+            brand: record.brand.clone()
+        }, Some(actor_id))?;
+
+        return Ok(UpsertOutcome::Created);
+    };
+
+    diesel::update(inventory::table.find(item_id))
+        .set(CatalogItemChanges {
+            name: &record.name,
+            price: record.price,
+            slug: record.slug.as_deref(),
+            description: record.description.as_deref(),
+            brand: record.brand.as_deref()
+        })
+        .execute(conn)?;
+
+    // Imported amount is the stock of the default variant, which shares the item's sku
+    if let Some(amount) = record.amount {
+        let default_variant = product_variants::table
+            .filter(product_variants::item_id.eq(item_id))
+            .filter(product_variants::sku.eq(&record.sku))
+            .select((product_variants::variant_id, product_variants::amount))
+            .get_result::<(Uuid, i32)>(conn)
+            .optional()?;
+
+        if let Some((variant_id, current_amount)) = default_variant {
+            if amount != current_amount {
+                adjust_variant_stock(conn, variant_id, amount - current_amount, &StockChange {
+                    actor_id: Some(actor_id),
+                    note: Some("Catalog import".to_string()),
+                    ..StockChange::new(StockMovementReason::Adjustment)
+                })?;
+            }
+        }
+    }
+
+    Ok(UpsertOutcome::Updated)
+}
+
+// Exported amount is the stock of the default variant, the same one import sets
+#[tracing::instrument(
+    "Getting batch of catalog items for export",
+    skip(conn)
+)]
+pub async fn get_catalog_batch(
+    mut conn: DbConnection,
+    after_sku: Option<String>,
+    limit: i64
+) -> Result<Vec<InventoryItem>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        let mut query = inventory::table
+            .left_join(product_variants::table.on(
+                product_variants::item_id.eq(inventory::item_id)
+                    .and(product_variants::sku.eq(inventory::sku))
+            ))
+            .select((InventoryItem::as_select(), product_variants::amount.nullable()))
+            .into_boxed();
+
+        if let Some(sku) = after_sku {
+            query = query.filter(inventory::sku.gt(sku));
+        }
+
+        query
+            .order(inventory::sku)
+            .limit(limit)
+            .load::<(InventoryItem, Option<i32>)>(&mut conn)
+            .context("Failed to get catalog items")
+            .map(|rows| rows.into_iter()
+                .map(|(item, amount)| InventoryItem { amount, ..item })
+                .collect::<Vec<InventoryItem>>())
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
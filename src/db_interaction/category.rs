@@ -0,0 +1,109 @@
+use std::{error::Error, fmt::Debug};
+
+use anyhow::Context;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{models::Category, pagination::{Page, PageRequest}, schema::categories, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Position of a category within listing ordered by name
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CategoryCursor {
+    pub name: String,
+    pub category_id: Uuid
+}
+
+#[tracing::instrument(
+    "Getting categories from db",
+    skip_all
+)]
+pub async fn get_categories(
+    mut conn: DbConnection,
+    page_request: PageRequest<CategoryCursor>
+) -> Result<Page<Category>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = categories::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .context("Failed to count categories")?;
+
+        let mut query = categories::table
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                categories::name.gt(after.name.clone())
+                    .or(categories::name.eq(after.name.clone()).and(categories::category_id.gt(after.category_id)))
+            );
+        }
+
+        let rows = query
+            .order((categories::name.asc(), categories::category_id.asc()))
+            .limit(page_request.fetch_limit())
+            .load::<Category>(&mut conn)
+            .context("Failed to get categories")?;
+
+        Ok::<_, anyhow::Error>(Page::new(rows, page_request.limit, total, |category| CategoryCursor {
+            name: category.name.clone(),
+            category_id: category.category_id
+        }))
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+// Errors associated with inserting records to categories table
+#[derive(Error)]
+pub enum CategoryInsertError{
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to insert into categories table")]
+    InsertError(#[from] diesel::result::Error),
+    #[error("Category with same slug already exists")]
+    NotUnique,
+    #[error("Parent category doesn't exist")]
+    NoParentError
+}
+
+impl Debug for CategoryInsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Insert a category to db",
+    skip_all
+)]
+pub async fn insert_category(
+    mut conn: DbConnection,
+    category: Category
+) -> Result<(), CategoryInsertError> {
+
+    spawn_blocking_with_tracing(move || {
+        diesel::insert_into(categories::table)
+            .values(category)
+            .execute(&mut conn)
+            .map_err(|e| {
+                match e {
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::UniqueViolation,
+                        _
+                    ) => CategoryInsertError::NotUnique,
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                        _
+                    ) => CategoryInsertError::NoParentError,
+                    _ => CategoryInsertError::InsertError(e)
+                }
+            })
+    })
+    .await??;
+
+    Ok(())
+}
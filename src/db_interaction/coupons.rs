@@ -0,0 +1,490 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{dsl::count_star, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{domain::coupon::{CouponCode, DiscountType}, models::{Coupon, CouponRedemption}, pagination::{Page, PageRequest}, schema::{categories, coupon_categories, coupon_items, coupon_redemptions, coupons, inventory, inventory_categories, orders}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing validated coupon settings saved by an admin
+#[derive(Debug, Clone)]
+pub struct CouponSettings {
+    pub code: CouponCode,
+    pub discount_type: DiscountType,
+    pub discount_value: i64,
+    pub min_order_cents: Option<i64>,
+    pub usage_limit: Option<i32>,
+    pub per_user_limit: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub active: bool,
+    pub item_ids: Vec<Uuid>,
+    pub category_ids: Vec<Uuid>
+}
+
+// Struct representing a coupon along with what it is restricted to and how often it was used
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CouponWithRestrictions {
+    #[serde(flatten)]
+    pub coupon: Coupon,
+    pub item_ids: Vec<Uuid>,
+    pub category_ids: Vec<Uuid>,
+    pub times_redeemed: i64
+}
+
+// Struct representing position of a coupon within coupons listing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CouponCursor {
+    pub code: String
+}
+
+// Errors associated with managing coupons
+#[derive(Error)]
+pub enum CouponError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("coupon_id: {0} doesn't exist")]
+    NoCouponIdError(Uuid),
+    #[error("Coupon with code {0} already exists")]
+    NotUnique(String),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid),
+    #[error("category_id: {0} doesn't exist")]
+    NoCategoryIdError(Uuid)
+}
+
+impl Debug for CouponError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Reasons a coupon can't be used on an order
+#[derive(Error, Debug)]
+pub enum CouponRejection {
+    #[error("Coupon {0} doesn't exist")]
+    Unknown(String),
+    #[error("Coupon is not active")]
+    Inactive,
+    #[error("Coupon can't be used yet")]
+    NotStarted,
+    #[error("Coupon has expired")]
+    Expired,
+    #[error("Coupon has been used up")]
+    UsageLimitReached,
+    #[error("Coupon was already used as many times as allowed")]
+    PerUserLimitReached,
+    #[error("Order subtotal should be at least {0} cents to use coupon")]
+    BelowMinimum(i64),
+    #[error("None of the ordered items are eligible for coupon")]
+    NotApplicable
+}
+
+fn check_restrictions_exist(conn: &mut DbConnection, settings: &CouponSettings) -> Result<(), CouponError> {
+    let items: HashSet<Uuid> = inventory::table
+        .filter(inventory::item_id.eq_any(&settings.item_ids))
+        .select(inventory::item_id)
+        .load::<Uuid>(conn)?
+        .into_iter()
+        .collect();
+
+    if let Some(missing) = settings.item_ids.iter().find(|item_id| !items.contains(item_id)) {
+        return Err(CouponError::NoItemIdError(*missing));
+    }
+
+    let found: HashSet<Uuid> = categories::table
+        .filter(categories::category_id.eq_any(&settings.category_ids))
+        .select(categories::category_id)
+        .load::<Uuid>(conn)?
+        .into_iter()
+        .collect();
+
+    if let Some(missing) = settings.category_ids.iter().find(|category_id| !found.contains(category_id)) {
+        return Err(CouponError::NoCategoryIdError(*missing));
+    }
+
+    Ok(())
+}
+
+fn replace_restrictions(conn: &mut DbConnection, coupon_id: Uuid, settings: &CouponSettings) -> QueryResult<()> {
+    diesel::delete(coupon_items::table.filter(coupon_items::coupon_id.eq(coupon_id)))
+        .execute(conn)?;
+    diesel::delete(coupon_categories::table.filter(coupon_categories::coupon_id.eq(coupon_id)))
+        .execute(conn)?;
+
+    let items: Vec<_> = settings.item_ids.iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|item_id| (coupon_items::coupon_id.eq(coupon_id), coupon_items::item_id.eq(*item_id)))
+        .collect();
+    if !items.is_empty() {
+        diesel::insert_into(coupon_items::table)
+            .values(items)
+            .execute(conn)?;
+    }
+
+    let categories: Vec<_> = settings.category_ids.iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|category_id| (coupon_categories::coupon_id.eq(coupon_id), coupon_categories::category_id.eq(*category_id)))
+        .collect();
+    if !categories.is_empty() {
+        diesel::insert_into(coupon_categories::table)
+            .values(categories)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+// Attach restrictions and redemption counts to coupons keeping their order
+fn with_restrictions(conn: &mut DbConnection, coupons: Vec<Coupon>) -> QueryResult<Vec<CouponWithRestrictions>> {
+    let coupon_ids: Vec<Uuid> = coupons.iter().map(|coupon| coupon.coupon_id).collect();
+
+    let mut items_by_coupon: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (coupon_id, item_id) in coupon_items::table
+        .filter(coupon_items::coupon_id.eq_any(&coupon_ids))
+        .order(coupon_items::item_id)
+        .load::<(Uuid, Uuid)>(conn)?
+    {
+        items_by_coupon.entry(coupon_id).or_default().push(item_id);
+    }
+
+    let mut categories_by_coupon: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (coupon_id, category_id) in coupon_categories::table
+        .filter(coupon_categories::coupon_id.eq_any(&coupon_ids))
+        .order(coupon_categories::category_id)
+        .load::<(Uuid, Uuid)>(conn)?
+    {
+        categories_by_coupon.entry(coupon_id).or_default().push(category_id);
+    }
+
+    let redeemed: HashMap<Uuid, i64> = coupon_redemptions::table
+        .filter(coupon_redemptions::coupon_id.eq_any(&coupon_ids))
+        .group_by(coupon_redemptions::coupon_id)
+        .select((coupon_redemptions::coupon_id, count_star()))
+        .load::<(Uuid, i64)>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(coupons.into_iter()
+        .map(|coupon| CouponWithRestrictions {
+            item_ids: items_by_coupon.remove(&coupon.coupon_id).unwrap_or_default(),
+            category_ids: categories_by_coupon.remove(&coupon.coupon_id).unwrap_or_default(),
+            times_redeemed: redeemed.get(&coupon.coupon_id).copied().unwrap_or(0),
+            coupon
+        })
+        .collect())
+}
+
+// Map unique violation on code to a readable error
+fn unique_code(e: diesel::result::Error, code: &CouponCode) -> CouponError {
+    match e {
+        diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => CouponError::NotUnique(code.inner()),
+        e => e.into()
+    }
+}
+
+#[tracing::instrument(
+    "Inserting coupon into db",
+    skip(conn)
+)]
+pub async fn insert_coupon(
+    mut conn: DbConnection,
+    settings: CouponSettings
+) -> Result<CouponWithRestrictions, CouponError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<CouponWithRestrictions, CouponError, _>(|conn| {
+            check_restrictions_exist(conn, &settings)?;
+
+            let now = Utc::now();
+            let coupon = diesel::insert_into(coupons::table)
+                .values(Coupon {
+                    coupon_id: Uuid::new_v4(),
+                    code: settings.code.inner(),
+                    discount_type: settings.discount_type.as_str().to_string(),
+                    discount_value: settings.discount_value,
+                    min_order_cents: settings.min_order_cents,
+                    usage_limit: settings.usage_limit,
+                    per_user_limit: settings.per_user_limit,
+                    starts_at: settings.starts_at,
+                    ends_at: settings.ends_at,
+                    active: settings.active,
+                    created_at: now,
+                    updated_at: now
+                })
+                .get_result::<Coupon>(conn)
+                .map_err(|e| unique_code(e, &settings.code))?;
+
+            replace_restrictions(conn, coupon.coupon_id, &settings)?;
+
+            Ok(with_restrictions(conn, vec![coupon])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Updating coupon in db",
+    skip(conn)
+)]
+pub async fn update_coupon(
+    mut conn: DbConnection,
+    coupon_id: Uuid,
+    settings: CouponSettings
+) -> Result<CouponWithRestrictions, CouponError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<CouponWithRestrictions, CouponError, _>(|conn| {
+            check_restrictions_exist(conn, &settings)?;
+
+            let coupon = diesel::update(coupons::table.find(coupon_id))
+                .set((
+                    coupons::code.eq(settings.code.inner()),
+                    coupons::discount_type.eq(settings.discount_type.as_str()),
+                    coupons::discount_value.eq(settings.discount_value),
+                    coupons::min_order_cents.eq(settings.min_order_cents),
+                    coupons::usage_limit.eq(settings.usage_limit),
+                    coupons::per_user_limit.eq(settings.per_user_limit),
+                    coupons::starts_at.eq(settings.starts_at),
+                    coupons::ends_at.eq(settings.ends_at),
+                    coupons::active.eq(settings.active),
+                    coupons::updated_at.eq(Utc::now())
+                ))
+                .get_result::<Coupon>(conn)
+                .optional()
+                .map_err(|e| unique_code(e, &settings.code))?
+                .ok_or(CouponError::NoCouponIdError(coupon_id))?;
+
+            replace_restrictions(conn, coupon_id, &settings)?;
+
+            Ok(with_restrictions(conn, vec![coupon])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Deleting coupon from db",
+    skip(conn)
+)]
+pub async fn delete_coupon(
+    mut conn: DbConnection,
+    coupon_id: Uuid
+) -> Result<(), CouponError> {
+    spawn_blocking_with_tracing(move || {
+        // Orders keep their discount after the coupon is gone
+        let deleted = diesel::delete(coupons::table.find(coupon_id))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(CouponError::NoCouponIdError(coupon_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting coupon from db",
+    skip(conn)
+)]
+pub async fn get_coupon(
+    mut conn: DbConnection,
+    coupon_id: Uuid
+) -> Result<CouponWithRestrictions, CouponError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let coupon = coupons::table
+            .find(coupon_id)
+            .get_result::<Coupon>(&mut conn)
+            .optional()?
+            .ok_or(CouponError::NoCouponIdError(coupon_id))?;
+
+        Ok::<_, CouponError>(with_restrictions(&mut conn, vec![coupon])?.remove(0))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting coupons from db",
+    skip(conn)
+)]
+pub async fn get_coupons(
+    mut conn: DbConnection,
+    page_request: PageRequest<CouponCursor>
+) -> Result<Page<CouponWithRestrictions>, CouponError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = coupons::table
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = coupons::table.into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(coupons::code.gt(after.code.clone()));
+        }
+
+        let rows = query
+            .order(coupons::code)
+            .limit(page_request.fetch_limit())
+            .load::<Coupon>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |coupon| CouponCursor {
+            code: coupon.code.clone()
+        })
+        .try_map_items(|coupons| with_restrictions(&mut conn, coupons))
+        .map_err(CouponError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Check coupon against an order being created and record its use
+// Lines are (item_id, subtotal in cents) of the order, the coupon row stays locked till the order commits
+// Along with redemption comes the discount taken off each line, in the order lines were given
+pub fn redeem_coupon(
+    conn: &mut DbConnection,
+    code: &CouponCode,
+    order_id: Uuid,
+    user_id: Uuid,
+    lines: &[(Uuid, i64)]
+) -> QueryResult<Result<(CouponRedemption, Vec<i64>), CouponRejection>> {
+    let Some(coupon) = coupons::table
+        .filter(coupons::code.eq(code.inner()))
+        .for_update()
+        .get_result::<Coupon>(conn)
+        .optional()?
+    else {
+        return Ok(Err(CouponRejection::Unknown(code.inner())));
+    };
+
+    let now = Utc::now();
+    if !coupon.active {
+        return Ok(Err(CouponRejection::Inactive));
+    }
+    if coupon.starts_at.is_some_and(|starts_at| now < starts_at) {
+        return Ok(Err(CouponRejection::NotStarted));
+    }
+    if coupon.ends_at.is_some_and(|ends_at| now >= ends_at) {
+        return Ok(Err(CouponRejection::Expired));
+    }
+
+    if let Some(usage_limit) = coupon.usage_limit {
+        let used = coupon_redemptions::table
+            .filter(coupon_redemptions::coupon_id.eq(coupon.coupon_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if used >= usage_limit as i64 {
+            return Ok(Err(CouponRejection::UsageLimitReached));
+        }
+    }
+
+    if let Some(per_user_limit) = coupon.per_user_limit {
+        let used = coupon_redemptions::table
+            .filter(coupon_redemptions::coupon_id.eq(coupon.coupon_id))
+            .filter(coupon_redemptions::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if used >= per_user_limit as i64 {
+            return Ok(Err(CouponRejection::PerUserLimitReached));
+        }
+    }
+
+    let subtotal: i64 = lines.iter().map(|(_, subtotal)| subtotal).sum();
+    if let Some(min_order_cents) = coupon.min_order_cents {
+        if subtotal < min_order_cents {
+            return Ok(Err(CouponRejection::BelowMinimum(min_order_cents)));
+        }
+    }
+
+    // Unrestricted coupons discount the whole order
+    let mut eligible_items: HashSet<Uuid> = coupon_items::table
+        .filter(coupon_items::coupon_id.eq(coupon.coupon_id))
+        .select(coupon_items::item_id)
+        .load::<Uuid>(conn)?
+        .into_iter()
+        .collect();
+
+    let restricted_categories = coupon_categories::table
+        .filter(coupon_categories::coupon_id.eq(coupon.coupon_id))
+        .select(coupon_categories::category_id)
+        .load::<Uuid>(conn)?;
+
+    let eligible: Vec<bool> = if eligible_items.is_empty() && restricted_categories.is_empty() {
+        vec![true; lines.len()]
+    } else {
+        eligible_items.extend(
+            inventory_categories::table
+                .filter(inventory_categories::category_id.eq_any(restricted_categories))
+                .select(inventory_categories::item_id)
+                .load::<Uuid>(conn)?
+        );
+
+        lines.iter()
+            .map(|(item_id, _)| eligible_items.contains(item_id))
+            .collect()
+    };
+
+    let eligible_cents: i64 = lines.iter()
+        .zip(&eligible)
+        .filter(|(_, eligible)| **eligible)
+        .map(|((_, subtotal), _)| subtotal)
+        .sum();
+
+    let discount_type = DiscountType::parse(&coupon.discount_type).unwrap_or(DiscountType::Fixed);
+    let discount_cents = discount_type.amount_off(coupon.discount_value, eligible_cents);
+    if discount_cents <= 0 {
+        return Ok(Err(CouponRejection::NotApplicable));
+    }
+
+    let redemption = diesel::insert_into(coupon_redemptions::table)
+        .values(CouponRedemption {
+            redemption_id: Uuid::new_v4(),
+            coupon_id: coupon.coupon_id,
+            order_id,
+            user_id: Some(user_id),
+            discount_cents,
+            created_at: now
+        })
+        .get_result::<CouponRedemption>(conn)?;
+
+    diesel::update(orders::table.find(order_id))
+        .set((
+            orders::coupon_id.eq(coupon.coupon_id),
+            orders::discount_cents.eq(discount_cents)
+        ))
+        .execute(conn)?;
+
+    // Discount is spread over eligible lines by their subtotal, rounding is left on the last one
+    let mut line_discounts = vec![0; lines.len()];
+    let mut left = discount_cents;
+    if let Some(last) = eligible.iter().rposition(|eligible| *eligible) {
+        for (line, (_, subtotal)) in lines.iter().enumerate().filter(|(line, _)| eligible[*line]) {
+            let share = if line == last {
+                left
+            } else {
+                (*subtotal as i128 * discount_cents as i128 / eligible_cents as i128) as i64
+            };
+            line_discounts[line] = share;
+            left -= share;
+        }
+    }
+
+    Ok(Ok((redemption, line_discounts)))
+}
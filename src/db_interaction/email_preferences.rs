@@ -0,0 +1,320 @@
+use std::{error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{domain::email_category::EmailCategory, models::{EmailPreferences, EmailSuppression}, pagination::{Page, PageRequest}, schema::{email_preferences, email_suppressions, users}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing categories to opt in or out of, those left out are unchanged
+#[derive(Deserialize, Debug, Default)]
+pub struct EmailPreferencesUpdate {
+    pub order_updates: Option<bool>,
+    pub promotions: Option<bool>,
+    pub back_in_stock: Option<bool>
+}
+
+// Enum representing why nothing is delivered to an address
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuppressionReason {
+    // Recipient followed an unsubscribe link
+    Unsubscribed,
+    // Mail provider reported the address as undeliverable
+    Bounced,
+    // Recipient marked an email as spam
+    Complained,
+    Manual
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::Unsubscribed => "unsubscribed",
+            SuppressionReason::Bounced => "bounced",
+            SuppressionReason::Complained => "complained",
+            SuppressionReason::Manual => "manual"
+        }
+    }
+}
+
+// Cursor of suppression listing, ordered by creation time, newest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuppressionCursor {
+    pub created_at: DateTime<Utc>,
+    pub email: String
+}
+
+// Errors associated with managing email preferences and suppressions
+#[derive(Error)]
+pub enum EmailPreferencesError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("email: {0} isn't suppressed")]
+    NotSuppressedError(String)
+}
+
+impl Debug for EmailPreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Users who never changed their preferences get every category
+fn default_preferences(user_id: Uuid) -> EmailPreferences {
+    EmailPreferences {
+        user_id,
+        order_updates: true,
+        promotions: true,
+        back_in_stock: true,
+        updated_at: Utc::now()
+    }
+}
+
+fn opt_out(preferences: &mut EmailPreferences, category: EmailCategory) {
+    match category {
+        EmailCategory::OrderUpdates => preferences.order_updates = false,
+        EmailCategory::Promotions => preferences.promotions = false,
+        EmailCategory::BackInStock => preferences.back_in_stock = false
+    }
+}
+
+fn wants(preferences: &EmailPreferences, category: EmailCategory) -> bool {
+    match category {
+        EmailCategory::OrderUpdates => preferences.order_updates,
+        EmailCategory::Promotions => preferences.promotions,
+        EmailCategory::BackInStock => preferences.back_in_stock
+    }
+}
+
+fn save_preferences(conn: &mut DbConnection, preferences: EmailPreferences) -> QueryResult<EmailPreferences> {
+    diesel::insert_into(email_preferences::table)
+        .values(&preferences)
+        .on_conflict(email_preferences::user_id)
+        .do_update()
+        .set((
+            email_preferences::order_updates.eq(preferences.order_updates),
+            email_preferences::promotions.eq(preferences.promotions),
+            email_preferences::back_in_stock.eq(preferences.back_in_stock),
+            email_preferences::updated_at.eq(Utc::now())
+        ))
+        .get_result::<EmailPreferences>(conn)
+}
+
+fn load_preferences(conn: &mut DbConnection, user_id: Uuid) -> QueryResult<EmailPreferences> {
+    Ok(email_preferences::table
+        .find(user_id)
+        .get_result::<EmailPreferences>(conn)
+        .optional()?
+        .unwrap_or_else(|| default_preferences(user_id)))
+}
+
+#[tracing::instrument(
+    "Getting email preferences from db",
+    skip(conn)
+)]
+pub async fn get_email_preferences(
+    mut conn: DbConnection,
+    user_id: Uuid
+) -> Result<EmailPreferences, EmailPreferencesError> {
+    let res = spawn_blocking_with_tracing(move || {
+        load_preferences(&mut conn, user_id)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Updating email preferences in db",
+    skip(conn)
+)]
+pub async fn update_email_preferences(
+    mut conn: DbConnection,
+    user_id: Uuid,
+    update: EmailPreferencesUpdate
+) -> Result<EmailPreferences, EmailPreferencesError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, EmailPreferencesError, _>(|conn| {
+            let mut preferences = load_preferences(conn, user_id)?;
+
+            preferences.order_updates = update.order_updates.unwrap_or(preferences.order_updates);
+            preferences.promotions = update.promotions.unwrap_or(preferences.promotions);
+            preferences.back_in_stock = update.back_in_stock.unwrap_or(preferences.back_in_stock);
+
+            Ok(save_preferences(conn, preferences)?)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Opt address out of a category, or out of every email without one
+// Addresses which don't belong to a user have no preferences, so they are suppressed instead
+#[tracing::instrument(
+    "Unsubscribing email",
+    skip(conn)
+)]
+pub async fn unsubscribe_email(
+    mut conn: DbConnection,
+    email: String,
+    category: Option<EmailCategory>
+) -> Result<(), EmailPreferencesError> {
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, EmailPreferencesError, _>(|conn| {
+            let user_id = users::table
+                .filter(users::email.eq(&email))
+                .select(users::user_id)
+                .get_result::<Uuid>(conn)
+                .optional()?;
+
+            match (category, user_id) {
+                (Some(category), Some(user_id)) => {
+                    let mut preferences = load_preferences(conn, user_id)?;
+                    opt_out(&mut preferences, category);
+                    save_preferences(conn, preferences)?;
+                },
+                _ => {
+                    suppress(conn, &email, SuppressionReason::Unsubscribed)?;
+                }
+            }
+
+            Ok(())
+        })
+    })
+    .await??;
+
+    Ok(())
+}
+
+fn suppress(conn: &mut DbConnection, email: &str, reason: SuppressionReason) -> QueryResult<EmailSuppression> {
+    // Suppressing an address again only updates why it is suppressed
+    diesel::insert_into(email_suppressions::table)
+        .values(EmailSuppression {
+            email: email.to_lowercase(),
+            reason: reason.as_str().to_string(),
+            created_at: Utc::now()
+        })
+        .on_conflict(email_suppressions::email)
+        .do_update()
+        .set(email_suppressions::reason.eq(reason.as_str()))
+        .get_result::<EmailSuppression>(conn)
+}
+
+#[tracing::instrument(
+    "Suppressing email in db",
+    skip(conn)
+)]
+pub async fn insert_email_suppression(
+    mut conn: DbConnection,
+    email: String,
+    reason: SuppressionReason
+) -> Result<EmailSuppression, EmailPreferencesError> {
+    let res = spawn_blocking_with_tracing(move || {
+        suppress(&mut conn, &email, reason)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Deleting email suppression from db",
+    skip(conn)
+)]
+pub async fn delete_email_suppression(
+    mut conn: DbConnection,
+    email: String
+) -> Result<(), EmailPreferencesError> {
+    spawn_blocking_with_tracing(move || {
+        let deleted = diesel::delete(email_suppressions::table.find(email.to_lowercase()))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(EmailPreferencesError::NotSuppressedError(email));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting email suppressions from db",
+    skip(conn, page_request)
+)]
+pub async fn get_email_suppressions(
+    mut conn: DbConnection,
+    page_request: PageRequest<SuppressionCursor>
+) -> Result<Page<EmailSuppression>, EmailPreferencesError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = email_suppressions::table
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = email_suppressions::table.into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                email_suppressions::created_at.lt(after.created_at)
+                    .or(email_suppressions::created_at.eq(after.created_at).and(email_suppressions::email.lt(after.email.clone())))
+            );
+        }
+
+        let rows = query
+            .order((email_suppressions::created_at.desc(), email_suppressions::email.desc()))
+            .limit(page_request.fetch_limit())
+            .load::<EmailSuppression>(&mut conn)?;
+
+        Ok::<_, EmailPreferencesError>(Page::new(rows, page_request.limit, total, |suppression| SuppressionCursor {
+            created_at: suppression.created_at,
+            email: suppression.email.clone()
+        }))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Why an email can't be delivered to recipient, None if it can
+// Suppressed addresses get nothing, users who opted out of a category get nothing from it
+pub fn delivery_blocked_reason(
+    conn: &mut DbConnection,
+    recipient: &str,
+    category: Option<EmailCategory>
+) -> QueryResult<Option<String>> {
+    let suppression = email_suppressions::table
+        .find(recipient.to_lowercase())
+        .select(email_suppressions::reason)
+        .get_result::<String>(conn)
+        .optional()?;
+
+    if let Some(reason) = suppression {
+        return Ok(Some(format!("Recipient is suppressed: {}", reason)));
+    }
+
+    let Some(category) = category else {
+        return Ok(None);
+    };
+
+    let user_id = users::table
+        .filter(users::email.eq(recipient))
+        .select(users::user_id)
+        .get_result::<Uuid>(conn)
+        .optional()?;
+
+    match user_id {
+        Some(user_id) if !wants(&load_preferences(conn, user_id)?, category) => {
+            Ok(Some(format!("Recipient opted out of {}", category.as_str())))
+        },
+        _ => Ok(None)
+    }
+}
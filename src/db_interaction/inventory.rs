@@ -1,10 +1,44 @@
-use std::{error::Error, fmt::Debug};
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt::Debug};
 
 use anyhow::Context;
-use diesel::{RunQueryDsl, QueryDsl};
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryResult, RunQueryDsl, QueryDsl, SelectableHelper};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use uuid::Uuid;
 
-use crate::{models::InventoryItem, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+use crate::{db_interaction::{default_warehouse_id, record_stock_movement, reserved_quantities, StockChange, StockMovementReason}, pagination::{Page, PageRequest}, models::{Category, InventoryCategory, InventoryItem, ItemAttribute, ProductVariant, VariantOption, WarehouseStock}, schema::{categories, inventory, inventory_categories, item_attributes, product_variants, variant_options, warehouse_stock}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Name given to the variant created along with an inventory item
+pub const DEFAULT_VARIANT_NAME: &str = "Default";
+
+// Struct representing a product variant along with its options
+#[derive(Serialize, Deserialize)]
+pub struct VariantWithOptions {
+    #[serde(flatten)]
+    pub variant: ProductVariant,
+    pub options: BTreeMap<String, String>,
+    // Stock on hand minus quantity held by active reservations
+    pub available_to_sell: i32,
+}
+
+// Struct representing an inventory item along with its catalog details
+#[derive(Serialize, Deserialize)]
+pub struct InventoryItemWithDetails {
+    #[serde(flatten)]
+    pub item: InventoryItem,
+    pub categories: Vec<Category>,
+    pub attributes: BTreeMap<String, String>,
+    pub variants: Vec<VariantWithOptions>,
+    pub available_to_sell: i32,
+}
+
+// Position of an inventory item within listing ordered by creation time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InventoryCursor {
+    pub created_at: DateTime<Utc>,
+    pub item_id: Uuid
+}
 
 #[tracing::instrument(
     "Getting inventory items from db",
@@ -12,19 +46,39 @@ use crate::{models::InventoryItem, telemetry::spawn_blocking_with_tracing, utils
 )]
 pub async fn get_inventory_items(
     mut conn: DbConnection,
-    page: i64,
-    limit: i64
-) -> Result<Vec<InventoryItem>, anyhow::Error>{
-    let offset_value = (page - 1) * limit;
-
+    page_request: PageRequest<InventoryCursor>
+) -> Result<Page<InventoryItemWithDetails>, anyhow::Error>{
     let res = spawn_blocking_with_tracing(move || {
-        use crate::schema::inventory; 
+        let total = inventory::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .context("Failed to count inventory items")?;
 
-        inventory::table
-            .limit(limit)
-            .offset(offset_value)
-            .load::<InventoryItem>(&mut conn)
-            .context("Failed to get inventory items")
+        let mut query = inventory::table
+            .select((InventoryItem::as_select(), inventory::created_at))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                inventory::created_at.gt(after.created_at)
+                    .or(inventory::created_at.eq(after.created_at).and(inventory::item_id.gt(after.item_id)))
+            );
+        }
+
+        let rows = query
+            .order((inventory::created_at, inventory::item_id))
+            .limit(page_request.fetch_limit())
+            .load::<(InventoryItem, DateTime<Utc>)>(&mut conn)
+            .context("Failed to get inventory items")?;
+
+        Page::new(rows, page_request.limit, total, |(item, created_at)| InventoryCursor {
+            created_at: *created_at,
+            item_id: item.item_id
+        })
+        .try_map_items(|rows| {
+            attach_item_details(&mut conn, rows.into_iter().map(|(item, _)| item).collect())
+        })
+        .context("Failed to get inventory item details")
     })
     .await
     .context("Failed due to threadpool error")??;
@@ -32,13 +86,98 @@ pub async fn get_inventory_items(
     Ok(res)
 }
 
+#[tracing::instrument(
+    "Attaching categories, attributes and variants to inventory items",
+    skip_all
+)]
+pub fn attach_item_details(
+    conn: &mut DbConnection,
+    items: Vec<InventoryItem>
+) -> QueryResult<Vec<InventoryItemWithDetails>>{
+    let item_ids: Vec<Uuid> = items.iter()
+        .map(|item| item.item_id)
+        .collect();
+
+    let assigned_categories = inventory_categories::table
+        .inner_join(categories::table)
+        .filter(inventory_categories::item_id.eq_any(&item_ids))
+        .select((inventory_categories::item_id, categories::all_columns))
+        .order(categories::name)
+        .load::<(Uuid, Category)>(conn)?;
+
+    let attributes = item_attributes::table
+        .filter(item_attributes::item_id.eq_any(&item_ids))
+        .load::<ItemAttribute>(conn)?;
+
+    let variants = product_variants::table
+        .filter(product_variants::item_id.eq_any(&item_ids))
+        .order(product_variants::sku)
+        .load::<ProductVariant>(conn)?;
+
+    let variant_ids: Vec<Uuid> = variants.iter()
+        .map(|variant| variant.variant_id)
+        .collect();
+
+    let options = variant_options::table
+        .filter(variant_options::variant_id.eq_any(&variant_ids))
+        .load::<VariantOption>(conn)?;
+
+    let reserved = reserved_quantities(conn, &variant_ids, None)?;
+
+    let mut options_by_variant: HashMap<Uuid, BTreeMap<String, String>> = HashMap::new();
+    for option in options {
+        options_by_variant.entry(option.variant_id)
+            .or_default()
+            .insert(option.name, option.value);
+    }
+
+    let mut variants_by_item: HashMap<Uuid, Vec<VariantWithOptions>> = HashMap::new();
+    for variant in variants {
+        variants_by_item.entry(variant.item_id)
+            .or_default()
+            .push(VariantWithOptions {
+                options: options_by_variant.remove(&variant.variant_id).unwrap_or_default(),
+                available_to_sell: (variant.amount as i64 - reserved.get(&variant.variant_id).copied().unwrap_or(0)).max(0) as i32,
+                variant
+            });
+    }
+
+    let mut categories_by_item: HashMap<Uuid, Vec<Category>> = HashMap::new();
+    for (item_id, category) in assigned_categories {
+        categories_by_item.entry(item_id).or_default().push(category);
+    }
+
+    let mut attributes_by_item: HashMap<Uuid, BTreeMap<String, String>> = HashMap::new();
+    for attribute in attributes {
+        attributes_by_item.entry(attribute.item_id)
+            .or_default()
+            .insert(attribute.key, attribute.value);
+    }
+
+    Ok(items.into_iter()
+        .map(|item| {
+            let variants = variants_by_item.remove(&item.item_id).unwrap_or_default();
+
+            InventoryItemWithDetails {
+                categories: categories_by_item.remove(&item.item_id).unwrap_or_default(),
+                attributes: attributes_by_item.remove(&item.item_id).unwrap_or_default(),
+                available_to_sell: variants.iter().map(|variant| variant.available_to_sell).sum(),
+                variants,
+                item,
+            }
+        })
+        .collect())
+}
+
 // Errors associated with inserting records to inventory table
 #[derive(Error)]
 pub enum InventoryInsertError{
     #[error("Failed due to threadpool error")]
     ThreadpoolError(#[from] tokio::task::JoinError),
     #[error("Failed to insert into inventory table")]
-    InsertError(#[from] diesel::result::Error)
+    InsertError(#[from] diesel::result::Error),
+    #[error("Item with same sku or slug already exists")]
+    NotUnique
 }
 
 impl Debug for InventoryInsertError {
@@ -54,17 +193,268 @@ impl Debug for InventoryInsertError {
 )]
 pub async fn insert_inventory_items(
     mut conn: DbConnection,
-    inventory_item: InventoryItem
+    inventory_item: InventoryItem,
+    actor_id: Uuid
 ) -> Result<(), InventoryInsertError> {
 
     spawn_blocking_with_tracing(move || {
-        use crate::schema::inventory;
+        conn.transaction::<(), InventoryInsertError, _>(|conn| {
+            insert_item_with_default_variant(conn, inventory_item, Some(actor_id))
+                .map_err(map_unique_violation)
+        })
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Insert item along with the variant its stock is held under
+pub fn insert_item_with_default_variant(
+    conn: &mut DbConnection,
+    inventory_item: InventoryItem,
+    actor_id: Option<Uuid>
+) -> QueryResult<()> {
+    let default_variant = ProductVariant{
+        variant_id: Uuid::new_v4(),
+        item_id: inventory_item.item_id,
+        sku: inventory_item.sku.clone(),
+        name: DEFAULT_VARIANT_NAME.to_string(),
+        price: None,
+        amount: inventory_item.amount.unwrap_or(0)
+    };
+
+    diesel::insert_into(
+        inventory::table
+    )
+    .values(inventory_item)
+    .execute(conn)?;
+
+    diesel::insert_into(product_variants::table)
+        .values(&default_variant)
+        .execute(conn)?;
+
+    record_initial_stock(conn, &default_variant, actor_id)
+}
+
+// Place stock a variant was created with in the default warehouse and record it as a restock
+fn record_initial_stock(
+    conn: &mut DbConnection,
+    variant: &ProductVariant,
+    actor_id: Option<Uuid>
+) -> QueryResult<()> {
+    if variant.amount != 0 {
+        let warehouse_id = default_warehouse_id(conn)?;
+
+        diesel::insert_into(warehouse_stock::table)
+            .values(WarehouseStock {
+                warehouse_id,
+                variant_id: variant.variant_id,
+                amount: variant.amount
+            })
+            .execute(conn)?;
+
+        record_stock_movement(conn, variant.item_id, variant.variant_id, variant.amount, &StockChange {
+            actor_id,
+            note: Some("Initial stock".to_string()),
+            warehouse_id: Some(warehouse_id),
+            ..StockChange::new(StockMovementReason::Restock)
+        })?;
+    }
+
+    Ok(())
+}
+
+fn map_unique_violation(e: diesel::result::Error) -> InventoryInsertError {
+    match e {
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _
+        ) => InventoryInsertError::NotUnique,
+        _ => InventoryInsertError::InsertError(e)
+    }
+}
+
+// Errors associated with inserting records to product_variants table
+#[derive(Error)]
+pub enum VariantInsertError{
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to insert into product_variants table")]
+    InsertError(#[from] diesel::result::Error),
+    #[error("Variant with same sku already exists")]
+    NotUnique,
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for VariantInsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Insert a product variant to db",
+    skip_all
+)]
+pub async fn insert_product_variant(
+    mut conn: DbConnection,
+    variant: ProductVariant,
+    options: BTreeMap<String, String>,
+    actor_id: Uuid
+) -> Result<(), VariantInsertError> {
+
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<(), VariantInsertError, _>(|conn| {
+            // Item's total stock includes stock of the new variant
+            let affected_rows = diesel::update(inventory::table)
+                .filter(inventory::item_id.eq(variant.item_id))
+                .set(inventory::amount.eq(inventory::amount + variant.amount))
+                .execute(conn)?;
+
+            if affected_rows == 0 {
+                return Err(VariantInsertError::NoItemIdError(variant.item_id))
+            }
+
+            let options: Vec<VariantOption> = options.into_iter()
+                .map(|(name, value)| VariantOption{ variant_id: variant.variant_id, name, value })
+                .collect();
+
+            diesel::insert_into(product_variants::table)
+                .values(&variant)
+                .execute(conn)
+                .map_err(|e| {
+                    match e {
+                        diesel::result::Error::DatabaseError(
+                            diesel::result::DatabaseErrorKind::UniqueViolation,
+                            _
+                        ) => VariantInsertError::NotUnique,
+                        _ => VariantInsertError::InsertError(e)
+                    }
+                })?;
+
+            diesel::insert_into(variant_options::table)
+                .values(&options)
+                .execute(conn)?;
+
+            record_initial_stock(conn, &variant, Some(actor_id))?;
+
+            Ok(())
+        })
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Errors associated with updating categories / attributes of an inventory item
+#[derive(Error)]
+pub enum UpdateItemDetailsError{
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid),
+    #[error("One or more category ids don't exist")]
+    InvalidCategoryError
+}
+
+impl Debug for UpdateItemDetailsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Check that item exists, locking it for the rest of the transaction
+fn lock_inventory_item(conn: &mut DbConnection, item_id: Uuid) -> Result<(), UpdateItemDetailsError> {
+    let found = inventory::table
+        .select(inventory::item_id)
+        .filter(inventory::item_id.eq(item_id))
+        .for_update()
+        .first::<Uuid>(conn)
+        .optional()?;
+
+    match found {
+        Some(_) => Ok(()),
+        None => Err(UpdateItemDetailsError::NoItemIdError(item_id))
+    }
+}
+
+#[tracing::instrument(
+    "Replacing categories of inventory item",
+    skip(conn)
+)]
+pub async fn set_item_categories(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    category_ids: Vec<Uuid>
+) -> Result<(), UpdateItemDetailsError> {
+
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<(), UpdateItemDetailsError, _>(|conn| {
+            lock_inventory_item(conn, item_id)?;
+
+            diesel::delete(inventory_categories::table)
+                .filter(inventory_categories::item_id.eq(item_id))
+                .execute(conn)?;
+
+            let assignments: Vec<InventoryCategory> = category_ids.iter()
+                .map(|category_id| InventoryCategory{ item_id, category_id: *category_id })
+                .collect();
+
+            diesel::insert_into(inventory_categories::table)
+                .values(&assignments)
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .map_err(|e| {
+                    match e {
+                        diesel::result::Error::DatabaseError(
+                            diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                            _
+                        ) => UpdateItemDetailsError::InvalidCategoryError,
+                        _ => UpdateItemDetailsError::RunQueryError(e)
+                    }
+                })?;
+
+            Ok(())
+        })
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Replacing attributes of inventory item",
+    skip(conn)
+)]
+pub async fn set_item_attributes(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    attributes: BTreeMap<String, String>
+) -> Result<(), UpdateItemDetailsError> {
+
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<(), UpdateItemDetailsError, _>(|conn| {
+            lock_inventory_item(conn, item_id)?;
+
+            diesel::delete(item_attributes::table)
+                .filter(item_attributes::item_id.eq(item_id))
+                .execute(conn)?;
+
+            let attributes: Vec<ItemAttribute> = attributes.into_iter()
+                .map(|(key, value)| ItemAttribute{ item_id, key, value })
+                .collect();
+
+            diesel::insert_into(item_attributes::table)
+                .values(&attributes)
+                .execute(conn)?;
 
-        diesel::insert_into(
-            inventory::table
-        )
-        .values(inventory_item)
-        .execute(&mut conn)
+            Ok(())
+        })
     })
     .await??;
 
@@ -0,0 +1,204 @@
+use std::{error::Error, fmt::Debug};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{enqueue_email, NewEmail}, pagination::{Page, PageRequest}, schema::inventory, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing an item whose stock is at or below its reorder threshold
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+pub struct LowStockItem {
+    pub item_id: Uuid,
+    pub sku: String,
+    pub name: String,
+    pub amount: Option<i32>,
+    pub reorder_threshold: i32,
+    pub low_stock_alerted_at: Option<DateTime<Utc>>
+}
+
+// Position of an item within low stock listing ordered by sku
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LowStockCursor {
+    pub sku: String
+}
+
+type LowStockColumns = (
+    inventory::item_id,
+    inventory::sku,
+    inventory::name,
+    inventory::amount,
+    diesel::dsl::AssumeNotNull<inventory::reorder_threshold>,
+    inventory::low_stock_alerted_at
+);
+
+fn low_stock_columns() -> LowStockColumns {
+    (
+        inventory::item_id,
+        inventory::sku,
+        inventory::name,
+        inventory::amount,
+        inventory::reorder_threshold.assume_not_null(),
+        inventory::low_stock_alerted_at
+    )
+}
+
+// Items with a threshold whose stock (missing stock counting as none) has fallen to it
+fn low_stock_items() -> inventory::BoxedQuery<'static, diesel::pg::Pg> {
+    inventory::table
+        .filter(inventory::reorder_threshold.is_not_null())
+        .filter(
+            inventory::amount.is_null()
+                .or(inventory::amount.assume_not_null().le(inventory::reorder_threshold.assume_not_null()))
+        )
+        .into_boxed()
+}
+
+// Errors associated with reorder thresholds of inventory items
+#[derive(Error)]
+pub enum LowStockError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for LowStockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Setting reorder threshold of inventory item",
+    skip(conn)
+)]
+pub async fn set_reorder_threshold(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    reorder_threshold: Option<i32>
+) -> Result<(), LowStockError> {
+    spawn_blocking_with_tracing(move || {
+        // Changing the threshold re-arms the alert for the item
+        let affected_rows = diesel::update(inventory::table.find(item_id))
+            .set((
+                inventory::reorder_threshold.eq(reorder_threshold),
+                inventory::low_stock_alerted_at.eq(None::<DateTime<Utc>>)
+            ))
+            .execute(&mut conn)?;
+
+        if affected_rows == 0 {
+            return Err(LowStockError::NoItemIdError(item_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting low stock items from db",
+    skip(conn, page_request)
+)]
+pub async fn get_low_stock_items(
+    mut conn: DbConnection,
+    page_request: PageRequest<LowStockCursor>
+) -> Result<Page<LowStockItem>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = low_stock_items()
+            .count()
+            .get_result::<i64>(&mut conn)
+            .context("Failed to count low stock items")?;
+
+        let mut query = low_stock_items();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(inventory::sku.gt(after.sku.clone()));
+        }
+
+        let rows = query
+            .select(low_stock_columns())
+            .order(inventory::sku)
+            .limit(page_request.fetch_limit())
+            .load::<LowStockItem>(&mut conn)
+            .context("Failed to get low stock items")?;
+
+        Ok::<_, anyhow::Error>(Page::new(rows, page_request.limit, total, |item| LowStockCursor {
+            sku: item.sku.clone()
+        }))
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting items which crossed reorder threshold since last alert",
+    skip(conn)
+)]
+pub async fn get_unalerted_low_stock_items(
+    mut conn: DbConnection
+) -> Result<Vec<LowStockItem>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<Vec<LowStockItem>, anyhow::Error, _>(|conn| {
+            // Items restocked above their threshold are alerted again the next time they cross it
+            diesel::update(inventory::table)
+                .filter(inventory::low_stock_alerted_at.is_not_null())
+                .filter(
+                    inventory::reorder_threshold.is_null()
+                        .or(inventory::amount.assume_not_null().gt(inventory::reorder_threshold.assume_not_null()))
+                )
+                .set(inventory::low_stock_alerted_at.eq(None::<DateTime<Utc>>))
+                .execute(conn)
+                .context("Failed to re-arm low stock alerts")?;
+
+            low_stock_items()
+                .filter(inventory::low_stock_alerted_at.is_null())
+                .select(low_stock_columns())
+                .order(inventory::sku)
+                .load::<LowStockItem>(conn)
+                .context("Failed to get low stock items")
+        })
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Marking low stock items as alerted",
+    skip(conn, item_ids, alerts)
+)]
+pub async fn mark_low_stock_alerted(
+    mut conn: DbConnection,
+    item_ids: Vec<Uuid>,
+    alerts: Vec<NewEmail>
+) -> Result<(), anyhow::Error> {
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            for alert in alerts {
+                enqueue_email(conn, alert)?;
+            }
+
+            diesel::update(inventory::table)
+                .filter(inventory::item_id.eq_any(item_ids))
+                .set(inventory::low_stock_alerted_at.eq(Utc::now()))
+                .execute(conn)
+        })
+        .context("Failed to mark low stock items as alerted")
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use uuid::Uuid;
+
+use crate::{db_interaction::{enqueue_email, order_price_breakdown, NewEmail}, email_templates::EmailTemplateError, schema::{inventory, order_items, orders, shipments, users}, utils::DbConnection};
+
+// Line of an order as customer is told about it
+#[derive(Debug, Clone)]
+pub struct OrderNoticeLine {
+    pub name: String,
+    pub sku: String,
+    pub quantity: i32,
+    // What the line costs after its discounts with its tax
+    pub amount_cents: i64
+}
+
+// Struct representing what customer is told about their order
+#[derive(Debug, Clone)]
+pub struct OrderNotice {
+    pub order_id: Uuid,
+    pub email: Option<String>,
+    pub lines: Vec<OrderNoticeLine>,
+    pub total_cents: i64,
+    // (carrier, tracking_number) of shipments sent so far
+    pub tracking: Vec<(String, String)>
+}
+
+// Details of an order needed to email its customer, None if order doesn't exist
+pub fn order_notice(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<Option<OrderNotice>> {
+    let Some(user_id) = orders::table
+        .find(order_id)
+        .select(orders::user_id)
+        .get_result::<Option<Uuid>>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let email = match user_id {
+        Some(user_id) => users::table
+            .find(user_id)
+            .select(users::email)
+            .get_result::<String>(conn)
+            .optional()?,
+        None => None
+    };
+
+    let lines = order_items::table
+        .inner_join(inventory::table)
+        .filter(order_items::order_id.eq(order_id))
+        .select((
+            inventory::name,
+            inventory::sku,
+            order_items::quantity,
+            (order_items::unit_price_cents, order_items::discount_cents, order_items::tax_cents)
+        ))
+        .order((inventory::name, order_items::order_item_id))
+        .load::<(String, String, i32, (i64, i64, i64))>(conn)?
+        .into_iter()
+        .map(|(name, sku, quantity, (unit_price_cents, discount_cents, tax_cents))| OrderNoticeLine {
+            name,
+            sku,
+            quantity,
+            amount_cents: unit_price_cents * quantity as i64 - discount_cents + tax_cents
+        })
+        .collect();
+
+    let tracking = shipments::table
+        .filter(shipments::order_id.eq(order_id))
+        .order((shipments::shipped_at, shipments::shipment_id))
+        .select((shipments::carrier, shipments::tracking_number))
+        .load::<(String, String)>(conn)?;
+
+    Ok(Some(OrderNotice {
+        order_id,
+        email,
+        lines,
+        total_cents: order_price_breakdown(conn, order_id)?.total_cents,
+        tracking
+    }))
+}
+
+// Queue email about order rendered from its details as they stand, within the caller's transaction
+pub fn enqueue_order_email<E>(
+    conn: &mut DbConnection,
+    order_id: Uuid,
+    email: impl FnOnce(&OrderNotice) -> Result<Option<NewEmail>, EmailTemplateError>
+) -> Result<(), E>
+where
+    E: From<diesel::result::Error> + From<EmailTemplateError>
+{
+    let Some(notice) = order_notice(conn, order_id)? else {
+        return Ok(());
+    };
+
+    if let Some(email) = email(&notice)? {
+        enqueue_email(conn, email)?;
+    }
+
+    Ok(())
+}
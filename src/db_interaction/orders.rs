@@ -1,35 +1,151 @@
-use std::{error::Error, fmt::Debug};
+use std::{collections::HashMap, error::Error, fmt::Debug};
 
-use chrono::Utc;
-use diesel::{Connection, JoinOnDsl};
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, JoinOnDsl, NullableExpressionMethods, OptionalExtension};
 use anyhow::Context;
 use diesel::{RunQueryDsl, QueryDsl, ExpressionMethods};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{models::{Order, OrderIntermediate, OrderItemModel}, routes::order::update::OrderStatus, schema::{order_items, orders}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+use crate::{db_interaction::{adjust_variant_stock, allocate_order_lines, commit_stock_reservations, enqueue_email, enqueue_order_email, lock_available_variant_stock, lock_backorder_capacity, order_notice, order_price_breakdown, order_shipments, NewEmail, OrderNotice, apply_order_promotions, item_tax_rates, lines_weight_grams, price_shipping, redeem_coupon, CouponRejection, ShipmentWithLines, ShippingRejection, StockChange, StockMovementReason}, domain::{allocation::{AllocationStrategy, Location}, coupon::CouponCode, promotion::{PriceBreakdown, PricedLine}, address::Destination, tax::tax_cents}, email_templates::EmailTemplateError, pagination::{Page, PageRequest}, models::{Order, OrderIntermediate, OrderItemAllocation, OrderItemModel}, routes::order::update::OrderStatus, schema::{coupons, inventory, order_item_allocations, order_items, orders, payments, product_variants, warehouses}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Error associated with deleting orders
+#[derive(Error)]
+pub enum DeleteOrderError{
+    #[error("Tokio threadpool error occured")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("order_id: {0} is paid for so it can't be deleted")]
+    PaidOrderError(Uuid),
+    #[error("order_id: {0} has shipments so it can't be deleted")]
+    ShippedOrderError(Uuid),
+    #[error("Failed to render email")]
+    EmailTemplateError(#[from] EmailTemplateError),
+    #[error("Failed to delete order")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for DeleteOrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
 
 // Function to delete order from DB
+// Stock of the order is returned to inventory as a cancellation
+// Orders whose payment went through or which started shipping are kept
+// Customer's cancellation email is queued in the same transaction
 pub async fn delete_order_from_database(
     mut conn: DbConnection,
-    order_id: Uuid
-) -> Result<(), anyhow::Error> {
-    let res = spawn_blocking_with_tracing(move || {
-        conn.transaction::<(), anyhow::Error, _>(|conn| {
+    order_id: Uuid,
+    actor_id: Uuid,
+    email: impl FnOnce(&OrderNotice) -> Result<Option<NewEmail>, EmailTemplateError> + Send + 'static
+) -> Result<(), DeleteOrderError> {
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<(), DeleteOrderError, _>(|conn| {
+            let order = orders::table
+                .find(order_id)
+                .select((orders::status, orders::paid_at))
+                .for_update()
+                .get_result::<(String, Option<DateTime<Utc>>)>(conn)
+                .optional()
+                .context("Failed to get order status")?;
+
+            let Some((status, paid_at)) = order else {
+                return Ok(());
+            };
+
+            // Authorized payments are about to be captured so they count as paid
+            let payment_held = payments::table
+                .filter(payments::order_id.eq(order_id))
+                .filter(payments::status.eq_any(["authorized", "captured"]))
+                .count()
+                .get_result::<i64>(conn)
+                .context("Failed to get payments of order")? > 0;
+
+            if paid_at.is_some() || payment_held {
+                return Err(DeleteOrderError::PaidOrderError(order_id));
+            }
+
+            // Stock handed to a carrier can't come back through a cancellation
+            if status != "pending" {
+                return Err(DeleteOrderError::ShippedOrderError(order_id));
+            }
+
+            let stock_change = StockChange {
+                reference_id: Some(order_id),
+                actor_id: Some(actor_id),
+                ..StockChange::new(StockMovementReason::Cancellation)
+            };
+
+            // Stock goes back to warehouses it was picked from
+            // Items ordered before warehouses existed go back to the default warehouse
+            // Backordered quantity never left stock so there is nothing to return
+            let ordered = order_items::table
+                .left_join(order_item_allocations::table)
+                .filter(order_items::order_id.eq(order_id))
+                .select((
+                    order_items::variant_id,
+                    order_items::quantity - order_items::backordered_quantity,
+                    order_item_allocations::warehouse_id.nullable(),
+                    order_item_allocations::quantity.nullable()
+                ))
+                .load::<(Uuid, i32, Option<Uuid>, Option<i32>)>(conn)
+                .context("Failed to get order items")?;
+
+            for (variant_id, picked_quantity, warehouse_id, allocated_quantity) in ordered {
+                let quantity = allocated_quantity.unwrap_or(picked_quantity);
+                if quantity == 0 {
+                    continue;
+                }
+
+                let change = StockChange {
+                    warehouse_id,
+                    ..stock_change.clone()
+                };
+
+                adjust_variant_stock(conn, variant_id, quantity, &change)
+                    .context("Failed to restock cancelled order item")?;
+            }
+
+            // Customer is told what was cancelled so it is rendered before the order goes
+            let cancellation_email = match order_notice(conn, order_id).context("Failed to get details of cancelled order")? {
+                Some(notice) => email(&notice)?,
+                None => None
+            };
+
+            // Attempts which never took money go with the order
+            diesel::delete(payments::table)
+                .filter(payments::order_id.eq(order_id))
+                .execute(conn)
+                .context("Failed to delete unpaid payments of order")?;
 
             diesel::delete(orders::table)
                 .filter(orders::order_id.eq(order_id))
                 .execute(conn)
                 .context("Failed to delete order")?;
+
+            if let Some(cancellation_email) = cancellation_email {
+                enqueue_email(conn, cancellation_email)
+                    .context("Failed to queue cancellation email")?;
+            }
             
             Ok(())
         })
     })
-    .await
-    .map_err(|_| anyhow::anyhow!("Failed due to internal error"))??;
+    .await??;
 
-    Ok(res)
+    Ok(())
+}
+
+// Position of an order within listing ordered from newest to oldest
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OrderCursor {
+    pub order_date: DateTime<Utc>,
+    pub order_id: Uuid
 }
 
 #[tracing::instrument(
@@ -38,23 +154,19 @@ pub async fn delete_order_from_database(
 )]
 pub async fn get_order_with_items(
     mut conn: DbConnection,
-    page: i64,
-    limit: i64,
+    page_request: PageRequest<OrderCursor>,
     user_id: Uuid,
     is_admin: bool
-) -> Result<Vec<OrderWithItems>, anyhow::Error> {
+) -> Result<Page<OrderWithItems>, anyhow::Error> {
 
     let res = spawn_blocking_with_tracing(move || {
-        conn.transaction::<Vec<OrderWithItems>, anyhow::Error, _>(|conn|{
-            let order_ids = get_order_ids(conn, is_admin, user_id, page, limit)?;
-            let mut ret: Vec<OrderWithItems> = Vec::new();
-
-            for order_id in order_ids{
-                let curr = get_order_with_items_by_id(conn, order_id)?;
-                ret.push(curr);
-            }
-
-            Ok(ret)
+        conn.transaction::<Page<OrderWithItems>, anyhow::Error, _>(|conn|{
+            get_order_ids(conn, is_admin, user_id, &page_request)?
+                .try_map_items(|order_ids| {
+                    order_ids.into_iter()
+                        .map(|(order_id, _)| get_order_with_items_by_id(conn, order_id))
+                        .collect()
+                })
         })
     })
     .await
@@ -71,32 +183,69 @@ pub fn get_order_ids(
     conn: &mut DbConnection,
     is_admin: bool,
     user_id: Uuid,
-    page: i64,
-    limit: i64
-) -> Result<Vec<Uuid>, anyhow::Error>{
+    page_request: &PageRequest<OrderCursor>
+) -> Result<Page<(Uuid, DateTime<Utc>)>, anyhow::Error>{
+    let mut count_query = orders::table
+        .into_boxed();
     let mut query = orders::table
         .into_boxed();
 
     if !is_admin {
+        count_query = count_query.filter(orders::user_id.eq(user_id));
         query = query.filter(orders::user_id.eq(user_id));
     }
 
-    let offset_value = (page - 1) * limit;
+    let total = count_query
+        .count()
+        .get_result::<i64>(conn)
+        .context("Failed to count orders")?;
 
-    let result = query.select(orders::order_id)
-        .limit(limit)
-        .offset(offset_value)
-        .load::<Uuid>(conn)
+    if let Some(after) = &page_request.after {
+        query = query.filter(
+            orders::order_date.lt(after.order_date)
+                .or(orders::order_date.eq(after.order_date).and(orders::order_id.lt(after.order_id)))
+        );
+    }
+
+    let result = query.select((orders::order_id, orders::order_date))
+        .order((orders::order_date.desc(), orders::order_id.desc()))
+        .limit(page_request.fetch_limit())
+        .load::<(Uuid, DateTime<Utc>)>(conn)
         .context("Failed to load order_ids")?;
 
-    Ok(result)
+    Ok(Page::new(result, page_request.limit, total, |(order_id, order_date)| OrderCursor {
+        order_date: *order_date,
+        order_id: *order_id
+    }))
+}
+
+// Struct to represent quantity of an order item to be picked from a warehouse
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrderItemPick {
+    pub warehouse_id: Uuid,
+    pub warehouse_code: String,
+    pub quantity: i32,
 }
 
 // Struct to represent order item within OrderWithItems
 #[derive(Serialize, Deserialize)]
 pub struct OrderItem {
+    pub order_item_id: Uuid,
     pub item_id: Uuid,
+    pub variant_id: Uuid,
     pub quantity: i32,
+    // Quantity waiting for a restock before it can be picked
+    pub backordered_quantity: i32,
+    // Price of a single unit in cents when ordered
+    pub unit_price_cents: i64,
+    pub refunded_quantity: i32,
+    // Taken off the line by promotions and coupon
+    pub discount_cents: i64,
+    pub tax_rate_basis_points: i32,
+    pub tax_cents: i64,
+    // Quantity handed to carriers so far
+    pub shipped_quantity: i32,
+    pub allocations: Vec<OrderItemPick>,
 }
 
 // Struct to represent an order (with associated items)
@@ -106,7 +255,16 @@ pub struct OrderWithItems {
     pub user_id: Uuid,
     pub order_date: String,
     pub status: String,
+    // When payment for the order was captured
+    pub paid_at: Option<DateTime<Utc>>,
+    pub coupon_code: Option<String>,
+    // Taken off the order's subtotal by its coupon
+    pub discount_cents: i64,
+    // Subtotal along with promotions and coupon taken off it
+    pub pricing: PriceBreakdown,
     pub items: Vec<OrderItem>,
+    // Parcels handed to carriers along with their tracking numbers
+    pub shipments: Vec<ShipmentWithLines>,
 }
 
 #[tracing::instrument(
@@ -123,11 +281,55 @@ pub fn get_order_with_items_by_id(conn: &mut DbConnection, target_order_id: Uuid
             orders::order_date,
             orders::status,
             order_items::item_id,
+            order_items::variant_id,
             order_items::quantity,
+            order_items::order_item_id,
+            order_items::backordered_quantity,
+            order_items::unit_price_cents,
+            order_items::refunded_quantity,
+            order_items::discount_cents,
+            order_items::tax_rate_basis_points,
+            order_items::tax_cents,
+            order_items::shipped_quantity,
         ))
         .load::<OrderIntermediate>(conn)
         .context("Failed to get order items by order_id")?;
 
+    let order_item_ids: Vec<Uuid> = results.iter()
+        .map(|order_intermediate| order_intermediate.order_item_id)
+        .collect();
+
+    let mut picks_by_order_item: HashMap<Uuid, Vec<OrderItemPick>> = HashMap::new();
+    for (order_item_id, pick) in order_item_allocations::table
+        .inner_join(warehouses::table)
+        .filter(order_item_allocations::order_item_id.eq_any(&order_item_ids))
+        .select((
+            order_item_allocations::order_item_id,
+            (warehouses::warehouse_id, warehouses::code, order_item_allocations::quantity)
+        ))
+        .order((warehouses::priority, warehouses::code))
+        .load::<(Uuid, (Uuid, String, i32))>(conn)
+        .context("Failed to get allocations of order items")?
+    {
+        let (warehouse_id, warehouse_code, quantity) = pick;
+        picks_by_order_item.entry(order_item_id)
+            .or_default()
+            .push(OrderItemPick { warehouse_id, warehouse_code, quantity });
+    }
+
+    let (paid_at, coupon_code, discount_cents) = orders::table
+        .left_join(coupons::table)
+        .filter(orders::order_id.eq(target_order_id))
+        .select((orders::paid_at, coupons::code.nullable(), orders::discount_cents))
+        .get_result::<(Option<DateTime<Utc>>, Option<String>, i64)>(conn)
+        .context("Failed to get payment and discount of order")?;
+
+    let pricing = order_price_breakdown(conn, target_order_id)
+        .context("Failed to get price breakdown of order")?;
+
+    let shipments = order_shipments(conn, target_order_id)
+        .context("Failed to get shipments of order")?;
+
     // Group items by order and create OrderWithItems structure
     let mut items = Vec::new();
     let mut order_info: Option<OrderWithItems> = None;
@@ -137,13 +339,31 @@ pub fn get_order_with_items_by_id(conn: &mut DbConnection, target_order_id: Uuid
             order_info = Some(OrderWithItems {
                 order_id: order_intermediate.order_id,
                 user_id: order_intermediate.user_id.unwrap(),
-                order_date: order_intermediate.order_date.unwrap().to_string(),
+                order_date: order_intermediate.order_date.to_string(),
                 status: order_intermediate.status,
+                paid_at,
+                coupon_code: coupon_code.clone(),
+                discount_cents,
+                pricing: pricing.clone(),
                 items: Vec::new(),
+                shipments: shipments.clone(),
             });
         }
 
-        items.push(OrderItem{ item_id: order_intermediate.item_id, quantity: order_intermediate.quantity });
+        items.push(OrderItem{
+            order_item_id: order_intermediate.order_item_id,
+            item_id: order_intermediate.item_id,
+            variant_id: order_intermediate.variant_id,
+            quantity: order_intermediate.quantity,
+            backordered_quantity: order_intermediate.backordered_quantity,
+            unit_price_cents: order_intermediate.unit_price_cents,
+            refunded_quantity: order_intermediate.refunded_quantity,
+            discount_cents: order_intermediate.discount_cents,
+            tax_rate_basis_points: order_intermediate.tax_rate_basis_points,
+            tax_cents: order_intermediate.tax_cents,
+            shipped_quantity: order_intermediate.shipped_quantity,
+            allocations: picks_by_order_item.remove(&order_intermediate.order_item_id).unwrap_or_default()
+        });
     }
 
     if let Some(mut order) = order_info {
@@ -154,6 +374,17 @@ pub fn get_order_with_items_by_id(conn: &mut DbConnection, target_order_id: Uuid
     }
 }
 
+// Price of variant in cents as it is charged, variant price taking precedence over item price
+pub fn unit_price_cents(conn: &mut DbConnection, variant_id: Uuid) -> diesel::QueryResult<i64> {
+    let (variant_price, item_price) = product_variants::table
+        .inner_join(inventory::table)
+        .filter(product_variants::variant_id.eq(variant_id))
+        .select((product_variants::price, inventory::price))
+        .get_result::<(Option<f64>, Option<f64>)>(conn)?;
+
+    Ok((variant_price.or(item_price).unwrap_or(0.0) * 100.0).round() as i64)
+}
+
 // Error associated with creating orders and decrementing inventory stock
 #[derive(Error)]
 pub enum CreateOrderUpdateInventoryError{
@@ -162,7 +393,13 @@ pub enum CreateOrderUpdateInventoryError{
     #[error("Failed to run query")]
     RunQueryError(#[from] diesel::result::Error),
     #[error("None of the requested items have Stocks available")]
-    NoStockError
+    NoStockError,
+    #[error(transparent)]
+    CouponRejected(#[from] CouponRejection),
+    #[error(transparent)]
+    ShippingRejected(#[from] ShippingRejection),
+    #[error("Failed to render email")]
+    EmailTemplateError(#[from] EmailTemplateError)
 }
 
 impl Debug for CreateOrderUpdateInventoryError {
@@ -172,49 +409,111 @@ impl Debug for CreateOrderUpdateInventoryError {
     }
 }
 
+// Struct representing what a customer gives along with order lines
+#[derive(Debug, Default)]
+pub struct OrderDetails {
+    // Location warehouses are picked by distance to
+    pub ship_to: Option<Location>,
+    pub coupon_code: Option<CouponCode>,
+    // Where the order is taxed and shipped, orders without one aren't taxed
+    pub destination: Option<Destination>,
+    // Orders without a shipping method aren't charged for shipping
+    pub shipping_method_id: Option<Uuid>
+}
+
+// Struct representing an order just placed along with how its total was arrived at
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlacedOrder {
+    pub order_id: Uuid,
+    // Variants that made it into the order
+    pub variant_ids: Vec<Uuid>,
+    pub pricing: PriceBreakdown
+}
+
 #[tracing::instrument(
     "Creating order in order table and updating inventory",
     skip_all
 )]
 pub async fn create_order_and_update_inventory(
     mut conn: DbConnection,
-    item_ids: Vec<Uuid>,
+    variant_ids: Vec<Uuid>,
     amounts: Vec<i32>,
-    user_id: Uuid
-) -> Result<Vec<Uuid>, CreateOrderUpdateInventoryError> {
+    user_id: Uuid,
+    strategy: AllocationStrategy,
+    details: OrderDetails,
+    email: impl FnOnce(&OrderNotice) -> Result<Option<NewEmail>, EmailTemplateError> + Send + 'static
+) -> Result<PlacedOrder, CreateOrderUpdateInventoryError> {
 
-    let ret: Vec<Uuid> = spawn_blocking_with_tracing(move || {
-        use crate::schema::inventory;
+    let ret = spawn_blocking_with_tracing(move || {
         use crate::schema::orders;
         use crate::schema::order_items;
 
-        conn.transaction::<Vec<Uuid>, CreateOrderUpdateInventoryError, _>(|conn|{
-            let mut successful_updates = Vec::new();
+        conn.transaction::<PlacedOrder, CreateOrderUpdateInventoryError, _>(|conn|{
+            let order_id = Uuid::new_v4();
+            let stock_change = StockChange {
+                reference_id: Some(order_id),
+                actor_id: Some(user_id),
+                ..StockChange::new(StockMovementReason::Order)
+            };
             
-            // Start of updating inventory of items whose requested amounts <= available stock
-            for (i, item_id) in item_ids.iter().enumerate() {
-                let affected_rows: usize = diesel::update(
-                       inventory::table.filter(inventory::item_id.eq(*item_id))
-                    )
-                    .set(inventory::amount.eq(inventory::amount - amounts[i]))
-                    .filter(inventory::amount.ge(amounts[i]))
-                    .execute(conn)?;
+            // Start of picking lines whose requested amounts <= available stock
+            // Stock held by other customers' reservations isn't available
+            // Shortfall of items taking backorders is accepted up to their limit
+            let mut lines = Vec::new();
+            let mut line_item_ids = Vec::new();
+            let mut line_backordered = Vec::new();
+            let mut line_unit_prices = Vec::new();
+            let mut taken_by_variant: HashMap<Uuid, i64> = HashMap::new();
+            let mut backordered_by_item: HashMap<Uuid, i64> = HashMap::new();
+
+            for (i, variant_id) in variant_ids.iter().enumerate() {
+                let Some((item_id, available)) = lock_available_variant_stock(conn, *variant_id, user_id)? else {
+                    continue;
+                };
 
+                let taken = taken_by_variant.entry(*variant_id).or_default();
+                let in_stock = (available - *taken).clamp(0, amounts[i] as i64);
+                let shortfall = amounts[i] as i64 - in_stock;
 
-                if affected_rows > 0 {
-                    successful_updates.push((*item_id, amounts[i]));
+                if shortfall > 0 {
+                    let backordered = backordered_by_item.entry(item_id).or_default();
+                    if lock_backorder_capacity(conn, item_id)? - *backordered < shortfall {
+                        continue;
+                    }
+                    *backordered += shortfall;
                 }
+
+                *taken += in_stock;
+                lines.push((*variant_id, in_stock as i32));
+                line_item_ids.push(item_id);
+                line_backordered.push(shortfall as i32);
+                line_unit_prices.push(unit_price_cents(conn, *variant_id)?);
             }
-            // End of updating inventory
+            // End of picking lines
 
-            if successful_updates.len() == 0 {
+            if lines.is_empty() {
                 return Err(CreateOrderUpdateInventoryError::NoStockError)
             }
 
+            // Start of updating stock at warehouses lines are allocated to
+            let allocations = allocate_order_lines(conn, strategy, &lines, details.ship_to)?
+                .ok_or(CreateOrderUpdateInventoryError::NoStockError)?;
+
+            for allocation in &allocations {
+                let change = StockChange {
+                    warehouse_id: Some(allocation.warehouse_id),
+                    ..stock_change.clone()
+                };
+
+                adjust_variant_stock(conn, allocation.variant_id, -allocation.quantity, &change)?
+                    .ok_or(CreateOrderUpdateInventoryError::NoStockError)?;
+            }
+            // End of updating stock
+
             // Start of Creating order
             
             let order = Order{
-                order_id: Uuid::new_v4(),
+                order_id,
                 user_id,
                 order_date: Utc::now(),
                 status: "pending".to_string()
@@ -227,24 +526,128 @@ pub async fn create_order_and_update_inventory(
             // End of creating order
             
 
-            // Start of creating order_item
+            let priced_lines: Vec<PricedLine> = lines.iter()
+                .zip(&line_item_ids)
+                .enumerate()
+                .map(|(line, ((_, in_stock), item_id))| PricedLine {
+                    item_id: *item_id,
+                    unit_price_cents: line_unit_prices[line],
+                    quantity: in_stock + line_backordered[line]
+                })
+                .collect();
+
+            // Start of discounting and taxing lines
+            // Promotions are taken off first, coupons discount what lines cost after them
+            // Coupons restricted to items or categories discount only their lines
+            let promotion_outcome = apply_order_promotions(conn, order_id, &priced_lines)?;
+            let mut line_discounts = promotion_outcome.line_discounts;
+
+            if let Some(code) = &details.coupon_code {
+                let coupon_lines: Vec<(Uuid, i64)> = priced_lines.iter()
+                    .zip(&line_discounts)
+                    .map(|(line, discount)| (line.item_id, line.unit_price_cents * line.quantity as i64 - discount))
+                    .collect();
+
+                let (_, coupon_discounts) = redeem_coupon(conn, code, order_id, user_id, &coupon_lines)??;
+                for (discount, coupon_discount) in line_discounts.iter_mut().zip(coupon_discounts) {
+                    *discount += coupon_discount;
+                }
+            }
+
+            // Tax is charged on what each line costs after discounts
+            let tax_rates = item_tax_rates(conn, details.destination.as_ref(), &line_item_ids)?;
+            let line_taxes: Vec<(i32, i64)> = priced_lines.iter()
+                .zip(&line_discounts)
+                .map(|(line, discount)| {
+                    let rate = tax_rates.get(&line.item_id).copied().unwrap_or(0);
+                    (rate, tax_cents(line.unit_price_cents * line.quantity as i64 - discount, rate))
+                })
+                .collect();
+
+            diesel::update(orders::table.find(order_id))
+                .set((
+                    orders::ship_country.eq(details.destination.as_ref().map(|destination| destination.country.clone())),
+                    orders::ship_region.eq(details.destination.as_ref().and_then(|destination| destination.region.clone())),
+                    orders::tax_cents.eq(line_taxes.iter().map(|(_, tax)| tax).sum::<i64>())
+                ))
+                .execute(conn)?;
+            // End of discounting and taxing lines
+
+            // Start of charging for shipping with the chosen method
+            if let Some(shipping_method_id) = details.shipping_method_id {
+                let subtotal_cents: i64 = priced_lines.iter()
+                    .map(|line| line.unit_price_cents * line.quantity as i64)
+                    .sum();
+                let weighed_lines: Vec<(Uuid, i32)> = priced_lines.iter()
+                    .map(|line| (line.item_id, line.quantity))
+                    .collect();
+                let weight_grams = lines_weight_grams(conn, &weighed_lines)?;
+
+                let (method, shipping_cents) = price_shipping(conn, shipping_method_id, details.destination.as_ref(), subtotal_cents, weight_grams)??;
+
+                diesel::update(orders::table.find(order_id))
+                    .set((
+                        orders::shipping_method_id.eq(Some(method.shipping_method_id)),
+                        orders::shipping_method_name.eq(Some(method.name)),
+                        orders::shipping_cents.eq(shipping_cents)
+                    ))
+                    .execute(conn)?;
+            }
+            // End of charging for shipping
+
+            // Start of creating order_item along with warehouses it is picked from
 
-            for (item_id, amount) in successful_updates.iter(){
+            for (line, ((variant_id, in_stock), item_id)) in lines.iter().zip(line_item_ids).enumerate() {
                 let order_item = OrderItemModel{
                     order_item_id: Uuid::new_v4(),
                     order_id: order.order_id,
-                    item_id: *item_id,
-                    quantity: *amount
+                    item_id,
+                    quantity: in_stock + line_backordered[line],
+                    variant_id: *variant_id,
+                    backordered_quantity: line_backordered[line],
+                    unit_price_cents: line_unit_prices[line],
+                    refunded_quantity: 0,
+                    discount_cents: line_discounts[line],
+                    tax_rate_basis_points: line_taxes[line].0,
+                    tax_cents: line_taxes[line].1,
+                    shipped_quantity: 0
                 };
 
+                let line_allocations: Vec<OrderItemAllocation> = allocations.iter()
+                    .filter(|allocation| allocation.line == line)
+                    .map(|allocation| OrderItemAllocation {
+                        allocation_id: Uuid::new_v4(),
+                        order_item_id: order_item.order_item_id,
+                        warehouse_id: allocation.warehouse_id,
+                        quantity: allocation.quantity
+                    })
+                    .collect();
+
                 diesel::insert_into(order_items::table)
                     .values(order_item)
                     .execute(conn)?;
+
+                // Fully backordered lines aren't picked from anywhere yet
+                if !line_allocations.is_empty() {
+                    diesel::insert_into(order_item_allocations::table)
+                        .values(line_allocations)
+                        .execute(conn)?;
+                }
             }
 
             // End of creating order_items 
 
-            Ok(successful_updates.iter().map(|entry| entry.0).collect())
+            let ordered_variant_ids: Vec<Uuid> = lines.iter().map(|(variant_id, _)| *variant_id).collect();
+            commit_stock_reservations(conn, user_id, order_id, &ordered_variant_ids)?;
+
+            // Confirmation is queued along with the order so it can't be lost
+            enqueue_order_email::<CreateOrderUpdateInventoryError>(conn, order_id, email)?;
+
+            Ok(PlacedOrder {
+                order_id,
+                variant_ids: ordered_variant_ids,
+                pricing: order_price_breakdown(conn, order_id)?
+            })
         })
     })
     .await??;
@@ -260,7 +663,13 @@ pub enum UpdateOrderStatusError{
     #[error("Failed to run query")]
     RunQueryError(#[from] diesel::result::Error),
     #[error("order_id: {0} doesn't exist")]
-    NoOrderIdError(Uuid)
+    NoOrderIdError(Uuid),
+    #[error("Only delivered status can be set, other statuses follow shipments")]
+    ManualStatusError,
+    #[error("order_id: {0} hasn't been shipped in full")]
+    NotShippedError(Uuid),
+    #[error("Failed to render email")]
+    EmailTemplateError(#[from] EmailTemplateError)
 }
 
 impl Debug for UpdateOrderStatusError {
@@ -271,33 +680,55 @@ impl Debug for UpdateOrderStatusError {
 }
 
 // Function to perform update order status operation
+// Only delivery is set by hand, once everything not refunded has shipped
+// Customer's email is queued along with the change, setting the same status again stays quiet
 pub async fn update_order_status(
     mut conn: DbConnection,
     status: OrderStatus,
-    order_id: Uuid
+    order_id: Uuid,
+    email: impl FnOnce(&OrderNotice) -> Result<Option<NewEmail>, EmailTemplateError> + Send + 'static
 ) -> Result<(), UpdateOrderStatusError> {
 
-    let res = spawn_blocking_with_tracing(move || {
+    spawn_blocking_with_tracing(move || {
         conn.transaction::<(), UpdateOrderStatusError, _>(|conn| {
             let status = match status {
-                OrderStatus::Pending => "pending",
-                OrderStatus::Shipped => "shipped",
+                OrderStatus::Pending | OrderStatus::Shipped => return Err(UpdateOrderStatusError::ManualStatusError),
                 OrderStatus::Delivered => "delivered"
             }.to_string();
 
-            let affected_rows = diesel::update(orders::table)
-                                    .filter(orders::order_id.eq(order_id))
-                                    .set(orders::status.eq(status))
-                                    .execute(conn)?;
+            let previous = orders::table
+                                    .find(order_id)
+                                    .select(orders::status)
+                                    .for_update()
+                                    .get_result::<String>(conn)
+                                    .optional()?
+                                    .ok_or(UpdateOrderStatusError::NoOrderIdError(order_id))?;
+
+            if previous == status {
+                return Ok(());
+            }
+
+            let unshipped = order_items::table
+                .filter(order_items::order_id.eq(order_id))
+                .filter(order_items::shipped_quantity.lt(order_items::quantity - order_items::refunded_quantity))
+                .count()
+                .get_result::<i64>(conn)?;
 
-            if affected_rows == 0 {
-                return Err(UpdateOrderStatusError::NoOrderIdError(order_id))
+            if previous != "shipped" || unshipped > 0 {
+                return Err(UpdateOrderStatusError::NotShippedError(order_id));
             }
+
+            diesel::update(orders::table)
+                .filter(orders::order_id.eq(order_id))
+                .set(orders::status.eq(&status))
+                .execute(conn)?;
+
+            enqueue_order_email::<UpdateOrderStatusError>(conn, order_id, email)?;
             
             Ok(())
         })
     })
     .await??;
 
-    Ok(res)
+    Ok(())
 }
@@ -0,0 +1,267 @@
+use std::{error::Error, fmt::Debug};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::delivery_blocked_reason, domain::email_category::EmailCategory, models::OutboxEmail, pagination::{Page, PageRequest}, schema::email_outbox, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing an email to be delivered by the outbox worker
+#[derive(Debug, Clone)]
+pub struct NewEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    // Category recipient can opt out of, None for emails which are always sent
+    pub category: Option<EmailCategory>
+}
+
+// Cursor of outbox listing, ordered by creation time, newest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxCursor {
+    pub created_at: DateTime<Utc>,
+    pub email_id: Uuid
+}
+
+// Errors associated with managing the email outbox
+#[derive(Error)]
+pub enum OutboxError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("email_id: {0} doesn't exist")]
+    NoEmailIdError(Uuid),
+    #[error("Only dead emails can be retried, email is {0}")]
+    NotRetryable(String)
+}
+
+impl Debug for OutboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Queue email for delivery, meant to be called within the transaction making the change it is about
+// so the email is sent if and only if that change commits
+pub fn enqueue_email(conn: &mut DbConnection, email: NewEmail) -> QueryResult<Uuid> {
+    let email_id = Uuid::new_v4();
+    let now = Utc::now();
+
+    diesel::insert_into(email_outbox::table)
+        .values(OutboxEmail {
+            email_id,
+            recipient: email.recipient,
+            subject: email.subject,
+            html_body: email.html_body,
+            text_body: email.text_body,
+            status: "pending".to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            created_at: now,
+            sent_at: None,
+            category: email.category.map(|category| category.as_str().to_string())
+        })
+        .execute(conn)?;
+
+    Ok(email_id)
+}
+
+// Claim emails due for delivery, those whose recipient is suppressed or opted out of their category
+// are marked suppressed instead of being handed to the worker
+#[tracing::instrument(
+    "Claiming due emails from outbox",
+    skip(conn)
+)]
+pub async fn claim_due_emails(
+    mut conn: DbConnection,
+    batch_size: i64,
+    lease_seconds: i64
+) -> Result<Vec<OutboxEmail>, anyhow::Error> {
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let now = Utc::now();
+
+            // Rows claimed by another worker are skipped instead of waited on
+            let due = email_outbox::table
+                .filter(email_outbox::status.eq("pending"))
+                .filter(email_outbox::next_attempt_at.le(now))
+                .order((email_outbox::next_attempt_at, email_outbox::email_id))
+                .limit(batch_size)
+                .for_update()
+                .skip_locked()
+                .load::<OutboxEmail>(conn)?;
+
+            let mut deliverable = Vec::with_capacity(due.len());
+            for email in due {
+                let category = email.category.as_deref().and_then(|category| EmailCategory::parse(category).ok());
+
+                match delivery_blocked_reason(conn, &email.recipient, category)? {
+                    Some(reason) => {
+                        diesel::update(email_outbox::table.find(email.email_id))
+                            .set((
+                                email_outbox::status.eq("suppressed"),
+                                email_outbox::last_error.eq(Some(reason))
+                            ))
+                            .execute(conn)?;
+                    },
+                    None => deliverable.push(email)
+                }
+            }
+
+            // Pushing the next attempt out leases the emails, a worker dying mid-send only delays them
+            let email_ids: Vec<Uuid> = deliverable.iter().map(|email| email.email_id).collect();
+            diesel::update(email_outbox::table)
+                .filter(email_outbox::email_id.eq_any(email_ids))
+                .set(email_outbox::next_attempt_at.eq(now + Duration::seconds(lease_seconds)))
+                .execute(conn)?;
+
+            Ok(deliverable)
+        })
+    })
+    .await
+    .context("Failed due to threadpool error")?
+    .context("Failed to claim due emails")
+}
+
+#[tracing::instrument(
+    "Marking outbox email as sent",
+    skip(conn)
+)]
+pub async fn mark_email_sent(
+    mut conn: DbConnection,
+    email_id: Uuid
+) -> Result<(), anyhow::Error> {
+    spawn_blocking_with_tracing(move || {
+        diesel::update(email_outbox::table.find(email_id))
+            .set((
+                email_outbox::status.eq("sent"),
+                email_outbox::attempts.eq(email_outbox::attempts + 1),
+                email_outbox::sent_at.eq(Utc::now())
+            ))
+            .execute(&mut conn)
+    })
+    .await
+    .context("Failed due to threadpool error")?
+    .context("Failed to mark email as sent")?;
+
+    Ok(())
+}
+
+// Record failed delivery attempt, email is retried after retry_in_seconds or dead-lettered without it
+#[tracing::instrument(
+    "Marking outbox email as failed",
+    skip(conn, error)
+)]
+pub async fn mark_email_failed(
+    mut conn: DbConnection,
+    email_id: Uuid,
+    error: String,
+    retry_in_seconds: Option<i64>
+) -> Result<(), anyhow::Error> {
+    spawn_blocking_with_tracing(move || {
+        let failed = diesel::update(email_outbox::table.find(email_id));
+        let attempts = email_outbox::attempts.eq(email_outbox::attempts + 1);
+        let last_error = email_outbox::last_error.eq(Some(error));
+
+        match retry_in_seconds {
+            Some(seconds) => failed
+                .set((attempts, last_error, email_outbox::next_attempt_at.eq(Utc::now() + Duration::seconds(seconds))))
+                .execute(&mut conn),
+            None => failed
+                .set((attempts, last_error, email_outbox::status.eq("dead")))
+                .execute(&mut conn)
+        }
+    })
+    .await
+    .context("Failed due to threadpool error")?
+    .context("Failed to mark email as failed")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting outbox emails from db",
+    skip(conn, page_request)
+)]
+pub async fn get_outbox_emails(
+    mut conn: DbConnection,
+    status: String,
+    page_request: PageRequest<OutboxCursor>
+) -> Result<Page<OutboxEmail>, OutboxError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = email_outbox::table
+            .filter(email_outbox::status.eq(&status))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = email_outbox::table
+            .filter(email_outbox::status.eq(&status))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                email_outbox::created_at.lt(after.created_at)
+                    .or(email_outbox::created_at.eq(after.created_at).and(email_outbox::email_id.lt(after.email_id)))
+            );
+        }
+
+        let rows = query
+            .order((email_outbox::created_at.desc(), email_outbox::email_id.desc()))
+            .limit(page_request.fetch_limit())
+            .load::<OutboxEmail>(&mut conn)?;
+
+        Ok::<_, OutboxError>(Page::new(rows, page_request.limit, total, |email| OutboxCursor {
+            created_at: email.created_at,
+            email_id: email.email_id
+        }))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Give a dead email a fresh set of attempts, delivered on the worker's next poll
+#[tracing::instrument(
+    "Retrying dead outbox email",
+    skip(conn)
+)]
+pub async fn retry_outbox_email(
+    mut conn: DbConnection,
+    email_id: Uuid
+) -> Result<OutboxEmail, OutboxError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<_, OutboxError, _>(|conn| {
+            let status = email_outbox::table
+                .find(email_id)
+                .select(email_outbox::status)
+                .for_update()
+                .get_result::<String>(conn)
+                .optional()?
+                .ok_or(OutboxError::NoEmailIdError(email_id))?;
+
+            if status != "dead" {
+                return Err(OutboxError::NotRetryable(status));
+            }
+
+            let email = diesel::update(email_outbox::table.find(email_id))
+                .set((
+                    email_outbox::status.eq("pending"),
+                    email_outbox::attempts.eq(0),
+                    email_outbox::next_attempt_at.eq(Utc::now())
+                ))
+                .get_result::<OutboxEmail>(conn)?;
+
+            Ok(email)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,244 @@
+use std::{error::Error, fmt::Debug};
+
+use chrono::Utc;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{models::Payment, schema::{order_items, orders, payments}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing lifecycle of a payment
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    RequiresPayment,
+    Authorized,
+    Captured,
+    Failed,
+    PartiallyRefunded,
+    Refunded
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::RequiresPayment => "requires_payment",
+            PaymentStatus::Authorized => "authorized",
+            PaymentStatus::Captured => "captured",
+            PaymentStatus::Failed => "failed",
+            PaymentStatus::PartiallyRefunded => "partially_refunded",
+            PaymentStatus::Refunded => "refunded"
+        }
+    }
+
+    // Statuses provider events can move a payment to from this one
+    // Refund statuses are only reached by issuing refunds
+    fn can_become(&self, next: PaymentStatus) -> bool {
+        match self {
+            PaymentStatus::RequiresPayment => matches!(next, PaymentStatus::Authorized | PaymentStatus::Captured | PaymentStatus::Failed),
+            PaymentStatus::Authorized => matches!(next, PaymentStatus::Captured | PaymentStatus::Failed),
+            _ => false
+        }
+    }
+
+    // Whether money was taken for the payment
+    pub fn is_paid(&self) -> bool {
+        matches!(self, PaymentStatus::Captured | PaymentStatus::PartiallyRefunded | PaymentStatus::Refunded)
+    }
+
+    pub fn parse(status: &str) -> Option<PaymentStatus> {
+        [
+            PaymentStatus::RequiresPayment,
+            PaymentStatus::Authorized,
+            PaymentStatus::Captured,
+            PaymentStatus::Failed,
+            PaymentStatus::PartiallyRefunded,
+            PaymentStatus::Refunded
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+// What has to happen to take payment for an order
+#[derive(Debug)]
+pub enum OrderPaymentState {
+    // Payment was started earlier and can be completed
+    Open(Payment),
+    // New payment of amount in cents is needed
+    Due(i64)
+}
+
+// Errors associated with payments of orders
+#[derive(Error)]
+pub enum PaymentError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("order_id: {0} doesn't exist")]
+    NoOrderIdError(Uuid),
+    #[error("Order is already paid")]
+    AlreadyPaid,
+    #[error("Order has nothing to pay for")]
+    NothingToPay,
+    #[error("Payment {0} doesn't exist")]
+    NoPaymentError(String)
+}
+
+impl Debug for PaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Payment of order which is in flight or captured
+fn open_payment(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<Option<Payment>> {
+    payments::table
+        .filter(payments::order_id.eq(order_id))
+        .filter(payments::status.ne(PaymentStatus::Failed.as_str()))
+        .get_result::<Payment>(conn)
+        .optional()
+}
+
+// Total of order in cents from prices recorded on its lines less its discounts plus its tax and shipping
+fn order_amount_cents(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<i64> {
+    let lines = order_items::table
+        .filter(order_items::order_id.eq(order_id))
+        .select((order_items::quantity, order_items::unit_price_cents))
+        .load::<(i32, i64)>(conn)?;
+
+    let (discount_cents, promotion_discount_cents, tax_cents, shipping_cents) = orders::table
+        .find(order_id)
+        .select((orders::discount_cents, orders::promotion_discount_cents, orders::tax_cents, orders::shipping_cents))
+        .get_result::<(i64, i64, i64, i64)>(conn)?;
+
+    let subtotal: i64 = lines.into_iter()
+        .map(|(quantity, unit_price_cents)| unit_price_cents * quantity as i64)
+        .sum();
+
+    Ok(subtotal - promotion_discount_cents - discount_cents + tax_cents + shipping_cents)
+}
+
+#[tracing::instrument(
+    "Getting payment state of order",
+    skip(conn)
+)]
+pub async fn get_order_payment_state(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    user_id: Uuid
+) -> Result<OrderPaymentState, PaymentError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let owner = orders::table
+            .find(order_id)
+            .select(orders::user_id)
+            .get_result::<Option<Uuid>>(&mut conn)
+            .optional()?;
+
+        if owner != Some(Some(user_id)) {
+            return Err(PaymentError::NoOrderIdError(order_id));
+        }
+
+        if let Some(payment) = open_payment(&mut conn, order_id)? {
+            if PaymentStatus::parse(&payment.status).is_some_and(|status| status.is_paid()) {
+                return Err(PaymentError::AlreadyPaid);
+            }
+
+            return Ok(OrderPaymentState::Open(payment));
+        }
+
+        let amount_cents = order_amount_cents(&mut conn, order_id)?;
+        if amount_cents <= 0 {
+            return Err(PaymentError::NothingToPay);
+        }
+
+        Ok(OrderPaymentState::Due(amount_cents))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Inserting payment of order",
+    skip(conn)
+)]
+pub async fn insert_payment(
+    mut conn: DbConnection,
+    payment: Payment
+) -> Result<Payment, PaymentError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let order_id = payment.order_id;
+
+        match diesel::insert_into(payments::table)
+            .values(payment)
+            .get_result::<Payment>(&mut conn)
+        {
+            Ok(payment) => Ok(payment),
+            // Another request started paying for the order first
+            Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
+                open_payment(&mut conn, order_id)?
+                    .ok_or(PaymentError::NoOrderIdError(order_id))
+            },
+            Err(e) => Err(e.into())
+        }
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Applying payment provider event to payment",
+    skip(conn)
+)]
+pub async fn update_payment_status(
+    mut conn: DbConnection,
+    provider_reference: String,
+    status: PaymentStatus,
+    failure_reason: Option<String>
+) -> Result<Payment, PaymentError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<Payment, PaymentError, _>(|conn| {
+            let payment = payments::table
+                .filter(payments::provider_reference.eq(&provider_reference))
+                .for_update()
+                .get_result::<Payment>(conn)
+                .optional()?
+                .ok_or_else(|| PaymentError::NoPaymentError(provider_reference.clone()))?;
+
+            // Providers retry and reorder events, stale ones leave the payment as it is
+            let current = PaymentStatus::parse(&payment.status);
+            if !current.is_some_and(|current| current.can_become(status)) {
+                return Ok(payment);
+            }
+
+            let now = Utc::now();
+            let captured_at = (status == PaymentStatus::Captured).then_some(now);
+
+            let payment = diesel::update(payments::table.find(payment.payment_id))
+                .set((
+                    payments::status.eq(status.as_str()),
+                    payments::failure_reason.eq(failure_reason),
+                    payments::updated_at.eq(now),
+                    payments::captured_at.eq(captured_at)
+                ))
+                .get_result::<Payment>(conn)?;
+
+            // Order records it was paid so it is kept along with its payment
+            if let Some(captured_at) = captured_at {
+                diesel::update(orders::table.find(payment.order_id))
+                    .set(orders::paid_at.eq(captured_at))
+                    .execute(conn)?;
+            }
+
+            Ok(payment)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
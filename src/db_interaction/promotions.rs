@@ -0,0 +1,451 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::unit_price_cents, domain::promotion::{apply_promotions, AppliedPromotion, BundleItem, PriceBreakdown, PricedLine, Promotion, PromotionOutcome, PromotionRule, PromotionTier}, models::{OrderPromotion, PromotionModel}, pagination::{Page, PageRequest}, schema::{inventory, order_items, order_promotions, orders, product_variants, promotion_items, promotion_tiers, promotions}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing validated promotion settings saved by an admin
+#[derive(Debug, Clone)]
+pub struct PromotionSettings {
+    pub name: String,
+    pub priority: i32,
+    pub active: bool,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub rule: PromotionRule
+}
+
+// Struct representing a promotion along with its rule
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PromotionWithRule {
+    pub promotion_id: Uuid,
+    pub name: String,
+    pub priority: i32,
+    pub active: bool,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub rule: PromotionRule
+}
+
+// Struct representing position of a promotion within promotions listing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PromotionCursor {
+    pub priority: i32,
+    pub promotion_id: Uuid
+}
+
+// Errors associated with managing and evaluating promotions
+#[derive(Error)]
+pub enum PromotionError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("promotion_id: {0} doesn't exist")]
+    NoPromotionIdError(Uuid),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for PromotionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Items a rule counts along with quantity needed of each
+fn rule_items(rule: &PromotionRule) -> Vec<(Uuid, i32)> {
+    match rule {
+        PromotionRule::BuyXGetY { item_ids, .. } | PromotionRule::Tiered { item_ids, .. } => item_ids.iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|item_id| (*item_id, 1))
+            .collect(),
+        PromotionRule::Bundle { items, .. } => items.iter()
+            .map(|item| (item.item_id, item.quantity))
+            .collect()
+    }
+}
+
+fn check_items_exist(conn: &mut DbConnection, rule: &PromotionRule) -> Result<(), PromotionError> {
+    let item_ids: Vec<Uuid> = rule_items(rule).into_iter().map(|(item_id, _)| item_id).collect();
+
+    let found: HashSet<Uuid> = inventory::table
+        .filter(inventory::item_id.eq_any(&item_ids))
+        .select(inventory::item_id)
+        .load::<Uuid>(conn)?
+        .into_iter()
+        .collect();
+
+    if let Some(missing) = item_ids.iter().find(|item_id| !found.contains(item_id)) {
+        return Err(PromotionError::NoItemIdError(*missing));
+    }
+
+    Ok(())
+}
+
+fn replace_rule_rows(conn: &mut DbConnection, promotion_id: Uuid, rule: &PromotionRule) -> QueryResult<()> {
+    diesel::delete(promotion_items::table.filter(promotion_items::promotion_id.eq(promotion_id)))
+        .execute(conn)?;
+    diesel::delete(promotion_tiers::table.filter(promotion_tiers::promotion_id.eq(promotion_id)))
+        .execute(conn)?;
+
+    let items: Vec<_> = rule_items(rule).into_iter()
+        .map(|(item_id, quantity)| (
+            promotion_items::promotion_id.eq(promotion_id),
+            promotion_items::item_id.eq(item_id),
+            promotion_items::quantity.eq(quantity)
+        ))
+        .collect();
+    diesel::insert_into(promotion_items::table)
+        .values(items)
+        .execute(conn)?;
+
+    if let PromotionRule::Tiered { tiers, .. } = rule {
+        let tiers: Vec<_> = tiers.iter()
+            .map(|tier| (
+                promotion_tiers::promotion_id.eq(promotion_id),
+                promotion_tiers::min_quantity.eq(tier.min_quantity),
+                promotion_tiers::percent_off.eq(tier.percent_off)
+            ))
+            .collect();
+        diesel::insert_into(promotion_tiers::table)
+            .values(tiers)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+// Rebuild rules of promotions from their rows keeping their order
+fn with_rules(conn: &mut DbConnection, rows: Vec<PromotionModel>) -> QueryResult<Vec<PromotionWithRule>> {
+    let promotion_ids: Vec<Uuid> = rows.iter().map(|promotion| promotion.promotion_id).collect();
+
+    let mut items_by_promotion: HashMap<Uuid, Vec<(Uuid, i32)>> = HashMap::new();
+    for (promotion_id, item_id, quantity) in promotion_items::table
+        .filter(promotion_items::promotion_id.eq_any(&promotion_ids))
+        .order(promotion_items::item_id)
+        .select((promotion_items::promotion_id, promotion_items::item_id, promotion_items::quantity))
+        .load::<(Uuid, Uuid, i32)>(conn)?
+    {
+        items_by_promotion.entry(promotion_id).or_default().push((item_id, quantity));
+    }
+
+    let mut tiers_by_promotion: HashMap<Uuid, Vec<PromotionTier>> = HashMap::new();
+    for (promotion_id, min_quantity, percent_off) in promotion_tiers::table
+        .filter(promotion_tiers::promotion_id.eq_any(&promotion_ids))
+        .order(promotion_tiers::min_quantity)
+        .load::<(Uuid, i32, i32)>(conn)?
+    {
+        tiers_by_promotion.entry(promotion_id).or_default().push(PromotionTier { min_quantity, percent_off });
+    }
+
+    Ok(rows.into_iter()
+        .map(|promotion| {
+            let items = items_by_promotion.remove(&promotion.promotion_id).unwrap_or_default();
+            let item_ids = items.iter().map(|(item_id, _)| *item_id).collect();
+
+            let rule = match promotion.kind.as_str() {
+                "buy_x_get_y" => PromotionRule::BuyXGetY {
+                    item_ids,
+                    buy_quantity: promotion.buy_quantity.unwrap_or_default(),
+                    get_quantity: promotion.get_quantity.unwrap_or_default(),
+                    percent_off: promotion.percent_off.unwrap_or_default()
+                },
+                "tiered" => PromotionRule::Tiered {
+                    item_ids,
+                    tiers: tiers_by_promotion.remove(&promotion.promotion_id).unwrap_or_default()
+                },
+                _ => PromotionRule::Bundle {
+                    items: items.into_iter().map(|(item_id, quantity)| BundleItem { item_id, quantity }).collect(),
+                    price_cents: promotion.bundle_price_cents.unwrap_or_default()
+                }
+            };
+
+            PromotionWithRule {
+                promotion_id: promotion.promotion_id,
+                name: promotion.name,
+                priority: promotion.priority,
+                active: promotion.active,
+                starts_at: promotion.starts_at,
+                ends_at: promotion.ends_at,
+                created_at: promotion.created_at,
+                rule
+            }
+        })
+        .collect())
+}
+
+// Columns holding rule parameters, those not used by the rule's kind are left empty
+fn rule_columns(rule: &PromotionRule) -> (Option<i32>, Option<i32>, Option<i32>, Option<i64>) {
+    match rule {
+        PromotionRule::BuyXGetY { buy_quantity, get_quantity, percent_off, .. } => (Some(*buy_quantity), Some(*get_quantity), Some(*percent_off), None),
+        PromotionRule::Tiered { .. } => (None, None, None, None),
+        PromotionRule::Bundle { price_cents, .. } => (None, None, None, Some(*price_cents))
+    }
+}
+
+#[tracing::instrument(
+    "Inserting promotion into db",
+    skip(conn)
+)]
+pub async fn insert_promotion(
+    mut conn: DbConnection,
+    settings: PromotionSettings
+) -> Result<PromotionWithRule, PromotionError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<PromotionWithRule, PromotionError, _>(|conn| {
+            check_items_exist(conn, &settings.rule)?;
+
+            let (buy_quantity, get_quantity, percent_off, bundle_price_cents) = rule_columns(&settings.rule);
+            let promotion = diesel::insert_into(promotions::table)
+                .values(PromotionModel {
+                    promotion_id: Uuid::new_v4(),
+                    name: settings.name,
+                    kind: settings.rule.kind().to_string(),
+                    priority: settings.priority,
+                    active: settings.active,
+                    starts_at: settings.starts_at,
+                    ends_at: settings.ends_at,
+                    buy_quantity,
+                    get_quantity,
+                    percent_off,
+                    bundle_price_cents,
+                    created_at: Utc::now()
+                })
+                .get_result::<PromotionModel>(conn)?;
+
+            replace_rule_rows(conn, promotion.promotion_id, &settings.rule)?;
+
+            Ok(with_rules(conn, vec![promotion])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Updating promotion in db",
+    skip(conn)
+)]
+pub async fn update_promotion(
+    mut conn: DbConnection,
+    promotion_id: Uuid,
+    settings: PromotionSettings
+) -> Result<PromotionWithRule, PromotionError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<PromotionWithRule, PromotionError, _>(|conn| {
+            check_items_exist(conn, &settings.rule)?;
+
+            let (buy_quantity, get_quantity, percent_off, bundle_price_cents) = rule_columns(&settings.rule);
+            let promotion = diesel::update(promotions::table.find(promotion_id))
+                .set((
+                    promotions::name.eq(settings.name),
+                    promotions::kind.eq(settings.rule.kind()),
+                    promotions::priority.eq(settings.priority),
+                    promotions::active.eq(settings.active),
+                    promotions::starts_at.eq(settings.starts_at),
+                    promotions::ends_at.eq(settings.ends_at),
+                    promotions::buy_quantity.eq(buy_quantity),
+                    promotions::get_quantity.eq(get_quantity),
+                    promotions::percent_off.eq(percent_off),
+                    promotions::bundle_price_cents.eq(bundle_price_cents)
+                ))
+                .get_result::<PromotionModel>(conn)
+                .optional()?
+                .ok_or(PromotionError::NoPromotionIdError(promotion_id))?;
+
+            replace_rule_rows(conn, promotion_id, &settings.rule)?;
+
+            Ok(with_rules(conn, vec![promotion])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Deleting promotion from db",
+    skip(conn)
+)]
+pub async fn delete_promotion(
+    mut conn: DbConnection,
+    promotion_id: Uuid
+) -> Result<(), PromotionError> {
+    spawn_blocking_with_tracing(move || {
+        // Orders keep the promotions applied to them by name
+        let deleted = diesel::delete(promotions::table.find(promotion_id))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(PromotionError::NoPromotionIdError(promotion_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting promotions from db",
+    skip(conn)
+)]
+pub async fn get_promotions(
+    mut conn: DbConnection,
+    page_request: PageRequest<PromotionCursor>
+) -> Result<Page<PromotionWithRule>, PromotionError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = promotions::table
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = promotions::table.into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                promotions::priority.gt(after.priority)
+                    .or(promotions::priority.eq(after.priority).and(promotions::promotion_id.gt(after.promotion_id)))
+            );
+        }
+
+        let rows = query
+            .order((promotions::priority, promotions::promotion_id))
+            .limit(page_request.fetch_limit())
+            .load::<PromotionModel>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |promotion| PromotionCursor {
+            priority: promotion.priority,
+            promotion_id: promotion.promotion_id
+        })
+        .try_map_items(|rows| with_rules(&mut conn, rows))
+        .map_err(PromotionError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Promotions running right now in the order they are evaluated
+fn active_promotions(conn: &mut DbConnection) -> QueryResult<Vec<Promotion>> {
+    let now = Utc::now();
+    let rows = promotions::table
+        .filter(promotions::active.eq(true))
+        .filter(promotions::starts_at.is_null().or(promotions::starts_at.le(now)))
+        .filter(promotions::ends_at.is_null().or(promotions::ends_at.gt(now)))
+        .order((promotions::priority, promotions::created_at, promotions::promotion_id))
+        .load::<PromotionModel>(conn)?;
+
+    Ok(with_rules(conn, rows)?
+        .into_iter()
+        .map(|promotion| Promotion {
+            promotion_id: promotion.promotion_id,
+            name: promotion.name,
+            rule: promotion.rule
+        })
+        .collect())
+}
+
+// Evaluate running promotions against lines of an order being created and record what they took off
+pub fn apply_order_promotions(
+    conn: &mut DbConnection,
+    order_id: Uuid,
+    lines: &[PricedLine]
+) -> QueryResult<PromotionOutcome> {
+    let outcome = apply_promotions(lines, &active_promotions(conn)?);
+    if outcome.applied.is_empty() {
+        return Ok(outcome);
+    }
+
+    let applied: Vec<OrderPromotion> = outcome.applied.iter()
+        .map(|applied| OrderPromotion {
+            order_promotion_id: Uuid::new_v4(),
+            order_id,
+            promotion_id: applied.promotion_id,
+            name: applied.name.clone(),
+            discount_cents: applied.discount_cents
+        })
+        .collect();
+    diesel::insert_into(order_promotions::table)
+        .values(applied)
+        .execute(conn)?;
+
+    diesel::update(orders::table.find(order_id))
+        .set(orders::promotion_discount_cents.eq(outcome.discount_cents()))
+        .execute(conn)?;
+
+    Ok(outcome)
+}
+
+// Price breakdown of an order as it was charged
+pub fn order_price_breakdown(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<PriceBreakdown> {
+    let subtotal_cents: i64 = order_items::table
+        .filter(order_items::order_id.eq(order_id))
+        .select((order_items::quantity, order_items::unit_price_cents))
+        .load::<(i32, i64)>(conn)?
+        .into_iter()
+        .map(|(quantity, unit_price_cents)| unit_price_cents * quantity as i64)
+        .sum();
+
+    let applied = order_promotions::table
+        .filter(order_promotions::order_id.eq(order_id))
+        .order(order_promotions::name)
+        .load::<OrderPromotion>(conn)?
+        .into_iter()
+        .map(|applied| AppliedPromotion {
+            promotion_id: applied.promotion_id,
+            name: applied.name,
+            discount_cents: applied.discount_cents
+        })
+        .collect();
+
+    let (coupon_discount_cents, tax_cents, shipping_cents) = orders::table
+        .find(order_id)
+        .select((orders::discount_cents, orders::tax_cents, orders::shipping_cents))
+        .get_result::<(i64, i64, i64)>(conn)?;
+
+    Ok(PriceBreakdown::new(subtotal_cents, applied, coupon_discount_cents, tax_cents, shipping_cents))
+}
+
+#[tracing::instrument(
+    "Pricing variants against running promotions",
+    skip(conn)
+)]
+pub async fn quote_promotions(
+    mut conn: DbConnection,
+    quantities: Vec<(Uuid, i32)>
+) -> Result<PriceBreakdown, PromotionError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let mut lines = Vec::new();
+        for (variant_id, quantity) in quantities {
+            let item_id = product_variants::table
+                .find(variant_id)
+                .select(product_variants::item_id)
+                .get_result::<Uuid>(&mut conn)?;
+
+            lines.push(PricedLine {
+                item_id,
+                unit_price_cents: unit_price_cents(&mut conn, variant_id)?,
+                quantity
+            });
+        }
+
+        let subtotal_cents = lines.iter().map(|line| line.unit_price_cents * line.quantity as i64).sum();
+        let outcome = apply_promotions(&lines, &active_promotions(&mut conn)?);
+
+        // Tax isn't known till the order gives its address
+        Ok::<_, PromotionError>(PriceBreakdown::new(subtotal_cents, outcome.applied, 0, 0, 0))
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,523 @@
+use std::{collections::BTreeMap, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{adjust_variant_stock, enqueue_email, refresh_fulfillment_status, NewEmail, PaymentStatus, StockChange, StockMovementReason}, email_templates::EmailTemplateError, models::{Payment, Refund, RefundLine}, pagination::{Page, PageRequest}, schema::{inventory, order_item_allocations, order_items, orders, payments, refund_lines, refunds, returns, users, warehouses}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing lifecycle of a refund
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RefundStatus {
+    // Recorded and waiting for provider to give back the money
+    Pending,
+    Succeeded,
+    Failed
+}
+
+impl RefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "pending",
+            RefundStatus::Succeeded => "succeeded",
+            RefundStatus::Failed => "failed"
+        }
+    }
+}
+
+// Struct representing quantity of an order line to refund
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefundLineRequest {
+    pub order_item_id: Uuid,
+    pub quantity: i32
+}
+
+// Position of a refund within listing of an order's refunds oldest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefundCursor {
+    pub created_at: DateTime<Utc>,
+    pub refund_id: Uuid
+}
+
+// Struct representing a refund along with lines it gave back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefundWithLines {
+    #[serde(flatten)]
+    pub refund: Refund,
+    pub lines: Vec<RefundLine>
+}
+
+// Refund waiting to be sent to payment provider
+#[derive(Debug)]
+pub struct PendingRefund {
+    pub refund: Refund,
+    pub payment_reference: String
+}
+
+// Line of a refund receipt sent to customer
+#[derive(Debug, Clone)]
+pub struct RefundReceiptLine {
+    pub name: String,
+    pub sku: String,
+    pub quantity: i32,
+    pub amount_cents: i64
+}
+
+// Struct representing what customer is told about a completed refund
+#[derive(Debug, Clone)]
+pub struct RefundReceipt {
+    pub order_id: Uuid,
+    pub email: Option<String>,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub lines: Vec<RefundReceiptLine>
+}
+
+// Errors associated with refunding orders
+#[derive(Error)]
+pub enum RefundError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("order_id: {0} doesn't exist")]
+    NoOrderIdError(Uuid),
+    #[error("Order has no captured payment to refund")]
+    NotPaid,
+    #[error("order_item_id: {0} isn't part of the order")]
+    NoOrderItemError(Uuid),
+    #[error("Refund quantity of order_item_id: {0} is more than what is left to refund")]
+    ExcessQuantityError(Uuid),
+    #[error("Failed to render email")]
+    EmailTemplateError(#[from] EmailTemplateError),
+    #[error("Nothing is left to refund")]
+    NothingToRefund,
+    #[error("Return was already refunded")]
+    ReturnAlreadyRefunded
+}
+
+impl Debug for RefundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Warehouse an order line was picked from, goods sent back are restocked there
+pub fn order_item_warehouse_id(conn: &mut DbConnection, order_item_id: Uuid) -> QueryResult<Option<Uuid>> {
+    order_item_allocations::table
+        .inner_join(warehouses::table)
+        .filter(order_item_allocations::order_item_id.eq(order_item_id))
+        .select(order_item_allocations::warehouse_id)
+        .order((warehouses::priority, warehouses::code))
+        .first::<Uuid>(conn)
+        .optional()
+}
+
+#[tracing::instrument(
+    "Recording pending refund of order",
+    skip(conn)
+)]
+pub async fn begin_refund(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    return_id: Option<Uuid>,
+    requested: Option<Vec<RefundLineRequest>>,
+    restock: bool,
+    reason: Option<String>,
+    actor_id: Uuid
+) -> Result<PendingRefund, RefundError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<PendingRefund, RefundError, _>(|conn| {
+            let order_exists = orders::table
+                .find(order_id)
+                .select(orders::order_id)
+                .for_update()
+                .get_result::<Uuid>(conn)
+                .optional()?
+                .is_some();
+
+            if !order_exists {
+                return Err(RefundError::NoOrderIdError(order_id));
+            }
+
+            let payment = payments::table
+                .filter(payments::order_id.eq(order_id))
+                .filter(payments::status.eq_any([
+                    PaymentStatus::Captured.as_str(),
+                    PaymentStatus::PartiallyRefunded.as_str(),
+                    PaymentStatus::Refunded.as_str()
+                ]))
+                .for_update()
+                .get_result::<Payment>(conn)
+                .optional()?
+                .ok_or(RefundError::NotPaid)?;
+
+            // (quantity, refunded_quantity, backordered_quantity, charged_cents) of lines
+            // Charged is what the whole line cost after its discounts with its tax
+            let lines: BTreeMap<Uuid, (i32, i32, i32, i64)> = order_items::table
+                .filter(order_items::order_id.eq(order_id))
+                .select((
+                    order_items::order_item_id,
+                    (order_items::quantity, order_items::refunded_quantity, order_items::backordered_quantity),
+                    (order_items::unit_price_cents, order_items::discount_cents, order_items::tax_cents)
+                ))
+                .for_update()
+                .load::<(Uuid, (i32, i32, i32), (i64, i64, i64))>(conn)?
+                .into_iter()
+                .map(|(order_item_id, (quantity, refunded_quantity, backordered_quantity), (unit_price_cents, discount_cents, tax_cents))| {
+                    (order_item_id, (quantity, refunded_quantity, backordered_quantity, unit_price_cents * quantity as i64 - discount_cents + tax_cents))
+                })
+                .collect();
+
+            // Without lines everything not refunded yet is refunded
+            let mut quantities: BTreeMap<Uuid, i32> = BTreeMap::new();
+            match requested {
+                Some(requested) => {
+                    for line in requested {
+                        if !lines.contains_key(&line.order_item_id) {
+                            return Err(RefundError::NoOrderItemError(line.order_item_id));
+                        }
+                        *quantities.entry(line.order_item_id).or_default() += line.quantity;
+                    }
+                },
+                None => {
+                    for (order_item_id, (quantity, refunded_quantity, _, _)) in &lines {
+                        if quantity > refunded_quantity {
+                            quantities.insert(*order_item_id, quantity - refunded_quantity);
+                        }
+                    }
+                }
+            }
+
+            let mut left_unrefunded: i32 = lines.values()
+                .map(|(quantity, refunded_quantity, _, _)| quantity - refunded_quantity)
+                .sum();
+
+            let refund_id = Uuid::new_v4();
+            let mut refund_lines = Vec::new();
+
+            for (order_item_id, quantity) in quantities {
+                let (ordered, refunded_quantity, backordered_quantity, charged_cents) = lines[&order_item_id];
+                if quantity > ordered - refunded_quantity {
+                    return Err(RefundError::ExcessQuantityError(order_item_id));
+                }
+
+                refund_lines.push(RefundLine {
+                    refund_line_id: Uuid::new_v4(),
+                    refund_id,
+                    order_item_id,
+                    quantity,
+                    // Quantity still waiting on backorder is given up first as it never shipped
+                    unpicked_quantity: quantity.min(backordered_quantity),
+                    // Units are refunded their share of what the line was charged
+                    amount_cents: charged_cents * quantity as i64 / ordered as i64
+                });
+                left_unrefunded -= quantity;
+            }
+
+            // Last refund of an order gives back whatever rounding left over along with shipping
+            if left_unrefunded == 0 {
+                let remaining_cents = payment.amount_cents - payment.refunded_cents;
+                let amount_cents: i64 = refund_lines.iter().map(|line| line.amount_cents).sum();
+                if let Some(last) = refund_lines.last_mut() {
+                    last.amount_cents += remaining_cents - amount_cents;
+                }
+            }
+
+            let amount_cents: i64 = refund_lines.iter().map(|line| line.amount_cents).sum();
+            if amount_cents <= 0 || payment.refunded_cents + amount_cents > payment.amount_cents {
+                return Err(RefundError::NothingToRefund);
+            }
+
+            let refund = diesel::insert_into(refunds::table)
+                .values(Refund {
+                    refund_id,
+                    order_id,
+                    payment_id: payment.payment_id,
+                    status: RefundStatus::Pending.as_str().to_string(),
+                    amount_cents,
+                    restock,
+                    reason,
+                    provider_reference: None,
+                    actor_id: Some(actor_id),
+                    created_at: Utc::now()
+                })
+                .get_result::<Refund>(conn)?;
+
+            diesel::insert_into(refund_lines::table)
+                .values(&refund_lines)
+                .execute(conn)?;
+
+            // Return refunded is claimed before provider is asked, so only one refund of it pays out
+            if let Some(return_id) = return_id {
+                let claimed = diesel::update(returns::table.find(return_id))
+                    .filter(returns::refund_id.is_null())
+                    .set((
+                        returns::refund_id.eq(refund_id),
+                        returns::updated_at.eq(Utc::now())
+                    ))
+                    .execute(conn)?;
+
+                if claimed == 0 {
+                    return Err(RefundError::ReturnAlreadyRefunded);
+                }
+            }
+
+            // Quantities and amount are held by the pending refund so they can't be refunded twice
+            for line in &refund_lines {
+                diesel::update(order_items::table.find(line.order_item_id))
+                    .set((
+                        order_items::refunded_quantity.eq(order_items::refunded_quantity + line.quantity),
+                        order_items::backordered_quantity.eq(order_items::backordered_quantity - line.unpicked_quantity)
+                    ))
+                    .execute(conn)?;
+            }
+
+            diesel::update(payments::table.find(payment.payment_id))
+                .set(payments::refunded_cents.eq(payments::refunded_cents + amount_cents))
+                .execute(conn)?;
+
+            Ok(PendingRefund {
+                refund,
+                payment_reference: payment.provider_reference
+            })
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Customer's receipt is queued in the same transaction as the refund is completed
+#[tracing::instrument(
+    "Completing refund of order",
+    skip(conn, receipt_email)
+)]
+pub async fn complete_refund(
+    mut conn: DbConnection,
+    refund_id: Uuid,
+    provider_reference: String,
+    receipt_email: impl FnOnce(&RefundReceipt) -> Result<Option<NewEmail>, EmailTemplateError> + Send + 'static
+) -> Result<RefundWithLines, RefundError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<RefundWithLines, RefundError, _>(|conn| {
+            let refund = diesel::update(refunds::table.find(refund_id))
+                .set((
+                    refunds::status.eq(RefundStatus::Succeeded.as_str()),
+                    refunds::provider_reference.eq(provider_reference)
+                ))
+                .get_result::<Refund>(conn)?;
+
+            let lines = refund_lines::table
+                .filter(refund_lines::refund_id.eq(refund_id))
+                .load::<RefundLine>(conn)?;
+
+            let mut receipt_lines = Vec::new();
+
+            for line in &lines {
+                let (variant_id, name, sku) = order_items::table
+                    .inner_join(inventory::table)
+                    .filter(order_items::order_item_id.eq(line.order_item_id))
+                    .select((order_items::variant_id, inventory::name, inventory::sku))
+                    .get_result::<(Uuid, String, String)>(conn)?;
+
+                receipt_lines.push(RefundReceiptLine {
+                    name,
+                    sku,
+                    quantity: line.quantity,
+                    amount_cents: line.amount_cents
+                });
+
+                let picked_quantity = line.quantity - line.unpicked_quantity;
+                if !refund.restock || picked_quantity == 0 {
+                    continue;
+                }
+
+                let change = StockChange {
+                    reference_id: Some(refund.order_id),
+                    actor_id: refund.actor_id,
+                    note: Some(format!("Refund {}", refund.refund_id)),
+                    warehouse_id: order_item_warehouse_id(conn, line.order_item_id)?,
+                    ..StockChange::new(StockMovementReason::Return)
+                };
+
+                adjust_variant_stock(conn, variant_id, picked_quantity, &change)?;
+            }
+
+            let payment = payments::table
+                .find(refund.payment_id)
+                .for_update()
+                .get_result::<Payment>(conn)?;
+
+            let payment_status = if payment.refunded_cents >= payment.amount_cents {
+                PaymentStatus::Refunded
+            } else {
+                PaymentStatus::PartiallyRefunded
+            };
+
+            diesel::update(payments::table.find(payment.payment_id))
+                .set((
+                    payments::status.eq(payment_status.as_str()),
+                    payments::updated_at.eq(Utc::now())
+                ))
+                .execute(conn)?;
+
+            let unrefunded_lines = order_items::table
+                .filter(order_items::order_id.eq(refund.order_id))
+                .filter(order_items::refunded_quantity.lt(order_items::quantity))
+                .count()
+                .get_result::<i64>(conn)?;
+
+            if unrefunded_lines == 0 {
+                diesel::update(orders::table.find(refund.order_id))
+                    .set(orders::status.eq("refunded"))
+                    .execute(conn)?;
+            } else {
+                // Refunding what was left to ship can leave nothing more to ship
+                refresh_fulfillment_status(conn, refund.order_id)?;
+            }
+
+            let email = orders::table
+                .inner_join(users::table)
+                .filter(orders::order_id.eq(refund.order_id))
+                .select(users::email)
+                .get_result::<String>(conn)
+                .optional()?;
+
+            let receipt = RefundReceipt {
+                order_id: refund.order_id,
+                email,
+                amount_cents: refund.amount_cents,
+                currency: payment.currency,
+                lines: receipt_lines
+            };
+
+            if let Some(receipt_email) = receipt_email(&receipt)? {
+                enqueue_email(conn, receipt_email)?;
+            }
+
+            Ok(RefundWithLines { refund, lines })
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Failing refund of order",
+    skip(conn)
+)]
+pub async fn fail_refund(
+    mut conn: DbConnection,
+    refund_id: Uuid
+) -> Result<(), RefundError> {
+    spawn_blocking_with_tracing(move || {
+        conn.transaction::<(), RefundError, _>(|conn| {
+            let refund = diesel::update(refunds::table.find(refund_id))
+                .set(refunds::status.eq(RefundStatus::Failed.as_str()))
+                .get_result::<Refund>(conn)?;
+
+            let lines = refund_lines::table
+                .filter(refund_lines::refund_id.eq(refund_id))
+                .load::<RefundLine>(conn)?;
+
+            // Give back what the pending refund held
+            for line in &lines {
+                diesel::update(order_items::table.find(line.order_item_id))
+                    .set((
+                        order_items::refunded_quantity.eq(order_items::refunded_quantity - line.quantity),
+                        order_items::backordered_quantity.eq(order_items::backordered_quantity + line.unpicked_quantity)
+                    ))
+                    .execute(conn)?;
+            }
+
+            diesel::update(payments::table.find(refund.payment_id))
+                .set(payments::refunded_cents.eq(payments::refunded_cents - refund.amount_cents))
+                .execute(conn)?;
+
+            // Return claimed by the refund can be refunded again
+            diesel::update(returns::table)
+                .filter(returns::refund_id.eq(refund_id))
+                .set(returns::refund_id.eq(None::<Uuid>))
+                .execute(conn)?;
+
+            Ok(())
+        })
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting refunds of order from db",
+    skip(conn)
+)]
+pub async fn get_order_refunds(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    page_request: PageRequest<RefundCursor>
+) -> Result<Page<RefundWithLines>, RefundError> {
+    let res = spawn_blocking_with_tracing(move || {
+        orders::table
+            .find(order_id)
+            .select(orders::order_id)
+            .get_result::<Uuid>(&mut conn)
+            .optional()?
+            .ok_or(RefundError::NoOrderIdError(order_id))?;
+
+        let total = refunds::table
+            .filter(refunds::order_id.eq(order_id))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = refunds::table
+            .filter(refunds::order_id.eq(order_id))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                refunds::created_at.gt(after.created_at)
+                    .or(refunds::created_at.eq(after.created_at).and(refunds::refund_id.gt(after.refund_id)))
+            );
+        }
+
+        let rows = query
+            .order((refunds::created_at, refunds::refund_id))
+            .limit(page_request.fetch_limit())
+            .load::<Refund>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |refund| RefundCursor {
+            created_at: refund.created_at,
+            refund_id: refund.refund_id
+        })
+        .try_map_items(|order_refunds| with_lines(&mut conn, order_refunds))
+        .map_err(RefundError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+fn with_lines(conn: &mut DbConnection, order_refunds: Vec<Refund>) -> QueryResult<Vec<RefundWithLines>> {
+    let refund_ids: Vec<Uuid> = order_refunds.iter().map(|refund| refund.refund_id).collect();
+    let mut lines_by_refund: BTreeMap<Uuid, Vec<RefundLine>> = BTreeMap::new();
+    for line in refund_lines::table
+        .filter(refund_lines::refund_id.eq_any(refund_ids))
+        .load::<RefundLine>(conn)?
+    {
+        lines_by_refund.entry(line.refund_id).or_default().push(line);
+    }
+
+    Ok(order_refunds.into_iter()
+        .map(|refund| RefundWithLines {
+            lines: lines_by_refund.remove(&refund.refund_id).unwrap_or_default(),
+            refund
+        })
+        .collect())
+}
@@ -0,0 +1,230 @@
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt::Debug};
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{models::StockReservation, schema::{product_variants, stock_reservations}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing lifecycle of a stock reservation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReservationStatus {
+    Active,
+    Committed,
+    Released,
+    Expired
+}
+
+impl ReservationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReservationStatus::Active => "active",
+            ReservationStatus::Committed => "committed",
+            ReservationStatus::Released => "released",
+            ReservationStatus::Expired => "expired"
+        }
+    }
+}
+
+// Quantity held by active reservations which haven't expired yet, per variant
+// Holds of excluded user are left out so a customer can buy what they reserved
+pub fn reserved_quantities(
+    conn: &mut DbConnection,
+    variant_ids: &[Uuid],
+    excluded_user_id: Option<Uuid>
+) -> QueryResult<HashMap<Uuid, i64>> {
+    let mut query = stock_reservations::table
+        .group_by(stock_reservations::variant_id)
+        .select((stock_reservations::variant_id, diesel::dsl::sum(stock_reservations::quantity)))
+        .filter(stock_reservations::variant_id.eq_any(variant_ids))
+        .filter(stock_reservations::status.eq(ReservationStatus::Active.as_str()))
+        .filter(stock_reservations::expires_at.gt(Utc::now()))
+        .into_boxed();
+
+    if let Some(user_id) = excluded_user_id {
+        query = query.filter(stock_reservations::user_id.ne(user_id));
+    }
+
+    let reserved = query
+        .load::<(Uuid, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(variant_id, quantity)| (variant_id, quantity.unwrap_or(0)))
+        .collect();
+
+    Ok(reserved)
+}
+
+// Lock variant and get its item along with stock which user is able to take
+// Returns None when variant doesn't exist
+pub fn lock_available_variant_stock(
+    conn: &mut DbConnection,
+    variant_id: Uuid,
+    user_id: Uuid
+) -> QueryResult<Option<(Uuid, i64)>> {
+    let variant = product_variants::table
+        .find(variant_id)
+        .select((product_variants::item_id, product_variants::amount))
+        .for_update()
+        .get_result::<(Uuid, i32)>(conn)
+        .optional()?;
+
+    let Some((item_id, amount)) = variant else {
+        return Ok(None);
+    };
+
+    let reserved = reserved_quantities(conn, &[variant_id], Some(user_id))?
+        .remove(&variant_id)
+        .unwrap_or(0);
+
+    Ok(Some((item_id, amount as i64 - reserved)))
+}
+
+// Mark active holds of user on ordered variants as taken by the order
+pub fn commit_stock_reservations(
+    conn: &mut DbConnection,
+    user_id: Uuid,
+    order_id: Uuid,
+    variant_ids: &[Uuid]
+) -> QueryResult<usize> {
+    diesel::update(stock_reservations::table)
+        .filter(stock_reservations::user_id.eq(user_id))
+        .filter(stock_reservations::variant_id.eq_any(variant_ids))
+        .filter(stock_reservations::status.eq(ReservationStatus::Active.as_str()))
+        .set((
+            stock_reservations::status.eq(ReservationStatus::Committed.as_str()),
+            stock_reservations::order_id.eq(order_id)
+        ))
+        .execute(conn)
+}
+
+fn release_active_reservations(conn: &mut DbConnection, user_id: Uuid) -> QueryResult<usize> {
+    diesel::update(stock_reservations::table)
+        .filter(stock_reservations::user_id.eq(user_id))
+        .filter(stock_reservations::status.eq(ReservationStatus::Active.as_str()))
+        .set(stock_reservations::status.eq(ReservationStatus::Released.as_str()))
+        .execute(conn)
+}
+
+// Errors associated with placing stock reservations
+#[derive(Error)]
+pub enum StockReservationError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("variant_id: {0} doesn't exist")]
+    NoVariantIdError(Uuid),
+    #[error("Not enough stock available to reserve variant_id: {0}")]
+    InsufficientStockError(Uuid),
+    #[error("Requested quantity of variant_id: {0} is too large")]
+    QuantityOverflowError(Uuid)
+}
+
+impl Debug for StockReservationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Placing stock reservations in db",
+    skip(conn, quantities)
+)]
+pub async fn place_stock_reservations(
+    mut conn: DbConnection,
+    user_id: Uuid,
+    quantities: Vec<(Uuid, i32)>,
+    ttl: Duration
+) -> Result<Vec<StockReservation>, StockReservationError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<Vec<StockReservation>, StockReservationError, _>(|conn| {
+            // A new checkout replaces holds of the previous one
+            release_active_reservations(conn, user_id)?;
+
+            // Variants are locked in a fixed order so concurrent checkouts can't deadlock
+            let mut quantity_by_variant: BTreeMap<Uuid, i32> = BTreeMap::new();
+            for (variant_id, quantity) in quantities {
+                let total = quantity_by_variant.entry(variant_id).or_default();
+                *total = total.checked_add(quantity)
+                    .ok_or(StockReservationError::QuantityOverflowError(variant_id))?;
+            }
+
+            let now = Utc::now();
+            let mut reservations = Vec::new();
+
+            for (variant_id, quantity) in quantity_by_variant {
+                let (item_id, available) = lock_available_variant_stock(conn, variant_id, user_id)?
+                    .ok_or(StockReservationError::NoVariantIdError(variant_id))?;
+
+                if available < quantity as i64 {
+                    return Err(StockReservationError::InsufficientStockError(variant_id));
+                }
+
+                let reservation = diesel::insert_into(stock_reservations::table)
+                    .values(StockReservation {
+                        reservation_id: Uuid::new_v4(),
+                        user_id,
+                        item_id,
+                        variant_id,
+                        quantity,
+                        status: ReservationStatus::Active.as_str().to_string(),
+                        order_id: None,
+                        expires_at: now + ttl,
+                        created_at: now
+                    })
+                    .get_result::<StockReservation>(conn)?;
+
+                reservations.push(reservation);
+            }
+
+            Ok(reservations)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Releasing stock reservations of user",
+    skip(conn)
+)]
+pub async fn release_stock_reservations(
+    mut conn: DbConnection,
+    user_id: Uuid
+) -> Result<usize, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        release_active_reservations(&mut conn, user_id)
+            .context("Failed to release stock reservations")
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Expiring unpaid stock reservations",
+    skip(conn)
+)]
+pub async fn expire_stock_reservations(
+    mut conn: DbConnection
+) -> Result<usize, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        diesel::update(stock_reservations::table)
+            .filter(stock_reservations::status.eq(ReservationStatus::Active.as_str()))
+            .filter(stock_reservations::expires_at.le(Utc::now()))
+            .set(stock_reservations::status.eq(ReservationStatus::Expired.as_str()))
+            .execute(&mut conn)
+            .context("Failed to expire stock reservations")
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
@@ -0,0 +1,470 @@
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{adjust_variant_stock, order_item_warehouse_id, RefundLineRequest, StockChange, StockMovementReason}, models::{OrderReturn, ReturnLine}, pagination::{Page, PageRequest}, schema::{order_items, orders, return_lines, returns}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing why a customer sends goods back
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnReason {
+    Damaged,
+    Defective,
+    WrongItem,
+    NotAsDescribed,
+    NoLongerNeeded,
+    Other
+}
+
+impl ReturnReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReturnReason::Damaged => "damaged",
+            ReturnReason::Defective => "defective",
+            ReturnReason::WrongItem => "wrong_item",
+            ReturnReason::NotAsDescribed => "not_as_described",
+            ReturnReason::NoLongerNeeded => "no_longer_needed",
+            ReturnReason::Other => "other"
+        }
+    }
+}
+
+// Enum representing lifecycle of a return
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnStatus {
+    // Waiting for an admin to accept or turn down
+    Requested,
+    // Customer may send goods back
+    Approved,
+    Rejected,
+    // Goods arrived back and were restocked
+    Received
+}
+
+impl ReturnStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReturnStatus::Requested => "requested",
+            ReturnStatus::Approved => "approved",
+            ReturnStatus::Rejected => "rejected",
+            ReturnStatus::Received => "received"
+        }
+    }
+
+    fn can_become(&self, next: ReturnStatus) -> bool {
+        match self {
+            ReturnStatus::Requested => matches!(next, ReturnStatus::Approved | ReturnStatus::Rejected),
+            ReturnStatus::Approved => matches!(next, ReturnStatus::Received | ReturnStatus::Rejected),
+            _ => false
+        }
+    }
+
+    fn parse(status: &str) -> Option<ReturnStatus> {
+        [
+            ReturnStatus::Requested,
+            ReturnStatus::Approved,
+            ReturnStatus::Rejected,
+            ReturnStatus::Received
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
+    }
+}
+
+// Struct representing quantity of an order line a customer wants to send back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReturnLineRequest {
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+    pub reason: ReturnReason
+}
+
+// Struct representing a return along with lines sent back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReturnWithLines {
+    #[serde(flatten)]
+    pub order_return: OrderReturn,
+    pub lines: Vec<ReturnLine>
+}
+
+// Struct representing position of a return within returns queue
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReturnCursor {
+    pub created_at: DateTime<Utc>,
+    pub return_id: Uuid
+}
+
+// Errors associated with returns of orders
+#[derive(Error)]
+pub enum ReturnError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("order_id: {0} doesn't exist")]
+    NoOrderIdError(Uuid),
+    #[error("return_id: {0} doesn't exist")]
+    NoReturnIdError(Uuid),
+    #[error("Only delivered orders can be returned")]
+    NotDelivered,
+    #[error("order_item_id: {0} isn't part of the order")]
+    NoOrderItemError(Uuid),
+    #[error("Return quantity of order_item_id: {0} is more than what can be returned")]
+    ExcessQuantityError(Uuid),
+    #[error("Return can't move from {0} to {1}")]
+    InvalidTransition(&'static str, &'static str),
+    #[error("Return has to be received before it is refunded")]
+    NotReceived,
+    #[error("Return was already refunded")]
+    AlreadyRefunded
+}
+
+impl Debug for ReturnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Attach lines to returns keeping their order
+fn with_lines(conn: &mut DbConnection, order_returns: Vec<OrderReturn>) -> diesel::QueryResult<Vec<ReturnWithLines>> {
+    let return_ids: Vec<Uuid> = order_returns.iter().map(|order_return| order_return.return_id).collect();
+
+    let mut lines_by_return: HashMap<Uuid, Vec<ReturnLine>> = HashMap::new();
+    for line in return_lines::table
+        .filter(return_lines::return_id.eq_any(return_ids))
+        .load::<ReturnLine>(conn)?
+    {
+        lines_by_return.entry(line.return_id).or_default().push(line);
+    }
+
+    Ok(order_returns.into_iter()
+        .map(|order_return| ReturnWithLines {
+            lines: lines_by_return.remove(&order_return.return_id).unwrap_or_default(),
+            order_return
+        })
+        .collect())
+}
+
+#[tracing::instrument(
+    "Requesting return of order",
+    skip(conn)
+)]
+pub async fn request_return(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    user_id: Uuid,
+    requested: Vec<ReturnLineRequest>,
+    note: Option<String>
+) -> Result<ReturnWithLines, ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ReturnWithLines, ReturnError, _>(|conn| {
+            let order = orders::table
+                .find(order_id)
+                .select((orders::user_id, orders::status))
+                .for_update()
+                .get_result::<(Option<Uuid>, String)>(conn)
+                .optional()?;
+
+            let status = match order {
+                Some((Some(owner), status)) if owner == user_id => status,
+                _ => return Err(ReturnError::NoOrderIdError(order_id))
+            };
+
+            if status != "delivered" {
+                return Err(ReturnError::NotDelivered);
+            }
+
+            // Quantity of each line which isn't refunded yet
+            let mut returnable: BTreeMap<Uuid, i32> = order_items::table
+                .filter(order_items::order_id.eq(order_id))
+                .select((order_items::order_item_id, order_items::quantity - order_items::refunded_quantity))
+                .load::<(Uuid, i32)>(conn)?
+                .into_iter()
+                .collect();
+
+            // Open returns already hold part of it until refunded or rejected
+            let pending = return_lines::table
+                .inner_join(returns::table)
+                .filter(returns::order_id.eq(order_id))
+                .filter(returns::status.ne(ReturnStatus::Rejected.as_str()))
+                .filter(returns::refund_id.is_null())
+                .select((return_lines::order_item_id, return_lines::quantity))
+                .load::<(Uuid, i32)>(conn)?;
+
+            for (order_item_id, quantity) in pending.into_iter().chain(requested.iter().map(|line| (line.order_item_id, line.quantity))) {
+                let left = returnable.get_mut(&order_item_id)
+                    .ok_or(ReturnError::NoOrderItemError(order_item_id))?;
+
+                *left -= quantity;
+                if *left < 0 {
+                    return Err(ReturnError::ExcessQuantityError(order_item_id));
+                }
+            }
+
+            let now = Utc::now();
+            let order_return = diesel::insert_into(returns::table)
+                .values(OrderReturn {
+                    return_id: Uuid::new_v4(),
+                    order_id,
+                    user_id: Some(user_id),
+                    status: ReturnStatus::Requested.as_str().to_string(),
+                    note,
+                    refund_id: None,
+                    actor_id: None,
+                    created_at: now,
+                    updated_at: now
+                })
+                .get_result::<OrderReturn>(conn)?;
+
+            let lines: Vec<ReturnLine> = requested.into_iter()
+                .map(|line| ReturnLine {
+                    return_line_id: Uuid::new_v4(),
+                    return_id: order_return.return_id,
+                    order_item_id: line.order_item_id,
+                    quantity: line.quantity,
+                    reason: line.reason.as_str().to_string()
+                })
+                .collect();
+
+            let lines = diesel::insert_into(return_lines::table)
+                .values(&lines)
+                .get_results::<ReturnLine>(conn)?;
+
+            Ok(ReturnWithLines { order_return, lines })
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting returns of order from db",
+    skip(conn)
+)]
+pub async fn get_order_returns(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    user_id: Uuid,
+    page_request: PageRequest<ReturnCursor>
+) -> Result<Page<ReturnWithLines>, ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let owner = orders::table
+            .find(order_id)
+            .select(orders::user_id)
+            .get_result::<Option<Uuid>>(&mut conn)
+            .optional()?;
+
+        if owner != Some(Some(user_id)) {
+            return Err(ReturnError::NoOrderIdError(order_id));
+        }
+
+        let total = returns::table
+            .filter(returns::order_id.eq(order_id))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = returns::table
+            .filter(returns::order_id.eq(order_id))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                returns::created_at.gt(after.created_at)
+                    .or(returns::created_at.eq(after.created_at).and(returns::return_id.gt(after.return_id)))
+            );
+        }
+
+        let rows = query
+            .order((returns::created_at, returns::return_id))
+            .limit(page_request.fetch_limit())
+            .load::<OrderReturn>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |order_return| ReturnCursor {
+            created_at: order_return.created_at,
+            return_id: order_return.return_id
+        })
+        .try_map_items(|order_returns| with_lines(&mut conn, order_returns))
+        .map_err(ReturnError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting returns queue from db",
+    skip(conn)
+)]
+pub async fn get_returns(
+    mut conn: DbConnection,
+    status: Option<ReturnStatus>,
+    page_request: PageRequest<ReturnCursor>
+) -> Result<Page<ReturnWithLines>, ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let mut count_query = returns::table.into_boxed();
+        let mut query = returns::table.into_boxed();
+
+        if let Some(status) = status {
+            count_query = count_query.filter(returns::status.eq(status.as_str()));
+            query = query.filter(returns::status.eq(status.as_str()));
+        }
+
+        let total = count_query
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        // Oldest returns come first as they were waiting longest
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                returns::created_at.gt(after.created_at)
+                    .or(returns::created_at.eq(after.created_at).and(returns::return_id.gt(after.return_id)))
+            );
+        }
+
+        let rows = query
+            .order((returns::created_at, returns::return_id))
+            .limit(page_request.fetch_limit())
+            .load::<OrderReturn>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |order_return| ReturnCursor {
+            created_at: order_return.created_at,
+            return_id: order_return.return_id
+        })
+        .try_map_items(|order_returns| with_lines(&mut conn, order_returns))
+        .map_err(ReturnError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Moving return to next status",
+    skip(conn)
+)]
+pub async fn update_return_status(
+    mut conn: DbConnection,
+    return_id: Uuid,
+    next: ReturnStatus,
+    actor_id: Uuid
+) -> Result<ReturnWithLines, ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ReturnWithLines, ReturnError, _>(|conn| {
+            let order_return = returns::table
+                .find(return_id)
+                .for_update()
+                .get_result::<OrderReturn>(conn)
+                .optional()?
+                .ok_or(ReturnError::NoReturnIdError(return_id))?;
+
+            let current = ReturnStatus::parse(&order_return.status).unwrap_or(ReturnStatus::Rejected);
+            if !current.can_become(next) {
+                return Err(ReturnError::InvalidTransition(current.as_str(), next.as_str()));
+            }
+
+            let lines = return_lines::table
+                .filter(return_lines::return_id.eq(return_id))
+                .load::<ReturnLine>(conn)?;
+
+            // Received goods go back to the warehouse they were picked from
+            if next == ReturnStatus::Received {
+                for line in &lines {
+                    let variant_id = order_items::table
+                        .find(line.order_item_id)
+                        .select(order_items::variant_id)
+                        .get_result::<Uuid>(conn)?;
+
+                    let change = StockChange {
+                        reference_id: Some(order_return.order_id),
+                        actor_id: Some(actor_id),
+                        note: Some(format!("Return {}", return_id)),
+                        warehouse_id: order_item_warehouse_id(conn, line.order_item_id)?,
+                        ..StockChange::new(StockMovementReason::Return)
+                    };
+
+                    adjust_variant_stock(conn, variant_id, line.quantity, &change)?;
+                }
+            }
+
+            let order_return = diesel::update(returns::table.find(return_id))
+                .set((
+                    returns::status.eq(next.as_str()),
+                    returns::actor_id.eq(actor_id),
+                    returns::updated_at.eq(Utc::now())
+                ))
+                .get_result::<OrderReturn>(conn)?;
+
+            Ok(ReturnWithLines { order_return, lines })
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting lines of received return to refund",
+    skip(conn)
+)]
+pub async fn get_refundable_return(
+    mut conn: DbConnection,
+    return_id: Uuid
+) -> Result<(Uuid, Vec<RefundLineRequest>), ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let order_return = returns::table
+            .find(return_id)
+            .get_result::<OrderReturn>(&mut conn)
+            .optional()?
+            .ok_or(ReturnError::NoReturnIdError(return_id))?;
+
+        if order_return.refund_id.is_some() {
+            return Err(ReturnError::AlreadyRefunded);
+        }
+
+        if order_return.status != ReturnStatus::Received.as_str() {
+            return Err(ReturnError::NotReceived);
+        }
+
+        let lines = return_lines::table
+            .filter(return_lines::return_id.eq(return_id))
+            .select((return_lines::order_item_id, return_lines::quantity))
+            .load::<(Uuid, i32)>(&mut conn)?
+            .into_iter()
+            .map(|(order_item_id, quantity)| RefundLineRequest { order_item_id, quantity })
+            .collect();
+
+        Ok((order_return.order_id, lines))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting return from db",
+    skip(conn)
+)]
+pub async fn get_return(
+    mut conn: DbConnection,
+    return_id: Uuid
+) -> Result<ReturnWithLines, ReturnError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let order_return = returns::table
+            .find(return_id)
+            .get_result::<OrderReturn>(&mut conn)
+            .optional()?
+            .ok_or(ReturnError::NoReturnIdError(return_id))?;
+
+        Ok::<_, ReturnError>(with_lines(&mut conn, vec![order_return])?.remove(0))
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::{pg::Pg, sql_types::Float, BoolExpressionMethods, BoxableExpression, ExpressionMethods, IntoSql, PgSortExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SelectableHelper};
+use diesel_full_text_search::{configuration::TsConfigurationByName, ts_rank, websearch_to_tsquery_with_search_config, TsVectorExtensions};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{db_interaction::{attach_item_details, InventoryItemWithDetails}, models::InventoryItem, pagination::{Page, PageRequest}, schema::{categories, inventory, inventory_categories}, telemetry::spawn_blocking_with_tracing, utils::DbConnection};
+
+// Text search configuration used when building search_vector column
+const SEARCH_CONFIG: TsConfigurationByName = TsConfigurationByName("english");
+
+// Enum representing order in which search results are returned
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    #[default]
+    Relevance,
+    PriceAsc,
+    PriceDesc,
+    Name,
+    Newest
+}
+
+// Struct representing filters applied to an inventory search
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub query: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub category_id: Option<Uuid>,
+    pub in_stock: bool,
+    pub sort: SearchSort
+}
+
+// Value of sort key of an item within search results
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchCursorKey {
+    Rank(f32),
+    Price(Option<f64>),
+    Name(String),
+    CreatedAt(DateTime<Utc>)
+}
+
+// Position of an item within search results
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchCursor {
+    pub key: SearchCursorKey,
+    pub item_id: Uuid
+}
+
+impl SearchSort {
+    // Sort actually applied, relevance is meaningless without a search term
+    fn effective(self, query: &Option<String>) -> SearchSort {
+        match (self, query) {
+            (SearchSort::Relevance, None) => SearchSort::Newest,
+            (sort, _) => sort
+        }
+    }
+}
+
+impl SearchFilters {
+    // Whether cursor was issued for results sorted the same way
+    pub fn accepts(&self, cursor: &SearchCursor) -> bool {
+        matches!(
+            (self.sort.effective(&self.query), &cursor.key),
+            (SearchSort::Relevance, SearchCursorKey::Rank(_))
+                | (SearchSort::PriceAsc | SearchSort::PriceDesc, SearchCursorKey::Price(_))
+                | (SearchSort::Name, SearchCursorKey::Name(_))
+                | (SearchSort::Newest, SearchCursorKey::CreatedAt(_))
+        )
+    }
+}
+
+// Inventory items matching every filter, without any ordering
+fn filtered_items(
+    filters: &SearchFilters,
+    category_ids: &Option<Vec<Uuid>>
+) -> inventory::BoxedQuery<'static, Pg> {
+    let mut query = inventory::table.into_boxed();
+
+    if let Some(text) = filters.query.clone() {
+        query = query.filter(
+            inventory::search_vector.matches(websearch_to_tsquery_with_search_config(SEARCH_CONFIG, text))
+        );
+    }
+
+    if let Some(min_price) = filters.min_price {
+        query = query.filter(inventory::price.ge(min_price));
+    }
+
+    if let Some(max_price) = filters.max_price {
+        query = query.filter(inventory::price.le(max_price));
+    }
+
+    if let Some(category_ids) = category_ids.clone() {
+        query = query.filter(
+            inventory::item_id.eq_any(
+                inventory_categories::table
+                    .filter(inventory_categories::category_id.eq_any(category_ids))
+                    .select(inventory_categories::item_id)
+            )
+        );
+    }
+
+    if filters.in_stock {
+        query = query.filter(inventory::amount.gt(0));
+    }
+
+    query
+}
+
+#[tracing::instrument(
+    "Searching inventory items in db",
+    skip_all
+)]
+pub async fn search_inventory_items(
+    mut conn: DbConnection,
+    filters: SearchFilters,
+    page_request: PageRequest<SearchCursor>
+) -> Result<Page<InventoryItemWithDetails>, anyhow::Error> {
+    let sort = filters.sort.effective(&filters.query);
+
+    let res = spawn_blocking_with_tracing(move || {
+        let category_ids = filters.category_id
+            .map(|category_id| category_with_descendants(&mut conn, category_id))
+            .transpose()
+            .context("Failed to get category tree")?;
+
+        let total = filtered_items(&filters, &category_ids)
+            .count()
+            .get_result::<i64>(&mut conn)
+            .context("Failed to count matching inventory items")?;
+
+        let rank = || -> Box<dyn BoxableExpression<inventory::table, Pg, SqlType = Float>> {
+            match filters.query.clone() {
+                Some(text) => Box::new(
+                    ts_rank(inventory::search_vector, websearch_to_tsquery_with_search_config(SEARCH_CONFIG, text))
+                ),
+                None => Box::new(0f32.into_sql::<Float>())
+            }
+        };
+
+        let mut query = filtered_items(&filters, &category_ids);
+
+        if let Some(after) = page_request.after.clone() {
+            query = match (sort, after.key) {
+                (SearchSort::Relevance, SearchCursorKey::Rank(value)) => query.filter(
+                    rank().lt(value)
+                        .or(rank().eq(value).and(inventory::item_id.gt(after.item_id)))
+                ),
+                (SearchSort::PriceAsc, SearchCursorKey::Price(Some(value))) => query.filter(
+                    inventory::price.gt(value)
+                        .or(inventory::price.eq(value).and(inventory::item_id.gt(after.item_id)))
+                        .or(inventory::price.is_null())
+                ),
+                (SearchSort::PriceDesc, SearchCursorKey::Price(Some(value))) => query.filter(
+                    inventory::price.lt(value)
+                        .or(inventory::price.eq(value).and(inventory::item_id.gt(after.item_id)))
+                        .or(inventory::price.is_null())
+                ),
+                // Items without a price are listed last irrespective of direction
+                (SearchSort::PriceAsc | SearchSort::PriceDesc, SearchCursorKey::Price(None)) => query.filter(
+                    inventory::price.is_null().and(inventory::item_id.gt(after.item_id))
+                ),
+                (SearchSort::Name, SearchCursorKey::Name(value)) => query.filter(
+                    inventory::name.gt(value.clone())
+                        .or(inventory::name.eq(value).and(inventory::item_id.gt(after.item_id)))
+                ),
+                (SearchSort::Newest, SearchCursorKey::CreatedAt(value)) => query.filter(
+                    inventory::created_at.lt(value)
+                        .or(inventory::created_at.eq(value).and(inventory::item_id.gt(after.item_id)))
+                ),
+                _ => return Err(anyhow::anyhow!("Cursor doesn't match sort order of search"))
+            };
+        }
+
+        query = match sort {
+            SearchSort::Relevance => query.order(rank().desc()),
+            SearchSort::Newest => query.order(inventory::created_at.desc()),
+            SearchSort::PriceAsc => query.order(inventory::price.asc().nulls_last()),
+            SearchSort::PriceDesc => query.order(inventory::price.desc().nulls_last()),
+            SearchSort::Name => query.order(inventory::name.asc())
+        };
+
+        let rows = query
+            .then_order_by(inventory::item_id)
+            .select((InventoryItem::as_select(), inventory::created_at, rank()))
+            .limit(page_request.fetch_limit())
+            .load::<(InventoryItem, DateTime<Utc>, f32)>(&mut conn)
+            .context("Failed to search inventory items")?;
+
+        Page::new(rows, page_request.limit, total, |(item, created_at, rank)| SearchCursor {
+            key: match sort {
+                SearchSort::Relevance => SearchCursorKey::Rank(*rank),
+                SearchSort::PriceAsc | SearchSort::PriceDesc => SearchCursorKey::Price(item.price),
+                SearchSort::Name => SearchCursorKey::Name(item.name.clone()),
+                SearchSort::Newest => SearchCursorKey::CreatedAt(*created_at)
+            },
+            item_id: item.item_id
+        })
+        .try_map_items(|rows| {
+            attach_item_details(&mut conn, rows.into_iter().map(|(item, _, _)| item).collect())
+        })
+        .context("Failed to get inventory item details")
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+// Collect id of given category along with ids of all categories nested under it
+fn category_with_descendants(
+    conn: &mut DbConnection,
+    category_id: Uuid
+) -> QueryResult<Vec<Uuid>> {
+    let tree = categories::table
+        .select((categories::category_id, categories::parent_id))
+        .load::<(Uuid, Option<Uuid>)>(conn)?;
+
+    let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (child_id, parent_id) in tree {
+        if let Some(parent_id) = parent_id {
+            children.entry(parent_id).or_default().push(child_id);
+        }
+    }
+
+    let mut found = HashSet::from([category_id]);
+    let mut pending = vec![category_id];
+    while let Some(current) = pending.pop() {
+        for child_id in children.remove(&current).unwrap_or_default() {
+            if found.insert(child_id) {
+                pending.push(child_id);
+            }
+        }
+    }
+
+    Ok(found.into_iter().collect())
+}
@@ -0,0 +1,276 @@
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::{enqueue_order_email, NewEmail, OrderNotice}, email_templates::EmailTemplateError, models::{Shipment, ShipmentLine}, pagination::{Page, PageRequest}, schema::{order_items, orders, shipment_lines, shipments}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing quantity of an order line to ship
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShipmentLineRequest {
+    pub order_item_id: Uuid,
+    pub quantity: i32
+}
+
+// Struct representing a shipment along with lines it carries
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShipmentWithLines {
+    #[serde(flatten)]
+    pub shipment: Shipment,
+    pub lines: Vec<ShipmentLine>
+}
+
+// Position of a shipment within listing of an order's shipments oldest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShipmentCursor {
+    pub shipped_at: DateTime<Utc>,
+    pub shipment_id: Uuid
+}
+
+// Errors associated with shipping orders
+#[derive(Error)]
+pub enum ShipmentError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("order_id: {0} doesn't exist")]
+    NoOrderIdError(Uuid),
+    #[error("Order can't be shipped while it is {0}")]
+    NotShippable(String),
+    #[error("order_item_id: {0} isn't part of the order")]
+    NoOrderItemError(Uuid),
+    #[error("Shipped quantity of order_item_id: {0} is more than what is ready to ship")]
+    ExcessQuantityError(Uuid),
+    #[error("Nothing is ready to ship")]
+    NothingToShip,
+    #[error("Failed to render email")]
+    EmailTemplateError(#[from] EmailTemplateError)
+}
+
+impl Debug for ShipmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Set status of an order still being fulfilled from how much of it was shipped
+// Refunded quantity never ships so it doesn't hold an order back from being shipped
+pub fn refresh_fulfillment_status(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<()> {
+    let lines = order_items::table
+        .filter(order_items::order_id.eq(order_id))
+        .select((order_items::quantity, order_items::refunded_quantity, order_items::shipped_quantity))
+        .load::<(i32, i32, i32)>(conn)?;
+
+    let status = if lines.iter().all(|(_, _, shipped)| *shipped == 0) {
+        "pending"
+    } else if lines.iter().all(|(quantity, refunded, shipped)| *shipped >= quantity - refunded) {
+        "shipped"
+    } else {
+        "partially_shipped"
+    };
+
+    diesel::update(orders::table.find(order_id))
+        .filter(orders::status.eq_any(["pending", "partially_shipped"]))
+        .set(orders::status.eq(status))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Shipments of an order oldest first
+pub fn order_shipments(conn: &mut DbConnection, order_id: Uuid) -> QueryResult<Vec<ShipmentWithLines>> {
+    let shipments = shipments::table
+        .filter(shipments::order_id.eq(order_id))
+        .order((shipments::shipped_at, shipments::shipment_id))
+        .load::<Shipment>(conn)?;
+
+    with_lines(conn, shipments)
+}
+
+fn with_lines(conn: &mut DbConnection, shipments: Vec<Shipment>) -> QueryResult<Vec<ShipmentWithLines>> {
+    let shipment_ids: Vec<Uuid> = shipments.iter().map(|shipment| shipment.shipment_id).collect();
+    let mut lines_by_shipment: HashMap<Uuid, Vec<ShipmentLine>> = HashMap::new();
+    for line in shipment_lines::table
+        .filter(shipment_lines::shipment_id.eq_any(&shipment_ids))
+        .load::<ShipmentLine>(conn)?
+    {
+        lines_by_shipment.entry(line.shipment_id).or_default().push(line);
+    }
+
+    Ok(shipments.into_iter()
+        .map(|shipment| ShipmentWithLines {
+            lines: lines_by_shipment.remove(&shipment.shipment_id).unwrap_or_default(),
+            shipment
+        })
+        .collect())
+}
+
+#[tracing::instrument(
+    "Recording shipment of order",
+    skip(conn, email)
+)]
+pub async fn create_shipment(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    carrier: String,
+    tracking_number: String,
+    requested: Option<Vec<ShipmentLineRequest>>,
+    actor_id: Uuid,
+    email: impl FnOnce(&OrderNotice) -> Result<Option<NewEmail>, EmailTemplateError> + Send + 'static
+) -> Result<ShipmentWithLines, ShipmentError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ShipmentWithLines, ShipmentError, _>(|conn| {
+            let status = orders::table
+                .find(order_id)
+                .select(orders::status)
+                .for_update()
+                .get_result::<String>(conn)
+                .optional()?
+                .ok_or(ShipmentError::NoOrderIdError(order_id))?;
+
+            if status != "pending" && status != "partially_shipped" {
+                return Err(ShipmentError::NotShippable(status));
+            }
+
+            // Quantity of lines ready to ship, backordered quantity has nothing picked to ship yet
+            let ready: BTreeMap<Uuid, i32> = order_items::table
+                .filter(order_items::order_id.eq(order_id))
+                .select((
+                    order_items::order_item_id,
+                    (order_items::quantity, order_items::refunded_quantity, order_items::backordered_quantity, order_items::shipped_quantity)
+                ))
+                .for_update()
+                .load::<(Uuid, (i32, i32, i32, i32))>(conn)?
+                .into_iter()
+                .map(|(order_item_id, (quantity, refunded, backordered, shipped))| {
+                    (order_item_id, (quantity - refunded - backordered - shipped).max(0))
+                })
+                .collect();
+
+            // Without lines everything ready is shipped
+            let mut quantities: BTreeMap<Uuid, i32> = BTreeMap::new();
+            match requested {
+                Some(requested) => {
+                    for line in requested {
+                        if !ready.contains_key(&line.order_item_id) {
+                            return Err(ShipmentError::NoOrderItemError(line.order_item_id));
+                        }
+                        *quantities.entry(line.order_item_id).or_default() += line.quantity;
+                    }
+                },
+                None => {
+                    quantities.extend(ready.iter().filter(|(_, quantity)| **quantity > 0));
+                }
+            }
+
+            if quantities.is_empty() {
+                return Err(ShipmentError::NothingToShip);
+            }
+
+            let shipment_id = Uuid::new_v4();
+            let mut lines = Vec::new();
+            for (order_item_id, quantity) in quantities {
+                if quantity > ready[&order_item_id] {
+                    return Err(ShipmentError::ExcessQuantityError(order_item_id));
+                }
+
+                lines.push(ShipmentLine {
+                    shipment_line_id: Uuid::new_v4(),
+                    shipment_id,
+                    order_item_id,
+                    quantity
+                });
+            }
+
+            let shipment = diesel::insert_into(shipments::table)
+                .values(Shipment {
+                    shipment_id,
+                    order_id,
+                    carrier,
+                    tracking_number,
+                    actor_id: Some(actor_id),
+                    shipped_at: Utc::now()
+                })
+                .get_result::<Shipment>(conn)?;
+
+            diesel::insert_into(shipment_lines::table)
+                .values(&lines)
+                .execute(conn)?;
+
+            for line in &lines {
+                diesel::update(order_items::table.find(line.order_item_id))
+                    .set(order_items::shipped_quantity.eq(order_items::shipped_quantity + line.quantity))
+                    .execute(conn)?;
+            }
+
+            refresh_fulfillment_status(conn, order_id)?;
+
+            // Every parcel gets its own email so customer has each tracking number
+            enqueue_order_email::<ShipmentError>(conn, order_id, email)?;
+
+            Ok(ShipmentWithLines { shipment, lines })
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting shipments of order",
+    skip(conn)
+)]
+pub async fn get_order_shipments(
+    mut conn: DbConnection,
+    order_id: Uuid,
+    page_request: PageRequest<ShipmentCursor>
+) -> Result<Page<ShipmentWithLines>, ShipmentError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let exists = orders::table
+            .find(order_id)
+            .select(orders::order_id)
+            .get_result::<Uuid>(&mut conn)
+            .optional()?
+            .is_some();
+
+        if !exists {
+            return Err(ShipmentError::NoOrderIdError(order_id));
+        }
+
+        let total = shipments::table
+            .filter(shipments::order_id.eq(order_id))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = shipments::table
+            .filter(shipments::order_id.eq(order_id))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                shipments::shipped_at.gt(after.shipped_at)
+                    .or(shipments::shipped_at.eq(after.shipped_at).and(shipments::shipment_id.gt(after.shipment_id)))
+            );
+        }
+
+        let rows = query
+            .order((shipments::shipped_at, shipments::shipment_id))
+            .limit(page_request.fetch_limit())
+            .load::<Shipment>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |shipment| ShipmentCursor {
+            shipped_at: shipment.shipped_at,
+            shipment_id: shipment.shipment_id
+        })
+        .try_map_items(|shipments| with_lines(&mut conn, shipments))
+        .map_err(ShipmentError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,432 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{db_interaction::unit_price_cents, domain::{address::Destination, shipping::ShippingRate}, models::{ShippingMethodModel, ShippingMethodZone}, pagination::{Page, PageRequest}, schema::{inventory, product_variants, shipping_method_zones, shipping_methods}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing validated shipping method settings saved by an admin
+#[derive(Debug, Clone)]
+pub struct ShippingMethodSettings {
+    pub name: String,
+    pub active: bool,
+    pub rate: ShippingRate,
+    pub zones: Vec<Destination>
+}
+
+// Struct representing a country or region within a shipping method's zones
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShippingZone {
+    pub country: String,
+    pub region: Option<String>
+}
+
+// Struct representing a shipping method along with its rate and zones
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShippingMethodWithZones {
+    pub shipping_method_id: Uuid,
+    pub name: String,
+    pub active: bool,
+    pub rate: ShippingRate,
+    // Methods without zones ship everywhere
+    pub zones: Vec<ShippingZone>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
+}
+
+// Position of a shipping method within listing ordered by name
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShippingMethodCursor {
+    pub name: String,
+    pub shipping_method_id: Uuid
+}
+
+// Struct representing what a shipping method would charge for a cart
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShippingQuote {
+    pub shipping_method_id: Uuid,
+    pub name: String,
+    pub cost_cents: i64
+}
+
+// Errors associated with managing shipping methods
+#[derive(Error)]
+pub enum ShippingError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("shipping_method_id: {0} doesn't exist")]
+    NoShippingMethodIdError(Uuid),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid),
+    #[error("variant_id: {0} doesn't exist")]
+    NoVariantIdError(Uuid)
+}
+
+impl Debug for ShippingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Reasons a shipping method can't be used on an order
+#[derive(Error, Debug)]
+pub enum ShippingRejection {
+    #[error("Shipping method {0} doesn't exist")]
+    Unknown(Uuid),
+    #[error("Shipping method is not available")]
+    Inactive,
+    #[error("Shipping method needs an address to ship to")]
+    AddressRequired,
+    #[error("Shipping method doesn't ship to {0}")]
+    NotShippedTo(String)
+}
+
+fn rate_of(method: &ShippingMethodModel) -> ShippingRate {
+    match method.kind.as_str() {
+        "weight_based" => ShippingRate::WeightBased {
+            base_cents: method.rate_cents,
+            per_kg_cents: method.per_kg_cents.unwrap_or_default()
+        },
+        "free_over" => ShippingRate::FreeOver {
+            rate_cents: method.rate_cents,
+            threshold_cents: method.threshold_cents.unwrap_or_default()
+        },
+        _ => ShippingRate::FlatRate {
+            rate_cents: method.rate_cents
+        }
+    }
+}
+
+// Columns holding rate parameters, those not used by the rate's kind are left empty
+fn rate_columns(rate: &ShippingRate) -> (i64, Option<i64>, Option<i64>) {
+    match rate {
+        ShippingRate::FlatRate { rate_cents } => (*rate_cents, None, None),
+        ShippingRate::WeightBased { base_cents, per_kg_cents } => (*base_cents, Some(*per_kg_cents), None),
+        ShippingRate::FreeOver { rate_cents, threshold_cents } => (*rate_cents, None, Some(*threshold_cents))
+    }
+}
+
+// Whether zones cover destination, a zone without region covers the whole country
+fn ships_to(zones: &[ShippingZone], destination: &Destination) -> bool {
+    zones.is_empty() || zones.iter().any(|zone| {
+        zone.country == destination.country && (zone.region.is_none() || zone.region == destination.region)
+    })
+}
+
+fn replace_zones(conn: &mut DbConnection, shipping_method_id: Uuid, zones: &[Destination]) -> QueryResult<()> {
+    diesel::delete(shipping_method_zones::table.filter(shipping_method_zones::shipping_method_id.eq(shipping_method_id)))
+        .execute(conn)?;
+
+    let zones: Vec<ShippingMethodZone> = zones.iter()
+        .map(|zone| (zone.country.clone(), zone.region.clone()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|(country, region)| ShippingMethodZone {
+            zone_id: Uuid::new_v4(),
+            shipping_method_id,
+            country,
+            region
+        })
+        .collect();
+    if !zones.is_empty() {
+        diesel::insert_into(shipping_method_zones::table)
+            .values(zones)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+// Attach rates and zones to shipping methods keeping their order
+fn with_zones(conn: &mut DbConnection, methods: Vec<ShippingMethodModel>) -> QueryResult<Vec<ShippingMethodWithZones>> {
+    let method_ids: Vec<Uuid> = methods.iter().map(|method| method.shipping_method_id).collect();
+
+    let mut zones_by_method: HashMap<Uuid, Vec<ShippingZone>> = HashMap::new();
+    for zone in shipping_method_zones::table
+        .filter(shipping_method_zones::shipping_method_id.eq_any(&method_ids))
+        .order((shipping_method_zones::country, shipping_method_zones::region))
+        .load::<ShippingMethodZone>(conn)?
+    {
+        zones_by_method.entry(zone.shipping_method_id)
+            .or_default()
+            .push(ShippingZone { country: zone.country, region: zone.region });
+    }
+
+    Ok(methods.into_iter()
+        .map(|method| ShippingMethodWithZones {
+            shipping_method_id: method.shipping_method_id,
+            rate: rate_of(&method),
+            zones: zones_by_method.remove(&method.shipping_method_id).unwrap_or_default(),
+            name: method.name,
+            active: method.active,
+            created_at: method.created_at,
+            updated_at: method.updated_at
+        })
+        .collect())
+}
+
+#[tracing::instrument(
+    "Inserting shipping method into db",
+    skip(conn)
+)]
+pub async fn insert_shipping_method(
+    mut conn: DbConnection,
+    settings: ShippingMethodSettings
+) -> Result<ShippingMethodWithZones, ShippingError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ShippingMethodWithZones, ShippingError, _>(|conn| {
+            let now = Utc::now();
+            let (rate_cents, per_kg_cents, threshold_cents) = rate_columns(&settings.rate);
+            let method = diesel::insert_into(shipping_methods::table)
+                .values(ShippingMethodModel {
+                    shipping_method_id: Uuid::new_v4(),
+                    name: settings.name,
+                    kind: settings.rate.kind().to_string(),
+                    rate_cents,
+                    per_kg_cents,
+                    threshold_cents,
+                    active: settings.active,
+                    created_at: now,
+                    updated_at: now
+                })
+                .get_result::<ShippingMethodModel>(conn)?;
+
+            replace_zones(conn, method.shipping_method_id, &settings.zones)?;
+
+            Ok(with_zones(conn, vec![method])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Updating shipping method in db",
+    skip(conn)
+)]
+pub async fn update_shipping_method(
+    mut conn: DbConnection,
+    shipping_method_id: Uuid,
+    settings: ShippingMethodSettings
+) -> Result<ShippingMethodWithZones, ShippingError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<ShippingMethodWithZones, ShippingError, _>(|conn| {
+            let (rate_cents, per_kg_cents, threshold_cents) = rate_columns(&settings.rate);
+            let method = diesel::update(shipping_methods::table.find(shipping_method_id))
+                .set((
+                    shipping_methods::name.eq(settings.name),
+                    shipping_methods::kind.eq(settings.rate.kind()),
+                    shipping_methods::rate_cents.eq(rate_cents),
+                    shipping_methods::per_kg_cents.eq(per_kg_cents),
+                    shipping_methods::threshold_cents.eq(threshold_cents),
+                    shipping_methods::active.eq(settings.active),
+                    shipping_methods::updated_at.eq(Utc::now())
+                ))
+                .get_result::<ShippingMethodModel>(conn)
+                .optional()?
+                .ok_or(ShippingError::NoShippingMethodIdError(shipping_method_id))?;
+
+            replace_zones(conn, shipping_method_id, &settings.zones)?;
+
+            Ok(with_zones(conn, vec![method])?.remove(0))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Deleting shipping method from db",
+    skip(conn)
+)]
+pub async fn delete_shipping_method(
+    mut conn: DbConnection,
+    shipping_method_id: Uuid
+) -> Result<(), ShippingError> {
+    spawn_blocking_with_tracing(move || {
+        // Orders keep name and cost of the method they were shipped with
+        let deleted = diesel::delete(shipping_methods::table.find(shipping_method_id))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(ShippingError::NoShippingMethodIdError(shipping_method_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Getting shipping methods from db",
+    skip(conn)
+)]
+pub async fn get_shipping_methods(
+    mut conn: DbConnection,
+    page_request: PageRequest<ShippingMethodCursor>
+) -> Result<Page<ShippingMethodWithZones>, ShippingError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = shipping_methods::table
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = shipping_methods::table
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                shipping_methods::name.gt(after.name.clone())
+                    .or(shipping_methods::name.eq(after.name.clone()).and(shipping_methods::shipping_method_id.gt(after.shipping_method_id)))
+            );
+        }
+
+        let rows = query
+            .order((shipping_methods::name.asc(), shipping_methods::shipping_method_id.asc()))
+            .limit(page_request.fetch_limit())
+            .load::<ShippingMethodModel>(&mut conn)?;
+
+        Page::new(rows, page_request.limit, total, |method| ShippingMethodCursor {
+            name: method.name.clone(),
+            shipping_method_id: method.shipping_method_id
+        })
+        .try_map_items(|methods| with_zones(&mut conn, methods))
+        .map_err(ShippingError::from)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Setting weight of inventory item",
+    skip(conn)
+)]
+pub async fn set_item_weight(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    weight_grams: i32
+) -> Result<(), ShippingError> {
+    spawn_blocking_with_tracing(move || {
+        let affected_rows = diesel::update(inventory::table.find(item_id))
+            .set(inventory::weight_grams.eq(weight_grams))
+            .execute(&mut conn)?;
+
+        if affected_rows == 0 {
+            return Err(ShippingError::NoItemIdError(item_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Total weight in grams of (item_id, quantity) lines
+pub fn lines_weight_grams(conn: &mut DbConnection, lines: &[(Uuid, i32)]) -> QueryResult<i64> {
+    let item_ids: Vec<Uuid> = lines.iter().map(|(item_id, _)| *item_id).collect();
+    let weights: HashMap<Uuid, i32> = inventory::table
+        .filter(inventory::item_id.eq_any(&item_ids))
+        .select((inventory::item_id, inventory::weight_grams))
+        .load::<(Uuid, i32)>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(lines.iter()
+        .map(|(item_id, quantity)| weights.get(item_id).copied().unwrap_or(0) as i64 * *quantity as i64)
+        .sum())
+}
+
+// Check chosen shipping method against an order being created and get its cost
+// Free shipping threshold is checked against merchandise subtotal before discounts
+pub fn price_shipping(
+    conn: &mut DbConnection,
+    shipping_method_id: Uuid,
+    destination: Option<&Destination>,
+    subtotal_cents: i64,
+    weight_grams: i64
+) -> QueryResult<Result<(ShippingMethodModel, i64), ShippingRejection>> {
+    let Some(method) = shipping_methods::table
+        .find(shipping_method_id)
+        .get_result::<ShippingMethodModel>(conn)
+        .optional()?
+    else {
+        return Ok(Err(ShippingRejection::Unknown(shipping_method_id)));
+    };
+
+    if !method.active {
+        return Ok(Err(ShippingRejection::Inactive));
+    }
+
+    let Some(destination) = destination else {
+        return Ok(Err(ShippingRejection::AddressRequired));
+    };
+
+    let zones = with_zones(conn, vec![method.clone()])?.remove(0).zones;
+    if !ships_to(&zones, destination) {
+        return Ok(Err(ShippingRejection::NotShippedTo(destination.country.clone())));
+    }
+
+    let cost_cents = rate_of(&method).cost_cents(subtotal_cents, weight_grams);
+
+    Ok(Ok((method, cost_cents)))
+}
+
+#[tracing::instrument(
+    "Quoting shipping methods for cart",
+    skip(conn)
+)]
+pub async fn quote_shipping(
+    mut conn: DbConnection,
+    quantities: Vec<(Uuid, i32)>,
+    destination: Destination
+) -> Result<Vec<ShippingQuote>, ShippingError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let mut subtotal_cents = 0;
+        let mut item_lines = Vec::new();
+        for (variant_id, quantity) in quantities {
+            let item_id = product_variants::table
+                .find(variant_id)
+                .select(product_variants::item_id)
+                .get_result::<Uuid>(&mut conn)
+                .optional()?
+                .ok_or(ShippingError::NoVariantIdError(variant_id))?;
+
+            subtotal_cents += unit_price_cents(&mut conn, variant_id)? * quantity as i64;
+            item_lines.push((item_id, quantity));
+        }
+        let weight_grams = lines_weight_grams(&mut conn, &item_lines)?;
+
+        let methods = shipping_methods::table
+            .filter(shipping_methods::active.eq(true))
+            .load::<ShippingMethodModel>(&mut conn)?;
+
+        let mut quotes: Vec<ShippingQuote> = with_zones(&mut conn, methods)?
+            .into_iter()
+            .filter(|method| ships_to(&method.zones, &destination))
+            .map(|method| ShippingQuote {
+                cost_cents: method.rate.cost_cents(subtotal_cents, weight_grams),
+                shipping_method_id: method.shipping_method_id,
+                name: method.name
+            })
+            .collect();
+
+        // Cheapest options first
+        quotes.sort_by(|a, b| a.cost_cents.cmp(&b.cost_cents).then_with(|| a.name.cmp(&b.name)));
+
+        Ok::<_, ShippingError>(quotes)
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,378 @@
+use std::{collections::HashMap, error::Error, fmt::Debug};
+
+use chrono::{DateTime, Utc};
+use diesel::{upsert::excluded, BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{models::{ProductVariant, StockMovement, WarehouseStock}, pagination::{Page, PageRequest}, schema::{inventory, product_variants, stock_movements, warehouse_stock, warehouses}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Enum representing why stock of a variant changed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StockMovementReason {
+    Order,
+    Cancellation,
+    Restock,
+    Adjustment,
+    Return
+}
+
+impl StockMovementReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StockMovementReason::Order => "order",
+            StockMovementReason::Cancellation => "cancellation",
+            StockMovementReason::Restock => "restock",
+            StockMovementReason::Adjustment => "adjustment",
+            StockMovementReason::Return => "return"
+        }
+    }
+}
+
+// Struct representing context recorded along with a change of stock
+#[derive(Debug, Clone)]
+pub struct StockChange {
+    pub reason: StockMovementReason,
+    pub reference_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+    // Warehouse whose stock changes, the default warehouse when not given
+    pub warehouse_id: Option<Uuid>
+}
+
+impl StockChange {
+    pub fn new(reason: StockMovementReason) -> Self {
+        Self {
+            reason,
+            reference_id: None,
+            actor_id: None,
+            note: None,
+            warehouse_id: None
+        }
+    }
+}
+
+// Warehouse holding stock which wasn't given a location
+pub fn default_warehouse_id(conn: &mut DbConnection) -> QueryResult<Uuid> {
+    warehouses::table
+        .filter(warehouses::is_default.eq(true))
+        .select(warehouses::warehouse_id)
+        .get_result::<Uuid>(conn)
+}
+
+// Add entry to stock ledger without touching stock itself
+// Used where stock is set directly, e.g. when a variant is created
+pub fn record_stock_movement(
+    conn: &mut DbConnection,
+    item_id: Uuid,
+    variant_id: Uuid,
+    quantity: i32,
+    change: &StockChange
+) -> QueryResult<StockMovement> {
+    diesel::insert_into(stock_movements::table)
+        .values(StockMovement {
+            movement_id: Uuid::new_v4(),
+            item_id,
+            variant_id,
+            quantity,
+            reason: change.reason.as_str().to_string(),
+            reference_id: change.reference_id,
+            actor_id: change.actor_id,
+            note: change.note.clone(),
+            created_at: Utc::now(),
+            warehouse_id: change.warehouse_id
+        })
+        .get_result::<StockMovement>(conn)
+}
+
+// Apply non-zero signed quantity to stock of variant at a warehouse (and its totals) and record it in the ledger
+// Returns None when variant doesn't exist or the warehouse doesn't have enough stock
+pub fn adjust_variant_stock(
+    conn: &mut DbConnection,
+    variant_id: Uuid,
+    quantity: i32,
+    change: &StockChange
+) -> QueryResult<Option<StockMovement>> {
+    let warehouse_id = match change.warehouse_id {
+        Some(warehouse_id) => warehouse_id,
+        None => default_warehouse_id(conn)?
+    };
+    let change = StockChange {
+        warehouse_id: Some(warehouse_id),
+        ..change.clone()
+    };
+
+    // Savepoint so totals are left untouched when the warehouse can't cover the change
+    let res = conn.transaction(|conn| {
+        let item_id = diesel::update(product_variants::table)
+            .filter(product_variants::variant_id.eq(variant_id))
+            .filter(product_variants::amount.ge(-quantity))
+            .set(product_variants::amount.eq(product_variants::amount + quantity))
+            .returning(product_variants::item_id)
+            .get_result::<Uuid>(conn)
+            .optional()?
+            .ok_or(diesel::result::Error::RollbackTransaction)?;
+
+        if !adjust_warehouse_stock(conn, warehouse_id, variant_id, quantity)? {
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        diesel::update(inventory::table)
+            .filter(inventory::item_id.eq(item_id))
+            .set(inventory::amount.eq(inventory::amount + quantity))
+            .execute(conn)?;
+
+        record_stock_movement(conn, item_id, variant_id, quantity, &change)
+    });
+
+    match res {
+        Ok(movement) => Ok(Some(movement)),
+        Err(diesel::result::Error::RollbackTransaction) => Ok(None),
+        Err(e) => Err(e)
+    }
+}
+
+// Returns false when warehouse doesn't hold enough stock to remove
+fn adjust_warehouse_stock(
+    conn: &mut DbConnection,
+    warehouse_id: Uuid,
+    variant_id: Uuid,
+    quantity: i32
+) -> QueryResult<bool> {
+    if quantity > 0 {
+        diesel::insert_into(warehouse_stock::table)
+            .values(WarehouseStock { warehouse_id, variant_id, amount: quantity })
+            .on_conflict((warehouse_stock::warehouse_id, warehouse_stock::variant_id))
+            .do_update()
+            .set(warehouse_stock::amount.eq(warehouse_stock::amount + excluded(warehouse_stock::amount)))
+            .execute(conn)?;
+
+        return Ok(true);
+    }
+
+    let affected_rows = diesel::update(warehouse_stock::table)
+        .filter(warehouse_stock::warehouse_id.eq(warehouse_id))
+        .filter(warehouse_stock::variant_id.eq(variant_id))
+        .filter(warehouse_stock::amount.ge(-quantity))
+        .set(warehouse_stock::amount.eq(warehouse_stock::amount + quantity))
+        .execute(conn)?;
+
+    Ok(affected_rows == 1)
+}
+
+// Errors associated with stock ledger of an inventory item
+#[derive(Error)]
+pub enum StockLedgerError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid),
+    #[error("variant_id: {0} doesn't belong to item")]
+    NoVariantIdError(Uuid),
+    #[error("warehouse_id: {0} doesn't exist")]
+    NoWarehouseIdError(Uuid),
+    #[error("Not enough stock to remove {0} units")]
+    InsufficientStockError(i32)
+}
+
+impl Debug for StockLedgerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+fn ensure_item_exists(conn: &mut DbConnection, item_id: Uuid) -> Result<(), StockLedgerError> {
+    inventory::table
+        .find(item_id)
+        .select(inventory::item_id)
+        .get_result::<Uuid>(conn)
+        .optional()?
+        .map(|_| ())
+        .ok_or(StockLedgerError::NoItemIdError(item_id))
+}
+
+#[tracing::instrument(
+    "Recording manual stock movement",
+    skip(conn, change)
+)]
+pub async fn insert_manual_stock_movement(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    variant_id: Uuid,
+    quantity: i32,
+    change: StockChange
+) -> Result<StockMovement, StockLedgerError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<StockMovement, StockLedgerError, _>(|conn| {
+            ensure_item_exists(conn, item_id)?;
+
+            let belongs_to_item = product_variants::table
+                .filter(product_variants::variant_id.eq(variant_id))
+                .filter(product_variants::item_id.eq(item_id))
+                .select(product_variants::variant_id)
+                .get_result::<Uuid>(conn)
+                .optional()?
+                .is_some();
+
+            if !belongs_to_item {
+                return Err(StockLedgerError::NoVariantIdError(variant_id));
+            }
+
+            if let Some(warehouse_id) = change.warehouse_id {
+                let warehouse_exists = warehouses::table
+                    .find(warehouse_id)
+                    .select(warehouses::warehouse_id)
+                    .get_result::<Uuid>(conn)
+                    .optional()?
+                    .is_some();
+
+                if !warehouse_exists {
+                    return Err(StockLedgerError::NoWarehouseIdError(warehouse_id));
+                }
+            }
+
+            adjust_variant_stock(conn, variant_id, quantity, &change)?
+                .ok_or(StockLedgerError::InsufficientStockError(-quantity))
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Position of a movement within ledger ordered from newest to oldest
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StockMovementCursor {
+    pub created_at: DateTime<Utc>,
+    pub movement_id: Uuid
+}
+
+#[tracing::instrument(
+    "Getting stock movements of item from db",
+    skip(conn, page_request)
+)]
+pub async fn get_stock_movements(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    page_request: PageRequest<StockMovementCursor>
+) -> Result<Page<StockMovement>, StockLedgerError> {
+    let res = spawn_blocking_with_tracing(move || {
+        ensure_item_exists(&mut conn, item_id)?;
+
+        let total = stock_movements::table
+            .filter(stock_movements::item_id.eq(item_id))
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = stock_movements::table
+            .filter(stock_movements::item_id.eq(item_id))
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                stock_movements::created_at.lt(after.created_at)
+                    .or(stock_movements::created_at.eq(after.created_at).and(stock_movements::movement_id.lt(after.movement_id)))
+            );
+        }
+
+        let rows = query
+            .order((stock_movements::created_at.desc(), stock_movements::movement_id.desc()))
+            .limit(page_request.fetch_limit())
+            .load::<StockMovement>(&mut conn)?;
+
+        Ok::<_, StockLedgerError>(Page::new(rows, page_request.limit, total, |movement| StockMovementCursor {
+            created_at: movement.created_at,
+            movement_id: movement.movement_id
+        }))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Struct representing stock of a variant compared against its ledger
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VariantReconciliation {
+    pub variant_id: Uuid,
+    pub sku: String,
+    pub amount: i32,
+    pub ledger_amount: i64,
+    pub discrepancy: i64
+}
+
+// Struct representing stock of an item compared against its ledger
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StockReconciliation {
+    pub item_id: Uuid,
+    pub recorded_amount: Option<i32>,
+    pub variant_amount: i64,
+    pub ledger_amount: i64,
+    pub is_consistent: bool,
+    pub variants: Vec<VariantReconciliation>
+}
+
+#[tracing::instrument(
+    "Reconciling stock of item against ledger",
+    skip(conn)
+)]
+pub async fn reconcile_item_stock(
+    mut conn: DbConnection,
+    item_id: Uuid
+) -> Result<StockReconciliation, StockLedgerError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let recorded_amount = inventory::table
+            .find(item_id)
+            .select(inventory::amount)
+            .get_result::<Option<i32>>(&mut conn)
+            .optional()?
+            .ok_or(StockLedgerError::NoItemIdError(item_id))?;
+
+        let variants = product_variants::table
+            .filter(product_variants::item_id.eq(item_id))
+            .order(product_variants::sku)
+            .load::<ProductVariant>(&mut conn)?;
+
+        let ledger: HashMap<Uuid, i64> = stock_movements::table
+            .filter(stock_movements::item_id.eq(item_id))
+            .group_by(stock_movements::variant_id)
+            .select((stock_movements::variant_id, diesel::dsl::sum(stock_movements::quantity)))
+            .load::<(Uuid, Option<i64>)>(&mut conn)?
+            .into_iter()
+            .map(|(variant_id, total)| (variant_id, total.unwrap_or(0)))
+            .collect();
+
+        let variants: Vec<VariantReconciliation> = variants.into_iter()
+            .map(|variant| {
+                let ledger_amount = ledger.get(&variant.variant_id).copied().unwrap_or(0);
+                VariantReconciliation {
+                    variant_id: variant.variant_id,
+                    sku: variant.sku,
+                    amount: variant.amount,
+                    ledger_amount,
+                    discrepancy: variant.amount as i64 - ledger_amount
+                }
+            })
+            .collect();
+
+        let variant_amount = variants.iter().map(|variant| variant.amount as i64).sum();
+        let ledger_amount = ledger.values().sum();
+
+        Ok::<_, StockLedgerError>(StockReconciliation {
+            item_id,
+            is_consistent: recorded_amount.map(i64::from) == Some(variant_amount)
+                && variants.iter().all(|variant| variant.discrepancy == 0),
+            recorded_amount,
+            variant_amount,
+            ledger_amount,
+            variants
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashMap, error::Error, fmt::Debug};
+
+use chrono::Utc;
+use diesel::{BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, PgExpressionMethods, PgSortExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{domain::{address::Destination, tax::TaxClass}, models::TaxRate, pagination::{Page, PageRequest}, schema::{inventory, tax_rates}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Position of a tax rate within listing ordered by destination and class, country wide rates come first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaxRateCursor {
+    pub country: String,
+    pub region: Option<String>,
+    pub tax_class: String
+}
+
+// Errors associated with managing tax rates and classes
+#[derive(Error)]
+pub enum TaxError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("tax_rate_id: {0} doesn't exist")]
+    NoTaxRateIdError(Uuid),
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for TaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Setting tax rate in db",
+    skip(conn)
+)]
+pub async fn set_tax_rate(
+    mut conn: DbConnection,
+    destination: Destination,
+    tax_class: TaxClass,
+    rate_basis_points: i32
+) -> Result<TaxRate, TaxError> {
+    let res = spawn_blocking_with_tracing(move || {
+        conn.transaction::<TaxRate, TaxError, _>(|conn| {
+            // A destination and class has a single rate, setting it again replaces it
+            let existing = tax_rates::table
+                .filter(tax_rates::country.eq(&destination.country))
+                .filter(tax_rates::region.is_not_distinct_from(destination.region.clone()))
+                .filter(tax_rates::tax_class.eq(tax_class.inner()))
+                .select(tax_rates::tax_rate_id)
+                .for_update()
+                .get_result::<Uuid>(conn)
+                .optional()?;
+
+            let now = Utc::now();
+            let rate = match existing {
+                Some(tax_rate_id) => diesel::update(tax_rates::table.find(tax_rate_id))
+                    .set((
+                        tax_rates::rate_basis_points.eq(rate_basis_points),
+                        tax_rates::updated_at.eq(now)
+                    ))
+                    .get_result::<TaxRate>(conn)?,
+                None => diesel::insert_into(tax_rates::table)
+                    .values(TaxRate {
+                        tax_rate_id: Uuid::new_v4(),
+                        country: destination.country,
+                        region: destination.region,
+                        tax_class: tax_class.inner(),
+                        rate_basis_points,
+                        created_at: now,
+                        updated_at: now
+                    })
+                    .get_result::<TaxRate>(conn)?
+            };
+
+            Ok(rate)
+        })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting tax rates from db",
+    skip(conn)
+)]
+pub async fn get_tax_rates(
+    mut conn: DbConnection,
+    page_request: PageRequest<TaxRateCursor>
+) -> Result<Page<TaxRate>, TaxError> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = tax_rates::table
+            .count()
+            .get_result::<i64>(&mut conn)?;
+
+        let mut query = tax_rates::table
+            .into_boxed();
+
+        // Destination and class are unique, a country wide rate sorts before its regions
+        if let Some(after) = &page_request.after {
+            query = match &after.region {
+                None => query.filter(
+                    tax_rates::country.gt(after.country.clone())
+                        .or(tax_rates::country.eq(after.country.clone()).and(
+                            tax_rates::region.is_not_null()
+                                .or(tax_rates::region.is_null().and(tax_rates::tax_class.gt(after.tax_class.clone())))
+                        ))
+                ),
+                Some(region) => query.filter(
+                    tax_rates::country.gt(after.country.clone())
+                        .or(tax_rates::country.eq(after.country.clone()).and(
+                            tax_rates::region.gt(region.clone())
+                                .or(tax_rates::region.eq(region.clone()).and(tax_rates::tax_class.gt(after.tax_class.clone())))
+                        ))
+                )
+            };
+        }
+
+        let rows = query
+            .order((tax_rates::country, tax_rates::region.asc().nulls_first(), tax_rates::tax_class))
+            .limit(page_request.fetch_limit())
+            .load::<TaxRate>(&mut conn)?;
+
+        Ok::<_, TaxError>(Page::new(rows, page_request.limit, total, |rate| TaxRateCursor {
+            country: rate.country.clone(),
+            region: rate.region.clone(),
+            tax_class: rate.tax_class.clone()
+        }))
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Deleting tax rate from db",
+    skip(conn)
+)]
+pub async fn delete_tax_rate(
+    mut conn: DbConnection,
+    tax_rate_id: Uuid
+) -> Result<(), TaxError> {
+    spawn_blocking_with_tracing(move || {
+        // Orders keep the rate they were charged on their lines
+        let deleted = diesel::delete(tax_rates::table.find(tax_rate_id))
+            .execute(&mut conn)?;
+
+        if deleted == 0 {
+            return Err(TaxError::NoTaxRateIdError(tax_rate_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    "Setting tax class of inventory item",
+    skip(conn)
+)]
+pub async fn set_item_tax_class(
+    mut conn: DbConnection,
+    item_id: Uuid,
+    tax_class: TaxClass
+) -> Result<(), TaxError> {
+    spawn_blocking_with_tracing(move || {
+        let affected_rows = diesel::update(inventory::table.find(item_id))
+            .set(inventory::tax_class.eq(tax_class.inner()))
+            .execute(&mut conn)?;
+
+        if affected_rows == 0 {
+            return Err(TaxError::NoItemIdError(item_id));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Rate in basis points each item is taxed at when shipped to destination
+// Items without a rate for their class, and orders without a destination, aren't taxed
+pub fn item_tax_rates(
+    conn: &mut DbConnection,
+    destination: Option<&Destination>,
+    item_ids: &[Uuid]
+) -> QueryResult<HashMap<Uuid, i32>> {
+    let Some(destination) = destination else {
+        return Ok(HashMap::new());
+    };
+
+    let classes: Vec<(Uuid, String)> = inventory::table
+        .filter(inventory::item_id.eq_any(item_ids))
+        .select((inventory::item_id, inventory::tax_class))
+        .load::<(Uuid, String)>(conn)?;
+
+    let rates = tax_rates::table
+        .filter(tax_rates::country.eq(&destination.country))
+        .load::<TaxRate>(conn)?;
+
+    Ok(classes.into_iter()
+        .filter_map(|(item_id, tax_class)| {
+            let for_class = || rates.iter().filter(|rate| rate.tax_class == tax_class);
+            for_class()
+                .find(|rate| rate.region.is_some() && rate.region == destination.region)
+                .or_else(|| for_class().find(|rate| rate.region.is_none()))
+                .map(|rate| (item_id, rate.rate_basis_points))
+        })
+        .collect())
+}
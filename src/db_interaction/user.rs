@@ -7,7 +7,7 @@ use secrecy::{ExposeSecret, SecretString};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{models::{ConfirmationMap, User, UserProfileInfo}, password::compute_password_hash, schema::users, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+use crate::{db_interaction::{enqueue_email, NewEmail}, email_templates::EmailTemplateError, models::{ConfirmationMap, User, UserProfileInfo}, password::compute_password_hash, schema::users, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
 
 
 // Function to query user from email id
@@ -53,15 +53,17 @@ impl Debug for UserInsertError {
     }
 }
 
+// Confirmation email is built from the confirmation id and queued along with the user
 #[tracing::instrument(
     "Inserting user into the database",
-    skip(conn)
+    skip(conn, confirmation_email)
 )]
 pub async fn insert_user_into_database(
     mut conn: DbConnection,
     name: String,
     email: String,
-    password: SecretString
+    password: SecretString,
+    confirmation_email: impl FnOnce(Uuid) -> Result<NewEmail, EmailTemplateError> + Send + 'static
 ) -> Result<Uuid, UserInsertError> {
 
     let password_hash = spawn_blocking_with_tracing(move || {
@@ -116,6 +118,13 @@ pub async fn insert_user_into_database(
                     .execute(conn)
                     .map_err(|_| UserInsertError::UnexpectedError(anyhow::anyhow!("Unexpected diesel / database error")))?;
 
+                let conf_email = confirmation_email(id)
+                    .context("Failed to render confirmation email")
+                    .map_err(UserInsertError::UnexpectedError)?;
+
+                enqueue_email(conn, conf_email)
+                    .map_err(|_| UserInsertError::UnexpectedError(anyhow::anyhow!("Failed to queue confirmation email")))?;
+
                 Ok(id)
             })
         })
@@ -141,6 +150,7 @@ pub async fn get_user_profile_info(
             users::name,
             users::email,
             users::phone_number,
+            users::phone_country,
             users::address
         ))
         .filter(users::user_id.eq(user_id))
@@ -184,6 +194,7 @@ pub async fn post_user_profile_info(
                 users::email.eq(new_info.email),
                 users::name.eq(new_info.name),
                 users::phone_number.eq(new_info.phone_number),
+                users::phone_country.eq(new_info.phone_country),
                 users::address.eq(new_info.address)
             ))
             .filter(users::user_id.eq(user_id))
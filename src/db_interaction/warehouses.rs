@@ -0,0 +1,188 @@
+use std::{collections::HashMap, error::Error, fmt::Debug};
+
+use anyhow::Context;
+use diesel::{BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{domain::allocation::{plan_allocations, Allocation, AllocationStrategy, Location, WarehouseCandidate}, models::Warehouse, pagination::{Page, PageRequest}, schema::{inventory, product_variants, warehouse_stock, warehouses}, telemetry::spawn_blocking_with_tracing, utils::{error_fmt_chain, DbConnection}};
+
+// Struct representing stock of a variant held at a warehouse
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+pub struct WarehouseStockLevel {
+    pub warehouse_id: Uuid,
+    pub warehouse_code: String,
+    pub variant_id: Uuid,
+    pub sku: String,
+    pub amount: i32
+}
+
+// Position of a warehouse within listing ordered by priority
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WarehouseCursor {
+    pub priority: i32,
+    pub code: String
+}
+
+// Errors associated with warehouses
+#[derive(Error)]
+pub enum WarehouseError {
+    #[error("Failed due to threadpool error")]
+    ThreadpoolError(#[from] tokio::task::JoinError),
+    #[error("Failed to run query")]
+    RunQueryError(#[from] diesel::result::Error),
+    #[error("Warehouse with same code already exists")]
+    NotUnique,
+    #[error("item_id: {0} doesn't exist")]
+    NoItemIdError(Uuid)
+}
+
+impl Debug for WarehouseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+#[tracing::instrument(
+    "Insert a warehouse to db",
+    skip_all
+)]
+pub async fn insert_warehouse(
+    mut conn: DbConnection,
+    warehouse: Warehouse
+) -> Result<Warehouse, WarehouseError> {
+    let res = spawn_blocking_with_tracing(move || {
+        diesel::insert_into(warehouses::table)
+            .values(warehouse)
+            .get_result::<Warehouse>(&mut conn)
+            .map_err(|e| {
+                match e {
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::UniqueViolation,
+                        _
+                    ) => WarehouseError::NotUnique,
+                    _ => WarehouseError::RunQueryError(e)
+                }
+            })
+    })
+    .await??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting warehouses from db",
+    skip(conn)
+)]
+pub async fn get_warehouses(
+    mut conn: DbConnection,
+    page_request: PageRequest<WarehouseCursor>
+) -> Result<Page<Warehouse>, anyhow::Error> {
+    let res = spawn_blocking_with_tracing(move || {
+        let total = warehouses::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .context("Failed to count warehouses")?;
+
+        let mut query = warehouses::table
+            .into_boxed();
+
+        if let Some(after) = &page_request.after {
+            query = query.filter(
+                warehouses::priority.gt(after.priority)
+                    .or(warehouses::priority.eq(after.priority).and(warehouses::code.gt(after.code.clone())))
+            );
+        }
+
+        let rows = query
+            .order((warehouses::priority.asc(), warehouses::code.asc()))
+            .limit(page_request.fetch_limit())
+            .load::<Warehouse>(&mut conn)
+            .context("Failed to get warehouses")?;
+
+        Ok::<_, anyhow::Error>(Page::new(rows, page_request.limit, total, |warehouse| WarehouseCursor {
+            priority: warehouse.priority,
+            code: warehouse.code.clone()
+        }))
+    })
+    .await
+    .context("Failed due to threadpool error")??;
+
+    Ok(res)
+}
+
+#[tracing::instrument(
+    "Getting stock of item per warehouse from db",
+    skip(conn)
+)]
+pub async fn get_item_warehouse_stock(
+    mut conn: DbConnection,
+    item_id: Uuid
+) -> Result<Vec<WarehouseStockLevel>, WarehouseError> {
+    let res = spawn_blocking_with_tracing(move || {
+        inventory::table
+            .find(item_id)
+            .select(inventory::item_id)
+            .get_result::<Uuid>(&mut conn)
+            .optional()?
+            .ok_or(WarehouseError::NoItemIdError(item_id))?;
+
+        let levels = warehouse_stock::table
+            .inner_join(warehouses::table)
+            .inner_join(product_variants::table)
+            .filter(product_variants::item_id.eq(item_id))
+            .select((
+                warehouses::warehouse_id,
+                warehouses::code,
+                product_variants::variant_id,
+                product_variants::sku,
+                warehouse_stock::amount
+            ))
+            .order((warehouses::priority, warehouses::code, product_variants::sku))
+            .load::<WarehouseStockLevel>(&mut conn)?;
+
+        Ok::<_, WarehouseError>(levels)
+    })
+    .await??;
+
+    Ok(res)
+}
+
+// Lock stock of ordered variants at every warehouse and plan which warehouses lines are picked from
+// Returns None when warehouses don't hold enough stock for every line
+pub fn allocate_order_lines(
+    conn: &mut DbConnection,
+    strategy: AllocationStrategy,
+    lines: &[(Uuid, i32)],
+    ship_to: Option<Location>
+) -> QueryResult<Option<Vec<Allocation>>> {
+    let variant_ids: Vec<Uuid> = lines.iter()
+        .map(|(variant_id, _)| *variant_id)
+        .collect();
+
+    let stock: HashMap<(Uuid, Uuid), i32> = warehouse_stock::table
+        .filter(warehouse_stock::variant_id.eq_any(&variant_ids))
+        .select((warehouse_stock::warehouse_id, warehouse_stock::variant_id, warehouse_stock::amount))
+        .order((warehouse_stock::warehouse_id, warehouse_stock::variant_id))
+        .for_update()
+        .load::<(Uuid, Uuid, i32)>(conn)?
+        .into_iter()
+        .map(|(warehouse_id, variant_id, amount)| ((warehouse_id, variant_id), amount))
+        .collect();
+
+    let candidates: Vec<WarehouseCandidate> = warehouses::table
+        .load::<Warehouse>(conn)?
+        .into_iter()
+        .map(|warehouse| WarehouseCandidate {
+            warehouse_id: warehouse.warehouse_id,
+            priority: warehouse.priority,
+            location: warehouse.latitude
+                .zip(warehouse.longitude)
+                .map(|(latitude, longitude)| Location { latitude, longitude })
+        })
+        .collect();
+
+    Ok(plan_allocations(strategy, lines, &candidates, &stock, ship_to))
+}
@@ -0,0 +1,53 @@
+// Wrapper struct defining domain for where an order is shipped and taxed
+// Country is an ISO 3166-1 alpha-2 code, region a subdivision code within it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destination {
+    pub country: String,
+    pub region: Option<String>
+}
+
+impl Destination {
+    const MAX_REGION_LENGTH: usize = 3;
+
+    pub fn parse(country: String, region: Option<String>) -> Result<Destination, String> {
+        let country = country.trim().to_uppercase();
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("{} is not a valid country code", country));
+        }
+
+        let region = region
+            .map(|region| region.trim().to_uppercase())
+            .filter(|region| !region.is_empty());
+
+        if let Some(region) = &region {
+            if region.len() > Self::MAX_REGION_LENGTH || !region.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(format!("{} is not a valid region code", region));
+            }
+        }
+
+        Ok(Self { country, region })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Destination;
+    use claim::assert_err;
+
+    #[test]
+    fn destination_is_normalised() {
+        let destination = Destination::parse(" in ".to_string(), Some("ka".to_string())).unwrap();
+        assert_eq!(destination.country, "IN");
+        assert_eq!(destination.region.as_deref(), Some("KA"));
+
+        let destination = Destination::parse("US".to_string(), Some(" ".to_string())).unwrap();
+        assert_eq!(destination.region, None);
+    }
+
+    #[test]
+    fn invalid_destination_is_rejected() {
+        assert_err!(Destination::parse("IND".to_string(), None));
+        assert_err!(Destination::parse("1N".to_string(), None));
+        assert_err!(Destination::parse("US".to_string(), Some("CA-1".to_string())));
+    }
+}
@@ -0,0 +1,287 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Strategy used to choose warehouses order lines are picked from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationStrategy {
+    // Ship whole order from one warehouse when any can fulfil it, splitting otherwise
+    #[default]
+    SingleLocation,
+    // Pick from warehouses closest to shipping location first
+    Nearest,
+    // Pick from warehouses in order of their priority
+    Priority
+}
+
+// Point on earth in degrees
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64
+}
+
+impl Location {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    pub fn parse(latitude: f64, longitude: f64) -> Result<Location, String> {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(format!("({}, {}) is not a valid location", latitude, longitude));
+        }
+
+        Ok(Self { latitude, longitude })
+    }
+
+    // Great-circle distance using the haversine formula
+    pub fn distance_km(&self, other: &Location) -> f64 {
+        let d_latitude = (other.latitude - self.latitude).to_radians();
+        let d_longitude = (other.longitude - self.longitude).to_radians();
+
+        let a = (d_latitude / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos() * other.latitude.to_radians().cos() * (d_longitude / 2.0).sin().powi(2);
+
+        2.0 * Self::EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+// Warehouse which order lines can be allocated to
+#[derive(Debug, Clone)]
+pub struct WarehouseCandidate {
+    pub warehouse_id: Uuid,
+    pub priority: i32,
+    pub location: Option<Location>
+}
+
+// Quantity of a variant to be picked from a warehouse for an order line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Allocation {
+    // Index of the line within lines being allocated
+    pub line: usize,
+    pub variant_id: Uuid,
+    pub warehouse_id: Uuid,
+    pub quantity: i32
+}
+
+// Split order lines across warehouses according to strategy
+// stock is keyed by (warehouse_id, variant_id)
+// Returns None when warehouses together don't hold enough stock for every line
+pub fn plan_allocations(
+    strategy: AllocationStrategy,
+    lines: &[(Uuid, i32)],
+    warehouses: &[WarehouseCandidate],
+    stock: &HashMap<(Uuid, Uuid), i32>,
+    ship_to: Option<Location>
+) -> Option<Vec<Allocation>> {
+    let ranked = rank_warehouses(strategy, warehouses, ship_to);
+
+    if strategy == AllocationStrategy::SingleLocation {
+        let mut quantity_by_variant: HashMap<Uuid, i32> = HashMap::new();
+        for (variant_id, quantity) in lines {
+            *quantity_by_variant.entry(*variant_id).or_default() += quantity;
+        }
+
+        let single = ranked.iter().find(|warehouse| {
+            quantity_by_variant.iter().all(|(variant_id, quantity)| {
+                stock.get(&(warehouse.warehouse_id, *variant_id)).copied().unwrap_or(0) >= *quantity
+            })
+        });
+
+        if let Some(warehouse) = single {
+            return Some(lines.iter()
+                .enumerate()
+                .filter(|(_, (_, quantity))| *quantity > 0)
+                .map(|(line, (variant_id, quantity))| Allocation {
+                    line,
+                    variant_id: *variant_id,
+                    warehouse_id: warehouse.warehouse_id,
+                    quantity: *quantity
+                })
+                .collect());
+        }
+    }
+
+    let mut remaining_stock = stock.clone();
+    let mut allocations = Vec::new();
+
+    for (line, (variant_id, quantity)) in lines.iter().enumerate() {
+        let mut remaining = *quantity;
+
+        for warehouse in &ranked {
+            if remaining == 0 {
+                break;
+            }
+
+            let available = remaining_stock.entry((warehouse.warehouse_id, *variant_id)).or_insert(0);
+            let taken = remaining.min(*available);
+            if taken > 0 {
+                *available -= taken;
+                remaining -= taken;
+                allocations.push(Allocation {
+                    line,
+                    variant_id: *variant_id,
+                    warehouse_id: warehouse.warehouse_id,
+                    quantity: taken
+                });
+            }
+        }
+
+        if remaining > 0 {
+            return None;
+        }
+    }
+
+    Some(allocations)
+}
+
+// Order warehouses are tried in, ties broken by priority and then id so plans are stable
+fn rank_warehouses(
+    strategy: AllocationStrategy,
+    warehouses: &[WarehouseCandidate],
+    ship_to: Option<Location>
+) -> Vec<&WarehouseCandidate> {
+    let mut ranked: Vec<&WarehouseCandidate> = warehouses.iter().collect();
+
+    // Priority strategy ignores location, others prefer closer warehouses when it is known
+    let ship_to = ship_to.filter(|_| strategy != AllocationStrategy::Priority);
+    let distance = |warehouse: &WarehouseCandidate| -> f64 {
+        match (ship_to, warehouse.location) {
+            (Some(ship_to), Some(location)) => ship_to.distance_km(&location),
+            _ => f64::INFINITY
+        }
+    };
+
+    ranked.sort_by(|a, b| {
+        distance(a).partial_cmp(&distance(b))
+            .unwrap_or(Ordering::Equal)
+            .then(a.priority.cmp(&b.priority))
+            .then(a.warehouse_id.cmp(&b.warehouse_id))
+    });
+
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use claim::{assert_err, assert_none, assert_ok};
+    use uuid::Uuid;
+
+    use super::{plan_allocations, Allocation, AllocationStrategy, Location, WarehouseCandidate};
+
+    // Delhi, Mumbai and Bengaluru
+    fn warehouses() -> Vec<WarehouseCandidate> {
+        vec![
+            WarehouseCandidate { warehouse_id: Uuid::new_v4(), priority: 0, location: Some(Location { latitude: 28.61, longitude: 77.21 }) },
+            WarehouseCandidate { warehouse_id: Uuid::new_v4(), priority: 1, location: Some(Location { latitude: 19.08, longitude: 72.88 }) },
+            WarehouseCandidate { warehouse_id: Uuid::new_v4(), priority: 2, location: Some(Location { latitude: 12.97, longitude: 77.59 }) }
+        ]
+    }
+
+    fn quantities(allocations: &[Allocation]) -> Vec<(Uuid, Uuid, i32)> {
+        allocations.iter()
+            .map(|allocation| (allocation.variant_id, allocation.warehouse_id, allocation.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn priority_strategy_splits_line_in_priority_order() {
+        let warehouses = warehouses();
+        let variant = Uuid::new_v4();
+        let stock = HashMap::from([
+            ((warehouses[0].warehouse_id, variant), 2),
+            ((warehouses[2].warehouse_id, variant), 5)
+        ]);
+
+        let allocations = plan_allocations(AllocationStrategy::Priority, &[(variant, 4)], &warehouses, &stock, None).unwrap();
+
+        assert_eq!(quantities(&allocations), vec![
+            (variant, warehouses[0].warehouse_id, 2),
+            (variant, warehouses[2].warehouse_id, 2)
+        ]);
+    }
+
+    #[test]
+    fn single_location_prefers_warehouse_holding_whole_order() {
+        let warehouses = warehouses();
+        let (shirt, cap) = (Uuid::new_v4(), Uuid::new_v4());
+        let stock = HashMap::from([
+            ((warehouses[0].warehouse_id, shirt), 10),
+            ((warehouses[1].warehouse_id, cap), 10),
+            ((warehouses[2].warehouse_id, shirt), 1),
+            ((warehouses[2].warehouse_id, cap), 1)
+        ]);
+
+        let allocations = plan_allocations(AllocationStrategy::SingleLocation, &[(shirt, 1), (cap, 1)], &warehouses, &stock, None).unwrap();
+
+        assert_eq!(quantities(&allocations), vec![
+            (shirt, warehouses[2].warehouse_id, 1),
+            (cap, warehouses[2].warehouse_id, 1)
+        ]);
+    }
+
+    #[test]
+    fn single_location_splits_when_no_warehouse_holds_whole_order() {
+        let warehouses = warehouses();
+        let (shirt, cap) = (Uuid::new_v4(), Uuid::new_v4());
+        let stock = HashMap::from([
+            ((warehouses[0].warehouse_id, shirt), 10),
+            ((warehouses[1].warehouse_id, cap), 10)
+        ]);
+
+        let allocations = plan_allocations(AllocationStrategy::SingleLocation, &[(shirt, 2), (cap, 3)], &warehouses, &stock, None).unwrap();
+
+        assert_eq!(quantities(&allocations), vec![
+            (shirt, warehouses[0].warehouse_id, 2),
+            (cap, warehouses[1].warehouse_id, 3)
+        ]);
+    }
+
+    #[test]
+    fn nearest_strategy_prefers_closest_warehouse() {
+        let warehouses = warehouses();
+        let variant = Uuid::new_v4();
+        let stock: HashMap<(Uuid, Uuid), i32> = warehouses.iter()
+            .map(|warehouse| ((warehouse.warehouse_id, variant), 10))
+            .collect();
+        // Chennai is closest to Bengaluru
+        let ship_to = Some(Location { latitude: 13.08, longitude: 80.27 });
+
+        let allocations = plan_allocations(AllocationStrategy::Nearest, &[(variant, 1)], &warehouses, &stock, ship_to).unwrap();
+        assert_eq!(quantities(&allocations), vec![(variant, warehouses[2].warehouse_id, 1)]);
+
+        // Without a shipping location priority decides
+        let allocations = plan_allocations(AllocationStrategy::Nearest, &[(variant, 1)], &warehouses, &stock, None).unwrap();
+        assert_eq!(quantities(&allocations), vec![(variant, warehouses[0].warehouse_id, 1)]);
+    }
+
+    #[test]
+    fn insufficient_stock_across_warehouses_is_not_allocated() {
+        let warehouses = warehouses();
+        let variant = Uuid::new_v4();
+        let stock = HashMap::from([
+            ((warehouses[0].warehouse_id, variant), 1),
+            ((warehouses[1].warehouse_id, variant), 1)
+        ]);
+
+        assert_none!(plan_allocations(AllocationStrategy::Priority, &[(variant, 3)], &warehouses, &stock, None));
+    }
+
+    #[test]
+    fn distance_between_known_cities_is_close_to_actual() {
+        let delhi = Location { latitude: 28.61, longitude: 77.21 };
+        let mumbai = Location { latitude: 19.08, longitude: 72.88 };
+
+        let distance = delhi.distance_km(&mumbai);
+        assert!((1140.0..1160.0).contains(&distance), "distance was {}", distance);
+    }
+
+    #[test]
+    fn location_out_of_range_is_rejected() {
+        assert_err!(Location::parse(91.0, 0.0));
+        assert_err!(Location::parse(0.0, -181.0));
+        assert_ok!(Location::parse(-90.0, 180.0));
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+// Wrapper struct defining domain for coupon codes customers type in
+#[derive(Debug, Clone)]
+pub struct CouponCode(pub String);
+
+impl CouponCode {
+    const MIN_LENGTH: usize = 3;
+    const MAX_LENGTH: usize = 32;
+
+    pub fn parse(code: String) -> Result<CouponCode, String> {
+        let code = code.trim().to_uppercase();
+
+        let is_valid = code.len() >= Self::MIN_LENGTH
+            && code.len() <= Self::MAX_LENGTH
+            && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if is_valid {
+            Ok(Self(code))
+        } else {
+            Err(format!("{} is not a valid coupon code", code))
+        }
+    }
+
+    pub fn inner(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl std::fmt::Display for CouponCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Enum representing how a coupon takes money off
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountType {
+    Percentage,
+    Fixed
+}
+
+impl DiscountType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscountType::Percentage => "percentage",
+            DiscountType::Fixed => "fixed"
+        }
+    }
+
+    pub fn parse(discount_type: &str) -> Option<DiscountType> {
+        [DiscountType::Percentage, DiscountType::Fixed]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == discount_type)
+    }
+
+    // Value is a percent for percentage coupons and cents for fixed ones
+    pub fn validate_value(&self, value: i64) -> Result<(), String> {
+        match self {
+            DiscountType::Percentage if !(1..=100).contains(&value) => Err("percentage discount should be between 1 and 100".to_string()),
+            DiscountType::Fixed if value <= 0 => Err("fixed discount should be positive".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    // Cents taken off eligible subtotal, never more than the subtotal itself
+    pub fn amount_off(&self, value: i64, eligible_cents: i64) -> i64 {
+        let amount = match self {
+            DiscountType::Percentage => eligible_cents * value / 100,
+            DiscountType::Fixed => value
+        };
+
+        amount.clamp(0, eligible_cents.max(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CouponCode, DiscountType};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn short_or_spaced_code_is_rejected() {
+        assert_err!(CouponCode::parse("AB".to_string()));
+        assert_err!(CouponCode::parse("SUMMER SALE".to_string()));
+        assert_err!(CouponCode::parse("A".repeat(33)));
+    }
+
+    #[test]
+    fn code_is_trimmed_and_uppercased() {
+        let code = CouponCode::parse(" summer-10 ".to_string());
+        assert_ok!(&code);
+        assert_eq!(code.unwrap().inner(), "SUMMER-10");
+    }
+
+    #[test]
+    fn discount_value_is_validated() {
+        assert_err!(DiscountType::Percentage.validate_value(0));
+        assert_err!(DiscountType::Percentage.validate_value(101));
+        assert_err!(DiscountType::Fixed.validate_value(-5));
+        assert_ok!(DiscountType::Fixed.validate_value(5000));
+    }
+
+    #[test]
+    fn discount_never_exceeds_eligible_subtotal() {
+        assert_eq!(DiscountType::Percentage.amount_off(15, 1999), 299);
+        assert_eq!(DiscountType::Fixed.amount_off(500, 1999), 500);
+        assert_eq!(DiscountType::Fixed.amount_off(5000, 1999), 1999);
+        assert_eq!(DiscountType::Percentage.amount_off(100, 0), 0);
+    }
+}
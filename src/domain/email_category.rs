@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+// Category of emails users can opt out of, emails without one (e.g. receipts) are always sent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailCategory {
+    OrderUpdates,
+    Promotions,
+    BackInStock
+}
+
+impl EmailCategory {
+    pub fn parse(category: &str) -> Result<EmailCategory, String> {
+        match category {
+            "order_updates" => Ok(Self::OrderUpdates),
+            "promotions" => Ok(Self::Promotions),
+            "back_in_stock" => Ok(Self::BackInStock),
+            _ => Err(format!("{} is not a valid email category", category))
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OrderUpdates => "order_updates",
+            Self::Promotions => "promotions",
+            Self::BackInStock => "back_in_stock"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailCategory;
+    use claim::assert_err;
+
+    #[test]
+    fn category_round_trips_through_its_name() {
+        for category in [EmailCategory::OrderUpdates, EmailCategory::Promotions, EmailCategory::BackInStock] {
+            assert_eq!(EmailCategory::parse(category.as_str()), Ok(category));
+        }
+    }
+
+    #[test]
+    fn unknown_category_is_rejected() {
+        assert_err!(EmailCategory::parse("newsletter"));
+    }
+}
@@ -1,2 +1,11 @@
 pub mod user_email;
 pub mod phone_number;
+pub mod sku;
+pub mod slug;
+pub mod allocation;
+pub mod coupon;
+pub mod promotion;
+pub mod tax;
+pub mod address;
+pub mod shipping;
+pub mod email_category;
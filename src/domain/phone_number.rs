@@ -1,28 +1,177 @@
 use std::fmt::Debug;
 
-use phonenumber::country;
+use phonenumber::{country, Mode};
 
 
 // Wrapper struct defining domain for phone number
+// Numbers are normalized to E.164 along with the region they belong to
 #[derive(Debug, Clone)]
-pub struct PhoneNumberDomain(pub String);
+pub struct PhoneNumberDomain{
+    number: String,
+    country: Option<country::Id>
+}
 
 impl PhoneNumberDomain{
-    pub fn parse(number: String) -> Result<PhoneNumberDomain, String>{
-        if phonenumber::parse(Some(country::IN), number.clone()).is_ok(){
-            Ok(Self(number))
+    // Parse number, falling back on default_region when number has no country code
+    pub fn parse(number: String, default_region: country::Id) -> Result<PhoneNumberDomain, String>{
+        // Default region's national prefix rules must not be applied to numbers
+        // which already carry a country code
+        let region = if number.trim_start().starts_with('+') {
+            None
         } else {
-            Err(format!("{} is not a valid user email", number))
+            Some(default_region)
+        };
+
+        match phonenumber::parse(region, &number){
+            Ok(parsed) if parsed.is_valid() => {
+                Ok(Self{
+                    number: parsed.format().mode(Mode::E164).to_string(),
+                    country: parsed.country().id()
+                })
+            },
+            _ => Err(format!("{} is not a valid phone number", number))
         }
     }
 
+    // E.164 representation of number
     pub fn inner(&self) -> String {
-        self.0.clone()
+        self.number.clone()
+    }
+
+    // ISO 3166-1 alpha-2 code of region number belongs to
+    pub fn country(&self) -> Option<String> {
+        self.country.map(|id| id.as_ref().to_string())
     }
 }
 
 impl std::fmt::Display for PhoneNumberDomain {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self.0, f)
+        std::fmt::Display::fmt(&self.number, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_err;
+    use phonenumber::{country, metadata::DATABASE};
+
+    use super::PhoneNumberDomain;
+
+    // Regions exercised by property tests
+    const REGIONS: [country::Id; 8] = [
+        country::IN,
+        country::US,
+        country::GB,
+        country::DE,
+        country::FR,
+        country::AU,
+        country::BR,
+        country::JP
+    ];
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(PhoneNumberDomain::parse("".to_string(), country::IN));
+    }
+
+    #[test]
+    fn too_short_number_is_rejected() {
+        assert_err!(PhoneNumberDomain::parse("12345".to_string(), country::IN));
+    }
+
+    #[test]
+    fn letters_are_rejected() {
+        assert_err!(PhoneNumberDomain::parse("not a number".to_string(), country::US));
+    }
+
+    #[test]
+    fn national_number_is_normalized_with_default_region() {
+        let number = PhoneNumberDomain::parse("8927401349".to_string(), country::IN).unwrap();
+        assert_eq!(number.inner(), "+918927401349");
+        assert_eq!(number.country(), Some("IN".to_string()));
+    }
+
+    #[test]
+    fn international_number_ignores_default_region() {
+        let number = PhoneNumberDomain::parse("+44 7400 123456".to_string(), country::IN).unwrap();
+        assert_eq!(number.inner(), "+447400123456");
+        assert_eq!(number.country(), Some("GB".to_string()));
+    }
+
+    #[test]
+    fn error_message_mentions_phone_number() {
+        let err = PhoneNumberDomain::parse("abc".to_string(), country::IN).unwrap_err();
+        assert!(err.contains("phone number"));
+    }
+
+    // Format in which a fixture number is written
+    #[derive(Debug, Clone)]
+    enum Format {
+        National,
+        International,
+        E164
+    }
+
+    #[derive(Debug, Clone)]
+    struct PhoneNumberFixture {
+        raw: String,
+        default_region: country::Id,
+        expected_e164: String,
+        expected_country: String
+    }
+
+    impl quickcheck::Arbitrary for PhoneNumberFixture {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let region = *g.choose(&REGIONS).unwrap();
+            let format = g.choose(&[Format::National, Format::International, Format::E164])
+                .unwrap()
+                .clone();
+
+            let metadata = DATABASE.by_id(region.as_ref()).unwrap();
+            let example = metadata.descriptors().mobile()
+                .and_then(|descriptor| descriptor.example())
+                .unwrap();
+            let code = metadata.country_code();
+            let expected_e164 = format!("+{}{}", code, example);
+
+            let (raw, default_region) = match format {
+                Format::National => (example.to_string(), region),
+                Format::International => {
+                    let (head, tail) = example.split_at(example.len() / 2);
+                    (format!("+{} {} {}", code, head, tail), *g.choose(&REGIONS).unwrap())
+                },
+                Format::E164 => (expected_e164.clone(), *g.choose(&REGIONS).unwrap())
+            };
+
+            Self {
+                raw,
+                default_region,
+                expected_e164,
+                expected_country: region.as_ref().to_string()
+            }
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn valid_numbers_are_normalized_to_e164(fixture: PhoneNumberFixture) -> bool {
+        match PhoneNumberDomain::parse(fixture.raw, fixture.default_region) {
+            Ok(number) => {
+                number.inner() == fixture.expected_e164
+                    && number.country() == Some(fixture.expected_country)
+            },
+            Err(_) => false
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn normalized_numbers_parse_to_themselves(fixture: PhoneNumberFixture) -> bool {
+        let e164 = match PhoneNumberDomain::parse(fixture.raw, fixture.default_region) {
+            Ok(number) => number.inner(),
+            Err(_) => return false
+        };
+
+        PhoneNumberDomain::parse(e164.clone(), country::IN)
+            .map(|number| number.inner() == e164)
+            .unwrap_or(false)
     }
 }
@@ -0,0 +1,448 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Struct representing a discount level of a tiered promotion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromotionTier {
+    pub min_quantity: i32,
+    pub percent_off: i32
+}
+
+// Struct representing quantity of an item making up a bundle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleItem {
+    pub item_id: Uuid,
+    pub quantity: i32
+}
+
+// Enum representing how a promotion prices the units it applies to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromotionRule {
+    // Every buy_quantity units of the items give get_quantity more units percent_off
+    BuyXGetY {
+        item_ids: Vec<Uuid>,
+        buy_quantity: i32,
+        get_quantity: i32,
+        percent_off: i32
+    },
+    // Units of the items are discounted by the highest tier their quantity reaches
+    Tiered {
+        item_ids: Vec<Uuid>,
+        tiers: Vec<PromotionTier>
+    },
+    // Each full set of the items costs price_cents
+    Bundle {
+        items: Vec<BundleItem>,
+        price_cents: i64
+    }
+}
+
+impl PromotionRule {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PromotionRule::BuyXGetY { .. } => "buy_x_get_y",
+            PromotionRule::Tiered { .. } => "tiered",
+            PromotionRule::Bundle { .. } => "bundle"
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            PromotionRule::BuyXGetY { item_ids, buy_quantity, get_quantity, percent_off } => {
+                if item_ids.is_empty() {
+                    return Err("buy x get y promotion needs at least one item".to_string());
+                }
+                if *buy_quantity <= 0 || *get_quantity <= 0 {
+                    return Err("buy and get quantities should be positive".to_string());
+                }
+                if !(1..=100).contains(percent_off) {
+                    return Err("percent_off should be between 1 and 100".to_string());
+                }
+            },
+            PromotionRule::Tiered { item_ids, tiers } => {
+                if item_ids.is_empty() {
+                    return Err("tiered promotion needs at least one item".to_string());
+                }
+                if tiers.is_empty() {
+                    return Err("tiered promotion needs at least one tier".to_string());
+                }
+                if tiers.iter().any(|tier| tier.min_quantity <= 0 || !(1..=100).contains(&tier.percent_off)) {
+                    return Err("tiers need a positive min_quantity and percent_off between 1 and 100".to_string());
+                }
+                let mut quantities: Vec<i32> = tiers.iter().map(|tier| tier.min_quantity).collect();
+                quantities.sort();
+                quantities.dedup();
+                if quantities.len() != tiers.len() {
+                    return Err("tiers should have distinct min_quantity".to_string());
+                }
+            },
+            PromotionRule::Bundle { items, price_cents } => {
+                if items.is_empty() {
+                    return Err("bundle needs at least one item".to_string());
+                }
+                if items.iter().any(|item| item.quantity <= 0) {
+                    return Err("bundle item quantity should be positive".to_string());
+                }
+                let mut item_ids: Vec<Uuid> = items.iter().map(|item| item.item_id).collect();
+                item_ids.sort();
+                item_ids.dedup();
+                if item_ids.len() != items.len() {
+                    return Err("bundle items should be distinct".to_string());
+                }
+                if *price_cents < 0 {
+                    return Err("bundle price can't be negative".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Struct representing a promotion as evaluated against an order
+#[derive(Debug, Clone)]
+pub struct Promotion {
+    pub promotion_id: Uuid,
+    pub name: String,
+    pub rule: PromotionRule
+}
+
+// Struct representing an order line being priced
+#[derive(Debug, Clone)]
+pub struct PricedLine {
+    pub item_id: Uuid,
+    pub unit_price_cents: i64,
+    pub quantity: i32
+}
+
+// Struct representing how much a promotion took off an order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppliedPromotion {
+    pub promotion_id: Option<Uuid>,
+    pub name: String,
+    pub discount_cents: i64
+}
+
+// Struct representing outcome of evaluating promotions against order lines
+#[derive(Debug, Clone, Default)]
+pub struct PromotionOutcome {
+    pub applied: Vec<AppliedPromotion>,
+    // Discount taken off each line, in the order lines were given
+    pub line_discounts: Vec<i64>
+}
+
+impl PromotionOutcome {
+    pub fn discount_cents(&self) -> i64 {
+        self.applied.iter().map(|applied| applied.discount_cents).sum()
+    }
+}
+
+// Struct representing units of an order line not yet taken by a promotion
+// Units are counted rather than listed, lines can hold any quantity
+struct Units {
+    line: usize,
+    item_id: Uuid,
+    price: i64,
+    remaining: i64
+}
+
+// Indexes of lines with unused units of given items, most expensive first
+fn candidates(units: &[Units], item_ids: &[Uuid]) -> Vec<usize> {
+    let mut candidates: Vec<usize> = units.iter()
+        .enumerate()
+        .filter(|(_, unit)| unit.remaining > 0 && item_ids.contains(&unit.item_id))
+        .map(|(i, _)| i)
+        .collect();
+    candidates.sort_by(|a, b| units[*b].price.cmp(&units[*a].price));
+    candidates
+}
+
+// Take quantity of the most expensive candidate units, as (index, count) pieces
+fn take(units: &[Units], candidates: &[usize], quantity: i64) -> Vec<(usize, i64)> {
+    let mut pieces = Vec::new();
+    let mut left = quantity;
+    for i in candidates {
+        if left == 0 {
+            break;
+        }
+        let count = units[*i].remaining.min(left);
+        pieces.push((*i, count));
+        left -= count;
+    }
+    pieces
+}
+
+// Spread a discount over pieces in proportion to their price, remainder goes to the last piece
+// Shares are what each piece's line gets, in the order pieces were given
+fn spread(units: &[Units], pieces: &[(usize, i64)], discount: i64) -> Vec<i64> {
+    let total: i64 = pieces.iter().map(|(i, count)| units[*i].price * count).sum();
+    if total == 0 {
+        return vec![0; pieces.len()];
+    }
+
+    let mut left = discount;
+    pieces.iter()
+        .enumerate()
+        .map(|(n, (i, count))| {
+            let share = if n + 1 == pieces.len() {
+                left
+            } else {
+                (discount as i128 * (units[*i].price * count) as i128 / total as i128) as i64
+            };
+            left -= share;
+            share
+        })
+        .collect()
+}
+
+// Evaluate promotions against order lines in the given order
+// Units discounted by a promotion aren't counted by the ones after it
+pub fn apply_promotions(lines: &[PricedLine], promotions: &[Promotion]) -> PromotionOutcome {
+    let mut units: Vec<Units> = lines.iter()
+        .enumerate()
+        .map(|(line, priced)| Units {
+            line,
+            item_id: priced.item_id,
+            price: priced.unit_price_cents,
+            remaining: priced.quantity.max(0) as i64
+        })
+        .collect();
+
+    let mut outcome = PromotionOutcome {
+        applied: Vec::new(),
+        line_discounts: vec![0; lines.len()]
+    };
+
+    for promotion in promotions {
+        let before = outcome.line_discounts.clone();
+
+        match &promotion.rule {
+            PromotionRule::BuyXGetY { item_ids, buy_quantity, get_quantity, percent_off } => {
+                let candidates = candidates(&units, item_ids);
+                let available: i64 = candidates.iter().map(|i| units[*i].remaining).sum();
+                let group = *buy_quantity as i64 + *get_quantity as i64;
+                let groups = available / group;
+                let taken = groups * group;
+
+                // Cheapest units of the groups are the ones given away
+                let free_from = taken - groups * *get_quantity as i64;
+                let mut position = 0;
+                for (i, count) in take(&units, &candidates, taken) {
+                    let free = (position + count).min(taken) - position.max(free_from);
+                    if free > 0 {
+                        outcome.line_discounts[units[i].line] += free * (units[i].price * *percent_off as i64 / 100);
+                    }
+                    position += count;
+                    units[i].remaining -= count;
+                }
+            },
+            PromotionRule::Tiered { item_ids, tiers } => {
+                let candidates = candidates(&units, item_ids);
+                let available: i64 = candidates.iter().map(|i| units[*i].remaining).sum();
+                let Some(tier) = tiers.iter()
+                    .filter(|tier| tier.min_quantity as i64 <= available)
+                    .max_by_key(|tier| tier.min_quantity)
+                else {
+                    continue;
+                };
+
+                for i in candidates {
+                    outcome.line_discounts[units[i].line] += units[i].remaining * (units[i].price * tier.percent_off as i64 / 100);
+                    units[i].remaining = 0;
+                }
+            },
+            PromotionRule::Bundle { items, price_cents } => {
+                loop {
+                    let mut pieces = Vec::new();
+                    // Sets in a row taking the same units are priced together
+                    let mut sets = i64::MAX;
+                    for item in items {
+                        let candidates = candidates(&units, &[item.item_id]);
+                        let item_pieces = take(&units, &candidates, item.quantity as i64);
+                        if item_pieces.iter().map(|(_, count)| count).sum::<i64>() < item.quantity as i64 {
+                            sets = 0;
+                            break;
+                        }
+                        sets = match item_pieces.as_slice() {
+                            [(i, _)] => sets.min(units[*i].remaining / item.quantity as i64),
+                            _ => sets.min(1)
+                        };
+                        pieces.extend(item_pieces);
+                    }
+                    if sets == 0 {
+                        break;
+                    }
+
+                    // Sets already cheaper than the bundle are left at their own price
+                    let regular: i64 = pieces.iter().map(|(i, count)| units[*i].price * count).sum();
+                    if regular <= *price_cents {
+                        break;
+                    }
+
+                    let shares = spread(&units, &pieces, regular - price_cents);
+                    for ((i, count), share) in pieces.into_iter().zip(shares) {
+                        outcome.line_discounts[units[i].line] += share * sets;
+                        units[i].remaining -= count * sets;
+                    }
+                }
+            }
+        }
+
+        let discount_cents: i64 = outcome.line_discounts.iter().sum::<i64>() - before.iter().sum::<i64>();
+        if discount_cents > 0 {
+            outcome.applied.push(AppliedPromotion {
+                promotion_id: Some(promotion.promotion_id),
+                name: promotion.name.clone(),
+                discount_cents
+            });
+        }
+    }
+
+    outcome
+}
+
+// Struct representing how an order's total is arrived at
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PriceBreakdown {
+    pub subtotal_cents: i64,
+    pub promotions: Vec<AppliedPromotion>,
+    pub promotion_discount_cents: i64,
+    pub coupon_discount_cents: i64,
+    // Charged on what lines cost after discounts
+    pub tax_cents: i64,
+    pub shipping_cents: i64,
+    pub total_cents: i64
+}
+
+impl PriceBreakdown {
+    pub fn new(subtotal_cents: i64, promotions: Vec<AppliedPromotion>, coupon_discount_cents: i64, tax_cents: i64, shipping_cents: i64) -> PriceBreakdown {
+        let promotion_discount_cents: i64 = promotions.iter().map(|applied| applied.discount_cents).sum();
+
+        PriceBreakdown {
+            subtotal_cents,
+            promotions,
+            promotion_discount_cents,
+            coupon_discount_cents,
+            tax_cents,
+            shipping_cents,
+            total_cents: subtotal_cents - promotion_discount_cents - coupon_discount_cents + tax_cents + shipping_cents
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_promotions, BundleItem, PricedLine, Promotion, PromotionRule, PromotionTier};
+    use claim::{assert_err, assert_ok};
+    use uuid::Uuid;
+
+    fn line(item_id: Uuid, unit_price_cents: i64, quantity: i32) -> PricedLine {
+        PricedLine { item_id, unit_price_cents, quantity }
+    }
+
+    fn promotion(name: &str, rule: PromotionRule) -> Promotion {
+        Promotion { promotion_id: Uuid::new_v4(), name: name.to_string(), rule }
+    }
+
+    #[test]
+    fn buy_two_get_one_gives_away_cheapest_units() {
+        let (shirt, socks) = (Uuid::new_v4(), Uuid::new_v4());
+        let lines = vec![line(shirt, 1000, 2), line(socks, 300, 2)];
+        let promotions = vec![promotion("3 for 2", PromotionRule::BuyXGetY {
+            item_ids: vec![shirt, socks],
+            buy_quantity: 2,
+            get_quantity: 1,
+            percent_off: 100
+        })];
+
+        let outcome = apply_promotions(&lines, &promotions);
+        assert_eq!(outcome.line_discounts, vec![0, 300]);
+        assert_eq!(outcome.discount_cents(), 300);
+        assert_eq!(outcome.applied[0].name, "3 for 2");
+    }
+
+    #[test]
+    fn tiered_promotion_uses_highest_reached_tier() {
+        let mug = Uuid::new_v4();
+        let rule = PromotionRule::Tiered {
+            item_ids: vec![mug],
+            tiers: vec![
+                PromotionTier { min_quantity: 3, percent_off: 10 },
+                PromotionTier { min_quantity: 5, percent_off: 20 }
+            ]
+        };
+        let promotions = vec![promotion("Bulk mugs", rule)];
+
+        assert!(apply_promotions(&[line(mug, 500, 2)], &promotions).applied.is_empty());
+        assert_eq!(apply_promotions(&[line(mug, 500, 4)], &promotions).discount_cents(), 200);
+        assert_eq!(apply_promotions(&[line(mug, 500, 6)], &promotions).discount_cents(), 600);
+    }
+
+    #[test]
+    fn bundle_is_priced_per_full_set() {
+        let (camera, case) = (Uuid::new_v4(), Uuid::new_v4());
+        let lines = vec![line(camera, 10000, 2), line(case, 2000, 1)];
+        let promotions = vec![promotion("Camera kit", PromotionRule::Bundle {
+            items: vec![BundleItem { item_id: camera, quantity: 1 }, BundleItem { item_id: case, quantity: 1 }],
+            price_cents: 11000
+        })];
+
+        let outcome = apply_promotions(&lines, &promotions);
+        assert_eq!(outcome.discount_cents(), 1000);
+        assert_eq!(outcome.line_discounts.iter().sum::<i64>(), 1000);
+    }
+
+    #[test]
+    fn bundle_sets_can_take_units_from_several_lines() {
+        let (camera, case) = (Uuid::new_v4(), Uuid::new_v4());
+        let lines = vec![line(camera, 10000, 1), line(camera, 9000, 3), line(case, 2000, 2)];
+        let promotions = vec![promotion("Two camera kit", PromotionRule::Bundle {
+            items: vec![BundleItem { item_id: camera, quantity: 2 }, BundleItem { item_id: case, quantity: 1 }],
+            price_cents: 15000
+        })];
+
+        let outcome = apply_promotions(&lines, &promotions);
+        assert_eq!(outcome.discount_cents(), 6000 + 5000);
+        assert_eq!(outcome.line_discounts.iter().sum::<i64>(), 11000);
+    }
+
+    #[test]
+    fn huge_quantities_are_counted_without_listing_units() {
+        let (camera, case) = (Uuid::new_v4(), Uuid::new_v4());
+        let free_camera = promotion("Free camera", PromotionRule::BuyXGetY { item_ids: vec![camera], buy_quantity: 2, get_quantity: 1, percent_off: 100 });
+        let bulk = promotion("Bulk", PromotionRule::Tiered { item_ids: vec![camera], tiers: vec![PromotionTier { min_quantity: 3, percent_off: 10 }] });
+        let kit = promotion("Camera kit", PromotionRule::Bundle {
+            items: vec![BundleItem { item_id: camera, quantity: 1 }, BundleItem { item_id: case, quantity: 1 }],
+            price_cents: 11000
+        });
+
+        let lines = vec![line(camera, 10000, i32::MAX), line(case, 2000, i32::MAX)];
+        assert_eq!(apply_promotions(&lines, &[free_camera]).discount_cents(), 715827882 * 10000);
+        assert_eq!(apply_promotions(&lines, &[bulk]).discount_cents(), i32::MAX as i64 * 1000);
+        assert_eq!(apply_promotions(&lines, &[kit]).discount_cents(), i32::MAX as i64 * 1000);
+    }
+
+    #[test]
+    fn units_are_not_discounted_twice() {
+        let mug = Uuid::new_v4();
+        let lines = vec![line(mug, 500, 3)];
+        let promotions = vec![
+            promotion("Free mug", PromotionRule::BuyXGetY { item_ids: vec![mug], buy_quantity: 2, get_quantity: 1, percent_off: 100 }),
+            promotion("Bulk mugs", PromotionRule::Tiered { item_ids: vec![mug], tiers: vec![PromotionTier { min_quantity: 1, percent_off: 50 }] })
+        ];
+
+        let outcome = apply_promotions(&lines, &promotions);
+        assert_eq!(outcome.applied.len(), 1);
+        assert_eq!(outcome.discount_cents(), 500);
+    }
+
+    #[test]
+    fn rules_are_validated() {
+        let item_id = Uuid::new_v4();
+        assert_err!(PromotionRule::BuyXGetY { item_ids: vec![item_id], buy_quantity: 0, get_quantity: 1, percent_off: 100 }.validate());
+        assert_err!(PromotionRule::Tiered { item_ids: vec![item_id], tiers: vec![] }.validate());
+        assert_err!(PromotionRule::Bundle { items: vec![BundleItem { item_id, quantity: 1 }, BundleItem { item_id, quantity: 2 }], price_cents: 100 }.validate());
+        assert_ok!(PromotionRule::Tiered { item_ids: vec![item_id], tiers: vec![PromotionTier { min_quantity: 2, percent_off: 5 }] }.validate());
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+// Enum representing how a shipping method charges for a shipment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShippingRate {
+    // Same cost for every shipment
+    FlatRate {
+        rate_cents: i64
+    },
+    // Base cost plus a cost for every started kilogram
+    WeightBased {
+        base_cents: i64,
+        per_kg_cents: i64
+    },
+    // Flat cost which is waived once merchandise subtotal reaches threshold
+    FreeOver {
+        rate_cents: i64,
+        threshold_cents: i64
+    }
+}
+
+impl ShippingRate {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ShippingRate::FlatRate { .. } => "flat_rate",
+            ShippingRate::WeightBased { .. } => "weight_based",
+            ShippingRate::FreeOver { .. } => "free_over"
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let amounts = match self {
+            ShippingRate::FlatRate { rate_cents } => vec![rate_cents],
+            ShippingRate::WeightBased { base_cents, per_kg_cents } => vec![base_cents, per_kg_cents],
+            ShippingRate::FreeOver { rate_cents, threshold_cents } => vec![rate_cents, threshold_cents]
+        };
+
+        if amounts.into_iter().any(|amount| *amount < 0) {
+            return Err("shipping rate amounts can't be negative".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Cost of shipping merchandise worth subtotal_cents weighing weight_grams
+    pub fn cost_cents(&self, subtotal_cents: i64, weight_grams: i64) -> i64 {
+        match self {
+            ShippingRate::FlatRate { rate_cents } => *rate_cents,
+            ShippingRate::WeightBased { base_cents, per_kg_cents } => {
+                let started_kgs = (weight_grams.max(0) + 999) / 1000;
+                base_cents + per_kg_cents * started_kgs
+            },
+            ShippingRate::FreeOver { rate_cents, threshold_cents } => if subtotal_cents >= *threshold_cents {
+                0
+            } else {
+                *rate_cents
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShippingRate;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn negative_amounts_are_rejected() {
+        assert_err!(ShippingRate::FlatRate { rate_cents: -1 }.validate());
+        assert_err!(ShippingRate::WeightBased { base_cents: 100, per_kg_cents: -5 }.validate());
+        assert_ok!(ShippingRate::FreeOver { rate_cents: 500, threshold_cents: 0 }.validate());
+    }
+
+    #[test]
+    fn weight_is_charged_per_started_kilogram() {
+        let rate = ShippingRate::WeightBased { base_cents: 200, per_kg_cents: 150 };
+        assert_eq!(rate.cost_cents(0, 0), 200);
+        assert_eq!(rate.cost_cents(0, 1), 350);
+        assert_eq!(rate.cost_cents(0, 1000), 350);
+        assert_eq!(rate.cost_cents(0, 2500), 650);
+    }
+
+    #[test]
+    fn free_over_threshold_waives_rate() {
+        let rate = ShippingRate::FreeOver { rate_cents: 499, threshold_cents: 5000 };
+        assert_eq!(rate.cost_cents(4999, 0), 499);
+        assert_eq!(rate.cost_cents(5000, 0), 0);
+        assert_eq!(ShippingRate::FlatRate { rate_cents: 300 }.cost_cents(100000, 9000), 300);
+    }
+}
@@ -0,0 +1,59 @@
+// Wrapper struct defining domain for stock keeping unit
+#[derive(Debug, Clone)]
+pub struct Sku(pub String);
+
+impl Sku {
+    const MAX_LENGTH: usize = 64;
+
+    pub fn parse(sku: String) -> Result<Sku, String> {
+        let sku = sku.trim().to_uppercase();
+
+        let is_valid = !sku.is_empty()
+            && sku.len() <= Self::MAX_LENGTH
+            && sku.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if is_valid {
+            Ok(Self(sku))
+        } else {
+            Err(format!("{} is not a valid sku", sku))
+        }
+    }
+
+    pub fn inner(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl std::fmt::Display for Sku {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sku;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(Sku::parse("  ".to_string()));
+    }
+
+    #[test]
+    fn whitespace_inside_sku_is_rejected() {
+        assert_err!(Sku::parse("TSHIRT RED".to_string()));
+    }
+
+    #[test]
+    fn too_long_sku_is_rejected() {
+        assert_err!(Sku::parse("A".repeat(65)));
+    }
+
+    #[test]
+    fn sku_is_trimmed_and_uppercased() {
+        let sku = Sku::parse(" tshirt-red_m ".to_string());
+        assert_ok!(&sku);
+        assert_eq!(sku.unwrap().inner(), "TSHIRT-RED_M");
+    }
+}
@@ -0,0 +1,73 @@
+// Wrapper struct defining domain for url slugs
+#[derive(Debug, Clone)]
+pub struct Slug(pub String);
+
+impl Slug {
+    pub fn parse(slug: String) -> Result<Slug, String> {
+        let is_valid = !slug.is_empty()
+            && !slug.starts_with('-')
+            && !slug.ends_with('-')
+            && !slug.contains("--")
+            && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+
+        if is_valid {
+            Ok(Self(slug))
+        } else {
+            Err(format!("{} is not a valid slug", slug))
+        }
+    }
+
+    // Derive slug from a human readable name
+    pub fn from_name(name: &str) -> Result<Slug, String> {
+        let slug = name.to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<&str>>()
+            .join("-");
+
+        Self::parse(slug)
+            .map_err(|_| format!("cannot derive slug from {}", name))
+    }
+
+    pub fn inner(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl std::fmt::Display for Slug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slug;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn uppercase_slug_is_rejected() {
+        assert_err!(Slug::parse("Red-Shirt".to_string()));
+    }
+
+    #[test]
+    fn slug_with_repeated_hyphens_is_rejected() {
+        assert_err!(Slug::parse("red--shirt".to_string()));
+    }
+
+    #[test]
+    fn valid_slug_is_accepted() {
+        assert_ok!(Slug::parse("red-shirt-2".to_string()));
+    }
+
+    #[test]
+    fn slug_is_derived_from_name() {
+        let slug = Slug::from_name("  Men's T-Shirt (Red) ").unwrap();
+        assert_eq!(slug.inner(), "men-s-t-shirt-red");
+    }
+
+    #[test]
+    fn name_without_alphanumerics_cannot_be_slugified() {
+        assert_err!(Slug::from_name("!!!"));
+    }
+}
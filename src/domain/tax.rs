@@ -0,0 +1,59 @@
+// Wrapper struct defining domain for tax classes products are put in
+#[derive(Debug, Clone)]
+pub struct TaxClass(pub String);
+
+impl TaxClass {
+    const MAX_LENGTH: usize = 32;
+
+    pub fn parse(tax_class: String) -> Result<TaxClass, String> {
+        let tax_class = tax_class.trim().to_lowercase();
+
+        let is_valid = !tax_class.is_empty()
+            && tax_class.len() <= Self::MAX_LENGTH
+            && tax_class.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+        if is_valid {
+            Ok(Self(tax_class))
+        } else {
+            Err(format!("{} is not a valid tax class", tax_class))
+        }
+    }
+
+    pub fn inner(&self) -> String {
+        self.0.clone()
+    }
+}
+
+// Rates are in basis points, 1 basis point is 0.01%
+pub const MAX_RATE_BASIS_POINTS: i32 = 10000;
+
+// Tax on an amount rounded half up to the nearest cent
+pub fn tax_cents(taxable_cents: i64, rate_basis_points: i32) -> i64 {
+    if taxable_cents <= 0 || rate_basis_points <= 0 {
+        return 0;
+    }
+
+    (taxable_cents * rate_basis_points as i64 + 5000) / 10000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tax_cents, TaxClass};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn tax_class_is_validated() {
+        assert_ok!(TaxClass::parse("Reduced".to_string()));
+        assert_err!(TaxClass::parse("".to_string()));
+        assert_err!(TaxClass::parse("food and drink".to_string()));
+    }
+
+    #[test]
+    fn tax_is_rounded_half_up() {
+        assert_eq!(tax_cents(1000, 1800), 180);
+        assert_eq!(tax_cents(999, 825), 82);
+        assert_eq!(tax_cents(1010, 825), 83);
+        assert_eq!(tax_cents(1000, 0), 0);
+        assert_eq!(tax_cents(-50, 1800), 0);
+    }
+}
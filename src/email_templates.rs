@@ -0,0 +1,216 @@
+use std::{error::Error, fmt::Debug, sync::Arc};
+
+use serde::Serialize;
+use tera::{Context, Tera};
+use thiserror::Error;
+
+use crate::{configuration::EmailTemplateSettings, db_interaction::NewEmail, domain::email_category::EmailCategory, utils::error_fmt_chain};
+
+// Extensions of the subject, html and plain text template every email has per locale
+const TEMPLATE_PARTS: [&str; 3] = ["subject.txt", "html", "txt"];
+
+// Enum representing emails rendered from templates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailTemplate {
+    Confirmation,
+    PasswordReset,
+    OrderPlaced,
+    OrderShipped,
+    OrderDelivered,
+    OrderCancelled,
+    RefundIssued,
+    BackorderFilled,
+    BackInStock,
+    LowStockAlert
+}
+
+impl EmailTemplate {
+    pub const ALL: [EmailTemplate; 10] = [
+        Self::Confirmation,
+        Self::PasswordReset,
+        Self::OrderPlaced,
+        Self::OrderShipped,
+        Self::OrderDelivered,
+        Self::OrderCancelled,
+        Self::RefundIssued,
+        Self::BackorderFilled,
+        Self::BackInStock,
+        Self::LowStockAlert
+    ];
+
+    // Name of template files within a locale's directory
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Confirmation => "confirmation",
+            Self::PasswordReset => "password_reset",
+            Self::OrderPlaced => "order_placed",
+            Self::OrderShipped => "order_shipped",
+            Self::OrderDelivered => "order_delivered",
+            Self::OrderCancelled => "order_cancelled",
+            Self::RefundIssued => "refund_issued",
+            Self::BackorderFilled => "backorder_filled",
+            Self::BackInStock => "back_in_stock",
+            Self::LowStockAlert => "low_stock_alert"
+        }
+    }
+
+    // Category recipients can opt out of, account emails, receipts and admin alerts are always sent
+    pub fn category(&self) -> Option<EmailCategory> {
+        match self {
+            Self::Confirmation | Self::PasswordReset | Self::RefundIssued | Self::LowStockAlert => None,
+            Self::OrderPlaced | Self::OrderShipped | Self::OrderDelivered | Self::OrderCancelled | Self::BackorderFilled => Some(EmailCategory::OrderUpdates),
+            Self::BackInStock => Some(EmailCategory::BackInStock)
+        }
+    }
+}
+
+// Errors associated with loading and rendering email templates
+#[derive(Error)]
+pub enum EmailTemplateError {
+    #[error("Failed to load email templates")]
+    LoadError(#[from] tera::Error),
+    #[error("Template {0} is missing for default locale")]
+    MissingTemplate(String),
+    #[error("Failed to render email template {0}")]
+    RenderError(String, #[source] tera::Error)
+}
+
+impl Debug for EmailTemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Renders emails from the templates directory, laid out as <locale>/<name>.<part>
+// Templates extend base.html or base.txt, only html ones have their variables escaped
+#[derive(Clone)]
+pub struct EmailRenderer {
+    tera: Arc<Tera>,
+    default_locale: String
+}
+
+impl EmailRenderer {
+    pub fn load(settings: &EmailTemplateSettings) -> Result<Self, EmailTemplateError> {
+        let tera = Tera::new(&format!("{}/**/*", settings.directory.trim_end_matches('/')))?;
+
+        // Other locales fall back to the default one, so it needs every template
+        for template in EmailTemplate::ALL {
+            for part in TEMPLATE_PARTS {
+                let name = format!("{}/{}.{}", settings.default_locale, template.name(), part);
+                if tera.get_template(&name).is_err() {
+                    return Err(EmailTemplateError::MissingTemplate(name));
+                }
+            }
+        }
+
+        Ok(Self {
+            tera: Arc::new(tera),
+            default_locale: settings.default_locale.clone()
+        })
+    }
+
+    // Render email to recipient in the closest locale available, e.g. es-MX falls back to es and then the default
+    pub fn render(
+        &self,
+        template: EmailTemplate,
+        locale: Option<&str>,
+        recipient: String,
+        context: &impl Serialize
+    ) -> Result<NewEmail, EmailTemplateError> {
+        let locale = self.resolve_locale(template, locale);
+        let context = Context::from_serialize(context)
+            .map_err(|e| EmailTemplateError::RenderError(template.name().to_string(), e))?;
+
+        let render = |part: &str| {
+            let name = format!("{}/{}.{}", locale, template.name(), part);
+            self.tera.render(&name, &context)
+                .map_err(|e| EmailTemplateError::RenderError(name, e))
+        };
+
+        Ok(NewEmail {
+            recipient,
+            subject: render("subject.txt")?.trim().to_string(),
+            html_body: render("html")?,
+            text_body: render("txt")?,
+            category: template.category()
+        })
+    }
+
+    fn resolve_locale(&self, template: EmailTemplate, locale: Option<&str>) -> String {
+        locale.into_iter()
+            .flat_map(|locale| [locale, locale.split('-').next().unwrap_or(locale)])
+            .find(|candidate| self.tera.get_template(&format!("{}/{}.txt", candidate, template.name())).is_ok())
+            .unwrap_or(&self.default_locale)
+            .to_string()
+    }
+}
+
+// Most preferred language of an Accept-Language header, e.g. "es-MX" for "es-MX,es;q=0.9"
+pub fn locale_from_accept_language(header: &str) -> Option<&str> {
+    header.split(',')
+        .next()
+        .and_then(|tag| tag.split(';').next())
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty() && *tag != "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locale_from_accept_language, EmailRenderer, EmailTemplate};
+    use crate::configuration::EmailTemplateSettings;
+
+    fn renderer() -> EmailRenderer {
+        EmailRenderer::load(&EmailTemplateSettings {
+            directory: "templates/email".to_string(),
+            default_locale: "en".to_string()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn variables_are_escaped_in_html_only() {
+        let context = serde_json::json!({ "name": "<b>Aman</b>", "confirmation_link": "http://localhost/confirm?id=1" });
+        let email = renderer().render(EmailTemplate::Confirmation, None, "a@example.com".to_string(), &context).unwrap();
+
+        assert_eq!(email.subject, "Confirmation email");
+        assert!(email.html_body.contains("Hi &lt;b&gt;Aman&lt;&#x2F;b&gt;"));
+        assert!(email.text_body.starts_with("Hi <b>Aman</b>,"));
+        assert!(email.text_body.contains("http://localhost/confirm?id=1"));
+    }
+
+    #[test]
+    fn password_reset_renders_link_in_each_locale() {
+        let context = serde_json::json!({ "name": "<b>Aman</b>", "reset_link": "http://localhost/reset?token=1" });
+        let renderer = renderer();
+
+        let email = renderer.render(EmailTemplate::PasswordReset, None, "a@example.com".to_string(), &context).unwrap();
+        assert_eq!(email.subject, "Reset your password");
+        assert!(email.html_body.contains("Hi &lt;b&gt;Aman&lt;&#x2F;b&gt;"));
+        assert!(email.text_body.contains("Choose a new password: http://localhost/reset?token=1"));
+        assert!(email.category.is_none());
+
+        let email = renderer.render(EmailTemplate::PasswordReset, Some("es"), "a@example.com".to_string(), &context).unwrap();
+        assert_eq!(email.subject, "Restablece tu contraseña");
+        assert!(email.text_body.contains("http://localhost/reset?token=1"));
+    }
+
+    #[test]
+    fn locales_fall_back_to_language_then_default() {
+        let context = serde_json::json!({ "name": "Aman", "confirmation_link": "http://localhost/confirm?id=1" });
+        let renderer = renderer();
+
+        let email = renderer.render(EmailTemplate::Confirmation, Some("es-MX"), "a@example.com".to_string(), &context).unwrap();
+        assert_eq!(email.subject, "Confirma tu cuenta");
+
+        let email = renderer.render(EmailTemplate::Confirmation, Some("fr"), "a@example.com".to_string(), &context).unwrap();
+        assert_eq!(email.subject, "Confirmation email");
+    }
+
+    #[test]
+    fn preferred_locale_is_read_from_accept_language() {
+        assert_eq!(locale_from_accept_language("es-MX,es;q=0.9,en;q=0.8"), Some("es-MX"));
+        assert_eq!(locale_from_accept_language("en;q=0.5"), Some("en"));
+        assert_eq!(locale_from_accept_language("*"), None);
+    }
+}
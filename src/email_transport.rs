@@ -0,0 +1,504 @@
+use std::{error::Error, fmt::Debug, path::PathBuf, time::Duration};
+
+use async_trait::async_trait;
+use lettre::{message::{header::{Header, HeaderName, HeaderValue}, Mailbox, MultiPart}, transport::smtp::authentication::Credentials, AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use reqwest::Client;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{configuration::{SmtpSettings, SmtpTls}, domain::user_email::UserEmail, utils::error_fmt_chain};
+
+// Errors associated with handing an email over to a transport
+#[derive(Error)]
+pub enum EmailTransportError {
+    // Retrying won't help, e.g. an address the transport can't parse
+    #[error("Email can't be sent: {0}")]
+    InvalidMessage(String),
+    #[error("Failed to deliver email")]
+    DeliveryError(#[source] anyhow::Error)
+}
+
+impl Debug for EmailTransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Way emails leave the application
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    // Name logged along with emails sent through transport
+    fn name(&self) -> &'static str;
+
+    // Emails recipients can opt out of carry their unsubscribe link in List-Unsubscribe headers
+    async fn send_email(
+        &self,
+        recipient: &UserEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: Option<&str>
+    ) -> Result<(), EmailTransportError>;
+}
+
+// Transport posting emails to a Postmark style HTTP API
+#[derive(Clone)]
+pub struct PostmarkTransport {
+    http_client: Client,
+    base_url: String,
+    sender: UserEmail,
+    authorization_token: SecretString,
+}
+
+impl PostmarkTransport {
+    // create new postmark transport
+    pub fn new(
+        base_url: String,
+        sender: UserEmail,
+        authorization_token: SecretString,
+        timeout: u64,
+    ) -> PostmarkTransport {
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(timeout))
+            .build()
+            .unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            sender,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for PostmarkTransport {
+    fn name(&self) -> &'static str {
+        "postmark"
+    }
+
+    #[tracing::instrument(
+        "Sending email to subscriber",
+        skip(self, subject, html_content, text_content, unsubscribe_link)
+    )]
+    async fn send_email(
+        &self,
+        recipient: &UserEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: Option<&str>
+    ) -> Result<(), EmailTransportError> {
+        let url = format!("{}/email", self.base_url);
+        let headers = unsubscribe_link
+            .map(|link| vec![
+                EmailHeader { name: ListUnsubscribe::name().to_string(), value: ListUnsubscribe(link.to_string()).value() },
+                EmailHeader { name: ListUnsubscribePost::name().to_string(), value: ListUnsubscribePost.value() }
+            ])
+            .unwrap_or_default();
+        let request_body = SendEmailRequest {
+            from: &self.sender.inner(),
+            to: &recipient.inner(),
+            subject,
+            html_body: html_content,
+            text_body: text_content,
+            headers
+        };
+        self.http_client
+            .post(url)
+            .json(&request_body)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| EmailTransportError::DeliveryError(e.into()))?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SendEmailRequest<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    pub text_body: &'a str,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<EmailHeader>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String
+}
+
+// Header pointing mail clients at the link which unsubscribes recipient
+#[derive(Clone)]
+struct ListUnsubscribe(String);
+
+impl ListUnsubscribe {
+    fn value(&self) -> String {
+        format!("<{}>", self.0)
+    }
+}
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self(s.trim().trim_start_matches('<').trim_end_matches('>').to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.value())
+    }
+}
+
+// Header telling mail clients a POST to the List-Unsubscribe link unsubscribes in one click, see RFC 8058
+#[derive(Clone)]
+struct ListUnsubscribePost;
+
+impl ListUnsubscribePost {
+    fn value(&self) -> String {
+        "List-Unsubscribe=One-Click".to_string()
+    }
+}
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.value())
+    }
+}
+
+// Transport relaying emails through an SMTP server
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox
+}
+
+impl SmtpTransport {
+    pub fn new(settings: &SmtpSettings, sender: &UserEmail, timeout: u64) -> Result<Self, EmailTransportError> {
+        let builder = match settings.tls {
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host),
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+        }
+        .map_err(|e| EmailTransportError::InvalidMessage(format!("SMTP host is invalid: {}", e)))?;
+
+        let mut builder = builder
+            .port(settings.port)
+            .timeout(Some(Duration::from_secs(timeout)));
+
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.expose_secret().to_string()));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+            sender: mailbox(sender)?
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    #[tracing::instrument(
+        "Sending email over SMTP",
+        skip(self, subject, html_content, text_content, unsubscribe_link)
+    )]
+    async fn send_email(
+        &self,
+        recipient: &UserEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: Option<&str>
+    ) -> Result<(), EmailTransportError> {
+        let message = message(&self.sender, recipient, subject, html_content, text_content, unsubscribe_link)?;
+
+        self.mailer.send(message)
+            .await
+            .map_err(|e| EmailTransportError::DeliveryError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+// Transport writing each email as an .eml file into a directory, used for local development
+pub struct FileTransport {
+    sink: AsyncFileTransport<Tokio1Executor>,
+    sender: Mailbox
+}
+
+impl FileTransport {
+    pub fn new(directory: impl Into<PathBuf>, sender: &UserEmail) -> Result<Self, EmailTransportError> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| EmailTransportError::DeliveryError(e.into()))?;
+
+        Ok(Self {
+            sink: AsyncFileTransport::new(directory),
+            sender: mailbox(sender)?
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    #[tracing::instrument(
+        "Writing email to file",
+        skip(self, subject, html_content, text_content, unsubscribe_link)
+    )]
+    async fn send_email(
+        &self,
+        recipient: &UserEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: Option<&str>
+    ) -> Result<(), EmailTransportError> {
+        let message = message(&self.sender, recipient, subject, html_content, text_content, unsubscribe_link)?;
+
+        let id = self.sink.send(message)
+            .await
+            .map_err(|e| EmailTransportError::DeliveryError(e.into()))?;
+        tracing::info!("Wrote email {} to {}", id, recipient.inner());
+
+        Ok(())
+    }
+}
+
+fn mailbox(email: &UserEmail) -> Result<Mailbox, EmailTransportError> {
+    email.inner()
+        .parse()
+        .map_err(|e| EmailTransportError::InvalidMessage(format!("{} isn't a valid address: {}", email.inner(), e)))
+}
+
+// Email with both plain text and html alternatives, as sent by SMTP and file transports
+fn message(
+    sender: &Mailbox,
+    recipient: &UserEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    unsubscribe_link: Option<&str>
+) -> Result<Message, EmailTransportError> {
+    let mut builder = Message::builder()
+        .from(sender.clone())
+        .to(mailbox(recipient)?)
+        .subject(subject);
+
+    if let Some(link) = unsubscribe_link {
+        builder = builder
+            .header(ListUnsubscribe(link.to_string()))
+            .header(ListUnsubscribePost);
+    }
+
+    builder
+        .multipart(MultiPart::alternative_plain_html(text_content.to_string(), html_content.to_string()))
+        .map_err(|e| EmailTransportError::InvalidMessage(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claim::{assert_err, assert_ok};
+    use fake::{
+        faker::{
+            internet::en::SafeEmail,
+            lorem::en::{Paragraph, Sentence},
+        },
+        Fake, Faker,
+    };
+    use secrecy::SecretString;
+    use wiremock::{
+        matchers::{any, body_partial_json, header, header_exists, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{EmailTransport, FileTransport, PostmarkTransport};
+    use crate::domain::user_email::UserEmail;
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> UserEmail {
+        UserEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn email_client(base_url: String) -> PostmarkTransport {
+        let key = Faker.fake::<String>();
+        PostmarkTransport::new(base_url, email(), SecretString::new(key.into()), 3)
+    }
+
+    struct SendEmailBodyMatcher;
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn send_email_fires_a_request_to_base_url() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(&email(), &subject(), &content(), &content(), None)
+            .await;
+    }
+
+    #[actix_web::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), None)
+            .await;
+        assert_ok!(outcome)
+    }
+
+    #[actix_web::test]
+    async fn send_email_adds_unsubscribe_headers_for_link() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "Headers": [
+                { "Name": "List-Unsubscribe", "Value": "<http://localhost/unsubscribe?token=abc>" },
+                { "Name": "List-Unsubscribe-Post", "Value": "List-Unsubscribe=One-Click" }
+            ] })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), Some("http://localhost/unsubscribe?token=abc"))
+            .await;
+        assert_ok!(outcome)
+    }
+
+    #[actix_web::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), None)
+            .await;
+        assert_err!(outcome);
+    }
+
+    #[actix_web::test]
+    async fn send_email_times_out_if_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), None)
+            .await;
+        assert_err!(outcome);
+    }
+
+    #[actix_web::test]
+    async fn file_transport_writes_email_into_directory() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let transport = FileTransport::new(&directory, &email()).unwrap();
+        let subject = subject();
+
+        let outcome = transport
+            .send_email(&email(), &subject, "<p>Html body</p>", "Text body", Some("http://localhost/unsubscribe?token=abc"))
+            .await;
+        assert_ok!(outcome);
+
+        let files: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+
+        let written = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(written.contains(&format!("Subject: {}", subject)));
+        assert!(written.contains("Text body"));
+        assert!(written.contains("<p>Html body</p>"));
+        assert!(written.contains("List-Unsubscribe: <http://localhost/unsubscribe?token=abc>"));
+        assert!(written.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{rt::{self, task::JoinHandle}, web};
+use anyhow::Context;
+
+use crate::{configuration::{BackInStockSettings, EmailOutboxSettings}, db_interaction::{claim_due_emails, expire_stock_reservations, get_restocked_subscriptions, get_unalerted_low_stock_items, mark_email_failed, mark_email_sent, mark_low_stock_alerted, queue_back_in_stock_emails}, domain::{email_category::EmailCategory, user_email::UserEmail}, email_templates::{EmailRenderer, EmailTemplateError}, email_transport::{EmailTransport, EmailTransportError}, notifications::{back_in_stock_email, low_stock_alert_email}, unsubscribe::UnsubscribeSigner, utils::{get_pooled_connection, DbPool}};
+
+// Periodically expire reservations which weren't turned into an order in time
+// Expired holds no longer count against available stock
+pub fn spawn_reservation_expiry(pool: web::Data<DbPool>, every: Duration) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match run_reservation_expiry(&pool).await {
+                Ok(0) => {},
+                Ok(expired) => tracing::info!("Expired {} stock reservations", expired),
+                Err(e) => tracing::error!("Failed to expire stock reservations: {:?}", e)
+            }
+        }
+    })
+}
+
+async fn run_reservation_expiry(pool: &web::Data<DbPool>) -> Result<usize, anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    expire_stock_reservations(conn).await
+}
+
+// Periodically queue emails to admins about items which fell to their reorder threshold
+// Each item is alerted once until it is restocked above its threshold
+pub fn spawn_low_stock_alerts(
+    pool: web::Data<DbPool>,
+    renderer: EmailRenderer,
+    recipients: Vec<UserEmail>,
+    every: Duration
+) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            match run_low_stock_alerts(&pool, &renderer, &recipients).await {
+                Ok(0) => {},
+                Ok(alerted) => tracing::info!("Queued low stock alert for {} items", alerted),
+                Err(e) => tracing::error!("Failed to queue low stock alerts: {:?}", e)
+            }
+        }
+    })
+}
+
+async fn run_low_stock_alerts(
+    pool: &web::Data<DbPool>,
+    renderer: &EmailRenderer,
+    recipients: &[UserEmail]
+) -> Result<usize, anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let items = get_unalerted_low_stock_items(conn).await?;
+    if items.is_empty() {
+        return Ok(0);
+    }
+
+    let alerts = recipients.iter()
+        .map(|recipient| low_stock_alert_email(renderer, recipient.inner(), &items))
+        .collect::<Result<Vec<_>, EmailTemplateError>>()?;
+
+    // Items are marked in the same transaction the alerts are queued in
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    mark_low_stock_alerted(conn, items.iter().map(|item| item.item_id).collect(), alerts).await?;
+
+    Ok(items.len())
+}
+
+// Periodically queue emails to users subscribed to items which are back in stock
+// Subscriptions are cleared as their emails are queued, so each subscriber is emailed once
+pub fn spawn_back_in_stock_notifications(
+    pool: web::Data<DbPool>,
+    renderer: EmailRenderer,
+    settings: BackInStockSettings
+) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(settings.check_interval_seconds));
+
+        loop {
+            interval.tick().await;
+
+            match run_back_in_stock_notifications(&pool, &renderer, settings.batch_size).await {
+                Ok(0) => {},
+                Ok(queued) => tracing::info!("Queued {} back in stock emails", queued),
+                Err(e) => tracing::error!("Failed to queue back in stock emails: {:?}", e)
+            }
+        }
+    })
+}
+
+async fn run_back_in_stock_notifications(
+    pool: &web::Data<DbPool>,
+    renderer: &EmailRenderer,
+    batch_size: i64
+) -> Result<usize, anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let subscriptions = get_restocked_subscriptions(conn, batch_size).await?;
+    if subscriptions.is_empty() {
+        return Ok(0);
+    }
+
+    let notifications = subscriptions.iter()
+        .map(|subscription| Ok((subscription.subscription_id, back_in_stock_email(renderer, subscription)?)))
+        .collect::<Result<Vec<_>, EmailTemplateError>>()?;
+
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    queue_back_in_stock_emails(conn, notifications).await
+}
+
+// Periodically deliver emails queued in the outbox
+// Failed deliveries are retried with exponential backoff and dead-lettered after max_attempts
+// Emails recipients can opt out of are sent with a signed one-click unsubscribe link
+pub fn spawn_email_outbox(
+    pool: web::Data<DbPool>,
+    email_transport: Arc<dyn EmailTransport>,
+    unsubscribe_signer: UnsubscribeSigner,
+    settings: EmailOutboxSettings
+) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_millis(settings.poll_interval_milliseconds));
+
+        loop {
+            interval.tick().await;
+
+            match run_email_outbox(&pool, email_transport.as_ref(), &unsubscribe_signer, &settings).await {
+                Ok(0) => {},
+                Ok(sent) => tracing::info!("Delivered {} queued emails", sent),
+                Err(e) => tracing::error!("Failed to deliver queued emails: {:?}", e)
+            }
+        }
+    })
+}
+
+async fn run_email_outbox(
+    pool: &web::Data<DbPool>,
+    email_transport: &dyn EmailTransport,
+    unsubscribe_signer: &UnsubscribeSigner,
+    settings: &EmailOutboxSettings
+) -> Result<usize, anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let emails = claim_due_emails(conn, settings.batch_size, settings.lease_seconds).await?;
+    let mut sent = 0;
+
+    for email in emails {
+        let unsubscribe_link = email.category.as_deref()
+            .and_then(|category| EmailCategory::parse(category).ok())
+            .map(|category| unsubscribe_signer.link(&email.recipient, Some(category)));
+
+        // An email the transport can't take never will be, so it isn't retried
+        let failure = match UserEmail::parse(email.recipient.clone()) {
+            Ok(recipient) => match email_transport.send_email(&recipient, &email.subject, &email.html_body, &email.text_body, unsubscribe_link.as_deref()).await {
+                Ok(()) => None,
+                Err(e @ EmailTransportError::InvalidMessage(_)) => Some((format!("{:?}", e), None)),
+                Err(e) => Some((format!("{:?}", e), retry_delay_seconds(email.attempts + 1, settings)))
+            },
+            Err(e) => Some((e, None))
+        };
+
+        let conn = get_pooled_connection(pool)
+                    .await
+                    .context("Failed to get connection from pool from within spawned task")?;
+
+        match failure {
+            None => {
+                mark_email_sent(conn, email.email_id).await?;
+                sent += 1;
+            },
+            Some((error, retry_in_seconds)) => {
+                tracing::warn!("Failed to deliver email {} on attempt {}: {}", email.email_id, email.attempts + 1, error);
+                mark_email_failed(conn, email.email_id, error, retry_in_seconds).await?;
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+// Seconds to wait before retrying an email which failed its nth attempt, None once it ran out of attempts
+fn retry_delay_seconds(attempts: i32, settings: &EmailOutboxSettings) -> Option<i64> {
+    if attempts >= settings.max_attempts {
+        return None;
+    }
+
+    let factor = 2_i64.saturating_pow((attempts - 1).max(0) as u32);
+    Some(settings.retry_base_seconds.saturating_mul(factor).min(settings.retry_max_seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::retry_delay_seconds;
+    use crate::configuration::EmailOutboxSettings;
+
+    #[test]
+    fn retries_back_off_exponentially_until_dead() {
+        let settings = EmailOutboxSettings {
+            poll_interval_milliseconds: 1000,
+            batch_size: 10,
+            max_attempts: 6,
+            retry_base_seconds: 30,
+            retry_max_seconds: 300,
+            lease_seconds: 60
+        };
+
+        let delays: Vec<Option<i64>> = (1..=6).map(|attempts| retry_delay_seconds(attempts, &settings)).collect();
+        assert_eq!(delays, vec![Some(30), Some(60), Some(120), Some(240), Some(300), None]);
+    }
+}
@@ -6,8 +6,14 @@ pub mod utils;
 pub mod schema;
 pub mod models;
 pub mod password;
-pub mod email_client;
+pub mod email_transport;
+pub mod email_templates;
+pub mod payment_provider;
 pub mod domain;
 pub mod auth;
 pub mod db_interaction;
 
+pub mod pagination;
+pub mod jobs;
+pub mod notifications;
+pub mod unsubscribe;
@@ -1,15 +1,43 @@
 use chrono::DateTime;
 use chrono::Utc;
-use diesel::prelude::{Insertable, Queryable};
+use diesel::prelude::{Insertable, Queryable, Selectable};
 use serde::Deserialize;
 use serde::Serialize;
 use uuid::Uuid;
 
+use crate::schema::categories;
+use crate::schema::coupons;
+use crate::schema::coupon_redemptions;
+use crate::schema::back_in_stock_subscriptions;
+use crate::schema::email_outbox;
+use crate::schema::email_preferences;
+use crate::schema::email_suppressions;
+use crate::schema::inventory_categories;
+use crate::schema::item_attributes;
 use crate::schema::order_items;
+use crate::schema::order_promotions;
+use crate::schema::payments;
+use crate::schema::promotions;
+use crate::schema::refunds;
+use crate::schema::refund_lines;
+use crate::schema::shipments;
+use crate::schema::shipment_lines;
+use crate::schema::returns;
+use crate::schema::shipping_methods;
+use crate::schema::shipping_method_zones;
+use crate::schema::tax_rates;
+use crate::schema::return_lines;
 use crate::schema::users;
 use crate::schema::confirmation;
 use crate::schema::inventory;
 use crate::schema::orders;
+use crate::schema::product_variants;
+use crate::schema::stock_movements;
+use crate::schema::stock_reservations;
+use crate::schema::variant_options;
+use crate::schema::warehouses;
+use crate::schema::warehouse_stock;
+use crate::schema::order_item_allocations;
 
 /// Model for users database
 #[derive(Queryable, Insertable, Clone)]
@@ -30,6 +58,7 @@ pub struct UserProfileInfo{
     pub name: String,
     pub email: String,
     pub phone_number: Option<String>,
+    pub phone_country: Option<String>,
     pub address: Option<String>
 }
 
@@ -43,13 +72,100 @@ pub struct ConfirmationMap{
 }
 
 /// Model for an Inventory item
-#[derive(Queryable, Insertable, Serialize, Deserialize)]
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = inventory)]
 pub struct InventoryItem{
     pub item_id: Uuid,
     pub name: String,
     pub amount: Option<i32>,
-    pub price: Option<f64>
+    pub price: Option<f64>,
+    pub sku: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub brand: Option<String>
+}
+
+/// Model for a product category
+/// Categories form a tree through parent_id
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = categories)]
+pub struct Category{
+    pub category_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub name: String,
+    pub slug: String
+}
+
+/// Model for assignment of an inventory item to a category
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = inventory_categories)]
+pub struct InventoryCategory{
+    pub item_id: Uuid,
+    pub category_id: Uuid
+}
+
+/// Model for a key / value attribute of an inventory item
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = item_attributes)]
+pub struct ItemAttribute{
+    pub item_id: Uuid,
+    pub key: String,
+    pub value: String
+}
+
+/// Model for a variant of an inventory item
+/// Stock is held per variant, price overrides item price when set
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = product_variants)]
+pub struct ProductVariant{
+    pub variant_id: Uuid,
+    pub item_id: Uuid,
+    pub sku: String,
+    pub name: String,
+    pub price: Option<f64>,
+    pub amount: i32
+}
+
+/// Model for an option (size, color, ...) of a product variant
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = variant_options)]
+pub struct VariantOption{
+    pub variant_id: Uuid,
+    pub name: String,
+    pub value: String
+}
+
+/// Model for an entry of the stock ledger
+/// quantity is signed, negative when stock leaves the warehouse
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = stock_movements)]
+pub struct StockMovement{
+    pub movement_id: Uuid,
+    pub item_id: Uuid,
+    pub variant_id: Uuid,
+    pub quantity: i32,
+    pub reason: String,
+    pub reference_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub warehouse_id: Option<Uuid>
+}
+
+/// Model for a hold placed on stock of a variant during checkout
+/// Held quantity isn't available to sell until the hold is released or expires
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = stock_reservations)]
+pub struct StockReservation{
+    pub reservation_id: Uuid,
+    pub user_id: Uuid,
+    pub item_id: Uuid,
+    pub variant_id: Uuid,
+    pub quantity: i32,
+    pub status: String,
+    pub order_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>
 }
 
 /// Model for inserting an order
@@ -67,8 +183,18 @@ pub struct Order{
 pub struct OrderQuery{
     pub order_id: Uuid,
     pub user_id: Option<Uuid>,
-    pub order_date: Option<DateTime<Utc>>,
-    pub status: String
+    pub order_date: DateTime<Utc>,
+    pub status: String,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub coupon_id: Option<Uuid>,
+    pub discount_cents: i64,
+    pub promotion_discount_cents: i64,
+    pub ship_country: Option<String>,
+    pub ship_region: Option<String>,
+    pub tax_cents: i64,
+    pub shipping_method_id: Option<Uuid>,
+    pub shipping_method_name: Option<String>,
+    pub shipping_cents: i64
 }
 
 /// Model for an order_item
@@ -78,7 +204,15 @@ pub struct OrderItemModel{
     pub order_item_id: Uuid,
     pub order_id: Uuid,
     pub item_id: Uuid,
-    pub quantity: i32
+    pub quantity: i32,
+    pub variant_id: Uuid,
+    pub backordered_quantity: i32,
+    pub unit_price_cents: i64,
+    pub refunded_quantity: i32,
+    pub discount_cents: i64,
+    pub tax_rate_basis_points: i32,
+    pub tax_cents: i64,
+    pub shipped_quantity: i32
 }
 
 /// Model for inner join between order_item and order
@@ -86,8 +220,289 @@ pub struct OrderItemModel{
 pub struct OrderIntermediate{
     pub order_id: Uuid,
     pub user_id: Option<Uuid>,
-    pub order_date: Option<DateTime<Utc>>,
+    pub order_date: DateTime<Utc>,
     pub status: String,
     pub item_id: Uuid,
+    pub variant_id: Uuid,
+    pub quantity: i32,
+    pub order_item_id: Uuid,
+    pub backordered_quantity: i32,
+    pub unit_price_cents: i64,
+    pub refunded_quantity: i32,
+    pub discount_cents: i64,
+    pub tax_rate_basis_points: i32,
+    pub tax_cents: i64,
+    pub shipped_quantity: i32
+}
+
+/// Model for a location stock is held at and shipped from
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = warehouses)]
+pub struct Warehouse{
+    pub warehouse_id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub priority: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>
+}
+
+/// Model for stock of a variant held at a warehouse
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = warehouse_stock)]
+pub struct WarehouseStock{
+    pub warehouse_id: Uuid,
+    pub variant_id: Uuid,
+    pub amount: i32
+}
+
+/// Model for quantity of an order_item to be picked from a warehouse
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = order_item_allocations)]
+pub struct OrderItemAllocation{
+    pub allocation_id: Uuid,
+    pub order_item_id: Uuid,
+    pub warehouse_id: Uuid,
+    pub quantity: i32
+}
+
+/// Model for a payment taken for an order through a payment provider
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = payments)]
+pub struct Payment{
+    pub payment_id: Uuid,
+    pub order_id: Uuid,
+    pub provider: String,
+    pub provider_reference: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub status: String,
+    pub failure_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub refunded_cents: i64
+}
+
+/// Model for money given back on an order's payment
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = refunds)]
+pub struct Refund{
+    pub refund_id: Uuid,
+    pub order_id: Uuid,
+    pub payment_id: Uuid,
+    pub status: String,
+    pub amount_cents: i64,
+    pub restock: bool,
+    pub reason: Option<String>,
+    pub provider_reference: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>
+}
+
+/// Model for quantity of an order_item given back by a refund
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = refund_lines)]
+pub struct RefundLine{
+    pub refund_line_id: Uuid,
+    pub refund_id: Uuid,
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+    pub unpicked_quantity: i32,
+    pub amount_cents: i64
+}
+
+/// Model for a parcel of order lines handed to a carrier
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = shipments)]
+pub struct Shipment{
+    pub shipment_id: Uuid,
+    pub order_id: Uuid,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub actor_id: Option<Uuid>,
+    pub shipped_at: DateTime<Utc>
+}
+
+/// Model for quantity of an order_item sent with a shipment
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = shipment_lines)]
+pub struct ShipmentLine{
+    pub shipment_line_id: Uuid,
+    pub shipment_id: Uuid,
+    pub order_item_id: Uuid,
     pub quantity: i32
 }
+
+/// Model for a customer's request to send back lines of a delivered order
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = returns)]
+pub struct OrderReturn{
+    pub return_id: Uuid,
+    pub order_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub status: String,
+    pub note: Option<String>,
+    pub refund_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
+}
+
+/// Model for quantity of an order_item sent back with a return
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = return_lines)]
+pub struct ReturnLine{
+    pub return_line_id: Uuid,
+    pub return_id: Uuid,
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+    pub reason: String
+}
+
+/// Model for a discount code
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = coupons)]
+pub struct Coupon{
+    pub coupon_id: Uuid,
+    pub code: String,
+    pub discount_type: String,
+    pub discount_value: i64,
+    pub min_order_cents: Option<i64>,
+    pub usage_limit: Option<i32>,
+    pub per_user_limit: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
+}
+
+/// Model for a coupon used on an order
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = coupon_redemptions)]
+pub struct CouponRedemption{
+    pub redemption_id: Uuid,
+    pub coupon_id: Uuid,
+    pub order_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub discount_cents: i64,
+    pub created_at: DateTime<Utc>
+}
+
+/// Model for an automatic promotion, rule columns used depend on its kind
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = promotions)]
+pub struct PromotionModel{
+    pub promotion_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub priority: i32,
+    pub active: bool,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub buy_quantity: Option<i32>,
+    pub get_quantity: Option<i32>,
+    pub percent_off: Option<i32>,
+    pub bundle_price_cents: Option<i64>,
+    pub created_at: DateTime<Utc>
+}
+
+/// Model for a promotion applied to an order
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = order_promotions)]
+pub struct OrderPromotion{
+    pub order_promotion_id: Uuid,
+    pub order_id: Uuid,
+    pub promotion_id: Option<Uuid>,
+    pub name: String,
+    pub discount_cents: i64
+}
+
+/// Model for tax charged on a tax class shipped to a country or region
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = tax_rates)]
+pub struct TaxRate{
+    pub tax_rate_id: Uuid,
+    pub country: String,
+    pub region: Option<String>,
+    pub tax_class: String,
+    pub rate_basis_points: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
+}
+
+/// Model for a way orders are shipped, rate columns used depend on its kind
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = shipping_methods)]
+pub struct ShippingMethodModel{
+    pub shipping_method_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub rate_cents: i64,
+    pub per_kg_cents: Option<i64>,
+    pub threshold_cents: Option<i64>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
+}
+
+/// Model for a country or region a shipping method ships to
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = shipping_method_zones)]
+pub struct ShippingMethodZone{
+    pub zone_id: Uuid,
+    pub shipping_method_id: Uuid,
+    pub country: String,
+    pub region: Option<String>
+}
+
+/// Model for an email waiting to be, or already, delivered by the outbox worker
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = email_outbox)]
+pub struct OutboxEmail{
+    pub email_id: Uuid,
+    pub recipient: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub category: Option<String>
+}
+
+/// Model for categories of emails a user wants to receive
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = email_preferences)]
+pub struct EmailPreferences{
+    pub user_id: Uuid,
+    pub order_updates: bool,
+    pub promotions: bool,
+    pub back_in_stock: bool,
+    pub updated_at: DateTime<Utc>
+}
+
+/// Model for an address no emails are delivered to
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = email_suppressions)]
+pub struct EmailSuppression{
+    pub email: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>
+}
+
+/// Model for a user waiting to be emailed when an item is back in stock
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = back_in_stock_subscriptions)]
+pub struct BackInStockSubscription{
+    pub subscription_id: Uuid,
+    pub item_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>
+}
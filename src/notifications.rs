@@ -0,0 +1,323 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{db_interaction::{FilledBackorder, LowStockItem, NewEmail, OrderNotice, RefundReceipt, RestockedSubscription}, email_templates::{EmailRenderer, EmailTemplate, EmailTemplateError}, startup::PaymentCurrency};
+
+// Enum representing events in an order's life its customer is emailed about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderEvent {
+    Placed,
+    Shipped,
+    Delivered,
+    Cancelled
+}
+
+impl OrderEvent {
+    fn template(&self) -> EmailTemplate {
+        match self {
+            Self::Placed => EmailTemplate::OrderPlaced,
+            Self::Shipped => EmailTemplate::OrderShipped,
+            Self::Delivered => EmailTemplate::OrderDelivered,
+            Self::Cancelled => EmailTemplate::OrderCancelled
+        }
+    }
+}
+
+// Variables of order email templates, amounts are formatted in the order's currency
+#[derive(Serialize, Debug)]
+struct OrderEmailContext {
+    order_id: Uuid,
+    lines: Vec<OrderLineContext>,
+    total: String,
+    tracking: Vec<TrackingContext>
+}
+
+#[derive(Serialize, Debug)]
+struct OrderLineContext {
+    quantity: i32,
+    name: String,
+    sku: String,
+    amount: String
+}
+
+#[derive(Serialize, Debug)]
+struct TrackingContext {
+    carrier: String,
+    tracking_number: String
+}
+
+// Renders emails to customers about their orders
+// Handed to functions changing an order so its email is queued in the same transaction as the change
+#[derive(Clone)]
+pub struct OrderMailer {
+    renderer: EmailRenderer,
+    currency: String
+}
+
+impl OrderMailer {
+    pub fn new(renderer: &EmailRenderer, currency: &PaymentCurrency) -> Self {
+        Self {
+            renderer: renderer.clone(),
+            currency: currency.0.clone()
+        }
+    }
+
+    // Email about event, None if order has no customer to email
+    pub fn email(&self, notice: &OrderNotice, event: OrderEvent) -> Result<Option<NewEmail>, EmailTemplateError> {
+        order_email(&self.renderer, notice, &self.currency, event)
+    }
+}
+
+// Variables of back in stock email template
+#[derive(Serialize, Debug)]
+struct BackInStockContext {
+    name: String,
+    sku: String
+}
+
+// Email telling a subscriber the item they waited for can be ordered again
+pub fn back_in_stock_email(renderer: &EmailRenderer, subscription: &RestockedSubscription) -> Result<NewEmail, EmailTemplateError> {
+    let context = BackInStockContext {
+        name: subscription.name.clone(),
+        sku: subscription.sku.clone()
+    };
+
+    renderer.render(EmailTemplate::BackInStock, None, subscription.email.clone(), &context)
+}
+
+// Variables of refund receipt template, amounts are formatted in the refunded payment's currency
+#[derive(Serialize, Debug)]
+struct RefundIssuedContext {
+    order_id: Uuid,
+    amount: String,
+    lines: Vec<OrderLineContext>
+}
+
+// Receipt of a refund, None if order has no customer to send it to
+pub fn refund_issued_email(renderer: &EmailRenderer, receipt: &RefundReceipt) -> Result<Option<NewEmail>, EmailTemplateError> {
+    let Some(recipient) = receipt.email.clone() else {
+        return Ok(None);
+    };
+
+    let context = RefundIssuedContext {
+        order_id: receipt.order_id,
+        amount: format_cents(receipt.amount_cents, &receipt.currency),
+        lines: receipt.lines.iter()
+            .map(|line| OrderLineContext {
+                quantity: line.quantity,
+                name: line.name.clone(),
+                sku: line.sku.clone(),
+                amount: format_cents(line.amount_cents, &receipt.currency)
+            })
+            .collect()
+    };
+
+    renderer.render(EmailTemplate::RefundIssued, None, recipient, &context)
+        .map(Some)
+}
+
+// Variables of backorder filled template
+#[derive(Serialize, Debug)]
+struct BackorderFilledContext {
+    order_id: Uuid,
+    name: String,
+    sku: String,
+    quantity: i32,
+    remaining: i32,
+    preorder: bool
+}
+
+// Email telling a customer their backordered or pre-ordered line is being prepared for shipping
+pub fn backorder_filled_email(renderer: &EmailRenderer, backorder: &FilledBackorder) -> Result<NewEmail, EmailTemplateError> {
+    let context = BackorderFilledContext {
+        order_id: backorder.order_id,
+        name: backorder.item_name.clone(),
+        sku: backorder.sku.clone(),
+        quantity: backorder.quantity,
+        remaining: backorder.remaining,
+        preorder: backorder.policy == "preorder"
+    };
+
+    renderer.render(EmailTemplate::BackorderFilled, None, backorder.email.clone(), &context)
+}
+
+// Variables of low stock alert template
+#[derive(Serialize, Debug)]
+struct LowStockAlertContext {
+    items: Vec<LowStockItemContext>
+}
+
+#[derive(Serialize, Debug)]
+struct LowStockItemContext {
+    name: String,
+    sku: String,
+    amount: i32,
+    reorder_threshold: i32
+}
+
+// Email telling an admin which items fell to their reorder threshold
+pub fn low_stock_alert_email(renderer: &EmailRenderer, recipient: String, items: &[LowStockItem]) -> Result<NewEmail, EmailTemplateError> {
+    let context = LowStockAlertContext {
+        items: items.iter()
+            .map(|item| LowStockItemContext {
+                name: item.name.clone(),
+                sku: item.sku.clone(),
+                amount: item.amount.unwrap_or(0),
+                reorder_threshold: item.reorder_threshold
+            })
+            .collect()
+    };
+
+    renderer.render(EmailTemplate::LowStockAlert, None, recipient, &context)
+}
+
+fn format_cents(cents: i64, currency: &str) -> String {
+    format!("{}.{:02} {}", cents / 100, cents % 100, currency)
+}
+
+fn order_email(renderer: &EmailRenderer, notice: &OrderNotice, currency: &str, event: OrderEvent) -> Result<Option<NewEmail>, EmailTemplateError> {
+    let Some(recipient) = notice.email.clone() else {
+        return Ok(None);
+    };
+
+    let context = OrderEmailContext {
+        order_id: notice.order_id,
+        lines: notice.lines.iter()
+            .map(|line| OrderLineContext {
+                quantity: line.quantity,
+                name: line.name.clone(),
+                sku: line.sku.clone(),
+                amount: format_cents(line.amount_cents, currency)
+            })
+            .collect(),
+        total: format_cents(notice.total_cents, currency),
+        tracking: notice.tracking.iter()
+            .map(|(carrier, tracking_number)| TrackingContext {
+                carrier: carrier.clone(),
+                tracking_number: tracking_number.clone()
+            })
+            .collect()
+    };
+
+    renderer.render(event.template(), None, recipient, &context)
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backorder_filled_email, low_stock_alert_email, order_email, refund_issued_email, OrderEvent};
+    use crate::{configuration::EmailTemplateSettings, db_interaction::{FilledBackorder, LowStockItem, OrderNotice, OrderNoticeLine, RefundReceipt, RefundReceiptLine}, domain::email_category::EmailCategory, email_templates::EmailRenderer};
+    use uuid::Uuid;
+
+    fn renderer() -> EmailRenderer {
+        EmailRenderer::load(&EmailTemplateSettings {
+            directory: "templates/email".to_string(),
+            default_locale: "en".to_string()
+        })
+        .unwrap()
+    }
+
+    fn notice() -> OrderNotice {
+        OrderNotice {
+            order_id: Uuid::new_v4(),
+            email: Some("customer@example.com".to_string()),
+            lines: vec![OrderNoticeLine {
+                name: "Lamp <deluxe>".to_string(),
+                sku: "LAMP-1".to_string(),
+                quantity: 2,
+                amount_cents: 2005
+            }],
+            total_cents: 2505,
+            tracking: vec![("DHL".to_string(), "DHL-001".to_string())]
+        }
+    }
+
+    #[test]
+    fn order_email_lists_lines_and_total() {
+        let email = order_email(&renderer(), &notice(), "USD", OrderEvent::Placed).unwrap().unwrap();
+        assert_eq!(email.subject, "We received your order");
+        assert!(email.text_body.contains("2 x Lamp <deluxe> (LAMP-1): 20.05 USD\nTotal: 25.05 USD"));
+        assert!(email.html_body.contains("Lamp &lt;deluxe&gt;"));
+        assert!(!email.text_body.contains("DHL-001"));
+    }
+
+    #[test]
+    fn shipped_email_includes_tracking_numbers() {
+        let email = order_email(&renderer(), &notice(), "USD", OrderEvent::Shipped).unwrap().unwrap();
+        assert!(email.text_body.contains("Tracking number (DHL): DHL-001"));
+        assert!(email.html_body.contains("Tracking number (DHL): DHL-001"));
+    }
+
+    #[test]
+    fn order_without_customer_gets_no_email() {
+        let notice = OrderNotice { email: None, ..notice() };
+        assert!(order_email(&renderer(), &notice, "USD", OrderEvent::Cancelled).unwrap().is_none());
+    }
+
+    #[test]
+    fn refund_receipt_lists_refunded_lines() {
+        let receipt = RefundReceipt {
+            order_id: Uuid::new_v4(),
+            email: Some("customer@example.com".to_string()),
+            amount_cents: 1000,
+            currency: "EUR".to_string(),
+            lines: vec![RefundReceiptLine {
+                name: "Mug <large>".to_string(),
+                sku: "MUG-1".to_string(),
+                quantity: 1,
+                amount_cents: 1000
+            }]
+        };
+
+        let email = refund_issued_email(&renderer(), &receipt).unwrap().unwrap();
+        assert_eq!(email.subject, "Your refund has been issued");
+        assert!(email.text_body.contains(&format!("10.00 EUR was refunded for order {}.", receipt.order_id)));
+        assert!(email.text_body.contains("1 x Mug <large> (MUG-1): 10.00 EUR"));
+        assert!(email.html_body.contains("Mug &lt;large&gt;"));
+        assert!(email.category.is_none());
+
+        let receipt = RefundReceipt { email: None, ..receipt };
+        assert!(refund_issued_email(&renderer(), &receipt).unwrap().is_none());
+    }
+
+    #[test]
+    fn backorder_email_names_policy_and_what_is_still_waiting() {
+        let backorder = FilledBackorder {
+            order_id: Uuid::new_v4(),
+            email: "customer@example.com".to_string(),
+            item_name: "Kettle".to_string(),
+            sku: "KETTLE-1".to_string(),
+            policy: "preorder".to_string(),
+            quantity: 2,
+            remaining: 1
+        };
+
+        let email = backorder_filled_email(&renderer(), &backorder).unwrap();
+        assert_eq!(email.subject, "Your pre-order is on its way");
+        assert!(email.text_body.contains("2 x Kettle (KETTLE-1) from your pre-order"));
+        assert!(email.text_body.contains("1 more will follow once restocked."));
+        assert_eq!(email.category, Some(EmailCategory::OrderUpdates));
+
+        let backorder = FilledBackorder { policy: "backorder".to_string(), remaining: 0, ..backorder };
+        let email = backorder_filled_email(&renderer(), &backorder).unwrap();
+        assert_eq!(email.subject, "Your backorder is on its way");
+        assert!(!email.text_body.contains("more will follow"));
+    }
+
+    #[test]
+    fn low_stock_alert_lists_items_below_threshold() {
+        let items = vec![LowStockItem {
+            item_id: Uuid::new_v4(),
+            sku: "LAMP-1".to_string(),
+            name: "Lamp".to_string(),
+            amount: None,
+            reorder_threshold: 5,
+            low_stock_alerted_at: None
+        }];
+
+        let email = low_stock_alert_email(&renderer(), "admin@example.com".to_string(), &items).unwrap();
+        assert_eq!(email.subject, "Low stock: 1 items at or below reorder threshold");
+        assert!(email.text_body.contains("Lamp (LAMP-1): 0 in stock, reorder threshold 5"));
+        assert!(email.html_body.contains("<li>Lamp (LAMP-1): 0 in stock, reorder threshold 5</li>"));
+    }
+}
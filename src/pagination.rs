@@ -0,0 +1,200 @@
+use actix_web::{http::{header, StatusCode}, HttpRequest, HttpResponse, ResponseError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+// Number of entries returned when request doesn't specify a limit
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+// Upper bound on number of entries returned in a single page
+pub const MAX_PAGE_LIMIT: i64 = 100;
+
+// Errors associated with validating pagination parameters
+#[derive(Error, Debug)]
+pub enum PaginationError {
+    #[error("limit must be between 1 and {}", MAX_PAGE_LIMIT)]
+    InvalidLimit,
+    #[error("cursor is malformed or doesn't belong to this listing")]
+    InvalidCursor
+}
+
+impl ResponseError for PaginationError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        HttpResponse::BadRequest().body(format!("{}", self))
+    }
+}
+
+// Validated request for a page of entries following an optional cursor
+#[derive(Debug, Clone)]
+pub struct PageRequest<C> {
+    pub limit: i64,
+    pub after: Option<C>
+}
+
+impl<C: DeserializeOwned> PageRequest<C> {
+    pub fn parse(limit: Option<i64>, cursor: Option<&str>) -> Result<Self, PaginationError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            return Err(PaginationError::InvalidLimit);
+        }
+
+        let after = match cursor {
+            Some(cursor) if !cursor.is_empty() => Some(decode_cursor(cursor)?),
+            _ => None
+        };
+
+        Ok(Self { limit, after })
+    }
+
+    // Number of rows to fetch, one extra row tells whether a next page exists
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+}
+
+// Page of entries along with metadata required to fetch the next one
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: i64
+}
+
+impl<T> Page<T> {
+    // Build page from rows fetched with PageRequest::fetch_limit
+    pub fn new<C: Serialize>(
+        mut rows: Vec<T>,
+        limit: i64,
+        total: i64,
+        cursor_of: impl Fn(&T) -> C
+    ) -> Self {
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let next_cursor = if has_more {
+            rows.last().map(|row| encode_cursor(&cursor_of(row)))
+        } else {
+            None
+        };
+
+        Self { items: rows, next_cursor, total }
+    }
+
+    // Replace entries of page, keeping its metadata
+    pub fn try_map_items<U, E>(
+        self,
+        f: impl FnOnce(Vec<T>) -> Result<Vec<U>, E>
+    ) -> Result<Page<U>, E> {
+        Ok(Page {
+            items: f(self.items)?,
+            next_cursor: self.next_cursor,
+            total: self.total
+        })
+    }
+}
+
+pub fn encode_cursor<C: Serialize>(cursor: &C) -> String {
+    let json = serde_json::to_vec(cursor).expect("Cursor should always serialize");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+pub fn decode_cursor<C: DeserializeOwned>(cursor: &str) -> Result<C, PaginationError> {
+    let json = URL_SAFE_NO_PAD.decode(cursor)
+        .map_err(|_| PaginationError::InvalidCursor)?;
+
+    serde_json::from_slice(&json)
+        .map_err(|_| PaginationError::InvalidCursor)
+}
+
+// Respond with page as JSON along with Link headers for first and next page
+pub fn page_response<T: Serialize>(req: &HttpRequest, page: &Page<T>) -> HttpResponse {
+    let mut links = vec![format!("<{}>; rel=\"first\"", page_link(req, None))];
+    if let Some(cursor) = &page.next_cursor {
+        links.push(format!("<{}>; rel=\"next\"", page_link(req, Some(cursor))));
+    }
+
+    HttpResponse::Ok()
+        .insert_header((header::LINK, links.join(", ")))
+        .json(page)
+}
+
+// Link to same listing with cursor substituted
+// Encoded cursors are URL safe so they can be appended as is
+fn page_link(req: &HttpRequest, cursor: Option<&str>) -> String {
+    let mut params: Vec<String> = req.query_string()
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("cursor="))
+        .map(String::from)
+        .collect();
+
+    if let Some(cursor) = cursor {
+        params.push(format!("cursor={}", cursor));
+    }
+
+    if params.is_empty() {
+        req.path().to_string()
+    } else {
+        format!("{}?{}", req.path(), params.join("&"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use claim::{assert_err, assert_ok};
+    use serde::{Deserialize, Serialize};
+
+    use super::{decode_cursor, encode_cursor, page_link, Page, PageRequest, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestCursor {
+        id: i64
+    }
+
+    #[test]
+    fn missing_limit_falls_back_on_default() {
+        let request = PageRequest::<TestCursor>::parse(None, None).unwrap();
+        assert_eq!(request.limit, DEFAULT_PAGE_LIMIT);
+        assert!(request.after.is_none());
+    }
+
+    #[test]
+    fn out_of_range_limits_are_rejected() {
+        assert_err!(PageRequest::<TestCursor>::parse(Some(0), None));
+        assert_err!(PageRequest::<TestCursor>::parse(Some(-5), None));
+        assert_err!(PageRequest::<TestCursor>::parse(Some(MAX_PAGE_LIMIT + 1), None));
+        assert_ok!(PageRequest::<TestCursor>::parse(Some(MAX_PAGE_LIMIT), None));
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = encode_cursor(&TestCursor { id: 42 });
+        assert_eq!(decode_cursor::<TestCursor>(&cursor).unwrap(), TestCursor { id: 42 });
+    }
+
+    #[test]
+    fn malformed_cursor_is_rejected() {
+        assert_err!(PageRequest::<TestCursor>::parse(None, Some("not a cursor")));
+        assert_err!(PageRequest::<TestCursor>::parse(None, Some(&encode_cursor(&"other"))));
+    }
+
+    #[test]
+    fn extra_row_produces_next_cursor_from_last_kept_row() {
+        let page = Page::new(vec![1, 2, 3], 2, 3, |id| TestCursor { id: *id });
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(decode_cursor::<TestCursor>(&page.next_cursor.unwrap()).unwrap(), TestCursor { id: 2 });
+
+        let page = Page::new(vec![1, 2], 2, 2, |id| TestCursor { id: *id });
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn page_link_replaces_existing_cursor() {
+        let req = TestRequest::with_uri("/inventory?limit=5&cursor=abc").to_http_request();
+        assert_eq!(page_link(&req, Some("def")), "/inventory?limit=5&cursor=def");
+        assert_eq!(page_link(&req, None), "/inventory?limit=5");
+    }
+}
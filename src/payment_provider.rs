@@ -0,0 +1,176 @@
+use std::{error::Error, fmt::Debug};
+
+use async_trait::async_trait;
+use ring::hmac;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::utils::{decode_hex, error_fmt_chain};
+
+// Intent created by a provider which the customer completes to pay for an order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PaymentIntent {
+    pub provider_reference: String,
+    pub client_secret: String
+}
+
+// Errors associated with talking to a payment provider
+#[derive(Error)]
+pub enum PaymentProviderError {
+    #[error("Payment provider rejected the request: {0}")]
+    Rejected(String),
+    #[error("Failed to reach payment provider")]
+    RequestError(#[source] anyhow::Error)
+}
+
+impl Debug for PaymentProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+// Gateway through which orders are paid
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    // Name recorded along with payments made through provider
+    fn name(&self) -> &'static str;
+
+    async fn create_intent(
+        &self,
+        order_id: Uuid,
+        amount_cents: i64,
+        currency: &str
+    ) -> Result<PaymentIntent, PaymentProviderError>;
+
+    // Give back part or all of a captured payment, returns provider's reference of the refund
+    async fn refund(
+        &self,
+        provider_reference: &str,
+        amount_cents: i64
+    ) -> Result<String, PaymentProviderError>;
+
+    // Check webhook payload was sent by provider
+    fn verify_webhook(&self, payload: &[u8], signature: &str) -> bool;
+}
+
+// Provider which never leaves the process, used for tests and development
+// Webhooks are signed with HMAC-SHA256 of the raw body, hex encoded
+pub struct FakePaymentProvider {
+    webhook_key: hmac::Key
+}
+
+impl FakePaymentProvider {
+    pub fn new(webhook_secret: &SecretString) -> Self {
+        Self {
+            webhook_key: hmac::Key::new(hmac::HMAC_SHA256, webhook_secret.expose_secret().as_bytes())
+        }
+    }
+
+    // Signature the provider would send along with payload
+    pub fn sign(&self, payload: &[u8]) -> String {
+        hmac::sign(&self.webhook_key, payload)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for FakePaymentProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    #[tracing::instrument(
+        "Creating payment intent with fake provider",
+        skip(self)
+    )]
+    async fn create_intent(
+        &self,
+        _order_id: Uuid,
+        amount_cents: i64,
+        _currency: &str
+    ) -> Result<PaymentIntent, PaymentProviderError> {
+        if amount_cents <= 0 {
+            return Err(PaymentProviderError::Rejected("amount should be positive".to_string()));
+        }
+
+        let provider_reference = format!("fake_pi_{}", Uuid::new_v4().simple());
+        let client_secret = format!("{}_secret_{}", provider_reference, Uuid::new_v4().simple());
+
+        Ok(PaymentIntent {
+            provider_reference,
+            client_secret
+        })
+    }
+
+    #[tracing::instrument(
+        "Refunding payment with fake provider",
+        skip(self)
+    )]
+    async fn refund(
+        &self,
+        provider_reference: &str,
+        amount_cents: i64
+    ) -> Result<String, PaymentProviderError> {
+        if amount_cents <= 0 {
+            return Err(PaymentProviderError::Rejected("amount should be positive".to_string()));
+        }
+
+        Ok(format!("fake_re_{}", Uuid::new_v4().simple()))
+    }
+
+    fn verify_webhook(&self, payload: &[u8], signature: &str) -> bool {
+        match decode_hex(signature) {
+            Some(tag) => hmac::verify(&self.webhook_key, payload, &tag).is_ok(),
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+    use secrecy::SecretString;
+    use uuid::Uuid;
+
+    use super::{FakePaymentProvider, PaymentProvider};
+
+    fn provider() -> FakePaymentProvider {
+        FakePaymentProvider::new(&SecretString::from("webhook-secret".to_string()))
+    }
+
+    #[test]
+    fn signed_payload_is_verified() {
+        let provider = provider();
+        let payload = br#"{"type":"payment.captured"}"#;
+
+        assert!(provider.verify_webhook(payload, &provider.sign(payload)));
+    }
+
+    #[test]
+    fn tampered_payload_or_bad_signature_is_rejected() {
+        let provider = provider();
+        let signature = provider.sign(br#"{"type":"payment.captured"}"#);
+
+        assert!(!provider.verify_webhook(br#"{"type":"payment.failed"}"#, &signature));
+        assert!(!provider.verify_webhook(br#"{"type":"payment.captured"}"#, "not-hex"));
+
+        let other = FakePaymentProvider::new(&SecretString::from("other-secret".to_string()));
+        assert!(!provider.verify_webhook(b"payload", &other.sign(b"payload")));
+    }
+
+    #[actix_web::test]
+    async fn intent_needs_positive_amount() {
+        let provider = provider();
+
+        assert_err!(provider.create_intent(Uuid::new_v4(), 0, "INR").await);
+
+        let intent = assert_ok!(provider.create_intent(Uuid::new_v4(), 100, "INR").await);
+        assert!(intent.client_secret.starts_with(&intent.provider_reference));
+    }
+}
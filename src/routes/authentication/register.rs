@@ -1,23 +1,23 @@
 use std::{error::Error, fmt::Debug};
 
-use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use secrecy::{ExposeSecret, SecretString};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{db_interaction::{insert_user_into_database, UserInsertError}, domain::user_email::UserEmail, email_client::EmailClient, startup::BaseUrl, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+use crate::{db_interaction::{insert_user_into_database, UserInsertError}, domain::user_email::UserEmail, email_templates::{locale_from_accept_language, EmailRenderer, EmailTemplate}, startup::BaseUrl, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
 
 // Route handler for user registration
 #[tracing::instrument(
     "User registration started",
-    skip(pool, email_client, base_url)
+    skip(pool, renderer, base_url)
 )]
 pub async fn register(
     req: HttpRequest,
     form: web::Form<RegistrationForm>,
     pool: web::Data<DbPool>,
-    email_client: web::Data<EmailClient>,
+    renderer: web::Data<EmailRenderer>,
     base_url: web::Data<BaseUrl>
 ) -> Result<HttpResponse, actix_web::Error> {
 
@@ -35,7 +35,26 @@ pub async fn register(
                 .context("Failed to get connection from pool from within spawned task")
                 .map_err(RegisterError::UnexpectedError)?;
 
-    let confirmation_id = insert_user_into_database(conn, form.0.name, form.0.email, form.0.password)
+    let locale = req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(locale_from_accept_language)
+        .map(str::to_string);
+
+    let name = form.0.name.clone();
+    let base_url = base_url.0.clone();
+    let renderer = renderer.get_ref().clone();
+    let confirmation_email = move |confirmation_id| {
+        let context = ConfirmationContext {
+            name,
+            confirmation_link: format!("{}confirm?id={}", base_url, confirmation_id)
+        };
+
+        renderer.render(EmailTemplate::Confirmation, locale.as_deref(), email.inner(), &context)
+    };
+
+    // Confirmation email is queued in the same transaction as the user, and delivered by the outbox worker
+    insert_user_into_database(conn, form.0.name, form.0.email, form.0.password, confirmation_email)
         .await
         .map_err(|e| {
             match e {
@@ -44,17 +63,6 @@ pub async fn register(
             }
         })?;
 
-    let conf_link = format!("{}confirm?id={}", base_url.0, confirmation_id);
-
-    email_client.send_email(
-        &email,
-        "Confirmation email",
-        "Click the link to confirm your ecomm account",
-        &format!("Click to confirm: {}", conf_link)
-    ).await
-    .map_err(|_| RegisterError::UnexpectedError(anyhow::anyhow!("Failed to send confirmation email")))?;
-    
-
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -68,6 +76,13 @@ pub struct RegistrationForm{
     confirm_password: SecretString
 }
 
+// Variables of the confirmation email template
+#[derive(Serialize, Debug)]
+struct ConfirmationContext{
+    name: String,
+    confirmation_link: String
+}
+
 // Error associated with error while registration
 #[derive(Error)]
 enum RegisterError{
@@ -0,0 +1,33 @@
+use actix_web::{error::ErrorInternalServerError, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::{db_interaction::get_categories, pagination::{page_response, PageRequest}, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting categories
+#[derive(Deserialize, Debug)]
+pub struct GetCategoryQuery {
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Get category entries",
+    skip(pool, req)
+)]
+pub async fn get_category(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetCategoryQuery>,
+    req: HttpRequest
+) -> Result<HttpResponse, actix_web::Error> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                    .await
+                    .map_err(|_| ErrorInternalServerError(anyhow::anyhow!("Failed due to internal server error")))?;
+
+    let categories = get_categories(conn, page_request)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(page_response(&req, &categories))
+}
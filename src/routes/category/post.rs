@@ -0,0 +1,87 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_category, CategoryInsertError}, domain::slug::Slug, models::Category, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing post category form
+#[derive(Deserialize, Debug)]
+pub struct CategoryForm{
+    name: String,
+    slug: Option<String>,
+    parent_id: Option<Uuid>
+}
+
+// Error response associated with posting category
+#[derive(Error)]
+pub enum PostCategoryError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Category with same slug already exists")]
+    NotUnique,
+    #[error("Parent category doesn't exist")]
+    IncorrectParentId,
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PostCategoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PostCategoryError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        let mut req_builder = match self {
+            Self::UnexpectedError(_) => HttpResponse::InternalServerError(),
+            _ => HttpResponse::BadRequest()
+        };
+
+        req_builder.body(format!("{}", self))
+    }
+}
+
+#[tracing::instrument(
+    "Posting category",
+    skip(pool)
+)]
+pub async fn post_category(
+    pool: web::Data<DbPool>,
+    form: web::Form<CategoryForm>,
+    _: IsAdmin
+) -> Result<HttpResponse, PostCategoryError>{
+    let slug = match &form.slug {
+        Some(slug) => Slug::parse(slug.clone()),
+        None => Slug::from_name(&form.name)
+    }
+    .map_err(PostCategoryError::InvalidInput)?;
+
+    let category = Category{
+        category_id: Uuid::new_v4(),
+        parent_id: form.parent_id,
+        name: form.name.clone(),
+        slug: slug.inner()
+    };
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    insert_category(conn, category.clone())
+        .await
+        .map_err(|e| {
+            match e {
+                CategoryInsertError::NotUnique => PostCategoryError::NotUnique,
+                CategoryInsertError::NoParentError => PostCategoryError::IncorrectParentId,
+                _ => PostCategoryError::UnexpectedError(e.into())
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(category))
+}
@@ -0,0 +1,26 @@
+use actix_web::{error::ErrorInternalServerError, web, HttpResponse};
+
+use crate::{auth::extractors::IsUser, db_interaction::release_stock_reservations, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Releasing stock reservations of checkout",
+    skip(pool, uid)
+)]
+pub async fn delete_checkout(
+    pool: web::Data<DbPool>,
+    uid: IsUser
+) -> Result<HttpResponse, actix_web::Error>{
+    let conn = get_pooled_connection(&pool)
+                    .await
+                    .map_err(|_|{
+                        ErrorInternalServerError(
+                            anyhow::anyhow!("Failed due to internal error")
+                        )
+                    })?;
+
+    release_stock_reservations(conn, uid.0)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,6 @@
+pub mod post;
+pub use post::post_checkout;
+pub mod delete;
+pub use delete::delete_checkout;
+pub mod payment;
+pub use payment::post_checkout_payment;
@@ -0,0 +1,114 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsUser, db_interaction::{get_order_payment_state, insert_payment, OrderPaymentState, PaymentError, PaymentStatus}, models::Payment, payment_provider::{PaymentProvider, PaymentProviderError}, startup::PaymentCurrency, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for paying an order
+#[derive(Deserialize, Debug)]
+pub struct CheckoutPaymentJson{
+    pub order_id: Uuid
+}
+
+// Struct representing payment intent customer completes with the provider
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckoutPaymentResponse{
+    pub payment: Payment,
+    pub client_secret: Option<String>
+}
+
+// Error response associated with creating payment intent
+#[derive(Error)]
+pub enum CheckoutPaymentError{
+    #[error("Failed to start payment")]
+    PaymentError(#[from] PaymentError),
+    #[error("Failed to create payment intent")]
+    PaymentProviderError(#[from] PaymentProviderError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for CheckoutPaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for CheckoutPaymentError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::PaymentError(e @ PaymentError::NoOrderIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::PaymentError(e @ PaymentError::AlreadyPaid) => HttpResponse::Conflict().body(format!("{}", e)),
+            Self::PaymentError(e @ PaymentError::NothingToPay) => HttpResponse::BadRequest().body(format!("{}", e)),
+            Self::PaymentProviderError(PaymentProviderError::Rejected(_)) => HttpResponse::BadGateway().body(format!("{}", self)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Creating payment intent for order",
+    skip(pool, provider, currency, uid)
+)]
+pub async fn post_checkout_payment(
+    pool: web::Data<DbPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    currency: web::Data<PaymentCurrency>,
+    json: web::Json<CheckoutPaymentJson>,
+    uid: IsUser
+) -> Result<HttpResponse, CheckoutPaymentError> {
+    let order_id = json.order_id;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    // Started payments are returned again, the client secret is only handed out once
+    let amount_cents = match get_order_payment_state(conn, order_id, uid.0).await? {
+        OrderPaymentState::Open(payment) => {
+            return Ok(HttpResponse::Ok().json(CheckoutPaymentResponse {
+                payment,
+                client_secret: None
+            }));
+        },
+        OrderPaymentState::Due(amount_cents) => amount_cents
+    };
+
+    let intent = provider.create_intent(order_id, amount_cents, &currency.0).await?;
+
+    let now = Utc::now();
+    let payment = Payment {
+        payment_id: Uuid::new_v4(),
+        order_id,
+        provider: provider.name().to_string(),
+        provider_reference: intent.provider_reference.clone(),
+        amount_cents,
+        currency: currency.0.clone(),
+        status: PaymentStatus::RequiresPayment.as_str().to_string(),
+        failure_reason: None,
+        created_at: now,
+        updated_at: now,
+        captured_at: None,
+        refunded_cents: 0
+    };
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let payment = insert_payment(conn, payment).await?;
+
+    // Lost a race with another request for the same order
+    let client_secret = (payment.provider_reference == intent.provider_reference).then_some(intent.client_secret);
+
+    Ok(HttpResponse::Ok().json(CheckoutPaymentResponse {
+        payment,
+        client_secret
+    }))
+}
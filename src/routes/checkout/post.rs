@@ -0,0 +1,107 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsUser, db_interaction::{place_stock_reservations, quote_promotions, PromotionError, StockReservationError}, domain::promotion::PriceBreakdown, models::StockReservation, startup::ReservationTtl, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// struct representing item (product variant) to be held during checkout
+#[derive(Deserialize, Debug)]
+pub struct CheckoutItem{
+    variant_id: Uuid,
+    amount: i32
+}
+
+// Struct representing holds placed by a checkout
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckoutResponse{
+    pub reservations: Vec<StockReservation>,
+    pub expires_at: DateTime<Utc>,
+    // What held items would cost with promotions running now, before any coupon
+    pub pricing: PriceBreakdown
+}
+
+// Error response associated with checkout
+#[derive(Error)]
+pub enum PostCheckoutError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Failed to reserve stock")]
+    StockReservationError(#[from] StockReservationError),
+    #[error("Failed to price checkout")]
+    PromotionError(#[from] PromotionError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PostCheckoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PostCheckoutError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::StockReservationError(e @ (StockReservationError::NoVariantIdError(_) | StockReservationError::InsufficientStockError(_) | StockReservationError::QuantityOverflowError(_))) => {
+                HttpResponse::BadRequest().body(format!("{}", e))
+            },
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Placing stock reservations for checkout",
+    skip(pool, ttl, uid)
+)]
+pub async fn post_checkout(
+    pool: web::Data<DbPool>,
+    ttl: web::Data<ReservationTtl>,
+    items: web::Json<Vec<CheckoutItem>>,
+    uid: IsUser
+) -> Result<HttpResponse, PostCheckoutError> {
+    if items.is_empty() {
+        return Err(PostCheckoutError::InvalidInput("checkout must contain at least one item".to_string()));
+    }
+
+    if items.iter().any(|item| item.amount <= 0) {
+        return Err(PostCheckoutError::InvalidInput("amount must be greater than zero".to_string()));
+    }
+
+    let quantities: Vec<(Uuid, i32)> = items.iter()
+                    .map(|item| (item.variant_id, item.amount))
+                    .collect();
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let reservations = place_stock_reservations(conn, uid.0, quantities, ttl.0).await?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let pricing = quote_promotions(
+        conn,
+        reservations.iter().map(|reservation| (reservation.variant_id, reservation.quantity)).collect()
+    ).await?;
+
+    let expires_at = reservations.iter()
+                    .map(|reservation| reservation.expires_at)
+                    .min()
+                    .context("No reservations were placed")?;
+
+    Ok(HttpResponse::Ok().json(CheckoutResponse {
+        reservations,
+        expires_at,
+        pricing
+    }))
+}
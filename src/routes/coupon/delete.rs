@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::delete_coupon, routes::coupon::CouponRouteError, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Deleting coupon",
+    skip(pool)
+)]
+pub async fn delete_coupon_by_id(
+    pool: web::Data<DbPool>,
+    coupon_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, CouponRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    delete_coupon(conn, coupon_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,52 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_coupon, get_coupons}, pagination::{page_response, PageRequest}, routes::coupon::CouponRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting coupons
+#[derive(Deserialize, Debug)]
+pub struct GetCouponsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Getting coupons",
+    skip(pool, req)
+)]
+pub async fn get_coupon_list(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetCouponsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, CouponRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let page = get_coupons(conn, page_request).await?;
+
+    Ok(page_response(&req, &page))
+}
+
+#[tracing::instrument(
+    "Getting coupon",
+    skip(pool)
+)]
+pub async fn get_coupon_by_id(
+    pool: web::Data<DbPool>,
+    coupon_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, CouponRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let coupon = get_coupon(conn, coupon_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(coupon))
+}
@@ -0,0 +1,138 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_coupon, update_coupon, CouponError, CouponSettings}, domain::coupon::{CouponCode, DiscountType}, pagination::PaginationError, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for creating or replacing a coupon
+#[derive(Deserialize, Debug)]
+pub struct CouponJson{
+    code: String,
+    discount_type: DiscountType,
+    // Percent off for percentage coupons, cents off for fixed ones
+    discount_value: i64,
+    min_order_cents: Option<i64>,
+    usage_limit: Option<i32>,
+    per_user_limit: Option<i32>,
+    starts_at: Option<DateTime<Utc>>,
+    ends_at: Option<DateTime<Utc>>,
+    active: Option<bool>,
+    #[serde(default)]
+    item_ids: Vec<Uuid>,
+    #[serde(default)]
+    category_ids: Vec<Uuid>
+}
+
+impl TryFrom<CouponJson> for CouponSettings {
+    type Error = String;
+
+    fn try_from(json: CouponJson) -> Result<Self, Self::Error> {
+        let code = CouponCode::parse(json.code)?;
+        json.discount_type.validate_value(json.discount_value)?;
+
+        if json.min_order_cents.is_some_and(|min_order_cents| min_order_cents < 0) {
+            return Err("min_order_cents can't be negative".to_string());
+        }
+        if json.usage_limit.is_some_and(|limit| limit <= 0) || json.per_user_limit.is_some_and(|limit| limit <= 0) {
+            return Err("usage limits should be positive".to_string());
+        }
+        if let (Some(starts_at), Some(ends_at)) = (json.starts_at, json.ends_at) {
+            if starts_at >= ends_at {
+                return Err("starts_at should be before ends_at".to_string());
+            }
+        }
+
+        Ok(CouponSettings {
+            code,
+            discount_type: json.discount_type,
+            discount_value: json.discount_value,
+            min_order_cents: json.min_order_cents,
+            usage_limit: json.usage_limit,
+            per_user_limit: json.per_user_limit,
+            starts_at: json.starts_at,
+            ends_at: json.ends_at,
+            active: json.active.unwrap_or(true),
+            item_ids: json.item_ids,
+            category_ids: json.category_ids
+        })
+    }
+}
+
+// Error response associated with coupon routes
+#[derive(Error)]
+pub enum CouponRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to save coupon")]
+    CouponError(#[from] CouponError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for CouponRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for CouponRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::CouponError(e @ CouponError::NoCouponIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::CouponError(e @ CouponError::NotUnique(_)) => HttpResponse::Conflict().body(format!("{}", e)),
+            Self::CouponError(e @ (CouponError::NoItemIdError(_) | CouponError::NoCategoryIdError(_))) => HttpResponse::BadRequest().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Posting coupon",
+    skip(pool)
+)]
+pub async fn post_coupon(
+    pool: web::Data<DbPool>,
+    json: web::Json<CouponJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, CouponRouteError> {
+    let settings = CouponSettings::try_from(json.into_inner()).map_err(CouponRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let coupon = insert_coupon(conn, settings).await?;
+
+    Ok(HttpResponse::Ok().json(coupon))
+}
+
+#[tracing::instrument(
+    "Replacing coupon",
+    skip(pool)
+)]
+pub async fn put_coupon(
+    pool: web::Data<DbPool>,
+    coupon_id: web::Path<Uuid>,
+    json: web::Json<CouponJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, CouponRouteError> {
+    let settings = CouponSettings::try_from(json.into_inner()).map_err(CouponRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let coupon = update_coupon(conn, coupon_id.into_inner(), settings).await?;
+
+    Ok(HttpResponse::Ok().json(coupon))
+}
@@ -0,0 +1,79 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_outbox_emails, OutboxError}, pagination::{page_response, PageRequest, PaginationError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Statuses an outbox email can be in
+const OUTBOX_STATUSES: [&str; 4] = ["pending", "sent", "dead", "suppressed"];
+
+// Struct representing query parameters for getting outbox emails, dead-lettered ones by default
+#[derive(Deserialize, Debug)]
+pub struct GetOutboxQuery{
+    status: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with email outbox routes
+#[derive(Error)]
+pub enum OutboxRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to manage email outbox")]
+    OutboxError(#[from] OutboxError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for OutboxRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for OutboxRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::OutboxError(e @ OutboxError::NoEmailIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::OutboxError(e @ OutboxError::NotRetryable(_)) => HttpResponse::Conflict().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Getting outbox emails",
+    skip(pool, req)
+)]
+pub async fn get_outbox(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetOutboxQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, OutboxRouteError> {
+    let query = query.into_inner();
+
+    let status = query.status.unwrap_or_else(|| "dead".to_string());
+    if !OUTBOX_STATUSES.contains(&status.as_str()) {
+        return Err(OutboxRouteError::InvalidInput(format!("status should be one of {}", OUTBOX_STATUSES.join(", "))));
+    }
+
+    let page_request = PageRequest::parse(query.limit, query.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let emails = get_outbox_emails(conn, status, page_request).await?;
+
+    Ok(page_response(&req, &emails))
+}
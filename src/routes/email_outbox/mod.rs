@@ -0,0 +1,4 @@
+pub mod get;
+pub use get::*;
+pub mod retry;
+pub use retry::*;
@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::retry_outbox_email, routes::email_outbox::OutboxRouteError, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Retrying dead outbox email",
+    skip(pool)
+)]
+pub async fn post_outbox_retry(
+    pool: web::Data<DbPool>,
+    email_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, OutboxRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let email = retry_outbox_email(conn, email_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(email))
+}
@@ -0,0 +1,6 @@
+pub mod preferences;
+pub use preferences::*;
+pub mod unsubscribe;
+pub use unsubscribe::*;
+pub mod suppressions;
+pub use suppressions::*;
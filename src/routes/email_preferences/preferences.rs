@@ -0,0 +1,76 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use thiserror::Error;
+
+use crate::{auth::extractors::IsUser, db_interaction::{get_email_preferences, update_email_preferences, EmailPreferencesError, EmailPreferencesUpdate}, pagination::PaginationError, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Error response associated with email preference, unsubscribe and suppression routes
+#[derive(Error)]
+pub enum EmailPreferencesRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Invalid unsubscribe token")]
+    InvalidToken,
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to manage email preferences")]
+    EmailPreferencesError(#[from] EmailPreferencesError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for EmailPreferencesRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for EmailPreferencesRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::InvalidToken => HttpResponse::Unauthorized().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::EmailPreferencesError(e @ EmailPreferencesError::NotSuppressedError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Getting email preferences of logged in user",
+    skip(pool, uid)
+)]
+pub async fn get_user_email_preferences(
+    pool: web::Data<DbPool>,
+    uid: IsUser
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let preferences = get_email_preferences(conn, uid.0).await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[tracing::instrument(
+    "Updating email preferences of logged in user",
+    skip(pool, uid)
+)]
+pub async fn put_user_email_preferences(
+    pool: web::Data<DbPool>,
+    update: web::Json<EmailPreferencesUpdate>,
+    uid: IsUser
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let preferences = update_email_preferences(conn, uid.0, update.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
@@ -0,0 +1,81 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{delete_email_suppression, get_email_suppressions, insert_email_suppression, SuppressionReason}, domain::user_email::UserEmail, pagination::{page_response, PageRequest}, routes::email_preferences::EmailPreferencesRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing json body for suppressing an address, manually by default
+#[derive(Deserialize, Debug)]
+pub struct SuppressionJson{
+    pub email: String,
+    pub reason: Option<SuppressionReason>
+}
+
+// Struct representing query parameters for getting suppressed addresses
+#[derive(Deserialize, Debug)]
+pub struct GetSuppressionsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Getting suppressed emails",
+    skip(pool, req)
+)]
+pub async fn get_suppressions(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetSuppressionsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let page_request = PageRequest::parse(query.limit, query.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let suppressions = get_email_suppressions(conn, page_request).await?;
+
+    Ok(page_response(&req, &suppressions))
+}
+
+#[tracing::instrument(
+    "Suppressing email",
+    skip(pool)
+)]
+pub async fn post_suppression(
+    pool: web::Data<DbPool>,
+    suppression: web::Json<SuppressionJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let suppression = suppression.into_inner();
+    let email = UserEmail::parse(suppression.email)
+        .map_err(EmailPreferencesRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let suppression = insert_email_suppression(conn, email.inner(), suppression.reason.unwrap_or(SuppressionReason::Manual)).await?;
+
+    Ok(HttpResponse::Ok().json(suppression))
+}
+
+// Lifting a suppression doesn't bring back emails already withheld from the address
+#[tracing::instrument(
+    "Deleting email suppression",
+    skip(pool)
+)]
+pub async fn delete_suppression(
+    pool: web::Data<DbPool>,
+    email: web::Path<String>,
+    _: IsAdmin
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    delete_email_suppression(conn, email.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
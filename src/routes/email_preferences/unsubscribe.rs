@@ -0,0 +1,81 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{db_interaction::unsubscribe_email, domain::email_category::EmailCategory, routes::email_preferences::EmailPreferencesRouteError, unsubscribe::UnsubscribeSigner, utils::{escape_html, get_pooled_connection, DbPool}};
+
+// Struct representing query parameters of a signed unsubscribe link, without category it covers every email
+#[derive(Deserialize, Debug)]
+pub struct UnsubscribeQuery{
+    email: String,
+    category: Option<String>,
+    token: String
+}
+
+// Followed from an email, link only leads to a page confirming the unsubscribe so that
+// mail scanners prefetching links don't opt anyone out
+#[tracing::instrument(
+    "Confirming unsubscribe link",
+    skip(signer, req, query)
+)]
+pub async fn get_unsubscribe(
+    signer: web::Data<UnsubscribeSigner>,
+    req: HttpRequest,
+    query: web::Query<UnsubscribeQuery>
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let (email, category) = verify_link(&signer, query.into_inner())?;
+
+    let emails = match category {
+        Some(category) => format!("{} emails", category.as_str().replace('_', " ")),
+        None => "all emails".to_string()
+    };
+
+    // Form posts back the same signed query the link carried
+    let body = format!(
+        "<!DOCTYPE html>\n<html>\n<body>\n<p>Unsubscribe {} from {}?</p>\n<form method=\"post\" action=\"/unsubscribe?{}\">\n<button type=\"submit\">Unsubscribe</button>\n</form>\n</body>\n</html>\n",
+        escape_html(&email),
+        emails,
+        escape_html(req.query_string())
+    );
+
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(body))
+}
+
+// POSTed from the confirmation page, or by mail clients supporting one-click unsubscribe (RFC 8058)
+// Link is signed for its email and category, so it needs no login
+#[tracing::instrument(
+    "Unsubscribing through link",
+    skip(pool, signer, query)
+)]
+pub async fn unsubscribe(
+    pool: web::Data<DbPool>,
+    signer: web::Data<UnsubscribeSigner>,
+    query: web::Query<UnsubscribeQuery>
+) -> Result<HttpResponse, EmailPreferencesRouteError> {
+    let (email, category) = verify_link(&signer, query.into_inner())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    unsubscribe_email(conn, email, category).await?;
+
+    Ok(HttpResponse::Ok().body("You have been unsubscribed"))
+}
+
+fn verify_link(
+    signer: &UnsubscribeSigner,
+    query: UnsubscribeQuery
+) -> Result<(String, Option<EmailCategory>), EmailPreferencesRouteError> {
+    let category = query.category
+        .as_deref()
+        .map(EmailCategory::parse)
+        .transpose()
+        .map_err(EmailPreferencesRouteError::InvalidInput)?;
+
+    if !signer.verify(&query.email, category, &query.token) {
+        return Err(EmailPreferencesRouteError::InvalidToken);
+    }
+
+    Ok((query.email, category))
+}
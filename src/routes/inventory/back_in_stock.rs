@@ -0,0 +1,103 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsUser, db_interaction::{get_back_in_stock_subscriptions, subscribe_back_in_stock, unsubscribe_back_in_stock, BackInStockError}, pagination::{page_response, PageRequest, PaginationError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting back in stock subscriptions
+#[derive(Deserialize, Debug)]
+pub struct GetBackInStockQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with back in stock subscription routes
+#[derive(Error)]
+pub enum BackInStockRouteError{
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to manage back in stock subscription")]
+    BackInStockError(#[from] BackInStockError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for BackInStockRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for BackInStockRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::PaginationError(e) => e.error_response(),
+            Self::BackInStockError(e @ (BackInStockError::NoItemIdError(_) | BackInStockError::NoSubscriptionError(_))) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::BackInStockError(e @ BackInStockError::InStockError(_)) => HttpResponse::Conflict().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+// Only out of stock items can be subscribed to, the user is emailed once when it is restocked
+#[tracing::instrument(
+    "Subscribing to back in stock email of item",
+    skip(pool, uid)
+)]
+pub async fn post_back_in_stock_subscription(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    uid: IsUser
+) -> Result<HttpResponse, BackInStockRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let subscription = subscribe_back_in_stock(conn, uid.0, item_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(subscription))
+}
+
+#[tracing::instrument(
+    "Unsubscribing from back in stock email of item",
+    skip(pool, uid)
+)]
+pub async fn delete_back_in_stock_subscription(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    uid: IsUser
+) -> Result<HttpResponse, BackInStockRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    unsubscribe_back_in_stock(conn, uid.0, item_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    "Getting back in stock subscriptions of logged in user",
+    skip(pool, req, uid)
+)]
+pub async fn get_back_in_stock_subscription_list(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetBackInStockQuery>,
+    req: HttpRequest,
+    uid: IsUser
+) -> Result<HttpResponse, BackInStockRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let subscriptions = get_back_in_stock_subscriptions(conn, uid.0, page_request).await?;
+
+    Ok(page_response(&req, &subscriptions))
+}
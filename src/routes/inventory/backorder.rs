@@ -0,0 +1,107 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{fill_backorders, get_fillable_backorder_variants, set_backorder_policy, BackorderError, BackorderPolicy}, domain::allocation::AllocationStrategy, email_templates::EmailRenderer, notifications::backorder_filled_email, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for setting backorder policy, null limit allows any quantity
+#[derive(Deserialize, Debug)]
+pub struct BackorderPolicyJson{
+    pub policy: BackorderPolicy,
+    pub backorder_limit: Option<i32>
+}
+
+// Error response associated with backorder policy route
+#[derive(Error)]
+pub enum BackorderRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Failed to update backorder policy")]
+    BackorderError(#[from] BackorderError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for BackorderRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for BackorderRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::BackorderError(e @ BackorderError::NoItemIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Setting backorder policy of inventory item",
+    skip(pool)
+)]
+pub async fn put_backorder_policy(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<BackorderPolicyJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, BackorderRouteError> {
+    if json.backorder_limit.is_some_and(|limit| limit < 0) {
+        return Err(BackorderRouteError::InvalidInput("backorder_limit can't be negative".to_string()));
+    }
+
+    if json.policy == BackorderPolicy::None && json.backorder_limit.is_some() {
+        return Err(BackorderRouteError::InvalidInput("backorder_limit needs backorder or preorder policy".to_string()));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_backorder_policy(conn, item_id.into_inner(), json.policy, json.backorder_limit).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+// Allocate stock which came back to backordered lines waiting for it
+// Whatever brought the stock back stands even if this fails, so errors are only logged
+pub async fn fill_waiting_backorders(
+    pool: &web::Data<DbPool>,
+    renderer: &EmailRenderer,
+    strategy: AllocationStrategy,
+    actor_id: Uuid
+) {
+    if let Err(e) = notify_filled_backorders(pool, renderer, strategy, actor_id).await {
+        tracing::error!("Failed to fill backorders: {:?}", e);
+    }
+}
+
+// Emails letting customers know their line got stock are queued along with each fill
+async fn notify_filled_backorders(
+    pool: &web::Data<DbPool>,
+    renderer: &EmailRenderer,
+    strategy: AllocationStrategy,
+    actor_id: Uuid
+) -> Result<(), anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    for variant_id in get_fillable_backorder_variants(conn).await? {
+        let conn = get_pooled_connection(pool)
+                    .await
+                    .context("Failed to get connection from pool from within spawned task")?;
+
+        let renderer = renderer.clone();
+        fill_backorders(conn, variant_id, strategy, Some(actor_id), move |backorder| backorder_filled_email(&renderer, backorder)).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use std::{collections::HashMap, error::Error, fmt::Debug};
+
+use actix_web::{error::ErrorInternalServerError, http::header::{self, ContentDisposition, DispositionParam, DispositionType}, web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_catalog_batch, upsert_catalog_records, CatalogImportError, CatalogRecord, ImportReport, ImportRowError}, domain::{allocation::AllocationStrategy, sku::Sku, slug::Slug}, email_templates::EmailRenderer, models::InventoryItem, routes::inventory::fill_waiting_backorders, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Largest catalog file accepted by import route
+pub const IMPORT_PAYLOAD_LIMIT: usize = 16 * 1024 * 1024;
+// Number of items fetched from db per chunk of export
+const EXPORT_BATCH_SIZE: i64 = 500;
+// Columns of catalog CSV, in order of fields of CatalogRow
+const CATALOG_COLUMNS: [&str; 7] = ["sku", "name", "price", "amount", "slug", "description", "brand"];
+
+// Struct representing a row of catalog file, shared by import and export
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CatalogRow {
+    sku: String,
+    name: String,
+    price: Option<f64>,
+    amount: Option<i32>,
+    slug: Option<String>,
+    description: Option<String>,
+    brand: Option<String>
+}
+
+impl From<InventoryItem> for CatalogRow {
+    fn from(item: InventoryItem) -> Self {
+        Self {
+            sku: item.sku,
+            name: item.name,
+            price: item.price,
+            amount: item.amount,
+            slug: Some(item.slug),
+            description: item.description,
+            brand: item.brand
+        }
+    }
+}
+
+impl CatalogRow {
+    fn validate(self, row: usize) -> Result<CatalogRecord, ImportRowError> {
+        let row_error = |message: String| ImportRowError {
+            row,
+            sku: Some(self.sku.clone()),
+            message
+        };
+
+        let sku = Sku::parse(self.sku.clone()).map_err(row_error)?;
+
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(row_error("name can't be empty".to_string()));
+        }
+
+        if self.price.is_some_and(|price| !price.is_finite() || price < 0.0) {
+            return Err(row_error("price must be a non-negative number".to_string()));
+        }
+
+        if self.amount.is_some_and(|amount| amount < 0) {
+            return Err(row_error("amount can't be negative".to_string()));
+        }
+
+        let slug = self.slug.clone()
+            .map(Slug::parse)
+            .transpose()
+            .map_err(row_error)?;
+        let derived_slug = Slug::from_name(&name).map_err(row_error)?;
+
+        Ok(CatalogRecord {
+            row,
+            sku: sku.inner(),
+            name,
+            price: self.price,
+            amount: self.amount,
+            slug: slug.map(|slug| slug.inner()),
+            derived_slug: derived_slug.inner(),
+            description: self.description,
+            brand: self.brand
+        })
+    }
+}
+
+// Formats accepted by import route
+enum ImportFormat {
+    Csv,
+    JsonLines
+}
+
+// Struct representing query parameters for import
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery {
+    #[serde(default)]
+    dry_run: bool
+}
+
+// Error response associated with catalog import
+#[derive(Error)]
+pub enum ImportInventoryError {
+    #[error("Content-Type must be text/csv or application/x-ndjson")]
+    UnsupportedFormat,
+    #[error("Import contains invalid rows")]
+    InvalidRows(ImportReport),
+    #[error("Failed to import catalog")]
+    CatalogImportError(#[from] CatalogImportError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for ImportInventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for ImportInventoryError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::UnsupportedFormat => HttpResponse::UnsupportedMediaType().body(format!("{}", self)),
+            Self::InvalidRows(report) => HttpResponse::BadRequest().json(report),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Importing catalog to inventory",
+    skip(pool, renderer, strategy, req, body, admin)
+)]
+pub async fn import_inventory(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    strategy: web::Data<AllocationStrategy>,
+    query: web::Query<ImportQuery>,
+    req: HttpRequest,
+    body: web::Bytes,
+    admin: IsAdmin
+) -> Result<HttpResponse, ImportInventoryError> {
+    let format = match req.content_type() {
+        "text/csv" => ImportFormat::Csv,
+        "application/x-ndjson" | "application/jsonl" => ImportFormat::JsonLines,
+        _ => return Err(ImportInventoryError::UnsupportedFormat)
+    };
+
+    let rows = match format {
+        ImportFormat::Csv => parse_csv(&body),
+        ImportFormat::JsonLines => parse_json_lines(&body)
+    };
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    let mut first_row_of_sku: HashMap<String, usize> = HashMap::new();
+
+    for (row, parsed) in rows {
+        let record = match parsed.and_then(|catalog_row| catalog_row.validate(row)) {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+
+        if let Some(first_row) = first_row_of_sku.insert(record.sku.clone(), row) {
+            errors.push(ImportRowError {
+                row,
+                sku: Some(record.sku),
+                message: format!("sku was already used in row {}", first_row)
+            });
+            continue;
+        }
+
+        records.push(record);
+    }
+
+    if !errors.is_empty() {
+        return Err(ImportInventoryError::InvalidRows(ImportReport {
+            dry_run: query.dry_run,
+            errors,
+            ..Default::default()
+        }));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let report = upsert_catalog_records(conn, records, query.dry_run, admin.0).await?;
+
+    if !report.errors.is_empty() {
+        return Err(ImportInventoryError::InvalidRows(report));
+    }
+
+    // Imported stock goes to orders waiting for it
+    if !report.dry_run {
+        fill_waiting_backorders(&pool, &renderer, **strategy, admin.0).await;
+    }
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Parse CSV with header row, rows are numbered from 1 excluding the header
+fn parse_csv(body: &[u8]) -> Vec<(usize, Result<CatalogRow, ImportRowError>)> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body)
+        .into_deserialize::<CatalogRow>()
+        .enumerate()
+        .map(|(index, parsed)| {
+            let row = index + 1;
+            (row, parsed.map_err(|e| ImportRowError { row, sku: None, message: e.to_string() }))
+        })
+        .collect()
+}
+
+// Parse one JSON object per line, rows are numbered by line and blank lines are skipped
+fn parse_json_lines(body: &[u8]) -> Vec<(usize, Result<CatalogRow, ImportRowError>)> {
+    String::from_utf8_lossy(body)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let row = index + 1;
+            (row, serde_json::from_str::<CatalogRow>(line)
+                .map_err(|e| ImportRowError { row, sku: None, message: e.to_string() }))
+        })
+        .collect()
+}
+
+// Position of export stream within catalog
+enum ExportState {
+    Start,
+    After(String),
+    Done
+}
+
+#[tracing::instrument(
+    "Exporting catalog from inventory",
+    skip(pool)
+)]
+pub async fn export_inventory(
+    pool: web::Data<DbPool>,
+    _: IsAdmin
+) -> HttpResponse {
+    let chunks = stream::unfold(ExportState::Start, move |state| {
+        let pool = pool.clone();
+
+        async move {
+            let after_sku = match state {
+                ExportState::Start => None,
+                ExportState::After(sku) => Some(sku),
+                ExportState::Done => return None
+            };
+            let is_first_chunk = after_sku.is_none();
+
+            match export_chunk(&pool, after_sku, is_first_chunk).await {
+                Ok((chunk, next_state)) => Some((Ok(chunk), next_state)),
+                Err(e) => {
+                    tracing::error!("Failed to export catalog: {:?}", e);
+                    Some((Err(ErrorInternalServerError(e)), ExportState::Done))
+                }
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("catalog.csv".to_string())]
+        })
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .streaming(chunks)
+}
+
+// Serialize next batch of catalog as CSV along with state following it
+async fn export_chunk(
+    pool: &web::Data<DbPool>,
+    after_sku: Option<String>,
+    with_header: bool
+) -> Result<(web::Bytes, ExportState), anyhow::Error> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let items = get_catalog_batch(conn, after_sku, EXPORT_BATCH_SIZE).await?;
+
+    let next_state = match items.last() {
+        Some(item) if items.len() as i64 == EXPORT_BATCH_SIZE => ExportState::After(item.sku.clone()),
+        _ => ExportState::Done
+    };
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+
+    if with_header {
+        writer.write_record(CATALOG_COLUMNS)?;
+    }
+
+    for item in items {
+        writer.serialize(CatalogRow::from(item))?;
+    }
+
+    let chunk = writer.into_inner().context("Failed to flush CSV writer")?;
+
+    Ok((web::Bytes::from(chunk), next_state))
+}
@@ -0,0 +1,99 @@
+use std::{collections::BTreeMap, error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{set_item_attributes, set_item_categories, UpdateItemDetailsError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for assigning categories to an item
+#[derive(Deserialize, Debug)]
+pub struct ItemCategoriesJson{
+    pub category_ids: Vec<Uuid>
+}
+
+// Error response associated with updating catalog details of an item
+#[derive(Error)]
+pub enum PutItemDetailsError{
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("Incorrect item id given: {0}")]
+    IncorrectItemId(Uuid),
+    #[error("One or more category ids don't exist")]
+    IncorrectCategoryId,
+    #[error("{0}")]
+    InvalidInput(String)
+}
+
+impl Debug for PutItemDetailsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PutItemDetailsError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        let mut req_builder = match self {
+            Self::UnexpectedError(_) => HttpResponse::InternalServerError(),
+            Self::IncorrectItemId(_) => HttpResponse::NotFound(),
+            Self::IncorrectCategoryId | Self::InvalidInput(_) => HttpResponse::BadRequest()
+        };
+
+        req_builder.body(format!("{}", self))
+    }
+}
+
+impl From<UpdateItemDetailsError> for PutItemDetailsError {
+    fn from(e: UpdateItemDetailsError) -> Self {
+        match e {
+            UpdateItemDetailsError::NoItemIdError(r) => Self::IncorrectItemId(r),
+            UpdateItemDetailsError::InvalidCategoryError => Self::IncorrectCategoryId,
+            _ => Self::UnexpectedError(e.into())
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Assigning categories to inventory item",
+    skip(pool)
+)]
+pub async fn put_item_categories(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<ItemCategoriesJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, PutItemDetailsError>{
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_item_categories(conn, item_id.into_inner(), json.0.category_ids).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    "Setting attributes of inventory item",
+    skip(pool)
+)]
+pub async fn put_item_attributes(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<BTreeMap<String, String>>,
+    _: IsAdmin
+) -> Result<HttpResponse, PutItemDetailsError>{
+    if json.keys().any(|key| key.trim().is_empty()) {
+        return Err(PutItemDetailsError::InvalidInput("attribute keys cannot be empty".to_string()))
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_item_attributes(conn, item_id.into_inner(), json.0).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
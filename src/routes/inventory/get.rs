@@ -1,23 +1,25 @@
-use actix_web::{error::ErrorInternalServerError, web, HttpResponse};
+use actix_web::{error::ErrorInternalServerError, web, HttpRequest, HttpResponse};
 use serde::Deserialize;
 
-use crate::{db_interaction::get_inventory_items, utils::{get_pooled_connection, DbPool}};
+use crate::{db_interaction::get_inventory_items, pagination::{page_response, PageRequest}, utils::{get_pooled_connection, DbPool}};
 
 // Struct representing query parameters for getting inventory
 #[derive(Deserialize, Debug)]
 pub struct GetInventoryQuery {
-    page: i64,
-    limit: i64
+    limit: Option<i64>,
+    cursor: Option<String>
 }
 
 #[tracing::instrument(
     "Get inventory entries",
-    skip(pool)
+    skip(pool, req)
 )]
 pub async fn get_inventory(
     pool: web::Data<DbPool>,
-    query: web::Query<GetInventoryQuery>
+    query: web::Query<GetInventoryQuery>,
+    req: HttpRequest
 ) -> Result<HttpResponse, actix_web::Error> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
 
     let conn = get_pooled_connection(&pool)
                     .await
@@ -25,12 +27,10 @@ pub async fn get_inventory(
 
     let inventory_items = get_inventory_items(
         conn,
-        query.0.page,
-        query.0.limit
+        page_request
     )
     .await
     .map_err(ErrorInternalServerError)?;
     
-    Ok(HttpResponse::Ok().json(inventory_items))
+    Ok(page_response(&req, &inventory_items))
 }
-
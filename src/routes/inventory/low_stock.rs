@@ -0,0 +1,97 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_low_stock_items, set_reorder_threshold, LowStockError}, pagination::{page_response, PageRequest, PaginationError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for setting reorder threshold, null clears it
+#[derive(Deserialize, Debug)]
+pub struct ReorderThresholdJson{
+    pub reorder_threshold: Option<i32>
+}
+
+// Struct representing query parameters for getting low stock items
+#[derive(Deserialize, Debug)]
+pub struct GetLowStockQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with low stock routes
+#[derive(Error)]
+pub enum LowStockRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to update reorder threshold")]
+    LowStockError(#[from] LowStockError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for LowStockRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for LowStockRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::LowStockError(e @ LowStockError::NoItemIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Setting reorder threshold of inventory item",
+    skip(pool)
+)]
+pub async fn put_reorder_threshold(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<ReorderThresholdJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, LowStockRouteError> {
+    if json.reorder_threshold.is_some_and(|threshold| threshold < 0) {
+        return Err(LowStockRouteError::InvalidInput("reorder_threshold can't be negative".to_string()));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_reorder_threshold(conn, item_id.into_inner(), json.0.reorder_threshold).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    "Getting low stock inventory items",
+    skip(pool, req)
+)]
+pub async fn get_low_stock(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetLowStockQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, LowStockRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let items = get_low_stock_items(conn, page_request).await?;
+
+    Ok(page_response(&req, &items))
+}
@@ -2,3 +2,19 @@ pub mod get;
 pub use get::*;
 pub mod post;
 pub use post::*;
+pub mod details;
+pub use details::*;
+pub mod variants;
+pub use variants::*;
+pub mod search;
+pub use search::*;
+pub mod catalog;
+pub use catalog::*;
+pub mod stock;
+pub use stock::*;
+pub mod low_stock;
+pub use low_stock::*;
+pub mod backorder;
+pub use backorder::*;
+pub mod back_in_stock;
+pub use back_in_stock::*;
@@ -6,7 +6,7 @@ use serde::Deserialize;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{auth::extractors::IsAdmin, db_interaction::insert_inventory_items, models::InventoryItem, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+use crate::{auth::extractors::IsAdmin, db_interaction::insert_inventory_items, domain::{sku::Sku, slug::Slug}, models::InventoryItem, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
 use crate::db_interaction::InventoryInsertError;
 
 // Struct representing post inventory form
@@ -14,12 +14,20 @@ use crate::db_interaction::InventoryInsertError;
 pub struct InventoryForm{
     name: String,
     amount: i32,
-    price: f64
+    price: f64,
+    sku: String,
+    slug: Option<String>,
+    description: Option<String>,
+    brand: Option<String>
 }
 
 // Error response associated with posting inventory
 #[derive(Error)]
 pub enum PostInventoryError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Item with same sku or slug already exists")]
+    NotUnique,
     #[error("Failed to insert item to inventory")]
     InsertInventoryError(#[from] InventoryInsertError),
     #[error("Failed due to internal server error")]
@@ -35,32 +43,61 @@ impl Debug for PostInventoryError {
 
 impl ResponseError for PostInventoryError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::InternalServerError().body(format!("{}", self))
+        let mut req_builder = match self {
+            Self::InvalidInput(_) | Self::NotUnique => HttpResponse::BadRequest(),
+            _ => HttpResponse::InternalServerError()
+        };
+
+        req_builder.body(format!("{}", self))
     }
 }
 
 #[tracing::instrument(
     "Posting items to inventory",
-    skip(pool)
+    skip(pool, admin)
 )]
 pub async fn post_inventory(
     pool: web::Data<DbPool>,
     form: web::Form<InventoryForm>,
-    _: IsAdmin
+    admin: IsAdmin
 ) -> Result<HttpResponse, PostInventoryError>{
 
+    if form.amount < 0 {
+        return Err(PostInventoryError::InvalidInput("amount cannot be negative".to_string()));
+    }
+
+    let sku = Sku::parse(form.sku.clone())
+                .map_err(PostInventoryError::InvalidInput)?;
+
+    let slug = match &form.slug {
+        Some(slug) => Slug::parse(slug.clone()),
+        None => Slug::from_name(&form.name)
+    }
+    .map_err(PostInventoryError::InvalidInput)?;
+
     let inventory_item = InventoryItem{
         item_id: Uuid::new_v4(),
         name: form.name.clone(),
         amount: Some(form.amount),
-        price: Some(form.price)
+        price: Some(form.price),
+        sku: sku.inner(),
+        slug: slug.inner(),
+        description: form.description.clone(),
+        brand: form.brand.clone()
     };
 
     let conn = get_pooled_connection(&pool)
                 .await
                 .context("Failed to get connection from pool from within spawned task")?;
 
-    insert_inventory_items(conn, inventory_item).await?;
+    insert_inventory_items(conn, inventory_item, admin.0)
+        .await
+        .map_err(|e| {
+            match e {
+                InventoryInsertError::NotUnique => PostInventoryError::NotUnique,
+                _ => PostInventoryError::InsertInventoryError(e)
+            }
+        })?;
 
     Ok(HttpResponse::Ok().finish())
 }
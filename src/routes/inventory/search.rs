@@ -0,0 +1,70 @@
+use actix_web::{error::{ErrorBadRequest, ErrorInternalServerError}, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{db_interaction::{search_inventory_items, SearchFilters, SearchSort}, pagination::{page_response, PageRequest, PaginationError}, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for searching inventory
+#[derive(Deserialize, Debug)]
+pub struct SearchInventoryQuery {
+    q: Option<String>,
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    category: Option<Uuid>,
+    #[serde(default)]
+    in_stock: bool,
+    #[serde(default)]
+    sort: SearchSort,
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Search inventory entries",
+    skip(pool, req)
+)]
+pub async fn search_inventory(
+    pool: web::Data<DbPool>,
+    query: web::Query<SearchInventoryQuery>,
+    req: HttpRequest
+) -> Result<HttpResponse, actix_web::Error> {
+    let query = query.into_inner();
+
+    if let (Some(min_price), Some(max_price)) = (query.min_price, query.max_price) {
+        if min_price > max_price {
+            return Err(ErrorBadRequest("min_price can't be greater than max_price"));
+        }
+    }
+
+    let filters = SearchFilters {
+        query: query.q
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty()),
+        min_price: query.min_price,
+        max_price: query.max_price,
+        category_id: query.category,
+        in_stock: query.in_stock,
+        sort: query.sort
+    };
+
+    let page_request = PageRequest::parse(query.limit, query.cursor.as_deref())?;
+    if let Some(cursor) = &page_request.after {
+        if !filters.accepts(cursor) {
+            return Err(PaginationError::InvalidCursor.into());
+        }
+    }
+
+    let conn = get_pooled_connection(&pool)
+                    .await
+                    .map_err(|_| ErrorInternalServerError(anyhow::anyhow!("Failed due to internal server error")))?;
+
+    let inventory_items = search_inventory_items(
+        conn,
+        filters,
+        page_request
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    Ok(page_response(&req, &inventory_items))
+}
@@ -0,0 +1,156 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_stock_movements, insert_manual_stock_movement, reconcile_item_stock, StockChange, StockLedgerError, StockMovementReason}, domain::allocation::AllocationStrategy, email_templates::EmailRenderer, pagination::{page_response, PageRequest, PaginationError}, routes::inventory::fill_waiting_backorders, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for recording a stock movement by hand
+#[derive(Deserialize, Debug)]
+pub struct StockMovementJson{
+    pub variant_id: Uuid,
+    pub quantity: i32,
+    pub reason: StockMovementReason,
+    pub reference_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub warehouse_id: Option<Uuid>
+}
+
+// Struct representing query parameters for getting stock movements
+#[derive(Deserialize, Debug)]
+pub struct GetStockMovementsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with stock ledger routes
+#[derive(Error)]
+pub enum StockLedgerRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to access stock ledger")]
+    StockLedgerError(#[from] StockLedgerError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for StockLedgerRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for StockLedgerRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::StockLedgerError(e @ StockLedgerError::NoItemIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::StockLedgerError(e @ (StockLedgerError::NoVariantIdError(_) | StockLedgerError::NoWarehouseIdError(_) | StockLedgerError::InsufficientStockError(_))) => {
+                HttpResponse::BadRequest().body(format!("{}", e))
+            },
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Recording stock movement of inventory item",
+    skip(pool, renderer, strategy, admin)
+)]
+pub async fn post_stock_movement(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    strategy: web::Data<AllocationStrategy>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<StockMovementJson>,
+    admin: IsAdmin
+) -> Result<HttpResponse, StockLedgerRouteError> {
+    let json = json.into_inner();
+
+    // Orders and cancellations are only recorded by the order flow
+    let is_valid = match json.reason {
+        StockMovementReason::Restock | StockMovementReason::Return => json.quantity > 0,
+        // Stock is taken off by negating quantity which i32::MIN can't be
+        StockMovementReason::Adjustment => json.quantity != 0 && json.quantity.checked_neg().is_some(),
+        StockMovementReason::Order | StockMovementReason::Cancellation => false
+    };
+
+    if !is_valid {
+        return Err(StockLedgerRouteError::InvalidInput(
+            "restock and return must add stock, adjustment must be non-zero and within range".to_string()
+        ));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let movement = insert_manual_stock_movement(
+        conn,
+        item_id.into_inner(),
+        json.variant_id,
+        json.quantity,
+        StockChange {
+            reason: json.reason,
+            reference_id: json.reference_id,
+            actor_id: Some(admin.0),
+            note: json.note,
+            warehouse_id: json.warehouse_id
+        }
+    )
+    .await?;
+
+    // Stock arriving goes to orders waiting for it
+    if movement.quantity > 0 {
+        fill_waiting_backorders(&pool, &renderer, **strategy, admin.0).await;
+    }
+
+    Ok(HttpResponse::Ok().json(movement))
+}
+
+#[tracing::instrument(
+    "Getting stock movements of inventory item",
+    skip(pool, req)
+)]
+pub async fn get_item_stock_movements(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    query: web::Query<GetStockMovementsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, StockLedgerRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let movements = get_stock_movements(conn, item_id.into_inner(), page_request).await?;
+
+    Ok(page_response(&req, &movements))
+}
+
+#[tracing::instrument(
+    "Reconciling stock of inventory item",
+    skip(pool)
+)]
+pub async fn get_stock_reconciliation(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, StockLedgerRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let reconciliation = reconcile_item_stock(conn, item_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(reconciliation))
+}
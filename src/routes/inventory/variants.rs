@@ -0,0 +1,97 @@
+use std::{collections::BTreeMap, error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_product_variant, VariantInsertError}, domain::sku::Sku, models::ProductVariant, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for posting a product variant
+#[derive(Deserialize, Debug)]
+pub struct VariantJson{
+    pub sku: String,
+    pub name: String,
+    pub price: Option<f64>,
+    pub amount: i32,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>
+}
+
+// Error response associated with posting a product variant
+#[derive(Error)]
+pub enum PostVariantError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Variant with same sku already exists")]
+    NotUnique,
+    #[error("Incorrect item id given: {0}")]
+    IncorrectItemId(Uuid),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PostVariantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PostVariantError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        let mut req_builder = match self {
+            Self::InvalidInput(_) | Self::NotUnique => HttpResponse::BadRequest(),
+            Self::IncorrectItemId(_) => HttpResponse::NotFound(),
+            Self::UnexpectedError(_) => HttpResponse::InternalServerError()
+        };
+
+        req_builder.body(format!("{}", self))
+    }
+}
+
+#[tracing::instrument(
+    "Posting variant of inventory item",
+    skip(pool, admin)
+)]
+pub async fn post_variant(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<VariantJson>,
+    admin: IsAdmin
+) -> Result<HttpResponse, PostVariantError>{
+    let json = json.into_inner();
+
+    let sku = Sku::parse(json.sku)
+                .map_err(PostVariantError::InvalidInput)?;
+
+    if json.amount < 0 {
+        return Err(PostVariantError::InvalidInput("amount cannot be negative".to_string()))
+    }
+
+    let variant = ProductVariant{
+        variant_id: Uuid::new_v4(),
+        item_id: item_id.into_inner(),
+        sku: sku.inner(),
+        name: json.name,
+        price: json.price,
+        amount: json.amount
+    };
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    insert_product_variant(conn, variant.clone(), json.options, admin.0)
+        .await
+        .map_err(|e| {
+            match e {
+                VariantInsertError::NotUnique => PostVariantError::NotUnique,
+                VariantInsertError::NoItemIdError(r) => PostVariantError::IncorrectItemId(r),
+                _ => PostVariantError::UnexpectedError(e.into())
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(variant))
+}
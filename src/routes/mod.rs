@@ -5,3 +5,13 @@ pub mod confirm;
 pub mod profile;
 pub mod order;
 pub mod inventory;
+pub mod category;
+pub mod checkout;
+pub mod warehouse;
+pub mod payment;
+pub mod coupon;
+pub mod promotion;
+pub mod tax;
+pub mod shipping;
+pub mod email_outbox;
+pub mod email_preferences;
@@ -1,8 +1,8 @@
-use actix_web::{error::ErrorInternalServerError, web, HttpResponse};
+use actix_web::{error::{ErrorConflict, ErrorInternalServerError}, web, HttpResponse};
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::{auth::extractors::IsUser, db_interaction::delete_order_from_database, utils::{get_pooled_connection, DbPool}};
+use crate::{auth::extractors::IsUser, db_interaction::{delete_order_from_database, DeleteOrderError}, domain::allocation::AllocationStrategy, email_templates::EmailRenderer, notifications::{OrderEvent, OrderMailer}, routes::inventory::fill_waiting_backorders, startup::PaymentCurrency, utils::{get_pooled_connection, DbPool}};
 
 // struct representing json body for deleting order
 #[derive(Deserialize, Debug)]
@@ -12,12 +12,15 @@ pub struct DeleteOrderJson{
 
 #[tracing::instrument(
     "Deleting order by id"
-    skip(pool)
+    skip(pool, renderer, currency, strategy, uid)
 )]
 pub async fn delete_order(
     pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    currency: web::Data<PaymentCurrency>,
+    strategy: web::Data<AllocationStrategy>,
     json: web::Json<DeleteOrderJson>,
-    _: IsUser
+    uid: IsUser
 ) -> Result<HttpResponse, actix_web::Error>{
     let conn = get_pooled_connection(&pool)
                     .await
@@ -27,9 +30,16 @@ pub async fn delete_order(
                         )
                     })?;
 
-    delete_order_from_database(conn, json.order_id)
+    let mailer = OrderMailer::new(&renderer, &currency);
+    delete_order_from_database(conn, json.order_id, uid.0, move |notice| mailer.email(notice, OrderEvent::Cancelled))
         .await
-        .map_err(ErrorInternalServerError)?;
+        .map_err(|e| match e {
+            DeleteOrderError::PaidOrderError(_) | DeleteOrderError::ShippedOrderError(_) => ErrorConflict(e),
+            _ => ErrorInternalServerError(e)
+        })?;
+
+    // Stock of the cancelled order goes to orders waiting for it
+    fill_waiting_backorders(&pool, &renderer, **strategy, uid.0).await;
 
     Ok(HttpResponse::Ok().finish())
 }
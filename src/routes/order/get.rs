@@ -1,25 +1,28 @@
 use std::error::Error;
 use std::fmt::Debug;
 
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use serde::Deserialize;
 use thiserror::Error;
 
 use crate::auth::extractors::IsUser;
 use crate::db_interaction::get_order_with_items;
+use crate::pagination::{page_response, PaginationError, PageRequest};
 use crate::utils::{error_fmt_chain, get_pooled_connection, DbPool};
 
 // Struct representing query parameters for get order
 #[derive(Deserialize, Debug)]
 pub struct GetOrderQuery{
-    pub page: i64,
-    pub limit: i64
+    pub limit: Option<i64>,
+    pub cursor: Option<String>
 }
 
 // Error response associated with get order
 #[derive(Error)]
 pub enum GetOrderError{
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
     #[error("Failed due to internal server error")]
     UnexpectedError(#[from] anyhow::Error)
 }
@@ -32,21 +35,26 @@ impl Debug for GetOrderError { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) ->
 
 impl ResponseError for GetOrderError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::InternalServerError().body(format!("{}", self))
+        match self {
+            Self::PaginationError(e) => e.error_response(),
+            Self::UnexpectedError(_) => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
     }
 }
 
 #[tracing::instrument(
     "Getting list of orders",
-    skip(pool, uid)
+    skip(pool, uid, req)
 )]
 pub async fn get_order(
     pool: web::Data<DbPool>,
     query: web::Query<GetOrderQuery>,
-    uid: IsUser
+    uid: IsUser,
+    req: HttpRequest
 ) -> Result<HttpResponse, GetOrderError> {
     let user_id = uid.0;
     let is_admin = uid.1;
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
 
     let conn = get_pooled_connection(&pool)
                 .await
@@ -54,14 +62,13 @@ pub async fn get_order(
     
     let order = get_order_with_items(
         conn,
-        query.0.page,
-        query.0.limit,
+        page_request,
         user_id,
         is_admin
     )
     .await
     .context("Failed to get order with items model")?;
     
-    Ok(HttpResponse::Ok().json(order))
+    Ok(page_response(&req, &order))
 }
 
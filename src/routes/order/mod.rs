@@ -6,3 +6,9 @@ pub mod update;
 pub use update::update_order;
 pub mod delete;
 pub use delete::delete_order;
+pub mod refunds;
+pub use refunds::{get_refunds, post_refund};
+pub mod returns;
+pub use returns::{get_returns_queue, get_user_order_returns, post_return, post_return_refund, put_return_status};
+pub mod shipments;
+pub use shipments::{get_shipments, post_shipment};
@@ -6,21 +6,51 @@ use serde::Deserialize;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{auth::extractors::IsUser, db_interaction::{create_order_and_update_inventory, CreateOrderUpdateInventoryError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+use crate::{auth::extractors::IsUser, db_interaction::{create_order_and_update_inventory, CreateOrderUpdateInventoryError, OrderDetails}, domain::{allocation::{AllocationStrategy, Location}, coupon::CouponCode, address::Destination}, email_templates::EmailRenderer, notifications::{OrderEvent, OrderMailer}, startup::PaymentCurrency, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
 
-// struct representing order item to be ordered
+// struct representing order item (product variant) to be ordered
 #[derive(Deserialize, Debug)]
 pub struct OrderItem{
-    item_id: Uuid,
+    variant_id: Uuid,
     amount: i32
 }
 
+// struct representing location order is shipped to
+#[derive(Deserialize, Debug)]
+pub struct ShipTo{
+    latitude: f64,
+    longitude: f64
+}
+
+// struct representing address an order is taxed at and shipped to
+#[derive(Deserialize, Debug)]
+pub struct AddressJson{
+    country: String,
+    region: Option<String>
+}
+
+// Order body is either a list of order items or the list along with shipping location
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum OrderJson{
+    Items(Vec<OrderItem>),
+    WithShipping{
+        items: Vec<OrderItem>,
+        ship_to: Option<ShipTo>,
+        coupon_code: Option<String>,
+        address: Option<AddressJson>,
+        shipping_method_id: Option<Uuid>
+    }
+}
+
 #[derive(Error)]
 pub enum PostOrderError{
     #[error("Internal server error occured")]
     UnexpectedError(#[from] anyhow::Error),
     #[error("No stock available")]
-    StockError
+    StockError,
+    #[error("{0}")]
+    ValidationError(String)
 }
 
 impl Debug for PostOrderError {
@@ -34,27 +64,56 @@ impl ResponseError for PostOrderError{
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
         match self {
             Self::UnexpectedError(_) => HttpResponse::InternalServerError().body(format!("{}", self)),
-            Self::StockError => HttpResponse::BadRequest().body(format!("{}", self))
+            Self::StockError => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::ValidationError(_) => HttpResponse::BadRequest().body(format!("{}", self))
         }
     }
 }
 
 #[tracing::instrument(
     "Posting order",
-    skip(pool, uid)
+    skip(pool, strategy, renderer, currency, uid)
 )]
 pub async fn post_order(
     pool: web::Data<DbPool>,
-    order: web::Json<Vec<OrderItem>>,
+    strategy: web::Data<AllocationStrategy>,
+    renderer: web::Data<EmailRenderer>,
+    currency: web::Data<PaymentCurrency>,
+    order: web::Json<OrderJson>,
     uid: IsUser
 ) -> Result<HttpResponse, PostOrderError> {
     let user_id = uid.0;
 
-    let item_ids: Vec<Uuid> = order.iter()
-                    .map(|item| item.item_id)
+    // Plain list of items keeps getting back ordered variant ids, the object body gets price breakdown too
+    let (items, ship_to, coupon_code, address, shipping_method_id, with_pricing) = match order.into_inner() {
+        OrderJson::Items(items) => (items, None, None, None, None, false),
+        OrderJson::WithShipping { items, ship_to, coupon_code, address, shipping_method_id } => (items, ship_to, coupon_code, address, shipping_method_id, true)
+    };
+
+    if items.iter().any(|item| item.amount <= 0) {
+        return Err(PostOrderError::ValidationError("amount should be positive".to_string()));
+    }
+
+    let ship_to = ship_to
+                    .map(|location| Location::parse(location.latitude, location.longitude))
+                    .transpose()
+                    .map_err(PostOrderError::ValidationError)?;
+
+    let coupon_code = coupon_code
+                    .map(CouponCode::parse)
+                    .transpose()
+                    .map_err(PostOrderError::ValidationError)?;
+
+    let destination = address
+                    .map(|address| Destination::parse(address.country, address.region))
+                    .transpose()
+                    .map_err(PostOrderError::ValidationError)?;
+
+    let variant_ids: Vec<Uuid> = items.iter()
+                    .map(|item| item.variant_id)
                     .collect();
 
-    let amounts: Vec<i32> = order.iter()
+    let amounts: Vec<i32> = items.iter()
                     .map(|item| item.amount)
                     .collect();
     
@@ -62,15 +121,23 @@ pub async fn post_order(
                 .await
                 .context("Failed to get connection from pool from spawned task")?;
 
-    Ok(HttpResponse::Ok().json(
-        create_order_and_update_inventory(conn, item_ids, amounts, user_id)
+    let mailer = OrderMailer::new(&renderer, &currency);
+    let placed = create_order_and_update_inventory(conn, variant_ids, amounts, user_id, **strategy, OrderDetails { ship_to, coupon_code, destination, shipping_method_id }, move |notice| mailer.email(notice, OrderEvent::Placed))
                 .await
                 .map_err(|e|
                     match e {
                         CreateOrderUpdateInventoryError::ThreadpoolError(r) => PostOrderError::UnexpectedError(r.into()),
                         CreateOrderUpdateInventoryError::RunQueryError(r)=> PostOrderError::UnexpectedError(r.into()),
-                        CreateOrderUpdateInventoryError::NoStockError => PostOrderError::StockError
+                        CreateOrderUpdateInventoryError::NoStockError => PostOrderError::StockError,
+                        CreateOrderUpdateInventoryError::CouponRejected(r) => PostOrderError::ValidationError(r.to_string()),
+                        CreateOrderUpdateInventoryError::ShippingRejected(r) => PostOrderError::ValidationError(r.to_string()),
+                        CreateOrderUpdateInventoryError::EmailTemplateError(r) => PostOrderError::UnexpectedError(r.into())
                     }
-                )?
-    ))
+                )?;
+
+    if with_pricing {
+        Ok(HttpResponse::Ok().json(placed))
+    } else {
+        Ok(HttpResponse::Ok().json(placed.variant_ids))
+    }
 }
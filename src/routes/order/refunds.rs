@@ -0,0 +1,153 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{begin_refund, complete_refund, fail_refund, get_order_refunds, RefundError, RefundLineRequest, RefundWithLines}, domain::allocation::AllocationStrategy, email_templates::EmailRenderer, notifications::refund_issued_email, pagination::{page_response, PageRequest, PaginationError}, payment_provider::{PaymentProvider, PaymentProviderError}, routes::inventory::fill_waiting_backorders, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for refunding an order
+// Without lines everything not refunded yet is refunded
+#[derive(Deserialize, Debug)]
+pub struct RefundJson{
+    pub lines: Option<Vec<RefundLineRequest>>,
+    #[serde(default)]
+    pub restock: bool,
+    pub reason: Option<String>
+}
+
+// Struct representing query parameters for getting refunds of an order
+#[derive(Deserialize, Debug)]
+pub struct GetRefundsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with refund routes
+#[derive(Error)]
+pub enum RefundRouteError{
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to refund order")]
+    RefundError(#[from] RefundError),
+    #[error("Payment provider failed to refund order")]
+    PaymentProviderError(#[from] PaymentProviderError),
+    #[error("Quantity of refunded lines should be positive")]
+    InvalidQuantity,
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for RefundRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for RefundRouteError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::PaginationError(e) => e.error_response(),
+            Self::RefundError(e @ RefundError::NoOrderIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::RefundError(e @ (RefundError::NotPaid | RefundError::ReturnAlreadyRefunded)) => HttpResponse::Conflict().body(format!("{}", e)),
+            Self::RefundError(e @ (RefundError::NoOrderItemError(_) | RefundError::ExcessQuantityError(_) | RefundError::NothingToRefund)) => HttpResponse::BadRequest().body(format!("{}", e)),
+            Self::InvalidQuantity => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaymentProviderError(_) => HttpResponse::BadGateway().body(format!("{}", self)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Refunding order",
+    skip(pool, renderer, provider, strategy, admin)
+)]
+pub async fn post_refund(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    provider: web::Data<dyn PaymentProvider>,
+    strategy: web::Data<AllocationStrategy>,
+    order_id: web::Path<Uuid>,
+    json: web::Json<RefundJson>,
+    admin: IsAdmin
+) -> Result<HttpResponse, RefundRouteError> {
+    let json = json.into_inner();
+    if json.lines.iter().flatten().any(|line| line.quantity <= 0) {
+        return Err(RefundRouteError::InvalidQuantity);
+    }
+
+    let restock = json.restock;
+    let refund = issue_refund(&pool, &renderer, &provider, order_id.into_inner(), None, json, admin.0).await?;
+
+    // Restocked goods go to orders waiting for them
+    if restock {
+        fill_waiting_backorders(&pool, &renderer, **strategy, admin.0).await;
+    }
+
+    Ok(HttpResponse::Ok().json(refund))
+}
+
+// Record refund, have provider give the money back and send customer a receipt
+// Return being refunded is claimed by the refund until provider turns it down
+pub async fn issue_refund(
+    pool: &web::Data<DbPool>,
+    renderer: &EmailRenderer,
+    provider: &web::Data<dyn PaymentProvider>,
+    order_id: Uuid,
+    return_id: Option<Uuid>,
+    json: RefundJson,
+    actor_id: Uuid
+) -> Result<RefundWithLines, RefundRouteError> {
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let pending = begin_refund(conn, order_id, return_id, json.lines, json.restock, json.reason, actor_id).await?;
+    let refund_id = pending.refund.refund_id;
+
+    let provider_reference = match provider.refund(&pending.payment_reference, pending.refund.amount_cents).await {
+        Ok(provider_reference) => provider_reference,
+        Err(e) => {
+            let conn = get_pooled_connection(pool)
+                        .await
+                        .context("Failed to get connection from pool from within spawned task")?;
+
+            fail_refund(conn, refund_id).await?;
+            return Err(e.into());
+        }
+    };
+
+    let conn = get_pooled_connection(pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let renderer = renderer.clone();
+    let refund = complete_refund(conn, refund_id, provider_reference, move |receipt| refund_issued_email(&renderer, receipt)).await?;
+
+    Ok(refund)
+}
+
+#[tracing::instrument(
+    "Getting refunds of order",
+    skip(pool, req)
+)]
+pub async fn get_refunds(
+    pool: web::Data<DbPool>,
+    order_id: web::Path<Uuid>,
+    query: web::Query<GetRefundsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, RefundRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let refunds = get_order_refunds(conn, order_id.into_inner(), page_request).await?;
+
+    Ok(page_response(&req, &refunds))
+}
@@ -0,0 +1,204 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::{IsAdmin, IsUser}, db_interaction::{get_order_returns, get_refundable_return, get_return, get_returns, request_return, update_return_status, ReturnError, ReturnLineRequest, ReturnStatus}, domain::allocation::AllocationStrategy, email_templates::EmailRenderer, pagination::{page_response, PageRequest, PaginationError}, payment_provider::PaymentProvider, routes::{inventory::fill_waiting_backorders, order::refunds::{issue_refund, RefundJson, RefundRouteError}}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for requesting a return
+#[derive(Deserialize, Debug)]
+pub struct ReturnJson{
+    pub lines: Vec<ReturnLineRequest>,
+    pub note: Option<String>
+}
+
+// Struct representing json body for moving a return along
+#[derive(Deserialize, Debug)]
+pub struct ReturnStatusJson{
+    pub status: ReturnStatus
+}
+
+// Struct representing query parameters for getting returns queue
+#[derive(Deserialize, Debug)]
+pub struct GetReturnsQuery{
+    status: Option<ReturnStatus>,
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Struct representing query parameters for getting returns of an order
+#[derive(Deserialize, Debug)]
+pub struct GetOrderReturnsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with return routes
+#[derive(Error)]
+pub enum ReturnRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to process return")]
+    ReturnError(#[from] ReturnError),
+    #[error(transparent)]
+    RefundRouteError(#[from] RefundRouteError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for ReturnRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for ReturnRouteError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::RefundRouteError(e) => e.error_response(),
+            Self::ReturnError(e @ (ReturnError::NoOrderIdError(_) | ReturnError::NoReturnIdError(_))) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::ReturnError(e @ (ReturnError::NotDelivered | ReturnError::InvalidTransition(..) | ReturnError::NotReceived | ReturnError::AlreadyRefunded)) => HttpResponse::Conflict().body(format!("{}", e)),
+            Self::ReturnError(e @ (ReturnError::NoOrderItemError(_) | ReturnError::ExcessQuantityError(_))) => HttpResponse::BadRequest().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Requesting return of order",
+    skip(pool, uid)
+)]
+pub async fn post_return(
+    pool: web::Data<DbPool>,
+    order_id: web::Path<Uuid>,
+    json: web::Json<ReturnJson>,
+    uid: IsUser
+) -> Result<HttpResponse, ReturnRouteError> {
+    let json = json.into_inner();
+    if json.lines.is_empty() {
+        return Err(ReturnRouteError::InvalidInput("Return should have at least one line".to_string()));
+    }
+    if json.lines.iter().any(|line| line.quantity <= 0) {
+        return Err(ReturnRouteError::InvalidInput("Quantity of returned lines should be positive".to_string()));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let order_return = request_return(conn, order_id.into_inner(), uid.0, json.lines, json.note).await?;
+
+    Ok(HttpResponse::Ok().json(order_return))
+}
+
+#[tracing::instrument(
+    "Getting returns of own order",
+    skip(pool, req, uid)
+)]
+pub async fn get_user_order_returns(
+    pool: web::Data<DbPool>,
+    order_id: web::Path<Uuid>,
+    query: web::Query<GetOrderReturnsQuery>,
+    req: HttpRequest,
+    uid: IsUser
+) -> Result<HttpResponse, ReturnRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let order_returns = get_order_returns(conn, order_id.into_inner(), uid.0, page_request).await?;
+
+    Ok(page_response(&req, &order_returns))
+}
+
+#[tracing::instrument(
+    "Getting returns queue",
+    skip(pool, req)
+)]
+pub async fn get_returns_queue(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetReturnsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, ReturnRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let page = get_returns(conn, query.0.status, page_request).await?;
+
+    Ok(page_response(&req, &page))
+}
+
+#[tracing::instrument(
+    "Updating return status",
+    skip(pool, renderer, strategy, admin)
+)]
+pub async fn put_return_status(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    strategy: web::Data<AllocationStrategy>,
+    return_id: web::Path<Uuid>,
+    json: web::Json<ReturnStatusJson>,
+    admin: IsAdmin
+) -> Result<HttpResponse, ReturnRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let order_return = update_return_status(conn, return_id.into_inner(), json.status, admin.0).await?;
+
+    // Received goods are back in stock for orders waiting for them
+    if json.status == ReturnStatus::Received {
+        fill_waiting_backorders(&pool, &renderer, **strategy, admin.0).await;
+    }
+
+    Ok(HttpResponse::Ok().json(order_return))
+}
+
+#[tracing::instrument(
+    "Refunding received return",
+    skip(pool, renderer, provider, admin)
+)]
+pub async fn post_return_refund(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    provider: web::Data<dyn PaymentProvider>,
+    return_id: web::Path<Uuid>,
+    admin: IsAdmin
+) -> Result<HttpResponse, ReturnRouteError> {
+    let return_id = return_id.into_inner();
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let (order_id, lines) = get_refundable_return(conn, return_id).await?;
+
+    // Goods were already restocked when the return was received
+    issue_refund(&pool, &renderer, &provider, order_id, Some(return_id), RefundJson {
+        lines: Some(lines),
+        restock: false,
+        reason: Some(format!("Return {}", return_id))
+    }, admin.0).await?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let order_return = get_return(conn, return_id).await?;
+
+    Ok(HttpResponse::Ok().json(order_return))
+}
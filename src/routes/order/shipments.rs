@@ -0,0 +1,114 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{create_shipment, get_order_shipments, ShipmentError, ShipmentLineRequest}, email_templates::EmailRenderer, notifications::{OrderEvent, OrderMailer}, pagination::{page_response, PageRequest, PaginationError}, startup::PaymentCurrency, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for shipping lines of an order
+// Without lines everything ready to ship is shipped
+#[derive(Deserialize, Debug)]
+pub struct ShipmentJson{
+    pub carrier: String,
+    pub tracking_number: String,
+    pub lines: Option<Vec<ShipmentLineRequest>>
+}
+
+// Struct representing query parameters for getting shipments of an order
+#[derive(Deserialize, Debug)]
+pub struct GetShipmentsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+// Error response associated with shipment routes
+#[derive(Error)]
+pub enum ShipmentRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to ship order")]
+    ShipmentError(#[from] ShipmentError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for ShipmentRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for ShipmentRouteError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::ShipmentError(e @ ShipmentError::NoOrderIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::ShipmentError(e @ ShipmentError::NotShippable(_)) => HttpResponse::Conflict().body(format!("{}", e)),
+            Self::ShipmentError(e @ (ShipmentError::NoOrderItemError(_) | ShipmentError::ExcessQuantityError(_) | ShipmentError::NothingToShip)) => HttpResponse::BadRequest().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Shipping order",
+    skip(pool, renderer, currency, admin)
+)]
+pub async fn post_shipment(
+    pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    currency: web::Data<PaymentCurrency>,
+    order_id: web::Path<Uuid>,
+    json: web::Json<ShipmentJson>,
+    admin: IsAdmin
+) -> Result<HttpResponse, ShipmentRouteError> {
+    let json = json.into_inner();
+
+    let carrier = json.carrier.trim().to_string();
+    let tracking_number = json.tracking_number.trim().to_string();
+    if carrier.is_empty() || tracking_number.is_empty() {
+        return Err(ShipmentRouteError::InvalidInput("carrier and tracking_number can't be empty".to_string()));
+    }
+
+    if json.lines.iter().flatten().any(|line| line.quantity <= 0) {
+        return Err(ShipmentRouteError::InvalidInput("Quantity of shipped lines should be positive".to_string()));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let mailer = OrderMailer::new(&renderer, &currency);
+    let shipment = create_shipment(conn, order_id.into_inner(), carrier, tracking_number, json.lines, admin.0, move |notice| mailer.email(notice, OrderEvent::Shipped)).await?;
+
+    Ok(HttpResponse::Ok().json(shipment))
+}
+
+#[tracing::instrument(
+    "Getting shipments of order",
+    skip(pool, req)
+)]
+pub async fn get_shipments(
+    pool: web::Data<DbPool>,
+    order_id: web::Path<Uuid>,
+    query: web::Query<GetShipmentsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, ShipmentRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let shipments = get_order_shipments(conn, order_id.into_inner(), page_request).await?;
+
+    Ok(page_response(&req, &shipments))
+}
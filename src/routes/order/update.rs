@@ -6,7 +6,7 @@ use serde::Deserialize;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{auth::extractors::IsAdmin, db_interaction::{update_order_status, UpdateOrderStatusError}, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+use crate::{auth::extractors::IsAdmin, db_interaction::{update_order_status, UpdateOrderStatusError}, email_templates::EmailRenderer, notifications::{OrderEvent, OrderMailer}, startup::PaymentCurrency, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
 
 // Struct representing put order status form
 #[derive(Deserialize, Debug)]
@@ -16,7 +16,7 @@ pub struct UpdateOrderStatusForm{
 }
 
 // Enum representing updated order status
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum OrderStatus{
     Pending,
@@ -30,7 +30,11 @@ pub enum UpdateOrderError {
     #[error("Failed due to internal server error")]
     UnexpectedError(#[from] anyhow::Error),
     #[error("Incorrect order id given: {0}")]
-    IncorrectOrderId(Uuid)
+    IncorrectOrderId(Uuid),
+    #[error("Only delivered status can be set, other statuses follow shipments")]
+    ManualStatus,
+    #[error("Order {0} hasn't been shipped in full")]
+    NotShipped(Uuid)
 }
 
 impl Debug for UpdateOrderError { fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -43,7 +47,8 @@ impl ResponseError for UpdateOrderError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
         let mut req_builder = match self { 
             Self::UnexpectedError(_) => HttpResponse::InternalServerError(),
-            Self::IncorrectOrderId(_) => HttpResponse::BadRequest()
+            Self::IncorrectOrderId(_) => HttpResponse::BadRequest(),
+            Self::ManualStatus | Self::NotShipped(_) => HttpResponse::Conflict()
         };
 
         req_builder.body(format!("{}", self))
@@ -52,10 +57,12 @@ impl ResponseError for UpdateOrderError {
 
 #[tracing::instrument(
     "Updating order status",
-    skip(pool)
+    skip(pool, renderer, currency)
 )]
 pub async fn update_order(
     pool: web::Data<DbPool>,
+    renderer: web::Data<EmailRenderer>,
+    currency: web::Data<PaymentCurrency>,
     form: web::Form<UpdateOrderStatusForm>,
     _: IsAdmin
 ) -> Result<HttpResponse, UpdateOrderError>{
@@ -63,17 +70,23 @@ pub async fn update_order(
                     .await
                     .context("Failed to get connection from pool from within spawned task")?;
 
+    let mailer = OrderMailer::new(&renderer, &currency);
+
     update_order_status(
         conn,
         form.0.status,
-        form.0.order_id
+        form.0.order_id,
+        move |notice| mailer.email(notice, OrderEvent::Delivered)
     )
     .await
     .map_err(|e| {
         match e {
             UpdateOrderStatusError::ThreadpoolError(_) => UpdateOrderError::UnexpectedError(e.into()),
             UpdateOrderStatusError::RunQueryError(_) => UpdateOrderError::UnexpectedError(e.into()),
-            UpdateOrderStatusError::NoOrderIdError(r) => UpdateOrderError::IncorrectOrderId(r)
+            UpdateOrderStatusError::EmailTemplateError(_) => UpdateOrderError::UnexpectedError(e.into()),
+            UpdateOrderStatusError::NoOrderIdError(r) => UpdateOrderError::IncorrectOrderId(r),
+            UpdateOrderStatusError::ManualStatusError => UpdateOrderError::ManualStatus,
+            UpdateOrderStatusError::NotShippedError(r) => UpdateOrderError::NotShipped(r)
         }
     })?;
 
@@ -0,0 +1,2 @@
+pub mod webhook;
+pub use webhook::*;
@@ -0,0 +1,100 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{db_interaction::{update_payment_status, PaymentError, PaymentStatus}, payment_provider::PaymentProvider, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Header carrying provider's signature of the raw webhook body
+pub const SIGNATURE_HEADER: &str = "X-Payment-Signature";
+
+// Events provider sends about a payment
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum PaymentEventType{
+    #[serde(rename = "payment.authorized")]
+    Authorized,
+    #[serde(rename = "payment.captured")]
+    Captured,
+    #[serde(rename = "payment.failed")]
+    Failed
+}
+
+// Struct representing webhook body sent by payment provider
+#[derive(Deserialize, Debug)]
+pub struct PaymentWebhookJson{
+    #[serde(rename = "type")]
+    pub event_type: PaymentEventType,
+    pub provider_reference: String,
+    pub failure_reason: Option<String>
+}
+
+// Error response associated with payment webhook
+#[derive(Error)]
+pub enum PaymentWebhookError{
+    #[error("Invalid webhook signature")]
+    InvalidSignature,
+    #[error("Invalid webhook body")]
+    InvalidBody(#[source] serde_json::Error),
+    #[error("Failed to update payment")]
+    PaymentError(#[from] PaymentError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PaymentWebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PaymentWebhookError{
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidSignature => HttpResponse::Unauthorized().body(format!("{}", self)),
+            Self::InvalidBody(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaymentError(e @ PaymentError::NoPaymentError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Receiving payment provider webhook",
+    skip_all
+)]
+pub async fn payment_webhook(
+    pool: web::Data<DbPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    req: HttpRequest,
+    body: web::Bytes
+) -> Result<HttpResponse, PaymentWebhookError> {
+    // Signature covers the exact bytes sent so body is only parsed after checking it
+    let signature = req.headers()
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(PaymentWebhookError::InvalidSignature)?;
+
+    if !provider.verify_webhook(&body, signature) {
+        return Err(PaymentWebhookError::InvalidSignature);
+    }
+
+    let event: PaymentWebhookJson = serde_json::from_slice(&body)
+        .map_err(PaymentWebhookError::InvalidBody)?;
+
+    let status = match event.event_type {
+        PaymentEventType::Authorized => PaymentStatus::Authorized,
+        PaymentEventType::Captured => PaymentStatus::Captured,
+        PaymentEventType::Failed => PaymentStatus::Failed
+    };
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let payment = update_payment_status(conn, event.provider_reference, status, event.failure_reason).await?;
+
+    Ok(HttpResponse::Ok().json(payment))
+}
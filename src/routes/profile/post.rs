@@ -5,7 +5,7 @@ use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
 use serde::Deserialize;
 
-use crate::{auth::extractors::IsUser, db_interaction::{post_user_profile_info, PostUserProfileInfoError}, domain::{phone_number::PhoneNumberDomain, user_email::UserEmail}, models::UserProfileInfo, utils::{error_fmt_chain, DbPool}};
+use crate::{auth::extractors::IsUser, db_interaction::{post_user_profile_info, PostUserProfileInfoError}, domain::{phone_number::PhoneNumberDomain, user_email::UserEmail}, models::UserProfileInfo, startup::DefaultPhoneRegion, utils::{error_fmt_chain, DbPool}};
 use crate::db_interaction::get_user_profile_info;
 
 // Struct representing posting profile
@@ -48,6 +48,7 @@ impl ResponseError for PostProfileError {
 pub async fn post_profile(
     pool: web::Data<DbPool>,
     form: web::Form<ProfileForm>,
+    default_region: web::Data<DefaultPhoneRegion>,
     uid: IsUser
 ) -> Result<HttpResponse, PostProfileError>{
     let user_id = uid.0;
@@ -55,8 +56,8 @@ pub async fn post_profile(
     let conn = pool.get()
                 .context("Failed to get connection from pool from within spawned task")?;
 
-    let info = get_user_profile_info(conn, user_id.clone()).await?;
-    let new_info = substitute_old_info_with_new(info, form.0)
+    let info = get_user_profile_info(conn, user_id).await?;
+    let new_info = substitute_old_info_with_new(info, form.0, &default_region)
                         .map_err(PostProfileError::InvalidEmailOrPhoneNumber)?;
 
     let conn = pool.get()
@@ -79,7 +80,8 @@ pub async fn post_profile(
 )]
 pub fn substitute_old_info_with_new(
     mut current_info: UserProfileInfo,
-    new_info: ProfileForm
+    new_info: ProfileForm,
+    default_region: &DefaultPhoneRegion
 ) -> Result<UserProfileInfo, anyhow::Error>{
 
     if let Some(email) = new_info.email{
@@ -94,16 +96,19 @@ pub fn substitute_old_info_with_new(
         current_info.name = name.clone()
     }
 
-    current_info.phone_number = match new_info.phone_number{
+    let phone_number = match new_info.phone_number{
         Some(number) => {
-            Some(PhoneNumberDomain::parse(number)
+            Some(PhoneNumberDomain::parse(number, default_region.0)
                     .map_err(|e|{
                         anyhow::anyhow!(e)
-                    })?.inner())
+                    })?)
         },
         None => None
     };
 
+    current_info.phone_number = phone_number.as_ref().map(|number| number.inner());
+    current_info.phone_country = phone_number.and_then(|number| number.country());
+
     current_info.address = new_info.address;
     
     Ok(current_info)
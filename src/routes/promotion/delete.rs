@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::delete_promotion, routes::promotion::PromotionRouteError, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Deleting promotion",
+    skip(pool)
+)]
+pub async fn delete_promotion_by_id(
+    pool: web::Data<DbPool>,
+    promotion_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, PromotionRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    delete_promotion(conn, promotion_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
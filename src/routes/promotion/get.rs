@@ -0,0 +1,33 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::get_promotions, pagination::{page_response, PageRequest}, routes::promotion::PromotionRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting promotions
+#[derive(Deserialize, Debug)]
+pub struct GetPromotionsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Getting promotions",
+    skip(pool, req)
+)]
+pub async fn get_promotion_list(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetPromotionsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, PromotionRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let page = get_promotions(conn, page_request).await?;
+
+    Ok(page_response(&req, &page))
+}
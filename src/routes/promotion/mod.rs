@@ -0,0 +1,6 @@
+pub mod get;
+pub use get::*;
+pub mod post;
+pub use post::*;
+pub mod delete;
+pub use delete::*;
@@ -0,0 +1,123 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_promotion, update_promotion, PromotionError, PromotionSettings}, domain::promotion::PromotionRule, pagination::PaginationError, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for creating or replacing a promotion
+#[derive(Deserialize, Debug)]
+pub struct PromotionJson{
+    name: String,
+    // Lower priorities are evaluated first
+    priority: Option<i32>,
+    active: Option<bool>,
+    starts_at: Option<DateTime<Utc>>,
+    ends_at: Option<DateTime<Utc>>,
+    rule: PromotionRule
+}
+
+impl TryFrom<PromotionJson> for PromotionSettings {
+    type Error = String;
+
+    fn try_from(json: PromotionJson) -> Result<Self, Self::Error> {
+        let name = json.name.trim().to_string();
+        if name.is_empty() {
+            return Err("promotion name can't be empty".to_string());
+        }
+
+        json.rule.validate()?;
+
+        if let (Some(starts_at), Some(ends_at)) = (json.starts_at, json.ends_at) {
+            if starts_at >= ends_at {
+                return Err("starts_at should be before ends_at".to_string());
+            }
+        }
+
+        Ok(PromotionSettings {
+            name,
+            priority: json.priority.unwrap_or(0),
+            active: json.active.unwrap_or(true),
+            starts_at: json.starts_at,
+            ends_at: json.ends_at,
+            rule: json.rule
+        })
+    }
+}
+
+// Error response associated with promotion routes
+#[derive(Error)]
+pub enum PromotionRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to save promotion")]
+    PromotionError(#[from] PromotionError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PromotionRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PromotionRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::PromotionError(e @ PromotionError::NoPromotionIdError(_)) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::PromotionError(e @ PromotionError::NoItemIdError(_)) => HttpResponse::BadRequest().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Posting promotion",
+    skip(pool)
+)]
+pub async fn post_promotion(
+    pool: web::Data<DbPool>,
+    json: web::Json<PromotionJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, PromotionRouteError> {
+    let settings = PromotionSettings::try_from(json.into_inner()).map_err(PromotionRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let promotion = insert_promotion(conn, settings).await?;
+
+    Ok(HttpResponse::Ok().json(promotion))
+}
+
+#[tracing::instrument(
+    "Replacing promotion",
+    skip(pool)
+)]
+pub async fn put_promotion(
+    pool: web::Data<DbPool>,
+    promotion_id: web::Path<Uuid>,
+    json: web::Json<PromotionJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, PromotionRouteError> {
+    let settings = PromotionSettings::try_from(json.into_inner()).map_err(PromotionRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let promotion = update_promotion(conn, promotion_id.into_inner(), settings).await?;
+
+    Ok(HttpResponse::Ok().json(promotion))
+}
@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::delete_shipping_method, routes::shipping::ShippingRouteError, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Deleting shipping method",
+    skip(pool)
+)]
+pub async fn delete_shipping_method_by_id(
+    pool: web::Data<DbPool>,
+    shipping_method_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, ShippingRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    delete_shipping_method(conn, shipping_method_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
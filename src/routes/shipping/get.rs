@@ -0,0 +1,33 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::get_shipping_methods, pagination::{page_response, PageRequest}, routes::shipping::ShippingRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting shipping methods
+#[derive(Deserialize, Debug)]
+pub struct GetShippingMethodsQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Getting shipping methods",
+    skip(pool, req)
+)]
+pub async fn get_shipping_method_list(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetShippingMethodsQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, ShippingRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let methods = get_shipping_methods(conn, page_request).await?;
+
+    Ok(page_response(&req, &methods))
+}
@@ -0,0 +1,10 @@
+pub mod get;
+pub use get::*;
+pub mod post;
+pub use post::*;
+pub mod delete;
+pub use delete::*;
+pub mod weight;
+pub use weight::*;
+pub mod quote;
+pub use quote::*;
@@ -0,0 +1,125 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_shipping_method, update_shipping_method, ShippingError, ShippingMethodSettings}, domain::{address::Destination, shipping::ShippingRate}, pagination::PaginationError, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing a country, or a region within it, a method ships to
+#[derive(Deserialize, Debug)]
+pub struct ZoneJson{
+    pub country: String,
+    pub region: Option<String>
+}
+
+// Struct representing json body for creating or replacing a shipping method
+#[derive(Deserialize, Debug)]
+pub struct ShippingMethodJson{
+    name: String,
+    active: Option<bool>,
+    rate: ShippingRate,
+    // Without zones the method ships everywhere
+    zones: Option<Vec<ZoneJson>>
+}
+
+impl TryFrom<ShippingMethodJson> for ShippingMethodSettings {
+    type Error = String;
+
+    fn try_from(json: ShippingMethodJson) -> Result<Self, Self::Error> {
+        let name = json.name.trim().to_string();
+        if name.is_empty() {
+            return Err("shipping method name can't be empty".to_string());
+        }
+
+        json.rate.validate()?;
+
+        let zones = json.zones
+            .unwrap_or_default()
+            .into_iter()
+            .map(|zone| Destination::parse(zone.country, zone.region))
+            .collect::<Result<Vec<Destination>, String>>()?;
+
+        Ok(ShippingMethodSettings {
+            name,
+            active: json.active.unwrap_or(true),
+            rate: json.rate,
+            zones
+        })
+    }
+}
+
+// Error response associated with shipping routes
+#[derive(Error)]
+pub enum ShippingRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to process shipping request")]
+    ShippingError(#[from] ShippingError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for ShippingRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for ShippingRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::ShippingError(e @ (ShippingError::NoShippingMethodIdError(_) | ShippingError::NoItemIdError(_))) => HttpResponse::NotFound().body(format!("{}", e)),
+            Self::ShippingError(e @ ShippingError::NoVariantIdError(_)) => HttpResponse::BadRequest().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Creating shipping method",
+    skip(pool)
+)]
+pub async fn post_shipping_method(
+    pool: web::Data<DbPool>,
+    json: web::Json<ShippingMethodJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, ShippingRouteError> {
+    let settings = ShippingMethodSettings::try_from(json.into_inner()).map_err(ShippingRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let method = insert_shipping_method(conn, settings).await?;
+
+    Ok(HttpResponse::Ok().json(method))
+}
+
+#[tracing::instrument(
+    "Replacing shipping method",
+    skip(pool)
+)]
+pub async fn put_shipping_method(
+    pool: web::Data<DbPool>,
+    shipping_method_id: web::Path<Uuid>,
+    json: web::Json<ShippingMethodJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, ShippingRouteError> {
+    let settings = ShippingMethodSettings::try_from(json.into_inner()).map_err(ShippingRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let method = update_shipping_method(conn, shipping_method_id.into_inner(), settings).await?;
+
+    Ok(HttpResponse::Ok().json(method))
+}
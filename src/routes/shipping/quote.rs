@@ -0,0 +1,50 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsUser, db_interaction::quote_shipping, domain::address::Destination, routes::shipping::{ShippingRouteError, ZoneJson}, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing a cart line to be quoted for
+#[derive(Deserialize, Debug)]
+pub struct QuoteItem{
+    variant_id: Uuid,
+    amount: i32
+}
+
+// Struct representing json body for quoting shipping of a cart to an address
+#[derive(Deserialize, Debug)]
+pub struct ShippingQuoteJson{
+    items: Vec<QuoteItem>,
+    address: ZoneJson
+}
+
+#[tracing::instrument(
+    "Quoting shipping for cart",
+    skip(pool, _uid)
+)]
+pub async fn post_shipping_quote(
+    pool: web::Data<DbPool>,
+    json: web::Json<ShippingQuoteJson>,
+    _uid: IsUser
+) -> Result<HttpResponse, ShippingRouteError> {
+    let json = json.into_inner();
+
+    if json.items.is_empty() || json.items.iter().any(|item| item.amount <= 0) {
+        return Err(ShippingRouteError::InvalidInput("items should be non empty with positive amounts".to_string()));
+    }
+
+    let destination = Destination::parse(json.address.country, json.address.region).map_err(ShippingRouteError::InvalidInput)?;
+
+    let quantities: Vec<(Uuid, i32)> = json.items.into_iter()
+        .map(|item| (item.variant_id, item.amount))
+        .collect();
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let quotes = quote_shipping(conn, quantities, destination).await?;
+
+    Ok(HttpResponse::Ok().json(quotes))
+}
@@ -0,0 +1,36 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::set_item_weight, routes::shipping::ShippingRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing json body for setting shipping weight of an item
+#[derive(Deserialize, Debug)]
+pub struct WeightJson{
+    weight_grams: i32
+}
+
+#[tracing::instrument(
+    "Setting weight of inventory item",
+    skip(pool)
+)]
+pub async fn put_item_weight(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<WeightJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, ShippingRouteError> {
+    let weight_grams = json.into_inner().weight_grams;
+    if weight_grams < 0 {
+        return Err(ShippingRouteError::InvalidInput("weight_grams can't be negative".to_string()));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_item_weight(conn, item_id.into_inner(), weight_grams).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
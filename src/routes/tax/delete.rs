@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::delete_tax_rate, routes::tax::TaxRouteError, utils::{get_pooled_connection, DbPool}};
+
+#[tracing::instrument(
+    "Deleting tax rate",
+    skip(pool)
+)]
+pub async fn delete_tax_rate_by_id(
+    pool: web::Data<DbPool>,
+    tax_rate_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, TaxRouteError> {
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    delete_tax_rate(conn, tax_rate_id.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
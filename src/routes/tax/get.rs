@@ -0,0 +1,33 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::get_tax_rates, pagination::{page_response, PageRequest}, routes::tax::TaxRouteError, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting tax rates
+#[derive(Deserialize, Debug)]
+pub struct GetTaxRatesQuery{
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Getting tax rates",
+    skip(pool, req)
+)]
+pub async fn get_tax_rate_list(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetTaxRatesQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, TaxRouteError> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let rates = get_tax_rates(conn, page_request).await?;
+
+    Ok(page_response(&req, &rates))
+}
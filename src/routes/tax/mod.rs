@@ -0,0 +1,6 @@
+pub mod get;
+pub use get::*;
+pub mod put;
+pub use put::*;
+pub mod delete;
+pub use delete::*;
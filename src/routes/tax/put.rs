@@ -0,0 +1,105 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{set_item_tax_class, set_tax_rate, TaxError}, domain::{address::Destination, tax::{TaxClass, MAX_RATE_BASIS_POINTS}}, pagination::PaginationError, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for setting tax rate of a class at a destination
+// Without region the rate covers whole country
+#[derive(Deserialize, Debug)]
+pub struct TaxRateJson{
+    country: String,
+    region: Option<String>,
+    tax_class: String,
+    // Rate in basis points, 1825 is 18.25%
+    rate_basis_points: i32
+}
+
+// Struct representing json body for setting tax class of an item
+#[derive(Deserialize, Debug)]
+pub struct TaxClassJson{
+    tax_class: String
+}
+
+// Error response associated with tax routes
+#[derive(Error)]
+pub enum TaxRouteError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    PaginationError(#[from] PaginationError),
+    #[error("Failed to update tax settings")]
+    TaxError(#[from] TaxError),
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for TaxRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for TaxRouteError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::InvalidInput(_) => HttpResponse::BadRequest().body(format!("{}", self)),
+            Self::PaginationError(e) => e.error_response(),
+            Self::TaxError(e @ (TaxError::NoTaxRateIdError(_) | TaxError::NoItemIdError(_))) => HttpResponse::NotFound().body(format!("{}", e)),
+            _ => HttpResponse::InternalServerError().body(format!("{}", self))
+        }
+    }
+}
+
+#[tracing::instrument(
+    "Setting tax rate",
+    skip(pool)
+)]
+pub async fn put_tax_rate(
+    pool: web::Data<DbPool>,
+    json: web::Json<TaxRateJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, TaxRouteError> {
+    let json = json.into_inner();
+
+    let destination = Destination::parse(json.country, json.region).map_err(TaxRouteError::InvalidInput)?;
+    let tax_class = TaxClass::parse(json.tax_class).map_err(TaxRouteError::InvalidInput)?;
+
+    if !(0..=MAX_RATE_BASIS_POINTS).contains(&json.rate_basis_points) {
+        return Err(TaxRouteError::InvalidInput(format!("rate_basis_points should be between 0 and {}", MAX_RATE_BASIS_POINTS)));
+    }
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let rate = set_tax_rate(conn, destination, tax_class, json.rate_basis_points).await?;
+
+    Ok(HttpResponse::Ok().json(rate))
+}
+
+#[tracing::instrument(
+    "Setting tax class of inventory item",
+    skip(pool)
+)]
+pub async fn put_item_tax_class(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    json: web::Json<TaxClassJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, TaxRouteError> {
+    let tax_class = TaxClass::parse(json.into_inner().tax_class).map_err(TaxRouteError::InvalidInput)?;
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    set_item_tax_class(conn, item_id.into_inner(), tax_class).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,60 @@
+use actix_web::{error::{ErrorInternalServerError, ErrorNotFound}, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{get_item_warehouse_stock, get_warehouses, WarehouseError}, pagination::{page_response, PageRequest}, utils::{get_pooled_connection, DbPool}};
+
+// Struct representing query parameters for getting warehouses
+#[derive(Deserialize, Debug)]
+pub struct GetWarehousesQuery {
+    limit: Option<i64>,
+    cursor: Option<String>
+}
+
+#[tracing::instrument(
+    "Get warehouses",
+    skip(pool, req)
+)]
+pub async fn get_warehouse(
+    pool: web::Data<DbPool>,
+    query: web::Query<GetWarehousesQuery>,
+    req: HttpRequest,
+    _: IsAdmin
+) -> Result<HttpResponse, actix_web::Error> {
+    let page_request = PageRequest::parse(query.0.limit, query.0.cursor.as_deref())?;
+
+    let conn = get_pooled_connection(&pool)
+                    .await
+                    .map_err(|_| ErrorInternalServerError(anyhow::anyhow!("Failed due to internal server error")))?;
+
+    let warehouses = get_warehouses(conn, page_request)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(page_response(&req, &warehouses))
+}
+
+#[tracing::instrument(
+    "Get stock of item per warehouse",
+    skip(pool)
+)]
+pub async fn get_warehouse_stock(
+    pool: web::Data<DbPool>,
+    item_id: web::Path<Uuid>,
+    _: IsAdmin
+) -> Result<HttpResponse, actix_web::Error> {
+    let conn = get_pooled_connection(&pool)
+                    .await
+                    .map_err(|_| ErrorInternalServerError(anyhow::anyhow!("Failed due to internal server error")))?;
+
+    let levels = get_item_warehouse_stock(conn, item_id.into_inner())
+        .await
+        .map_err(|e| {
+            match e {
+                WarehouseError::NoItemIdError(_) => ErrorNotFound(e),
+                _ => ErrorInternalServerError(e)
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(levels))
+}
@@ -0,0 +1,4 @@
+pub mod get;
+pub use get::*;
+pub mod post;
+pub use post::*;
@@ -0,0 +1,117 @@
+use std::{error::Error, fmt::Debug};
+
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{auth::extractors::IsAdmin, db_interaction::{insert_warehouse, WarehouseError}, domain::allocation::Location, models::Warehouse, utils::{error_fmt_chain, get_pooled_connection, DbPool}};
+
+// Struct representing json body for creating a warehouse
+#[derive(Deserialize, Debug)]
+pub struct WarehouseJson{
+    code: String,
+    name: String,
+    priority: Option<i32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>
+}
+
+// Error response associated with posting warehouse
+#[derive(Error)]
+pub enum PostWarehouseError{
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Warehouse with same code already exists")]
+    NotUnique,
+    #[error("Failed due to internal server error")]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl Debug for PostWarehouseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+        error_fmt_chain(f, &self.source())
+    }
+}
+
+impl ResponseError for PostWarehouseError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        let mut req_builder = match self {
+            Self::UnexpectedError(_) => HttpResponse::InternalServerError(),
+            _ => HttpResponse::BadRequest()
+        };
+
+        req_builder.body(format!("{}", self))
+    }
+}
+
+// Warehouse codes are short uppercase identifiers printed on pick lists
+fn parse_code(code: &str) -> Result<String, String> {
+    let code = code.trim();
+
+    if code.is_empty() || code.len() > 32 {
+        return Err("code should be between 1 and 32 characters".to_string());
+    }
+
+    if !code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        return Err(format!("{} is not a valid warehouse code", code));
+    }
+
+    Ok(code.to_string())
+}
+
+#[tracing::instrument(
+    "Posting warehouse",
+    skip(pool)
+)]
+pub async fn post_warehouse(
+    pool: web::Data<DbPool>,
+    json: web::Json<WarehouseJson>,
+    _: IsAdmin
+) -> Result<HttpResponse, PostWarehouseError> {
+    let json = json.into_inner();
+
+    let code = parse_code(&json.code).map_err(PostWarehouseError::InvalidInput)?;
+
+    let name = json.name.trim().to_string();
+    if name.is_empty() {
+        return Err(PostWarehouseError::InvalidInput("name can't be empty".to_string()));
+    }
+
+    let location = match (json.latitude, json.longitude) {
+        (Some(latitude), Some(longitude)) => Some(
+            Location::parse(latitude, longitude).map_err(PostWarehouseError::InvalidInput)?
+        ),
+        (None, None) => None,
+        _ => return Err(PostWarehouseError::InvalidInput("latitude and longitude should be given together".to_string()))
+    };
+
+    let warehouse = Warehouse{
+        warehouse_id: Uuid::new_v4(),
+        code,
+        name,
+        priority: json.priority.unwrap_or(0),
+        latitude: location.map(|location| location.latitude),
+        longitude: location.map(|location| location.longitude),
+        is_default: false,
+        created_at: Utc::now()
+    };
+
+    let conn = get_pooled_connection(&pool)
+                .await
+                .context("Failed to get connection from pool from within spawned task")?;
+
+    let warehouse = insert_warehouse(conn, warehouse)
+        .await
+        .map_err(|e| {
+            match e {
+                WarehouseError::NotUnique => PostWarehouseError::NotUnique,
+                _ => PostWarehouseError::UnexpectedError(e.into())
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(warehouse))
+}
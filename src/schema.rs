@@ -1,6 +1,33 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    back_in_stock_subscriptions (subscription_id) {
+        subscription_id -> Uuid,
+        item_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    categories (category_id) {
+        category_id -> Uuid,
+        parent_id -> Nullable<Uuid>,
+        name -> Text,
+        slug -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     confirmation (confirmation_id) {
         confirmation_id -> Uuid,
         user_id -> Nullable<Uuid>,
@@ -8,55 +35,606 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    coupon_categories (coupon_id, category_id) {
+        coupon_id -> Uuid,
+        category_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    coupon_items (coupon_id, item_id) {
+        coupon_id -> Uuid,
+        item_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    coupon_redemptions (redemption_id) {
+        redemption_id -> Uuid,
+        coupon_id -> Uuid,
+        order_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        discount_cents -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    coupons (coupon_id) {
+        coupon_id -> Uuid,
+        code -> Text,
+        discount_type -> Text,
+        discount_value -> Int8,
+        min_order_cents -> Nullable<Int8>,
+        usage_limit -> Nullable<Int4>,
+        per_user_limit -> Nullable<Int4>,
+        starts_at -> Nullable<Timestamptz>,
+        ends_at -> Nullable<Timestamptz>,
+        active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    email_outbox (email_id) {
+        email_id -> Uuid,
+        recipient -> Text,
+        subject -> Text,
+        html_body -> Text,
+        text_body -> Text,
+        status -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        sent_at -> Nullable<Timestamptz>,
+        category -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    email_preferences (user_id) {
+        user_id -> Uuid,
+        order_updates -> Bool,
+        promotions -> Bool,
+        back_in_stock -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    email_suppressions (email) {
+        email -> Text,
+        reason -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     inventory (item_id) {
         item_id -> Uuid,
         name -> Text,
         amount -> Nullable<Int4>,
         price -> Nullable<Float8>,
+        sku -> Text,
+        slug -> Text,
+        description -> Nullable<Text>,
+        brand -> Nullable<Text>,
+        created_at -> Timestamptz,
+        search_vector -> Tsvector,
+        reorder_threshold -> Nullable<Int4>,
+        low_stock_alerted_at -> Nullable<Timestamptz>,
+        backorder_policy -> Text,
+        backorder_limit -> Nullable<Int4>,
+        tax_class -> Text,
+        weight_grams -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    inventory_categories (item_id, category_id) {
+        item_id -> Uuid,
+        category_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    item_attributes (item_id, key) {
+        item_id -> Uuid,
+        key -> Text,
+        value -> Text,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    order_item_allocations (allocation_id) {
+        allocation_id -> Uuid,
+        order_item_id -> Uuid,
+        warehouse_id -> Uuid,
+        quantity -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     order_items (order_item_id) {
         order_item_id -> Uuid,
         order_id -> Uuid,
         item_id -> Uuid,
         quantity -> Int4,
+        variant_id -> Uuid,
+        backordered_quantity -> Int4,
+        unit_price_cents -> Int8,
+        refunded_quantity -> Int4,
+        discount_cents -> Int8,
+        tax_rate_basis_points -> Int4,
+        tax_cents -> Int8,
+        shipped_quantity -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    order_promotions (order_promotion_id) {
+        order_promotion_id -> Uuid,
+        order_id -> Uuid,
+        promotion_id -> Nullable<Uuid>,
+        name -> Text,
+        discount_cents -> Int8,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     orders (order_id) {
         order_id -> Uuid,
         user_id -> Nullable<Uuid>,
-        order_date -> Nullable<Timestamptz>,
+        order_date -> Timestamptz,
         status -> Text,
+        paid_at -> Nullable<Timestamptz>,
+        coupon_id -> Nullable<Uuid>,
+        discount_cents -> Int8,
+        promotion_discount_cents -> Int8,
+        ship_country -> Nullable<Text>,
+        ship_region -> Nullable<Text>,
+        tax_cents -> Int8,
+        shipping_method_id -> Nullable<Uuid>,
+        shipping_method_name -> Nullable<Text>,
+        shipping_cents -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    payments (payment_id) {
+        payment_id -> Uuid,
+        order_id -> Uuid,
+        provider -> Text,
+        provider_reference -> Text,
+        amount_cents -> Int8,
+        currency -> Text,
+        status -> Text,
+        failure_reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        captured_at -> Nullable<Timestamptz>,
+        refunded_cents -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    product_variants (variant_id) {
+        variant_id -> Uuid,
+        item_id -> Uuid,
+        sku -> Text,
+        name -> Text,
+        price -> Nullable<Float8>,
+        amount -> Int4,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    promotion_items (promotion_id, item_id) {
+        promotion_id -> Uuid,
+        item_id -> Uuid,
+        quantity -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    promotion_tiers (promotion_id, min_quantity) {
+        promotion_id -> Uuid,
+        min_quantity -> Int4,
+        percent_off -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    promotions (promotion_id) {
+        promotion_id -> Uuid,
+        name -> Text,
+        kind -> Text,
+        priority -> Int4,
+        active -> Bool,
+        starts_at -> Nullable<Timestamptz>,
+        ends_at -> Nullable<Timestamptz>,
+        buy_quantity -> Nullable<Int4>,
+        get_quantity -> Nullable<Int4>,
+        percent_off -> Nullable<Int4>,
+        bundle_price_cents -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    refund_lines (refund_line_id) {
+        refund_line_id -> Uuid,
+        refund_id -> Uuid,
+        order_item_id -> Uuid,
+        quantity -> Int4,
+        unpicked_quantity -> Int4,
+        amount_cents -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    refunds (refund_id) {
+        refund_id -> Uuid,
+        order_id -> Uuid,
+        payment_id -> Uuid,
+        status -> Text,
+        amount_cents -> Int8,
+        restock -> Bool,
+        reason -> Nullable<Text>,
+        provider_reference -> Nullable<Text>,
+        actor_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    return_lines (return_line_id) {
+        return_line_id -> Uuid,
+        return_id -> Uuid,
+        order_item_id -> Uuid,
+        quantity -> Int4,
+        reason -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    returns (return_id) {
+        return_id -> Uuid,
+        order_id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        status -> Text,
+        note -> Nullable<Text>,
+        refund_id -> Nullable<Uuid>,
+        actor_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    shipment_lines (shipment_line_id) {
+        shipment_line_id -> Uuid,
+        shipment_id -> Uuid,
+        order_item_id -> Uuid,
+        quantity -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    shipments (shipment_id) {
+        shipment_id -> Uuid,
+        order_id -> Uuid,
+        carrier -> Text,
+        tracking_number -> Text,
+        actor_id -> Nullable<Uuid>,
+        shipped_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    shipping_method_zones (zone_id) {
+        zone_id -> Uuid,
+        shipping_method_id -> Uuid,
+        country -> Text,
+        region -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    shipping_methods (shipping_method_id) {
+        shipping_method_id -> Uuid,
+        name -> Text,
+        kind -> Text,
+        rate_cents -> Int8,
+        per_kg_cents -> Nullable<Int8>,
+        threshold_cents -> Nullable<Int8>,
+        active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    stock_movements (movement_id) {
+        movement_id -> Uuid,
+        item_id -> Uuid,
+        variant_id -> Uuid,
+        quantity -> Int4,
+        reason -> Text,
+        reference_id -> Nullable<Uuid>,
+        actor_id -> Nullable<Uuid>,
+        note -> Nullable<Text>,
+        created_at -> Timestamptz,
+        warehouse_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    stock_reservations (reservation_id) {
+        reservation_id -> Uuid,
+        user_id -> Uuid,
+        item_id -> Uuid,
+        variant_id -> Uuid,
+        quantity -> Int4,
+        status -> Text,
+        order_id -> Nullable<Uuid>,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    tax_rates (tax_rate_id) {
+        tax_rate_id -> Uuid,
+        country -> Text,
+        region -> Nullable<Text>,
+        tax_class -> Text,
+        rate_basis_points -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     users (user_id) {
         user_id -> Uuid,
         name -> Text,
         email -> Text,
         password -> Text,
         status -> Nullable<Text>,
-        #[max_length = 10]
+        #[max_length = 16]
         phone_number -> Nullable<Varchar>,
         address -> Nullable<Text>,
         is_admin -> Bool,
+        #[max_length = 2]
+        phone_country -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    variant_options (variant_id, name) {
+        variant_id -> Uuid,
+        name -> Text,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    warehouse_stock (warehouse_id, variant_id) {
+        warehouse_id -> Uuid,
+        variant_id -> Uuid,
+        amount -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    warehouses (warehouse_id) {
+        warehouse_id -> Uuid,
+        code -> Text,
+        name -> Text,
+        priority -> Int4,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        is_default -> Bool,
+        created_at -> Timestamptz,
     }
 }
 
+diesel::joinable!(back_in_stock_subscriptions -> inventory (item_id));
+diesel::joinable!(back_in_stock_subscriptions -> users (user_id));
 diesel::joinable!(confirmation -> users (user_id));
+diesel::joinable!(coupon_categories -> categories (category_id));
+diesel::joinable!(coupon_categories -> coupons (coupon_id));
+diesel::joinable!(coupon_items -> coupons (coupon_id));
+diesel::joinable!(coupon_items -> inventory (item_id));
+diesel::joinable!(coupon_redemptions -> coupons (coupon_id));
+diesel::joinable!(coupon_redemptions -> orders (order_id));
+diesel::joinable!(coupon_redemptions -> users (user_id));
+diesel::joinable!(email_preferences -> users (user_id));
+diesel::joinable!(inventory_categories -> categories (category_id));
+diesel::joinable!(inventory_categories -> inventory (item_id));
+diesel::joinable!(item_attributes -> inventory (item_id));
+diesel::joinable!(order_item_allocations -> order_items (order_item_id));
+diesel::joinable!(order_item_allocations -> warehouses (warehouse_id));
 diesel::joinable!(order_items -> inventory (item_id));
 diesel::joinable!(order_items -> orders (order_id));
+diesel::joinable!(order_items -> product_variants (variant_id));
+diesel::joinable!(order_promotions -> orders (order_id));
+diesel::joinable!(order_promotions -> promotions (promotion_id));
+diesel::joinable!(orders -> coupons (coupon_id));
+diesel::joinable!(orders -> shipping_methods (shipping_method_id));
 diesel::joinable!(orders -> users (user_id));
+diesel::joinable!(payments -> orders (order_id));
+diesel::joinable!(product_variants -> inventory (item_id));
+diesel::joinable!(promotion_items -> inventory (item_id));
+diesel::joinable!(promotion_items -> promotions (promotion_id));
+diesel::joinable!(promotion_tiers -> promotions (promotion_id));
+diesel::joinable!(refund_lines -> order_items (order_item_id));
+diesel::joinable!(refund_lines -> refunds (refund_id));
+diesel::joinable!(refunds -> orders (order_id));
+diesel::joinable!(refunds -> payments (payment_id));
+diesel::joinable!(refunds -> users (actor_id));
+diesel::joinable!(return_lines -> order_items (order_item_id));
+diesel::joinable!(return_lines -> returns (return_id));
+diesel::joinable!(returns -> orders (order_id));
+diesel::joinable!(returns -> refunds (refund_id));
+diesel::joinable!(shipment_lines -> order_items (order_item_id));
+diesel::joinable!(shipment_lines -> shipments (shipment_id));
+diesel::joinable!(shipments -> orders (order_id));
+diesel::joinable!(shipments -> users (actor_id));
+diesel::joinable!(shipping_method_zones -> shipping_methods (shipping_method_id));
+diesel::joinable!(stock_movements -> inventory (item_id));
+diesel::joinable!(stock_movements -> product_variants (variant_id));
+diesel::joinable!(stock_movements -> users (actor_id));
+diesel::joinable!(stock_movements -> warehouses (warehouse_id));
+diesel::joinable!(stock_reservations -> inventory (item_id));
+diesel::joinable!(stock_reservations -> orders (order_id));
+diesel::joinable!(stock_reservations -> product_variants (variant_id));
+diesel::joinable!(stock_reservations -> users (user_id));
+diesel::joinable!(variant_options -> product_variants (variant_id));
+diesel::joinable!(warehouse_stock -> product_variants (variant_id));
+diesel::joinable!(warehouse_stock -> warehouses (warehouse_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    back_in_stock_subscriptions,
+    categories,
     confirmation,
+    coupon_categories,
+    coupon_items,
+    coupon_redemptions,
+    coupons,
+    email_outbox,
+    email_preferences,
+    email_suppressions,
     inventory,
+    inventory_categories,
+    item_attributes,
+    order_item_allocations,
     order_items,
+    order_promotions,
     orders,
+    payments,
+    product_variants,
+    promotion_items,
+    promotion_tiers,
+    promotions,
+    refund_lines,
+    refunds,
+    return_lines,
+    returns,
+    shipment_lines,
+    shipments,
+    shipping_method_zones,
+    shipping_methods,
+    stock_movements,
+    stock_reservations,
+    tax_rates,
     users,
+    variant_options,
+    warehouse_stock,
+    warehouses,
 );
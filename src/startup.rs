@@ -1,17 +1,31 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc, time::Duration};
 
 use actix_web::{dev::Server, web::{self, Data}, App, HttpServer};
+use anyhow::Context;
 use diesel::{r2d2::ConnectionManager, PgConnection};
+use phonenumber::country;
 use r2d2::Pool;
 use secrecy::SecretString;
 use tracing_actix_web::TracingLogger;
 
-use crate::{auth::jwt::Tokenizer, configuration::Settings, domain::user_email::UserEmail, email_client::EmailClient, routes::{authentication::{login::login, register::register}, confirm::confirm, health_check, inventory::{get_inventory, post_inventory}, order::{delete_order, get_order, post_order, update_order}, profile::{get_profile, post_profile}}};
+use crate::{auth::jwt::Tokenizer, configuration::{EmailTransportKind, PaymentProviderKind, Settings}, payment_provider::{FakePaymentProvider, PaymentProvider}, jobs::{spawn_back_in_stock_notifications, spawn_email_outbox, spawn_low_stock_alerts, spawn_reservation_expiry}, domain::user_email::UserEmail, email_transport::{EmailTransport, FileTransport, PostmarkTransport, SmtpTransport}, email_templates::EmailRenderer, unsubscribe::UnsubscribeSigner, routes::{authentication::{login::login, register::register}, category::{get_category, post_category}, coupon::{delete_coupon_by_id, get_coupon_by_id, get_coupon_list, post_coupon, put_coupon}, checkout::{delete_checkout, post_checkout, post_checkout_payment}, confirm::confirm, email_outbox::{get_outbox, post_outbox_retry}, email_preferences::{delete_suppression, get_suppressions, get_user_email_preferences, post_suppression, put_user_email_preferences, get_unsubscribe, unsubscribe}, health_check, inventory::{delete_back_in_stock_subscription, export_inventory, get_back_in_stock_subscription_list, post_back_in_stock_subscription, put_backorder_policy, get_inventory, get_item_stock_movements, get_low_stock, put_reorder_threshold, get_stock_reconciliation, import_inventory, post_stock_movement, post_inventory, IMPORT_PAYLOAD_LIMIT, post_variant, put_item_attributes, put_item_categories, search_inventory}, order::{delete_order, get_order, get_refunds, get_returns_queue, get_shipments, get_user_order_returns, post_order, post_refund, post_return, post_return_refund, post_shipment, put_return_status, update_order}, payment::payment_webhook, profile::{get_profile, post_profile}, promotion::{delete_promotion_by_id, get_promotion_list, post_promotion, put_promotion}, shipping::{delete_shipping_method_by_id, get_shipping_method_list, post_shipping_method, post_shipping_quote, put_item_weight, put_shipping_method}, tax::{delete_tax_rate_by_id, get_tax_rate_list, put_item_tax_class, put_tax_rate}, warehouse::{get_warehouse, get_warehouse_stock, post_warehouse}}};
 
 // Base URL of application
 #[derive(Clone)]
 pub struct BaseUrl(pub String);
 
+// Region assumed for phone numbers given without country code
+#[derive(Clone)]
+pub struct DefaultPhoneRegion(pub country::Id);
+
+// How long stock reserved during checkout is held
+#[derive(Clone)]
+pub struct ReservationTtl(pub chrono::Duration);
+
+// Currency orders are charged in
+#[derive(Clone)]
+pub struct PaymentCurrency(pub String);
+
 // Application related data and server
 pub struct Application{
     pub host: String,
@@ -35,17 +49,30 @@ impl Application {
                     .expect("Failed to create pool for application");
 
 
-        let sender = UserEmail::parse(settings.email.sender).unwrap();
-        let key = SecretString::from(settings.email.key.to_string());
+        let sender = UserEmail::parse(settings.email.sender.clone()).unwrap();
 
-        let email_client = EmailClient::new(
-            settings.email.api_uri,
-            sender,
-            key,
-            3
-        );
+        let email_transport: Arc<dyn EmailTransport> = match settings.email.transport {
+            EmailTransportKind::Postmark => Arc::new(PostmarkTransport::new(
+                settings.email.api_uri.clone().context("email.api_uri is required by postmark transport")?,
+                sender,
+                SecretString::from(settings.email.key.clone().context("email.key is required by postmark transport")?),
+                3
+            )),
+            EmailTransportKind::Smtp => Arc::new(SmtpTransport::new(
+                settings.email.smtp.as_ref().context("email.smtp is required by smtp transport")?,
+                &sender,
+                3
+            )?),
+            EmailTransportKind::File => Arc::new(FileTransport::new(
+                settings.email.file_directory.clone().context("email.file_directory is required by file transport")?,
+                &sender
+            )?)
+        };
+        tracing::info!("Sending emails through {} transport", email_transport.name());
 
 
+        let email_renderer = EmailRenderer::load(&settings.email_templates)?;
+
         let base_url = BaseUrl(format!(
             "http://{}:{}/",
             settings.application.host,
@@ -54,6 +81,55 @@ impl Application {
 
         let tokenizer = Tokenizer::new(&settings.jwt);
 
+        let default_phone_region = DefaultPhoneRegion(settings.phone.default_region);
+
+        let allocation_strategy = settings.fulfillment.allocation_strategy;
+
+        let payment_provider: Data<dyn PaymentProvider> = match settings.payment.provider {
+            PaymentProviderKind::Fake => Data::from(
+                Arc::new(FakePaymentProvider::new(&settings.payment.webhook_secret)) as Arc<dyn PaymentProvider>
+            )
+        };
+
+        let payment_currency = PaymentCurrency(settings.payment.currency);
+
+        let reservation_ttl = ReservationTtl(chrono::Duration::seconds(settings.reservation.ttl_seconds));
+
+        spawn_reservation_expiry(
+            Data::new(pool.clone()),
+            Duration::from_secs(settings.reservation.expiry_interval_seconds)
+        );
+
+        let low_stock_recipients = settings.low_stock.alert_recipients
+            .into_iter()
+            .map(UserEmail::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        if !low_stock_recipients.is_empty() {
+            spawn_low_stock_alerts(
+                Data::new(pool.clone()),
+                email_renderer.clone(),
+                low_stock_recipients,
+                Duration::from_secs(settings.low_stock.check_interval_seconds)
+            );
+        }
+
+        spawn_back_in_stock_notifications(
+            Data::new(pool.clone()),
+            email_renderer.clone(),
+            settings.back_in_stock
+        );
+
+        let unsubscribe_signer = UnsubscribeSigner::new(&settings.email.unsubscribe_secret, &base_url.0)?;
+
+        spawn_email_outbox(
+            Data::new(pool.clone()),
+            email_transport,
+            unsubscribe_signer.clone(),
+            settings.email_outbox
+        );
+
         let server = HttpServer::new(move || {
             App::new()
                 .wrap(TracingLogger::default())
@@ -62,7 +138,15 @@ impl Application {
                 .route("/confirm", web::get().to(confirm)) // Confirmation endpoint for user
                 .route("/login", web::post().to(login)) // Route for user to login
                 .route("/inventory", web::get().to(get_inventory)) // Route to view items available
+                .route("/inventory/search", web::get().to(search_inventory)) // Route to search items
+                .route("/category", web::get().to(get_category)) // Route to view product categories
                 .route("/order", web::get().to(get_order)) // Route to view order details
+                .route("/payments/webhook", web::post().to(payment_webhook)) // Route for payment provider
+                                                                             // to report payment events
+                .route("/unsubscribe", web::get().to(get_unsubscribe)) // Route to follow unsubscribe link
+                                                                        // to a confirmation page
+                .route("/unsubscribe", web::post().to(unsubscribe)) // Route for the confirmation page and mail
+                                                                    // clients to unsubscribe in one click
                 .service(web::scope("/user")
                     .route("/profile", web::get().to(get_profile)) // Route to view user profile
                                                                    // details
@@ -72,18 +156,162 @@ impl Application {
 
                     .route("/order", web::post().to(post_order)) // Route to create an order
                     .route("/order", web::delete().to(delete_order)) // Route to delete an order
+                    .route("/checkout", web::post().to(post_checkout)) // Route to reserve stock
+                                                                       // during checkout
+
+                    .route("/checkout", web::delete().to(delete_checkout)) // Route to release
+                                                                           // reserved stock
+
+                    .route("/checkout/payment", web::post().to(post_checkout_payment)) // Route to start
+                                                                                       // paying for an order
+
+                    .route("/shipping/quote", web::post().to(post_shipping_quote)) // Route to compare
+                                                                                   // shipping options for a cart
+
+                    .route("/order/{order_id}/returns", web::post().to(post_return)) // Route to request
+                                                                                     // a return
+                    .route("/order/{order_id}/returns", web::get().to(get_user_order_returns)) // Route to view
+                                                                                               // returns of an order
+
+                    .route("/email-preferences", web::get().to(get_user_email_preferences)) // Route to view
+                                                                                            // email preferences
+                    .route("/email-preferences", web::put().to(put_user_email_preferences)) // Route to opt in
+                                                                                            // or out of emails
+
+                    .route("/back-in-stock", web::get().to(get_back_in_stock_subscription_list)) // Route to view
+                                                                                                 // items waited on
+                    .route("/back-in-stock/{item_id}", web::post().to(post_back_in_stock_subscription)) // Route to
+                                                                                                        // wait for
+                                                                                                        // a restock
+                    .route("/back-in-stock/{item_id}", web::delete().to(delete_back_in_stock_subscription)) // Route to
+                                                                                                            // stop waiting
+                                                                                                            // for a restock
                 )
                 .service(web::scope("/admin")
                     .route("/inventory", web::post().to(post_inventory)) // Route to post items to
                                                                          // inventory
 
+                    .service(web::resource("/inventory/import")
+                        .app_data(web::PayloadConfig::new(IMPORT_PAYLOAD_LIMIT))
+                        .route(web::post().to(import_inventory)) // Route to bulk import catalog
+                    )
+
+                    .route("/inventory/export", web::get().to(export_inventory)) // Route to export
+                                                                                 // catalog as CSV
+
+                    .route("/inventory/low-stock", web::get().to(get_low_stock)) // Route to view items
+                                                                                 // at reorder threshold
+
+                    .route("/inventory/{item_id}/categories", web::put().to(put_item_categories)) // Route to assign
+                                                                                                  // categories to item
+
+                    .route("/inventory/{item_id}/attributes", web::put().to(put_item_attributes)) // Route to set
+                                                                                                  // item attributes
+
+                    .route("/inventory/{item_id}/variants", web::post().to(post_variant)) // Route to add
+                                                                                          // variant to item
+
+                    .route("/inventory/{item_id}/movements", web::post().to(post_stock_movement)) // Route to
+                                                                                                  // record stock movement
+
+                    .route("/inventory/{item_id}/movements", web::get().to(get_item_stock_movements)) // Route to
+                                                                                                      // view stock ledger
+
+                    .route("/inventory/{item_id}/reconciliation", web::get().to(get_stock_reconciliation)) // Route to
+                                                                                                           // compare stock
+                                                                                                           // with ledger
+
+                    .route("/inventory/{item_id}/reorder-threshold", web::put().to(put_reorder_threshold)) // Route to
+                                                                                                           // set low stock
+                                                                                                           // threshold
+
+                    .route("/inventory/{item_id}/backorder-policy", web::put().to(put_backorder_policy)) // Route to
+                                                                                                         // allow ordering
+                                                                                                         // beyond stock
+
+                    .route("/inventory/{item_id}/tax-class", web::put().to(put_item_tax_class)) // Route to set
+                                                                                                // item tax class
+
+                    .route("/inventory/{item_id}/weight", web::put().to(put_item_weight)) // Route to set
+                                                                                          // item shipping weight
+
+                    .route("/inventory/{item_id}/warehouse-stock", web::get().to(get_warehouse_stock)) // Route to
+                                                                                                      // view stock
+                                                                                                      // per warehouse
+
+                    .route("/warehouses", web::post().to(post_warehouse)) // Route to create a warehouse
+                    .route("/warehouses", web::get().to(get_warehouse)) // Route to view warehouses
+
+                    .route("/category", web::post().to(post_category)) // Route to create a category
+
+                    .route("/coupons", web::post().to(post_coupon)) // Route to create a coupon
+                    .route("/coupons", web::get().to(get_coupon_list)) // Route to view coupons
+                    .route("/coupons/{coupon_id}", web::get().to(get_coupon_by_id)) // Route to view a coupon
+                    .route("/coupons/{coupon_id}", web::put().to(put_coupon)) // Route to replace a coupon
+                    .route("/coupons/{coupon_id}", web::delete().to(delete_coupon_by_id)) // Route to delete
+                                                                                          // a coupon
+
+                    .route("/promotions", web::post().to(post_promotion)) // Route to create a promotion
+                    .route("/promotions", web::get().to(get_promotion_list)) // Route to view promotions
+                    .route("/promotions/{promotion_id}", web::put().to(put_promotion)) // Route to replace a promotion
+                    .route("/promotions/{promotion_id}", web::delete().to(delete_promotion_by_id)) // Route to delete
+                                                                                                   // a promotion
+
+                    .route("/tax-rates", web::put().to(put_tax_rate)) // Route to set a tax rate
+                    .route("/tax-rates", web::get().to(get_tax_rate_list)) // Route to view tax rates
+                    .route("/tax-rates/{tax_rate_id}", web::delete().to(delete_tax_rate_by_id)) // Route to delete
+                                                                                                // a tax rate
+
+                    .route("/shipping-methods", web::post().to(post_shipping_method)) // Route to create
+                                                                                      // a shipping method
+                    .route("/shipping-methods", web::get().to(get_shipping_method_list)) // Route to view
+                                                                                         // shipping methods
+                    .route("/shipping-methods/{shipping_method_id}", web::put().to(put_shipping_method)) // Route to
+                                                                                                         // replace a
+                                                                                                         // shipping method
+                    .route("/shipping-methods/{shipping_method_id}", web::delete().to(delete_shipping_method_by_id)) // Route to
+                                                                                                                     // delete a
+                                                                                                                     // shipping method
+
                     .route("/order", web::put().to(update_order)) // Route to update order status
                     .route("/order", web::delete().to(delete_order)) // Route to delete an order
+                    .route("/order/{order_id}/refunds", web::post().to(post_refund)) // Route to refund an order
+                    .route("/order/{order_id}/refunds", web::get().to(get_refunds)) // Route to view refunds of an order
+                    .route("/order/{order_id}/shipments", web::post().to(post_shipment)) // Route to ship lines
+                                                                                         // of an order
+                    .route("/order/{order_id}/shipments", web::get().to(get_shipments)) // Route to view
+                                                                                        // shipments of an order
+
+                    .route("/returns", web::get().to(get_returns_queue)) // Route to view requested returns
+                    .route("/returns/{return_id}", web::put().to(put_return_status)) // Route to approve, reject
+                                                                                     // or receive a return
+                    .route("/returns/{return_id}/refund", web::post().to(post_return_refund)) // Route to refund
+                                                                                              // a received return
+
+                    .route("/email-outbox", web::get().to(get_outbox)) // Route to view queued, sent
+                                                                       // or dead emails
+                    .route("/email-outbox/{email_id}/retry", web::post().to(post_outbox_retry)) // Route to
+                                                                                                // retry a
+                                                                                                // dead email
+
+                    .route("/email-suppressions", web::get().to(get_suppressions)) // Route to view
+                                                                                   // suppressed emails
+                    .route("/email-suppressions", web::post().to(post_suppression)) // Route to stop
+                                                                                    // emailing an address
+                    .route("/email-suppressions/{email}", web::delete().to(delete_suppression)) // Route to
+                                                                                                // lift a
+                                                                                                // suppression
                 )
                 .app_data(Data::new(pool.clone())) // Database Connection Pool
-                .app_data(Data::new(email_client.clone())) // Email Client
+                .app_data(Data::new(email_renderer.clone())) // Templates emails are rendered from
                 .app_data(Data::new(base_url.clone())) // Base URL
+                .app_data(Data::new(unsubscribe_signer.clone())) // Signer of one-click unsubscribe links
                 .app_data(Data::new(tokenizer.clone())) // JWT encoder and decoder
+                .app_data(Data::new(default_phone_region.clone())) // Default phone number region
+                .app_data(Data::new(reservation_ttl.clone())) // Hold time of stock reservations
+                .app_data(Data::new(allocation_strategy)) // Strategy to pick warehouses for orders
+                .app_data(payment_provider.clone()) // Gateway orders are paid through
+                .app_data(Data::new(payment_currency.clone())) // Currency orders are charged in
         })
         .listen(listener)?
         .run();
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use reqwest::Url;
+use ring::hmac;
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{domain::email_category::EmailCategory, utils::decode_hex};
+
+// Signs one-click unsubscribe links so an address can only be opted out through emails sent to it
+#[derive(Clone)]
+pub struct UnsubscribeSigner {
+    key: hmac::Key,
+    endpoint: Url
+}
+
+impl UnsubscribeSigner {
+    pub fn new(secret: &SecretString, base_url: &str) -> Result<Self, anyhow::Error> {
+        let endpoint = Url::parse(base_url)
+            .and_then(|base_url| base_url.join("unsubscribe"))
+            .context("Base url of unsubscribe links is invalid")?;
+
+        Ok(Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.expose_secret().as_bytes()),
+            endpoint
+        })
+    }
+
+    // Link opting email out of category, or out of every email without one
+    pub fn link(&self, email: &str, category: Option<EmailCategory>) -> String {
+        let mut link = self.endpoint.clone();
+        {
+            let mut query = link.query_pairs_mut();
+            query.append_pair("email", email);
+            if let Some(category) = category {
+                query.append_pair("category", category.as_str());
+            }
+            query.append_pair("token", &self.token(email, category));
+        }
+
+        link.into()
+    }
+
+    pub fn token(&self, email: &str, category: Option<EmailCategory>) -> String {
+        hmac::sign(&self.key, signed_message(email, category).as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn verify(&self, email: &str, category: Option<EmailCategory>, token: &str) -> bool {
+        match decode_hex(token) {
+            Some(tag) => hmac::verify(&self.key, signed_message(email, category).as_bytes(), &tag).is_ok(),
+            None => false
+        }
+    }
+}
+
+// Addresses are suppressed lowercased, so the token doesn't depend on their case either
+fn signed_message(email: &str, category: Option<EmailCategory>) -> String {
+    format!("{}\n{}", email.to_lowercase(), category.map_or("all", |category| category.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+    use secrecy::SecretString;
+
+    use super::UnsubscribeSigner;
+    use crate::domain::email_category::EmailCategory;
+
+    fn signer() -> UnsubscribeSigner {
+        UnsubscribeSigner::new(&SecretString::from("unsubscribe-secret".to_string()), "http://localhost:8080/").unwrap()
+    }
+
+    #[test]
+    fn link_carries_token_for_its_email_and_category() {
+        let signer = signer();
+        let link = Url::parse(&signer.link("Aman+shop@example.com", Some(EmailCategory::Promotions))).unwrap();
+        assert_eq!(link.path(), "/unsubscribe");
+
+        let query = |name: &str| link.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+        assert_eq!(query("email").as_deref(), Some("Aman+shop@example.com"));
+        assert_eq!(query("category").as_deref(), Some("promotions"));
+
+        let token = query("token").unwrap();
+        assert!(signer.verify("aman+shop@example.com", Some(EmailCategory::Promotions), &token));
+    }
+
+    #[test]
+    fn token_of_another_email_or_category_is_rejected() {
+        let signer = signer();
+        let token = signer.token("aman@example.com", Some(EmailCategory::Promotions));
+
+        assert!(!signer.verify("other@example.com", Some(EmailCategory::Promotions), &token));
+        assert!(!signer.verify("aman@example.com", Some(EmailCategory::OrderUpdates), &token));
+        assert!(!signer.verify("aman@example.com", None, &token));
+        assert!(!signer.verify("aman@example.com", Some(EmailCategory::Promotions), "not-hex"));
+    }
+}
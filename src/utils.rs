@@ -52,3 +52,23 @@ impl Debug for PoolGetError {
         error_fmt_chain(f, &self.source())
     }
 }
+
+// Escape text interpolated into html emails
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Decode signatures sent as lowercase or uppercase hex, None if text isn't hex
+pub fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
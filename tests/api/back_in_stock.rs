@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use ecommerce::{models::{BackInStockSubscription, OutboxEmail}, pagination::Page};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn mount_email(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+}
+
+async fn restock(app: &TestApp, item_id: Uuid, variant_id: Uuid, access_token: &String) {
+    let response = app.post_stock_movement(item_id, serde_json::json!({
+        "variant_id": variant_id,
+        "quantity": 3,
+        "reason": "restock"
+    }), access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn subscriptions(app: &TestApp, access_token: &String) -> Vec<BackInStockSubscription> {
+    let response = app.get_back_in_stock(20, None, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Page<BackInStockSubscription>>().await.unwrap().items
+}
+
+#[actix_web::test]
+async fn only_out_of_stock_items_can_be_subscribed_to(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let sold_out = app.insert_inventory_item("Sold out kettle", 0, 25.0);
+    let available = app.insert_inventory_item("Available kettle", 4, 25.0);
+
+    let response = app.post_back_in_stock(sold_out.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let subscription = response.json::<BackInStockSubscription>().await.unwrap();
+    assert_eq!(subscription.item_id, sold_out.item_id);
+
+    // Subscribing again keeps the existing subscription
+    let response = app.post_back_in_stock(sold_out.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let again = response.json::<BackInStockSubscription>().await.unwrap();
+    assert_eq!(again.subscription_id, subscription.subscription_id);
+
+    let response = app.post_back_in_stock(available.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.post_back_in_stock(Uuid::new_v4(), &user_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_back_in_stock(sold_out.item_id, &"not-a-token".to_string()).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(subscriptions(&app, &user_token).await.len(), 1);
+
+    let response = app.delete_back_in_stock(sold_out.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_back_in_stock(sold_out.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    assert!(subscriptions(&app, &user_token).await.is_empty());
+}
+
+#[actix_web::test]
+async fn subscriptions_are_paginated_newest_first(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let mut item_ids = Vec::new();
+    for name in ["Sold out cup", "Sold out plate", "Sold out bowl"] {
+        let variant = app.insert_inventory_item(name, 0, 5.0);
+        let response = app.post_back_in_stock(variant.item_id, &user_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+        item_ids.push(variant.item_id);
+    }
+
+    let first_page = app.get_back_in_stock(2, None, &user_token)
+        .await
+        .json::<Page<BackInStockSubscription>>()
+        .await
+        .unwrap();
+    assert_eq!(first_page.total, 3);
+    let items: Vec<Uuid> = first_page.items.iter().map(|subscription| subscription.item_id).collect();
+    assert_eq!(items, vec![item_ids[2], item_ids[1]]);
+
+    let second_page = app.get_back_in_stock(2, first_page.next_cursor.as_deref(), &user_token)
+        .await
+        .json::<Page<BackInStockSubscription>>()
+        .await
+        .unwrap();
+    let items: Vec<Uuid> = second_page.items.iter().map(|subscription| subscription.item_id).collect();
+    assert_eq!(items, vec![item_ids[0]]);
+    assert!(second_page.next_cursor.is_none());
+
+    let response = app.get_back_in_stock(0, None, &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn restock_emails_each_subscriber_once(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Restocked teapot", 0, 30.0);
+
+    for token in [&admin_token, &user_token] {
+        let response = app.post_back_in_stock(variant.item_id, token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    restock(&app, variant.item_id, variant.variant_id, &admin_token).await;
+
+    let emails = app.wait_for_emails(2, |email| email.subject == "Restocked teapot is back in stock").await;
+    assert_eq!(emails.len(), 2);
+    assert!(emails.iter().any(|email| email.to == "amanrao032@gmail.com"));
+    assert!(emails.iter().any(|email| email.to == app.admin.email));
+    assert!(emails.iter().all(|email| email.text_body.contains(&variant.sku)));
+
+    assert!(subscriptions(&app, &admin_token).await.is_empty());
+    assert!(subscriptions(&app, &user_token).await.is_empty());
+
+    // Cleared subscriptions aren't notified of later restocks
+    restock(&app, variant.item_id, variant.variant_id, &admin_token).await;
+    actix_web::rt::time::sleep(Duration::from_secs(3)).await;
+
+    let emails = app.wait_for_emails(2, |email| email.subject == "Restocked teapot is back in stock").await;
+    assert_eq!(emails.len(), 2);
+
+    // Item is in stock now so it can't be subscribed to until it sells out again
+    let response = app.post_back_in_stock(variant.item_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn restock_email_is_withheld_from_users_who_opted_out(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    mount_email(&app).await;
+
+    let response = app.put_email_preferences(serde_json::json!({ "back_in_stock": false }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let variant = app.insert_inventory_item("Opted out teapot", 0, 30.0);
+    let response = app.post_back_in_stock(variant.item_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    restock(&app, variant.item_id, variant.variant_id, &admin_token).await;
+
+    // Job and worker run in the background so wait until the email was withheld
+    let mut suppressed = Vec::new();
+    for _ in 0..50 {
+        suppressed = app.get_outbox("suppressed", &admin_token)
+            .await
+            .json::<Page<OutboxEmail>>()
+            .await
+            .unwrap()
+            .items;
+        if !suppressed.is_empty() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(suppressed.len(), 1);
+    assert_eq!(suppressed[0].subject, "Opted out teapot is back in stock");
+    assert_eq!(suppressed[0].category.as_deref(), Some("back_in_stock"));
+    assert_eq!(suppressed[0].last_error.as_deref(), Some("Recipient opted out of back_in_stock"));
+    assert!(subscriptions(&app, &admin_token).await.is_empty());
+}
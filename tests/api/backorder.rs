@@ -0,0 +1,223 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::OrderWithItems, pagination::Page, schema::{order_items, product_variants}};
+use uuid::Uuid;
+use wiremock::{matchers::{body_string_contains, method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, variant_id: Uuid, amount: i32, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": amount }]))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+fn backordered_quantity(app: &TestApp, order_id: Uuid) -> i32 {
+    let mut conn = app.pool.get().unwrap();
+    order_items::table
+        .filter(order_items::order_id.eq(order_id))
+        .select(order_items::backordered_quantity)
+        .get_result::<i32>(&mut conn)
+        .unwrap()
+}
+
+fn variant_amount(app: &TestApp, variant_id: Uuid) -> i32 {
+    let mut conn = app.pool.get().unwrap();
+    product_variants::table
+        .find(variant_id)
+        .select(product_variants::amount)
+        .get_result::<i32>(&mut conn)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn backorder_policy_is_validated(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let variant = app.insert_inventory_item("Policy item", 5, 10.0);
+
+    let test_cases = vec![
+        ("backorder", Some(-1), 400, "negative limit"),
+        ("none", Some(5), 400, "limit without backorders"),
+        ("sometimes", None, 400, "unknown policy")
+    ];
+
+    for (policy, limit, status, description) in test_cases {
+        let response = app.put_backorder_policy(variant.item_id, policy, limit, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Policy wasn't rejected for {}", description);
+    }
+
+    let response = app.put_backorder_policy(Uuid::new_v4(), "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.put_backorder_policy(variant.item_id, "backorder", None, &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn orders_beyond_stock_are_backordered_up_to_limit(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Backordered item", 2, 10.0);
+
+    // Without a policy the line is dropped
+    let response = post_order(&app, variant.variant_id, 4, &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.put_backorder_policy(variant.item_id, "backorder", Some(3), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, variant.variant_id, 4, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.items[0].quantity, 4);
+    assert_eq!(order.items[0].backordered_quantity, 2);
+    assert_eq!(order.items[0].allocations.iter().map(|pick| pick.quantity).sum::<i32>(), 2);
+    assert_eq!(variant_amount(&app, variant.variant_id), 0);
+
+    // Only 1 more fits within the limit
+    let response = post_order(&app, variant.variant_id, 2, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = post_order(&app, variant.variant_id, 1, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Cancelled orders don't count against the limit
+    let response = app.api_client.delete(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "order_id": order.order_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, variant.variant_id, 4, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn restock_fills_oldest_backorders_first_and_notifies_customers(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Pre-order item", 0, 10.0);
+    let response = app.put_backorder_policy(variant.item_id, "preorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, variant.variant_id, 3, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let first = latest_order(&app, &user_token).await;
+
+    let response = post_order(&app, variant.variant_id, 2, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let second = latest_order(&app, &admin_token).await;
+    assert_eq!(second.items[0].backordered_quantity, 2);
+
+    // Customers are also emailed when their orders are placed
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_string_contains("on its way"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_api)
+        .await;
+
+    let response = app.post_stock_movement(variant.item_id, serde_json::json!({
+        "variant_id": variant.variant_id,
+        "quantity": 4,
+        "reason": "restock"
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(backordered_quantity(&app, first.order_id), 0);
+    assert_eq!(backordered_quantity(&app, second.order_id), 1);
+    assert_eq!(variant_amount(&app, variant.variant_id), 0);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.items[0].allocations.iter().map(|pick| pick.quantity).sum::<i32>(), 3);
+
+    let emails = app.wait_for_emails(2, |email| email.subject.contains("on its way") && email.text_body.contains(&variant.sku)).await;
+    assert_eq!(emails.len(), 2);
+    assert!(emails.iter().any(|email| email.to == "amanrao032@gmail.com" && email.text_body.starts_with("3 x")));
+    assert!(emails.iter().any(|email| email.to == app.admin.email && email.text_body.contains("1 more will follow")));
+}
+
+#[actix_web::test]
+async fn cancelling_backordered_order_returns_only_picked_stock(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Cancelled backorder item", 1, 10.0);
+    let response = app.put_backorder_policy(variant.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, variant.variant_id, 3, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+
+    let response = app.api_client.delete(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "order_id": order.order_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(variant_amount(&app, variant.variant_id), 1);
+}
+
+#[actix_web::test]
+async fn stock_from_cancellation_and_import_fills_backorders(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Refilled item", 1, 10.0);
+    let response = app.put_backorder_policy(variant.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, variant.variant_id, 1, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let cancelled = latest_order(&app, &user_token).await;
+
+    let response = post_order(&app, variant.variant_id, 2, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let waiting = latest_order(&app, &admin_token).await;
+    assert_eq!(waiting.items[0].backordered_quantity, 2);
+
+    // Stock of a cancelled order goes to the backorder
+    let response = app.api_client.delete(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "order_id": cancelled.order_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(backordered_quantity(&app, waiting.order_id), 1);
+    assert_eq!(variant_amount(&app, variant.variant_id), 0);
+
+    // So does stock set by a catalog import
+    let body = format!("sku,name,price,amount,slug,description,brand\n{},Refilled item,10,3,,,\n", variant.sku);
+    let response = app.import_inventory(&body, "text/csv", false, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(backordered_quantity(&app, waiting.order_id), 0);
+    assert_eq!(variant_amount(&app, variant.variant_id), 2);
+}
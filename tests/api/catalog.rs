@@ -0,0 +1,261 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use ecommerce::schema::{inventory, product_variants};
+
+use crate::helpers::TestApp;
+
+// Fetch name, price and amount of item with given sku
+fn item_by_sku(app: &TestApp, sku: &str) -> Option<(String, Option<f64>, Option<i32>)> {
+    let mut conn = app.pool.get().unwrap();
+
+    inventory::table
+        .filter(inventory::sku.eq(sku))
+        .select((inventory::name, inventory::price, inventory::amount))
+        .get_result(&mut conn)
+        .ok()
+}
+
+fn inventory_count(app: &TestApp) -> i64 {
+    let mut conn = app.pool.get().unwrap();
+
+    inventory::table
+        .count()
+        .get_result(&mut conn)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn csv_import_creates_items_with_default_variants(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let body = "sku,name,price,amount,slug,description,brand\n\
+                lamp-1,Desk Lamp,15.5,3,,Adjustable arm,Acme\n\
+                LAMP-2,Floor Lamp,60,0,tall-lamp,,\n";
+
+    let response = app.import_inventory(body, "text/csv", false, &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["updated"], 0);
+
+    assert_eq!(item_by_sku(&app, "LAMP-1"), Some(("Desk Lamp".to_string(), Some(15.5), Some(3))));
+
+    let mut conn = app.pool.get().unwrap();
+    let (slug, description): (String, Option<String>) = inventory::table
+        .filter(inventory::sku.eq("LAMP-2"))
+        .select((inventory::slug, inventory::description))
+        .get_result(&mut conn)
+        .unwrap();
+    assert_eq!(slug, "tall-lamp");
+    assert_eq!(description, None);
+
+    let variant_amount: i32 = product_variants::table
+        .filter(product_variants::sku.eq("LAMP-1"))
+        .select(product_variants::amount)
+        .get_result(&mut conn)
+        .unwrap();
+    assert_eq!(variant_amount, 3);
+}
+
+#[actix_web::test]
+async fn import_updates_items_with_existing_sku(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let item = serde_json::json!({
+        "name" : "Desk Lamp",
+        "amount" : "3",
+        "price" : "15",
+        "sku" : "LAMP-1",
+        "brand" : "Acme"
+    });
+    app.post_inventory(item, access_token.clone()).await;
+
+    let body = "{\"sku\": \"LAMP-1\", \"name\": \"Desk Lamp Pro\", \"price\": 20.0, \"amount\": 9}\n\
+                \n\
+                {\"sku\": \"LAMP-2\", \"name\": \"Floor Lamp\"}\n";
+
+    let response = app.import_inventory(body, "application/x-ndjson", false, &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["updated"], 1);
+
+    assert_eq!(item_by_sku(&app, "LAMP-1"), Some(("Desk Lamp Pro".to_string(), Some(20.0), Some(9))));
+    assert_eq!(item_by_sku(&app, "LAMP-2"), Some(("Floor Lamp".to_string(), None, Some(0))));
+
+    // Columns missing from the row keep their existing value
+    let mut conn = app.pool.get().unwrap();
+    let brand: Option<String> = inventory::table
+        .filter(inventory::sku.eq("LAMP-1"))
+        .select(inventory::brand)
+        .get_result(&mut conn)
+        .unwrap();
+    assert_eq!(brand, Some("Acme".to_string()));
+}
+
+#[actix_web::test]
+async fn dry_run_import_writes_nothing(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let body = "sku,name,price,amount\nLAMP-1,Desk Lamp,15,3\n";
+
+    let response = app.import_inventory(body, "text/csv", true, &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["created"], 1);
+
+    assert_eq!(inventory_count(&app), 0);
+}
+
+#[actix_web::test]
+async fn import_with_invalid_rows_reports_them_and_writes_nothing(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let body = "sku,name,price,amount\n\
+                LAMP-1,Desk Lamp,15,3\n\
+                LAMP-2,Floor Lamp,cheap,3\n\
+                LAMP 3,Table Lamp,15,3\n\
+                LAMP-4,Wall Lamp,-2,3\n\
+                lamp-1,Desk Lamp Again,15,3\n";
+
+    let response = app.import_inventory(body, "text/csv", false, &access_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    let failed_rows: Vec<u64> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["row"].as_u64().unwrap())
+        .collect();
+    assert_eq!(failed_rows, vec![2, 3, 4, 5]);
+
+    assert_eq!(inventory_count(&app), 0);
+}
+
+#[actix_web::test]
+async fn import_conflicting_with_existing_slug_is_rolled_back(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let item = serde_json::json!({
+        "name" : "Desk Lamp",
+        "amount" : "3",
+        "price" : "15",
+        "sku" : "LAMP-1"
+    });
+    app.post_inventory(item, access_token.clone()).await;
+
+    let body = "sku,name,price,amount\nLAMP-2,Floor Lamp,60,1\nLAMP-3,Desk Lamp,20,1\n";
+
+    let response = app.import_inventory(body, "text/csv", false, &access_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["errors"][0]["row"], 2);
+    assert_eq!(report["errors"][0]["sku"], "LAMP-3");
+
+    assert_eq!(inventory_count(&app), 1);
+}
+
+#[actix_web::test]
+async fn import_with_unsupported_content_type_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let response = app.import_inventory("sku,name\n", "application/json", false, &access_token).await;
+    assert_eq!(response.status().as_u16(), 415);
+}
+
+#[actix_web::test]
+async fn export_streams_catalog_which_can_be_imported_back(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let body = "sku,name,price,amount,slug,description,brand\n\
+                LAMP-1,Desk Lamp,15.5,3,,\"Adjustable, with clamp\",Acme\n\
+                LAMP-2,Floor Lamp,60,0,,,\n";
+    app.import_inventory(body, "text/csv", false, &access_token).await;
+
+    let response = app.export_inventory(&access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[reqwest::header::CONTENT_TYPE], "text/csv");
+
+    let exported = response.text().await.unwrap();
+    let mut lines = exported.lines();
+    assert_eq!(lines.next(), Some("sku,name,price,amount,slug,description,brand"));
+    assert_eq!(lines.next(), Some("LAMP-1,Desk Lamp,15.5,3,desk-lamp,\"Adjustable, with clamp\",Acme"));
+    assert_eq!(lines.next(), Some("LAMP-2,Floor Lamp,60.0,0,floor-lamp,,"));
+    assert_eq!(lines.next(), None);
+
+    let response = app.import_inventory(&exported, "text/csv", false, &access_token).await;
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["updated"], 2);
+    assert_eq!(report["created"], 0);
+}
+
+#[actix_web::test]
+async fn export_of_item_with_variants_keeps_their_stock_when_imported_back(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let body = "sku,name,price,amount,slug,description,brand\n\
+                LAMP-1,Desk Lamp,15,3,,,\n";
+    app.import_inventory(body, "text/csv", false, &access_token).await;
+
+    let mut conn = app.pool.get().unwrap();
+    let item_id = inventory::table
+        .filter(inventory::sku.eq("LAMP-1"))
+        .select(inventory::item_id)
+        .get_result::<uuid::Uuid>(&mut conn)
+        .unwrap();
+    let response = app.post_variant(
+        item_id,
+        serde_json::json!({ "sku": "LAMP-1-XL", "name": "Large", "amount": 4, "options": { "size": "XL" } }),
+        &access_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Amount exported is the default variant's, not the total across variants
+    let exported = app.export_inventory(&access_token).await.text().await.unwrap();
+    assert_eq!(exported.lines().nth(1), Some("LAMP-1,Desk Lamp,15.0,3,desk-lamp,,"));
+
+    let response = app.import_inventory(&exported, "text/csv", false, &access_token).await;
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["updated"], 1);
+
+    let mut amounts = product_variants::table
+        .filter(product_variants::item_id.eq(item_id))
+        .select((product_variants::sku, product_variants::amount))
+        .load::<(String, i32)>(&mut conn)
+        .unwrap();
+    amounts.sort();
+    assert_eq!(amounts, vec![("LAMP-1".to_string(), 3), ("LAMP-1-XL".to_string(), 4)]);
+}
+
+#[actix_web::test]
+async fn catalog_routes_require_admin(){
+    let app = TestApp::spawn_app().await;
+
+    let response = app.api_client.get(format!("http://{}:{}/admin/inventory/export", app.host, app.port))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,215 @@
+use chrono::{Duration, Utc};
+use ecommerce::{db_interaction::{CouponWithRestrictions, OrderWithItems}, models::Category, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, items: serde_json::Value, coupon_code: &str, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!({ "items": items, "coupon_code": coupon_code }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+async fn create_coupon(app: &TestApp, body: serde_json::Value, access_token: &String) -> CouponWithRestrictions {
+    let response = app.post_coupon(body, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<CouponWithRestrictions>().await.unwrap()
+}
+
+#[actix_web::test]
+async fn coupon_settings_are_validated(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let test_cases = vec![
+        (serde_json::json!({ "code": "X", "discount_type": "fixed", "discount_value": 100 }), 400, "short code"),
+        (serde_json::json!({ "code": "HALF", "discount_type": "percentage", "discount_value": 150 }), 400, "percentage over 100"),
+        (serde_json::json!({ "code": "FREE", "discount_type": "fixed", "discount_value": 0 }), 400, "zero discount"),
+        (serde_json::json!({ "code": "LIMIT", "discount_type": "fixed", "discount_value": 100, "usage_limit": 0 }), 400, "zero usage limit"),
+        (serde_json::json!({
+            "code": "BACKWARDS",
+            "discount_type": "fixed",
+            "discount_value": 100,
+            "starts_at": Utc::now(),
+            "ends_at": Utc::now() - Duration::days(1)
+        }), 400, "window ending before it starts"),
+        (serde_json::json!({ "code": "GHOST", "discount_type": "fixed", "discount_value": 100, "item_ids": [Uuid::new_v4()] }), 400, "unknown item")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.post_coupon(body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Coupon wasn't rejected for {}", description);
+    }
+
+    let coupon = create_coupon(&app, serde_json::json!({ "code": "welcome10", "discount_type": "percentage", "discount_value": 10 }), &admin_token).await;
+    assert_eq!(coupon.coupon.code, "WELCOME10");
+
+    let response = app.post_coupon(serde_json::json!({ "code": "WELCOME10", "discount_type": "fixed", "discount_value": 100 }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.post_coupon(serde_json::json!({ "code": "MINE", "discount_type": "fixed", "discount_value": 100 }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn admin_can_update_list_and_delete_coupons(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let coupon = create_coupon(&app, serde_json::json!({ "code": "SPRING", "discount_type": "fixed", "discount_value": 200 }), &admin_token).await;
+    create_coupon(&app, serde_json::json!({ "code": "AUTUMN", "discount_type": "fixed", "discount_value": 300 }), &admin_token).await;
+
+    let response = app.put_coupon(coupon.coupon.coupon_id, serde_json::json!({
+        "code": "SPRING",
+        "discount_type": "percentage",
+        "discount_value": 20,
+        "active": false
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let updated = response.json::<CouponWithRestrictions>().await.unwrap();
+    assert_eq!(updated.coupon.discount_type, "percentage");
+    assert!(!updated.coupon.active);
+
+    let page = app.get_coupons(&admin_token)
+        .await
+        .json::<Page<CouponWithRestrictions>>()
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.items[0].coupon.code, "AUTUMN");
+
+    let response = app.delete_coupon(coupon.coupon.coupon_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_coupon(coupon.coupon.coupon_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.put_coupon(coupon.coupon.coupon_id, serde_json::json!({
+        "code": "SPRING",
+        "discount_type": "fixed",
+        "discount_value": 100
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn coupon_discount_is_stored_on_order_and_charged(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    create_coupon(&app, serde_json::json!({ "code": "TENOFF", "discount_type": "percentage", "discount_value": 10 }), &admin_token).await;
+
+    let variant = app.insert_inventory_item("Discounted item", 10, 12.5);
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]), "tenoff", &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.coupon_code.as_deref(), Some("TENOFF"));
+    assert_eq!(order.discount_cents, 250);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_eq!(intent.payment.amount_cents, 2250);
+
+    // Unknown coupons fail the order without taking stock
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]), "NOPE", &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(latest_order(&app, &user_token).await.order_id, order.order_id);
+}
+
+#[actix_web::test]
+async fn coupon_limits_and_windows_are_enforced(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Limited item", 20, 10.0);
+    let items = serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]);
+
+    create_coupon(&app, serde_json::json!({ "code": "ONCE", "discount_type": "fixed", "discount_value": 100, "per_user_limit": 1, "usage_limit": 2 }), &admin_token).await;
+    create_coupon(&app, serde_json::json!({ "code": "BIGSPEND", "discount_type": "fixed", "discount_value": 100, "min_order_cents": 5000 }), &admin_token).await;
+    create_coupon(&app, serde_json::json!({ "code": "LATER", "discount_type": "fixed", "discount_value": 100, "starts_at": Utc::now() + Duration::days(1) }), &admin_token).await;
+    create_coupon(&app, serde_json::json!({ "code": "OVER", "discount_type": "fixed", "discount_value": 100, "ends_at": Utc::now() - Duration::seconds(1), "starts_at": Utc::now() - Duration::days(1) }), &admin_token).await;
+    create_coupon(&app, serde_json::json!({ "code": "PAUSED", "discount_type": "fixed", "discount_value": 100, "active": false }), &admin_token).await;
+
+    let response = post_order(&app, items.clone(), "ONCE", &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let test_cases = vec![
+        ("ONCE", "already used by customer"),
+        ("BIGSPEND", "order below minimum"),
+        ("LATER", "coupon not started"),
+        ("OVER", "coupon expired"),
+        ("PAUSED", "coupon inactive")
+    ];
+
+    for (code, description) in test_cases {
+        let response = post_order(&app, items.clone(), code, &user_token).await;
+        assert_eq!(response.status().as_u16(), 400, "Coupon wasn't rejected for {}", description);
+    }
+
+    // Second use overall is still allowed for another customer, the third isn't
+    let response = post_order(&app, items.clone(), "ONCE", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 5 }]), "BIGSPEND", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn restricted_coupon_only_discounts_eligible_lines(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let shirt = app.insert_inventory_item("Coupon shirt", 10, 20.0);
+    let mug = app.insert_inventory_item("Coupon mug", 10, 8.0);
+    let hat = app.insert_inventory_item("Coupon hat", 10, 5.0);
+
+    let hats: Category = app.post_category(serde_json::json!({ "name": "Hats" }), &admin_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let response = app.put_item_categories(hat.item_id, serde_json::json!({ "category_ids": [hats.category_id] }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    create_coupon(&app, serde_json::json!({
+        "code": "SHIRTSANDHATS",
+        "discount_type": "percentage",
+        "discount_value": 50,
+        "item_ids": [shirt.item_id],
+        "category_ids": [hats.category_id]
+    }), &admin_token).await;
+
+    let response = post_order(&app, serde_json::json!([{ "variant_id": mug.variant_id, "amount": 1 }]), "SHIRTSANDHATS", &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = post_order(&app, serde_json::json!([
+        { "variant_id": shirt.variant_id, "amount": 1 },
+        { "variant_id": mug.variant_id, "amount": 1 },
+        { "variant_id": hat.variant_id, "amount": 2 }
+    ]), "SHIRTSANDHATS", &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.discount_cents, 1500);
+}
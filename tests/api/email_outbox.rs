@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use ecommerce::{configuration::EmailTransportKind, models::OutboxEmail, pagination::Page};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::TestApp;
+
+async fn register(app: &TestApp) {
+    let body = serde_json::json!({
+        "email" : "amanrao032@gmail.com",
+        "name" : "Aman Rao",
+        "password" : "testpassword",
+        "confirm_password" : "testpassword"
+    });
+
+    let response = app.api_client.post(format!("http://{}:{}/register", app.host, app.port))
+        .form(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Worker runs in the background so wait until an email reached given status
+async fn outbox_emails(app: &TestApp, status: &str, access_token: &String) -> Vec<OutboxEmail> {
+    let mut emails = Vec::new();
+    for _ in 0..50 {
+        emails = app.get_outbox(status, access_token)
+            .await
+            .json::<Page<OutboxEmail>>()
+            .await
+            .unwrap()
+            .items;
+        if !emails.is_empty() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    emails
+}
+
+#[actix_web::test]
+async fn failed_email_is_retried_after_backoff(){
+    let app = TestApp::spawn_app_with(|settings| {
+        settings.email_outbox.retry_base_seconds = 1;
+    }).await;
+    let admin_token = app.login_admin().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_api)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_api)
+        .await;
+
+    // Registration succeeds even though the email service is failing
+    register(&app).await;
+
+    let emails = app.wait_for_emails(2, |email| email.subject == "Confirmation email").await;
+    assert_eq!(emails.len(), 2);
+
+    let sent = outbox_emails(&app, "sent", &admin_token).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].recipient, "amanrao032@gmail.com");
+    assert_eq!(sent[0].attempts, 2);
+    assert!(sent[0].last_error.is_some());
+
+    // Second attempt waited out the backoff
+    let sent_at = sent[0].sent_at.unwrap();
+    assert!(sent_at - sent[0].created_at >= chrono::Duration::seconds(1));
+}
+
+#[actix_web::test]
+async fn email_is_dead_lettered_and_can_be_retried(){
+    let app = TestApp::spawn_app_with(|settings| {
+        settings.email_outbox.max_attempts = 2;
+        settings.email_outbox.retry_base_seconds = 0;
+    }).await;
+    let admin_token = app.login_admin().await;
+
+    let failing = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount_as_scoped(&app.email_api)
+        .await;
+
+    register(&app).await;
+
+    let dead = outbox_emails(&app, "dead", &admin_token).await;
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].attempts, 2);
+    assert!(dead[0].last_error.as_ref().unwrap().contains("500"));
+    drop(failing);
+
+    let response = app.get_outbox("dead", &"not-a-token".to_string()).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_outbox("lost", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_outbox_retry(Uuid::new_v4(), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_api)
+        .await;
+
+    let response = app.post_outbox_retry(dead[0].email_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Only dead emails can be retried
+    let response = app.post_outbox_retry(dead[0].email_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let sent = outbox_emails(&app, "sent", &admin_token).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].email_id, dead[0].email_id);
+    assert_eq!(sent[0].attempts, 1);
+}
+
+#[actix_web::test]
+async fn file_transport_delivers_without_email_service(){
+    let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let app = TestApp::spawn_app_with(|settings| {
+        settings.email.transport = EmailTransportKind::File;
+        settings.email.file_directory = Some(directory.to_string_lossy().to_string());
+    }).await;
+    let admin_token = app.login_admin().await;
+
+    register(&app).await;
+
+    let sent = outbox_emails(&app, "sent", &admin_token).await;
+    assert_eq!(sent.len(), 1);
+    assert!(app.email_api.received_requests().await.unwrap().is_empty());
+
+    let files: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(files.len(), 1);
+    let written = std::fs::read_to_string(&files[0]).unwrap();
+    assert!(written.contains("Subject: Confirmation email"));
+    assert!(written.contains("To: amanrao032@gmail.com"));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use ecommerce::{models::{EmailPreferences, EmailSuppression, OutboxEmail}, pagination::Page};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, variant_id: Uuid, access_token: &String) {
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": 1 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn mount_email(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+}
+
+// Worker runs in the background so wait until it withheld an email from recipient
+async fn suppressed_emails(app: &TestApp, recipient: &str, access_token: &String) -> Vec<OutboxEmail> {
+    let mut emails = Vec::new();
+    for _ in 0..50 {
+        emails = app.get_outbox("suppressed", access_token)
+            .await
+            .json::<Page<OutboxEmail>>()
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .filter(|email| email.recipient == recipient)
+            .collect();
+        if !emails.is_empty() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    emails
+}
+
+#[actix_web::test]
+async fn preferences_default_to_every_category_and_can_be_changed(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let preferences = app.get_email_preferences(&user_token)
+        .await
+        .json::<EmailPreferences>()
+        .await
+        .unwrap();
+    assert!(preferences.order_updates && preferences.promotions && preferences.back_in_stock);
+
+    let response = app.put_email_preferences(serde_json::json!({ "promotions": false }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.put_email_preferences(serde_json::json!({ "back_in_stock": false }), &user_token).await;
+    let preferences = response.json::<EmailPreferences>().await.unwrap();
+    assert!(preferences.order_updates);
+    assert!(!preferences.promotions);
+    assert!(!preferences.back_in_stock);
+
+    let response = app.get_email_preferences(&"not-a-token".to_string()).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn one_click_unsubscribe_stops_order_emails(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Unsubscribed lamp", 5, 10.0);
+    post_order(&app, variant.variant_id, &admin_token).await;
+
+    let emails = app.wait_for_emails(1, |email| email.subject == "We received your order").await;
+    assert_eq!(emails.len(), 1);
+
+    let header = |name: &str| emails[0].headers.iter().find(|header| header.name == name).map(|header| header.value.clone());
+    assert_eq!(header("List-Unsubscribe-Post").as_deref(), Some("List-Unsubscribe=One-Click"));
+
+    let link = header("List-Unsubscribe").unwrap();
+    let link = link.trim_start_matches('<').trim_end_matches('>');
+    assert!(link.contains("category=order_updates"));
+
+    // Following the link only asks to confirm, so prefetching it doesn't unsubscribe
+    let response = app.get_unsubscribe(link).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("<form method=\"post\" action=\"/unsubscribe?"));
+    assert!(page.contains("order updates emails"));
+
+    let preferences = app.get_email_preferences(&admin_token)
+        .await
+        .json::<EmailPreferences>()
+        .await
+        .unwrap();
+    assert!(preferences.order_updates);
+
+    let response = app.post_unsubscribe(link).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let preferences = app.get_email_preferences(&admin_token)
+        .await
+        .json::<EmailPreferences>()
+        .await
+        .unwrap();
+    assert!(!preferences.order_updates);
+    assert!(preferences.promotions);
+
+    post_order(&app, variant.variant_id, &admin_token).await;
+
+    let suppressed = suppressed_emails(&app, &app.admin.email, &admin_token).await;
+    assert_eq!(suppressed.len(), 1);
+    assert_eq!(suppressed[0].subject, "We received your order");
+    assert_eq!(suppressed[0].last_error.as_deref(), Some("Recipient opted out of order_updates"));
+}
+
+#[actix_web::test]
+async fn unsubscribe_link_with_bad_token_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let test_cases = vec![
+        ("email=a%40example.com&category=promotions&token=abcd", 401, "wrong token"),
+        ("email=a%40example.com&token=not-hex", 401, "malformed token"),
+        ("email=a%40example.com&category=newsletter&token=abcd", 400, "unknown category"),
+        ("email=a%40example.com", 400, "missing token")
+    ];
+
+    for (query, status, description) in test_cases {
+        let response = app.post_unsubscribe(&format!("http://localhost/unsubscribe?{}", query)).await;
+        assert_eq!(response.status().as_u16(), status, "Unsubscribe wasn't rejected for {}", description);
+
+        let response = app.get_unsubscribe(&format!("http://localhost/unsubscribe?{}", query)).await;
+        assert_eq!(response.status().as_u16(), status, "Unsubscribe page wasn't rejected for {}", description);
+    }
+
+    let admin_token = app.login_admin().await;
+    let suppressions = app.get_email_suppressions(&admin_token)
+        .await
+        .json::<Page<EmailSuppression>>()
+        .await
+        .unwrap();
+    assert!(suppressions.items.is_empty());
+}
+
+#[actix_web::test]
+async fn suppressed_address_receives_no_emails_until_lifted(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let response = app.post_email_suppression(serde_json::json!({ "email": "AmanRao032@gmail.com", "reason": "bounced" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let suppression = response.json::<EmailSuppression>().await.unwrap();
+    assert_eq!(suppression.email, "amanrao032@gmail.com");
+
+    let response = app.post_email_suppression(serde_json::json!({ "email": "not-an-email" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = serde_json::json!({
+        "email" : "amanrao032@gmail.com",
+        "name" : "Aman Rao",
+        "password" : "testpassword",
+        "confirm_password" : "testpassword"
+    });
+    let response = app.api_client.post(format!("http://{}:{}/register", app.host, app.port))
+        .form(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Account emails can't be opted out of, but suppression still withholds them
+    let suppressed = suppressed_emails(&app, "amanrao032@gmail.com", &admin_token).await;
+    assert_eq!(suppressed.len(), 1);
+    assert_eq!(suppressed[0].subject, "Confirmation email");
+    assert_eq!(suppressed[0].last_error.as_deref(), Some("Recipient is suppressed: bounced"));
+
+    let suppressions = app.get_email_suppressions(&admin_token)
+        .await
+        .json::<Page<EmailSuppression>>()
+        .await
+        .unwrap();
+    assert_eq!(suppressions.items.len(), 1);
+
+    let response = app.delete_email_suppression("amanrao032@gmail.com", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_email_suppression("amanrao032@gmail.com", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -1,9 +1,10 @@
 use std::error::Error;
 
+use actix_web::dev::ServerHandle;
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use diesel::{pg::Pg, r2d2::ConnectionManager, Connection, PgConnection, RunQueryDsl};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use ecommerce::{configuration::{DatabaseSettings, Settings}, models::User, startup::Application, telemetry::{get_subscriber, init_subscriber}, utils::DbPool};
+use ecommerce::{configuration::{DatabaseSettings, Settings}, models::{InventoryItem, ProductVariant, User, WarehouseStock}, payment_provider::FakePaymentProvider, startup::Application, telemetry::{get_subscriber, init_subscriber}, utils::DbPool};
 use fake::{faker::internet::en::FreeEmail, Fake};
 use once_cell::sync::Lazy;
 use r2d2::Pool;
@@ -11,7 +12,7 @@ use rand::rngs::OsRng;
 use reqwest::redirect::Policy;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use wiremock::{matchers::{header_exists, path}, Mock, MockServer, ResponseTemplate};
+use wiremock::{matchers::{body_string_contains, header_exists, path}, Mock, MockServer, ResponseTemplate};
 
 use crate::registration::ReceiveEmailRequest;
 
@@ -94,7 +95,18 @@ pub struct TestApp{
     pub email_api: MockServer,
     pub api_client: reqwest::Client,
     pub admin: TestUser,
-    pub user: TestUser
+    pub user: TestUser,
+    // Signs webhooks with the secret application verifies them with
+    pub payment_provider: FakePaymentProvider,
+    // Stops the application once a test is done so its database connections are released
+    server: ServerHandle
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        // Stop command is sent right away, completion isn't waited on
+        drop(self.server.stop(false));
+    }
 }
 
 impl TestApp {
@@ -103,9 +115,609 @@ impl TestApp {
     where 
         Body: Serialize
     {
-        self.api_client.delete(format!("http://{}:{}/admin/order",
+        self.api_client.delete(format!("http://{}:{}/admin/order",
+            self.host,
+            self.port,
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to update orders status returning response
+    pub async fn put_orders<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/order",
+            self.host,
+            self.port,
+        ))
+        .form(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get order request returning request builder
+    pub async fn get_orders_request(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::RequestBuilder{
+        self.api_client.get(format!("http://{}:{}/order?limit={}&cursor={}",
+            self.host,
+            self.port,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+    }
+
+    // API request to get order request returning response
+    pub async fn get_orders(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response{
+        self.get_orders_request(limit, cursor, access_token)
+            .await
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to get inventory returning response
+    pub async fn get_inventory(&self, limit: i64, cursor: Option<&str>) -> reqwest::Response{
+        self.api_client.get(format!("http://{}:{}/inventory?limit={}&cursor={}",
+            self.host,
+            self.port,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .send()
+        .await
+        .unwrap()
+    }
+    
+    // API request to search inventory returning response
+    pub async fn search_inventory<Query>(&self, query: Query) -> reqwest::Response
+    where
+        Query: Serialize
+    {
+        self.api_client.get(format!("http://{}:{}/inventory/search",
+            self.host,
+            self.port
+        ))
+        .query(&query)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // Function to perform admin user login
+    pub async fn login_admin(&self) -> String {
+        let login_request = serde_json::json!({
+            "email": self.admin.email,
+            "password": self.admin.password
+        });
+
+        let login_response = self.api_client.post(format!("http://{}:{}/login", self.host, self.port))
+            .form(&login_request)
+            .send()
+            .await
+            .unwrap();
+
+        let login_response_json: LoginResponse = serde_json::from_str(&login_response.text().await.unwrap()).unwrap();
+        return login_response_json.access_token
+    }
+    
+    // API request to post inventory returning response
+    pub async fn post_inventory<Body>(&self, item: Body, access_token: String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.post(
+            format!("http://{}:{}/admin/inventory",
+                self.host,
+                self.port
+            )
+        )
+        .bearer_auth(access_token)
+        .form(&item)
+        .send()
+        .await
+        .unwrap()
+    }
+    
+    // API request to post category returning response
+    pub async fn post_category<Body>(&self, category: Body, access_token: &String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/category",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .form(&category)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to assign categories to inventory item returning response
+    pub async fn put_item_categories<Body>(&self, item_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/categories",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set attributes of inventory item returning response
+    pub async fn put_item_attributes<Body>(&self, item_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/attributes",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+    }
+    
+    // API request to post variant of inventory item returning response
+    pub async fn post_variant<Body>(&self, item_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where 
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/inventory/{}/variants",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to import catalog file returning response
+    pub async fn import_inventory(&self, body: &str, content_type: &str, dry_run: bool, access_token: &String) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/admin/inventory/import?dry_run={}",
+            self.host,
+            self.port,
+            dry_run
+        ))
+        .bearer_auth(access_token)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to export catalog returning response
+    pub async fn export_inventory(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/inventory/export",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to record stock movement of inventory item returning response
+    pub async fn post_stock_movement<Body>(&self, item_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/inventory/{}/movements",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get stock movements of inventory item returning response
+    pub async fn get_stock_movements(&self, item_id: Uuid, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/inventory/{}/movements?limit={}&cursor={}",
+            self.host,
+            self.port,
+            item_id,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to reconcile stock of inventory item returning response
+    pub async fn get_stock_reconciliation(&self, item_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/inventory/{}/reconciliation",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to reserve stock during checkout returning response
+    pub async fn post_checkout<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/user/checkout",
+            self.host,
+            self.port
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to release reserved stock returning response
+    pub async fn delete_checkout(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/user/checkout",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set reorder threshold of inventory item returning response
+    pub async fn put_reorder_threshold(&self, item_id: Uuid, reorder_threshold: Option<i32>, access_token: &String) -> reqwest::Response {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/reorder-threshold",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .json(&serde_json::json!({ "reorder_threshold": reorder_threshold }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set backorder policy of inventory item returning response
+    pub async fn put_backorder_policy(&self, item_id: Uuid, policy: &str, backorder_limit: Option<i32>, access_token: &String) -> reqwest::Response {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/backorder-policy",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .json(&serde_json::json!({ "policy": policy, "backorder_limit": backorder_limit }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to start paying for an order returning response
+    pub async fn post_checkout_payment(&self, order_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/user/checkout/payment",
+            self.host,
+            self.port
+        ))
+        .json(&serde_json::json!({ "order_id": order_id }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to refund an order returning response
+    pub async fn post_refund<Body>(&self, order_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/order/{}/refunds",
+            self.host,
+            self.port,
+            order_id
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view refunds of an order returning response
+    pub async fn get_refunds(&self, order_id: Uuid, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/order/{}/refunds?limit={}&cursor={}",
+            self.host,
+            self.port,
+            order_id,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to return lines of an order returning response
+    pub async fn post_return<Body>(&self, order_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/user/order/{}/returns",
+            self.host,
+            self.port,
+            order_id
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view returns of own order returning response
+    pub async fn get_order_returns(&self, order_id: Uuid, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/user/order/{}/returns?limit={}&cursor={}",
+            self.host,
+            self.port,
+            order_id,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view returns queue filtered by status returning response
+    pub async fn get_returns_queue(&self, status: &str, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/returns?status={}",
+            self.host,
+            self.port,
+            status
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to move a return to given status returning response
+    pub async fn put_return_status(&self, return_id: Uuid, status: &str, access_token: &String) -> reqwest::Response {
+        self.api_client.put(format!("http://{}:{}/admin/returns/{}",
+            self.host,
+            self.port,
+            return_id
+        ))
+        .json(&serde_json::json!({ "status": status }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to refund a received return returning response
+    pub async fn post_return_refund(&self, return_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/admin/returns/{}/refund",
+            self.host,
+            self.port,
+            return_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to create a coupon returning response
+    pub async fn post_coupon<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/coupons",
+            self.host,
+            self.port
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to replace a coupon returning response
+    pub async fn put_coupon<Body>(&self, coupon_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/coupons/{}",
+            self.host,
+            self.port,
+            coupon_id
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view coupons returning response
+    pub async fn get_coupons(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/coupons",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to delete a coupon returning response
+    pub async fn delete_coupon(&self, coupon_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/admin/coupons/{}",
+            self.host,
+            self.port,
+            coupon_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to create a promotion returning response
+    pub async fn post_promotion<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/promotions",
+            self.host,
+            self.port
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to replace a promotion returning response
+    pub async fn put_promotion<Body>(&self, promotion_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/promotions/{}",
+            self.host,
+            self.port,
+            promotion_id
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get promotions returning response
+    pub async fn get_promotions(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/promotions",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to delete a promotion returning response
+    pub async fn delete_promotion(&self, promotion_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/admin/promotions/{}",
+            self.host,
+            self.port,
+            promotion_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set a tax rate returning response
+    pub async fn put_tax_rate<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/admin/tax-rates",
+            self.host,
+            self.port
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get tax rates returning response
+    pub async fn get_tax_rates(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/tax-rates?limit={}&cursor={}",
+            self.host,
+            self.port,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to delete a tax rate returning response
+    pub async fn delete_tax_rate(&self, tax_rate_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/admin/tax-rates/{}",
+            self.host,
+            self.port,
+            tax_rate_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set tax class of an item returning response
+    pub async fn put_item_tax_class(&self, item_id: Uuid, tax_class: &str, access_token: &String) -> reqwest::Response {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/tax-class",
             self.host,
             self.port,
+            item_id
+        ))
+        .json(&serde_json::json!({ "tax_class": tax_class }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to create a shipping method returning response
+    pub async fn post_shipping_method<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/shipping-methods",
+            self.host,
+            self.port
         ))
         .json(&body)
         .bearer_auth(access_token)
@@ -114,89 +726,361 @@ impl TestApp {
         .unwrap()
     }
 
-    // API request to update orders status returning response
-    pub async fn put_orders<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
-    where 
+    // API request to replace a shipping method returning response
+    pub async fn put_shipping_method<Body>(&self, shipping_method_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
         Body: Serialize
     {
-        self.api_client.put(format!("http://{}:{}/admin/order",
+        self.api_client.put(format!("http://{}:{}/admin/shipping-methods/{}",
             self.host,
             self.port,
+            shipping_method_id
         ))
-        .form(&body)
+        .json(&body)
         .bearer_auth(access_token)
         .send()
         .await
         .unwrap()
     }
 
-    // API request to get order request returning request builder
-    pub async fn get_orders_request(&self, page: i64, limit: i64, access_token: &String) -> reqwest::RequestBuilder{
-        self.api_client.get(format!("http://{}:{}/order?page={}&limit={}",
+    // API request to get shipping methods returning response
+    pub async fn get_shipping_methods(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/shipping-methods?limit={}&cursor={}",
             self.host,
             self.port,
-            page,
-            limit
+            limit,
+            cursor.unwrap_or_default()
         ))
         .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
     }
 
-    // API request to get order request returning response
-    pub async fn get_orders(&self, page: i64, limit: i64, access_token: &String) -> reqwest::Response{
-        self.get_orders_request(page, limit, access_token)
-            .await
+    // API request to delete a shipping method returning response
+    pub async fn delete_shipping_method(&self, shipping_method_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/admin/shipping-methods/{}",
+            self.host,
+            self.port,
+            shipping_method_id
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to set shipping weight of an item returning response
+    pub async fn put_item_weight(&self, item_id: Uuid, weight_grams: i32, access_token: &String) -> reqwest::Response {
+        self.api_client.put(format!("http://{}:{}/admin/inventory/{}/weight",
+            self.host,
+            self.port,
+            item_id
+        ))
+        .json(&serde_json::json!({ "weight_grams": weight_grams }))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to quote shipping of a cart returning response
+    pub async fn post_shipping_quote<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/user/shipping/quote",
+            self.host,
+            self.port
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to ship lines of an order returning response
+    pub async fn post_shipment<Body>(&self, order_id: Uuid, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/order/{}/shipments",
+            self.host,
+            self.port,
+            order_id
+        ))
+        .json(&body)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view shipments of an order returning response
+    pub async fn get_shipments(&self, order_id: Uuid, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/order/{}/shipments?limit={}&cursor={}",
+            self.host,
+            self.port,
+            order_id,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to view outbox emails with given status returning response
+    pub async fn get_outbox(&self, status: &str, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/email-outbox", self.host, self.port))
+            .query(&[("status", status)])
+            .bearer_auth(access_token)
             .send()
             .await
             .unwrap()
     }
 
-    // API request to get inventory returning response
-    pub async fn get_inventory(&self, page: i64, limit: i64) -> reqwest::Response{
-        self.api_client.get(format!("http://{}:{}/inventory?page={}&limit={}",
+    // API request to retry a dead outbox email returning response
+    pub async fn post_outbox_retry(&self, email_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/admin/email-outbox/{}/retry",
             self.host,
             self.port,
-            page,
-            limit
+            email_id
         ))
+        .bearer_auth(access_token)
         .send()
         .await
         .unwrap()
     }
-    
-    // Function to perform admin user login
-    pub async fn login_admin(&self) -> String {
-        let login_request = serde_json::json!({
-            "email": self.admin.email,
-            "password": self.admin.password
-        });
 
-        let login_response = self.api_client.post(format!("http://{}:{}/login", self.host, self.port))
-            .form(&login_request)
+    // API request to view email preferences of logged in user returning response
+    pub async fn get_email_preferences(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/user/email-preferences", self.host, self.port))
+            .bearer_auth(access_token)
             .send()
             .await
-            .unwrap();
+            .unwrap()
+    }
 
-        let login_response_json: LoginResponse = serde_json::from_str(&login_response.text().await.unwrap()).unwrap();
-        return login_response_json.access_token
+    // API request to update email preferences of logged in user returning response
+    pub async fn put_email_preferences<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.put(format!("http://{}:{}/user/email-preferences", self.host, self.port))
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
     }
-    
-    // API request to post inventory returning response
-    pub async fn post_inventory<Body>(&self, item: Body, access_token: String) -> reqwest::Response
-    where 
+
+    // API request following unsubscribe link from an email returning response
+    // Link points at the configured base url, so only its query is kept
+    pub async fn get_unsubscribe(&self, link: &str) -> reqwest::Response {
+        let query = reqwest::Url::parse(link).unwrap().query().unwrap_or_default().to_string();
+
+        self.api_client.get(format!("http://{}:{}/unsubscribe?{}", self.host, self.port, query))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request one-click unsubscribing through link from an email returning response
+    pub async fn post_unsubscribe(&self, link: &str) -> reqwest::Response {
+        let query = reqwest::Url::parse(link).unwrap().query().unwrap_or_default().to_string();
+
+        self.api_client.post(format!("http://{}:{}/unsubscribe?{}", self.host, self.port, query))
+            .form(&[("List-Unsubscribe", "One-Click")])
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to view suppressed emails returning response
+    pub async fn get_email_suppressions(&self, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/email-suppressions", self.host, self.port))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to suppress an email returning response
+    pub async fn post_email_suppression<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
         Body: Serialize
     {
-        self.api_client.post(
-            format!("http://{}:{}/admin/inventory",
-                self.host,
-                self.port
-            )
-        )
+        self.api_client.post(format!("http://{}:{}/admin/email-suppressions", self.host, self.port))
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to lift suppression of an email returning response
+    pub async fn delete_email_suppression(&self, email: &str, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/admin/email-suppressions/{}", self.host, self.port, email))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to be emailed when an item is back in stock returning response
+    pub async fn post_back_in_stock(&self, item_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/user/back-in-stock/{}", self.host, self.port, item_id))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to stop waiting for an item to be back in stock returning response
+    pub async fn delete_back_in_stock(&self, item_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.delete(format!("http://{}:{}/user/back-in-stock/{}", self.host, self.port, item_id))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request to get back in stock subscriptions of logged in user returning response
+    pub async fn get_back_in_stock(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/user/back-in-stock?limit={}&cursor={}", self.host, self.port, limit, cursor.unwrap_or_default()))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // API request delivering a payment provider webhook with given signature returning response
+    pub async fn post_payment_webhook(&self, body: &[u8], signature: &str) -> reqwest::Response {
+        self.api_client.post(format!("http://{}:{}/payments/webhook",
+            self.host,
+            self.port
+        ))
+        .header("X-Payment-Signature", signature)
+        .header("Content-Type", "application/json")
+        .body(body.to_vec())
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to create a warehouse returning response
+    pub async fn post_warehouse<Body>(&self, body: Body, access_token: &String) -> reqwest::Response
+    where
+        Body: Serialize
+    {
+        self.api_client.post(format!("http://{}:{}/admin/warehouses",
+            self.host,
+            self.port
+        ))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get warehouses returning response
+    pub async fn get_warehouses(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/warehouses?limit={}&cursor={}",
+            self.host,
+            self.port,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // API request to get stock of an item per warehouse returning response
+    pub async fn get_warehouse_stock(&self, item_id: Uuid, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/inventory/{}/warehouse-stock",
+            self.host,
+            self.port,
+            item_id
+        ))
         .bearer_auth(access_token)
-        .form(&item)
         .send()
         .await
         .unwrap()
     }
+
+    // API request to get low stock items returning response
+    pub async fn get_low_stock(&self, limit: i64, cursor: Option<&str>, access_token: &String) -> reqwest::Response {
+        self.api_client.get(format!("http://{}:{}/admin/inventory/low-stock?limit={}&cursor={}",
+            self.host,
+            self.port,
+            limit,
+            cursor.unwrap_or_default()
+        ))
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .unwrap()
+    }
+
+    // Insert inventory item with unique sku and slug along with its default variant
+    pub fn insert_inventory_item(&self, name: &str, amount: i32, price: f64) -> ProductVariant{
+        use diesel::{ExpressionMethods, QueryDsl};
+        use ecommerce::schema::{inventory, product_variants, warehouse_stock, warehouses};
+
+        let mut conn = self.pool.get().unwrap();
+        let item_id = Uuid::new_v4();
+        let sku = item_id.simple().to_string().to_uppercase();
+
+        let item = InventoryItem{
+            item_id,
+            name: name.to_string(),
+            amount: Some(amount),
+            price: Some(price),
+            sku: sku.clone(),
+            slug: item_id.to_string(),
+            description: None,
+            brand: None
+        };
+
+        let variant = ProductVariant{
+            variant_id: Uuid::new_v4(),
+            item_id,
+            sku,
+            name: "Default".to_string(),
+            price: None,
+            amount
+        };
+
+        diesel::insert_into(inventory::table)
+            .values(&item)
+            .execute(&mut conn)
+            .unwrap();
+
+        diesel::insert_into(product_variants::table)
+            .values(&variant)
+            .execute(&mut conn)
+            .unwrap();
+
+        // Stock without a location is held at the default warehouse
+        let warehouse_id = warehouses::table
+            .filter(warehouses::is_default.eq(true))
+            .select(warehouses::warehouse_id)
+            .get_result::<Uuid>(&mut conn)
+            .unwrap();
+
+        diesel::insert_into(warehouse_stock::table)
+            .values(&WarehouseStock{ warehouse_id, variant_id: variant.variant_id, amount })
+            .execute(&mut conn)
+            .unwrap();
+
+        variant
+    }
     
     // Create logical database
     fn create_db(settings: &DatabaseSettings) -> DbPool{
@@ -225,6 +1109,11 @@ impl TestApp {
 
     // Spawn app
     pub async fn spawn_app() -> TestApp{
+        TestApp::spawn_app_with(|_| {}).await
+    }
+
+    // Spawn app with settings adjusted by given closure before the app is built
+    pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp{
         Lazy::force(&LOGGER_INSTANCE);
 
         let email_api = MockServer::start().await;
@@ -232,9 +1121,15 @@ impl TestApp {
         let mut settings = Settings::get();
         settings.application.port = 0;
         settings.database.name = Uuid::new_v4().to_string();
-        settings.email.api_uri = email_api.uri();
+        settings.email.api_uri = Some(email_api.uri());
+        settings.reservation.expiry_interval_seconds = 1;
+        settings.low_stock.check_interval_seconds = 1;
+        settings.email_outbox.poll_interval_milliseconds = 50;
+        settings.back_in_stock.check_interval_seconds = 1;
+        configure(&mut settings);
 
         let pool = TestApp::create_db(&settings.database);
+        let payment_provider = FakePaymentProvider::new(&settings.payment.webhook_secret);
 
         
         let application = Application::new(settings)
@@ -242,7 +1137,9 @@ impl TestApp {
                             .expect("Failed to build application");
 
 
-        tokio::task::spawn(application.server);
+        // Server runs on its own system so it can still be stopped while a test's runtime shuts down
+        let server = application.server.handle();
+        std::thread::spawn(move || actix_web::rt::System::new().block_on(application.server));
 
         let api_client = reqwest::Client::builder()
                             .redirect(Policy::none())
@@ -260,8 +1157,34 @@ impl TestApp {
             email_api,
             api_client,
             admin,
-            user
+            user,
+            payment_provider,
+            server
+        }
+    }
+
+    // Emails are delivered by the outbox worker, so wait until the expected number of matching ones arrived
+    pub async fn wait_for_emails(
+        &self,
+        expected: usize,
+        matches: impl Fn(&ReceiveEmailRequest) -> bool
+    ) -> Vec<ReceiveEmailRequest> {
+        let mut emails = Vec::new();
+        for _ in 0..50 {
+            emails = self.email_api.received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .filter_map(|request| request.body_json::<ReceiveEmailRequest>().ok())
+                .filter(|email| matches(email))
+                .collect();
+            if emails.len() >= expected {
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(100)).await;
         }
+
+        emails
     }
 
     // Get confirmation link from confirmation email
@@ -291,8 +1214,9 @@ pub async fn create_user_and_login(app: &TestApp) -> String{
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
+        .and(body_string_contains("Confirmation email"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
         .mount_as_scoped(&app.email_api)
@@ -304,8 +1228,9 @@ pub async fn create_user_and_login(app: &TestApp) -> String{
             .await
             .expect("Failed to send request to register endpoint");
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -1,6 +1,6 @@
 use crate::helpers::TestApp;
 use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
-use ecommerce::{models::InventoryItem, schema::inventory};
+use ecommerce::{db_interaction::InventoryItemWithDetails, models::{Category, InventoryItem}, pagination::Page, schema::inventory};
 
 #[actix_web::test]
 pub async fn add_item_to_inventory(){
@@ -11,7 +11,8 @@ pub async fn add_item_to_inventory(){
     let item = serde_json::json!({
         "name" : "example item",
         "amount" : "500",
-        "price" : "500"
+        "price" : "500",
+        "sku" : "EX-ITEM-1"
     });
 
     let response = app.post_inventory(item, access_token).await;
@@ -24,6 +25,8 @@ pub async fn add_item_to_inventory(){
             inventory::name.eq("example item")
                 .and(inventory::amount.eq(500 as i32))
                 .and(inventory::price.eq(500 as f64))
+                .and(inventory::sku.eq("EX-ITEM-1"))
+                .and(inventory::slug.eq("example-item"))
         )
         .count()
         .get_result::<i64>(&mut conn)
@@ -42,7 +45,8 @@ pub async fn get_item_from_inventory(){
     let item = serde_json::json!({
         "name" : "example item",
         "amount" : "500",
-        "price" : "500"
+        "price" : "500",
+        "sku" : "EX-ITEM-1"
     });
 
     let _post_response = app.post_inventory(item, access_token).await;
@@ -59,13 +63,446 @@ pub async fn get_item_from_inventory(){
         .unwrap();
 
 
-    let get_response: Vec<InventoryItem> = app.get_inventory(1, 5)
+    let get_response: Vec<InventoryItem> = app.get_inventory(5, None)
         .await
-        .json::<Vec<InventoryItem>>()
+        .json::<Page<InventoryItem>>()
         .await
-        .unwrap();
+        .unwrap()
+        .items;
 
     assert_eq!(get_response[0].name, "example item");
     assert_eq!(get_response[0].amount, Some(500_i32));
     assert_eq!(get_response[0].price, Some(500_f64));
 }
+
+
+#[actix_web::test]
+pub async fn add_item_with_duplicate_sku_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let item = serde_json::json!({
+        "name" : "example item",
+        "amount" : "500",
+        "price" : "500",
+        "sku" : "EX-ITEM-1"
+    });
+
+    let response = app.post_inventory(&item, access_token.clone()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let duplicate = serde_json::json!({
+        "name" : "another item",
+        "amount" : "5",
+        "price" : "50",
+        "sku" : "ex-item-1"
+    });
+
+    let response = app.post_inventory(duplicate, access_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn add_item_with_invalid_sku_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let item = serde_json::json!({
+        "name" : "example item",
+        "amount" : "500",
+        "price" : "500",
+        "sku" : "not a sku"
+    });
+
+    let response = app.post_inventory(item, access_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn get_inventory_includes_categories_and_attributes(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let apparel: Category = app.post_category(serde_json::json!({ "name": "Apparel" }), &access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    let shirts: Category = app.post_category(
+        serde_json::json!({ "name": "Shirts", "parent_id": apparel.category_id }),
+        &access_token
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    assert_eq!(shirts.parent_id, Some(apparel.category_id));
+    assert_eq!(shirts.slug, "shirts");
+
+    let item = serde_json::json!({
+        "name" : "Oxford Shirt",
+        "amount" : "10",
+        "price" : "40",
+        "sku" : "OXF-1",
+        "description" : "A button down shirt",
+        "brand" : "Acme"
+    });
+
+    app.post_inventory(item, access_token.clone()).await;
+
+    let mut conn = app.pool.get().unwrap();
+    let item_id = inventory::table
+        .filter(inventory::sku.eq("OXF-1"))
+        .select(inventory::item_id)
+        .get_result::<uuid::Uuid>(&mut conn)
+        .unwrap();
+
+    let response = app.put_item_categories(
+        item_id,
+        serde_json::json!({ "category_ids": [apparel.category_id, shirts.category_id] }),
+        &access_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.put_item_attributes(
+        item_id,
+        serde_json::json!({ "material": "cotton", "fit": "slim" }),
+        &access_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let get_response: Vec<InventoryItemWithDetails> = app.get_inventory(5, None)
+        .await
+        .json::<Page<InventoryItemWithDetails>>()
+        .await
+        .unwrap()
+        .items;
+
+    assert_eq!(get_response.len(), 1);
+    assert_eq!(get_response[0].item.slug, "oxford-shirt");
+    assert_eq!(get_response[0].item.brand, Some("Acme".to_string()));
+    assert_eq!(get_response[0].categories.len(), 2);
+    assert_eq!(get_response[0].attributes.get("material"), Some(&"cotton".to_string()));
+    assert_eq!(get_response[0].attributes.len(), 2);
+    assert_eq!(get_response[0].variants.len(), 1);
+    assert_eq!(get_response[0].variants[0].variant.sku, "OXF-1");
+}
+
+#[actix_web::test]
+pub async fn get_inventory_nests_variants_with_options(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let default_variant = app.insert_inventory_item("hoodie", 0, 60_f64);
+
+    for (sku, size, amount) in [("HOODIE-M", "M", 3), ("HOODIE-L", "L", 5)] {
+        let response = app.post_variant(
+            default_variant.item_id,
+            serde_json::json!({
+                "sku": sku,
+                "name": format!("Size {}", size),
+                "amount": amount,
+                "options": { "size": size, "color": "grey" }
+            }),
+            &access_token
+        ).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let duplicate = app.post_variant(
+        default_variant.item_id,
+        serde_json::json!({ "sku": "hoodie-m", "name": "Size M", "amount": 1 }),
+        &access_token
+    ).await;
+    assert_eq!(duplicate.status().as_u16(), 400);
+
+    let get_response: Vec<InventoryItemWithDetails> = app.get_inventory(5, None)
+        .await
+        .json::<Page<InventoryItemWithDetails>>()
+        .await
+        .unwrap()
+        .items;
+
+    assert_eq!(get_response[0].item.amount, Some(8));
+    assert_eq!(get_response[0].variants.len(), 3);
+
+    let large = get_response[0].variants.iter()
+        .find(|v| v.variant.sku == "HOODIE-L")
+        .unwrap();
+
+    assert_eq!(large.variant.amount, 5);
+    assert_eq!(large.options.get("size"), Some(&"L".to_string()));
+    assert_eq!(large.options.get("color"), Some(&"grey".to_string()));
+}
+
+#[actix_web::test]
+pub async fn assigning_unknown_category_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let item = serde_json::json!({
+        "name" : "example item",
+        "amount" : "500",
+        "price" : "500",
+        "sku" : "EX-ITEM-1"
+    });
+
+    app.post_inventory(item, access_token.clone()).await;
+
+    let mut conn = app.pool.get().unwrap();
+    let item_id = inventory::table
+        .select(inventory::item_id)
+        .get_result::<uuid::Uuid>(&mut conn)
+        .unwrap();
+
+    let response = app.put_item_categories(
+        item_id,
+        serde_json::json!({ "category_ids": [uuid::Uuid::new_v4()] }),
+        &access_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.put_item_categories(
+        uuid::Uuid::new_v4(),
+        serde_json::json!({ "category_ids": [] }),
+        &access_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+pub async fn get_inventory_pages_through_all_items(){
+    let app = TestApp::spawn_app().await;
+
+    for index in 0..5 {
+        app.insert_inventory_item(&format!("item {}", index), 10, 10_f64);
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = app.get_inventory(2, cursor.as_deref())
+            .await
+            .json::<Page<InventoryItemWithDetails>>()
+            .await
+            .unwrap();
+
+        assert_eq!(page.total, 5);
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.into_iter().map(|item| item.item.item_id));
+
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break
+        }
+    }
+
+    let mut unique = seen.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(seen.len(), 5);
+    assert_eq!(unique.len(), 5);
+}
+
+#[actix_web::test]
+pub async fn get_inventory_with_invalid_pagination_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let response = app.get_inventory(0, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_inventory(1000, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_inventory(5, Some("garbage")).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+// Post an item to inventory returning its id
+async fn post_searchable_item(
+    app: &TestApp,
+    access_token: &str,
+    sku: &str,
+    name: &str,
+    description: &str,
+    price: f64,
+    amount: i32
+) -> uuid::Uuid {
+    let item = serde_json::json!({
+        "name" : name,
+        "amount" : amount.to_string(),
+        "price" : price.to_string(),
+        "sku" : sku,
+        "description" : description
+    });
+
+    let response = app.post_inventory(item, access_token.to_string()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut conn = app.pool.get().unwrap();
+    inventory::table
+        .filter(inventory::sku.eq(sku))
+        .select(inventory::item_id)
+        .get_result::<uuid::Uuid>(&mut conn)
+        .unwrap()
+}
+
+// Extract skus of search results in the order they were returned
+async fn search_skus(app: &TestApp, query: serde_json::Value) -> Vec<String> {
+    let response = app.search_inventory(query).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Page<InventoryItemWithDetails>>()
+        .await
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|item| item.item.sku)
+        .collect()
+}
+
+#[actix_web::test]
+pub async fn search_ranks_name_matches_above_description_matches(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    post_searchable_item(&app, &access_token, "SOCKS-1", "Sports Socks", "Pairs well with running shoes", 5.0, 10).await;
+    post_searchable_item(&app, &access_token, "SHOES-1", "Running Shoes", "Lightweight trainers", 80.0, 10).await;
+    post_searchable_item(&app, &access_token, "MUG-1", "Coffee Mug", "Ceramic mug", 12.0, 10).await;
+
+    let skus = search_skus(&app, serde_json::json!({ "q": "running", "limit": 10 })).await;
+
+    assert_eq!(skus, vec!["SHOES-1", "SOCKS-1"]);
+}
+
+#[actix_web::test]
+pub async fn search_filters_by_price_range_and_stock(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    post_searchable_item(&app, &access_token, "LAMP-1", "Desk Lamp", "", 15.0, 3).await;
+    post_searchable_item(&app, &access_token, "LAMP-2", "Floor Lamp", "", 60.0, 0).await;
+    post_searchable_item(&app, &access_token, "LAMP-3", "Reading Lamp", "", 35.0, 5).await;
+    post_searchable_item(&app, &access_token, "LAMP-4", "Table Lamp", "", 120.0, 5).await;
+
+    let skus = search_skus(&app, serde_json::json!({
+        "min_price": 20,
+        "max_price": 100,
+        "sort": "price_asc",
+        "limit": 10
+    })).await;
+    assert_eq!(skus, vec!["LAMP-3", "LAMP-2"]);
+
+    let skus = search_skus(&app, serde_json::json!({
+        "max_price": 100,
+        "in_stock": true,
+        "sort": "price_desc",
+        "limit": 10
+    })).await;
+    assert_eq!(skus, vec!["LAMP-3", "LAMP-1"]);
+}
+
+#[actix_web::test]
+pub async fn search_by_category_includes_subcategories(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    let apparel: Category = app.post_category(serde_json::json!({ "name": "Apparel" }), &access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    let shirts: Category = app.post_category(
+        serde_json::json!({ "name": "Shirts", "parent_id": apparel.category_id }),
+        &access_token
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    let scarf_id = post_searchable_item(&app, &access_token, "SCARF-1", "Wool Scarf", "", 25.0, 4).await;
+    let shirt_id = post_searchable_item(&app, &access_token, "SHIRT-1", "Linen Shirt", "", 30.0, 4).await;
+    post_searchable_item(&app, &access_token, "KETTLE-1", "Kettle", "", 40.0, 4).await;
+
+    app.put_item_categories(scarf_id, serde_json::json!({ "category_ids": [apparel.category_id] }), &access_token).await;
+    app.put_item_categories(shirt_id, serde_json::json!({ "category_ids": [shirts.category_id] }), &access_token).await;
+
+    let skus = search_skus(&app, serde_json::json!({
+        "category": apparel.category_id,
+        "sort": "name",
+        "limit": 10
+    })).await;
+    assert_eq!(skus, vec!["SHIRT-1", "SCARF-1"]);
+
+    let skus = search_skus(&app, serde_json::json!({
+        "category": shirts.category_id,
+        "limit": 10
+    })).await;
+    assert_eq!(skus, vec!["SHIRT-1"]);
+}
+
+#[actix_web::test]
+pub async fn search_with_inverted_price_range_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let response = app.search_inventory(serde_json::json!({
+        "min_price": 50,
+        "max_price": 10,
+        "limit": 10
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn search_with_unknown_sort_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let response = app.search_inventory(serde_json::json!({
+        "sort": "popularity",
+        "limit": 10
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+pub async fn search_pages_follow_sort_order(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = app.login_admin().await;
+
+    post_searchable_item(&app, &access_token, "CUP-1", "Red Cup", "", 3.0, 1).await;
+    post_searchable_item(&app, &access_token, "CUP-2", "Blue Cup", "", 1.0, 1).await;
+    post_searchable_item(&app, &access_token, "CUP-3", "Green Cup", "", 2.0, 1).await;
+
+    let first_page = app.search_inventory(serde_json::json!({ "sort": "price_asc", "limit": 2 }))
+        .await
+        .json::<Page<InventoryItemWithDetails>>()
+        .await
+        .unwrap();
+
+    let skus: Vec<String> = first_page.items.into_iter().map(|item| item.item.sku).collect();
+    assert_eq!(skus, vec!["CUP-2", "CUP-3"]);
+    assert_eq!(first_page.total, 3);
+
+    let cursor = first_page.next_cursor.unwrap();
+    let skus = search_skus(&app, serde_json::json!({ "sort": "price_asc", "limit": 2, "cursor": cursor })).await;
+    assert_eq!(skus, vec!["CUP-1"]);
+
+    // Cursor issued for one sort order can't be used with another
+    let response = app.search_inventory(serde_json::json!({ "sort": "name", "limit": 2, "cursor": cursor })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -2,7 +2,7 @@ use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 use ecommerce::models::User;
 use wiremock::{matchers::{header_exists, path}, Mock, ResponseTemplate};
 
-use crate::{helpers::{TestApp, LoginResponse}, registration::ReceiveEmailRequest};
+use crate::helpers::{TestApp, LoginResponse};
 
 #[actix_web::test]
 async fn post_login_with_correct_data(){
@@ -14,7 +14,7 @@ async fn post_login_with_correct_data(){
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -30,8 +30,9 @@ async fn post_login_with_correct_data(){
 
     assert_eq!(response.status().as_u16(), 200);
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -71,7 +72,7 @@ async fn post_login_with_incorrect_data(){
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -87,8 +88,9 @@ async fn post_login_with_incorrect_data(){
 
     assert_eq!(response.status().as_u16(), 200);
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::{QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::LowStockItem, pagination::Page, schema::inventory};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::{helpers::{create_user_and_login, TestApp}, registration::ReceiveEmailRequest};
+
+async fn low_stock_skus(app: &TestApp, access_token: &String) -> Vec<String> {
+    let response = app.get_low_stock(20, None, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Page<LowStockItem>>()
+        .await
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|item| item.sku)
+        .collect()
+}
+
+fn low_stock_alerted_at(app: &TestApp, item_id: Uuid) -> Option<DateTime<Utc>> {
+    let mut conn = app.pool.get().unwrap();
+    inventory::table
+        .find(item_id)
+        .select(inventory::low_stock_alerted_at)
+        .get_result(&mut conn)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn low_stock_lists_items_at_or_below_their_threshold(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let low = app.insert_inventory_item("Low item", 5, 10.0);
+    let plenty = app.insert_inventory_item("Plenty item", 10, 10.0);
+    let untracked = app.insert_inventory_item("Untracked item", 0, 10.0);
+
+    for variant in [&low, &plenty] {
+        let response = app.put_reorder_threshold(variant.item_id, Some(5), &admin_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let skus = low_stock_skus(&app, &admin_token).await;
+    assert_eq!(skus, vec![low.sku.clone()]);
+    assert!(!skus.contains(&untracked.sku));
+
+    // Clearing the threshold stops tracking the item
+    let response = app.put_reorder_threshold(low.item_id, None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(low_stock_skus(&app, &admin_token).await.is_empty());
+}
+
+#[actix_web::test]
+async fn reorder_threshold_is_validated(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let variant = app.insert_inventory_item("Threshold item", 5, 10.0);
+
+    let response = app.put_reorder_threshold(variant.item_id, Some(-1), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.put_reorder_threshold(Uuid::new_v4(), Some(1), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.put_reorder_threshold(variant.item_id, Some(1), &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_low_stock(20, None, &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn crossing_threshold_sends_single_alert_to_admins(){
+    let app = TestApp::spawn_app_with(|settings| {
+        settings.low_stock.alert_recipients = vec!["alerts@example.com".to_string()];
+    }).await;
+    let admin_token = app.login_admin().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_api)
+        .await;
+
+    let variant = app.insert_inventory_item("Alerted item", 2, 10.0);
+    let response = app.put_reorder_threshold(variant.item_id, Some(3), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut alerted_at = None;
+    for _ in 0..50 {
+        alerted_at = low_stock_alerted_at(&app, variant.item_id);
+        if alerted_at.is_some() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(alerted_at.is_some());
+
+    let alerts = app.wait_for_emails(1, |email| email.to == "alerts@example.com").await;
+    assert_eq!(alerts.len(), 1);
+
+    // Item stays low but isn't alerted on following checks
+    actix_web::rt::time::sleep(Duration::from_millis(1500)).await;
+
+    let requests = app.email_api.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+
+    let email: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    assert_eq!(email.to, "alerts@example.com");
+    assert!(email.text_body.contains(&variant.sku));
+}
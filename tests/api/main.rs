@@ -5,3 +5,21 @@ pub mod login;
 pub mod user_profile;
 pub mod inventory;
 pub mod order;
+pub mod catalog;
+pub mod stock;
+pub mod reservation;
+pub mod low_stock;
+pub mod warehouse;
+pub mod backorder;
+pub mod payment;
+pub mod refund;
+pub mod returns;
+pub mod coupon;
+pub mod promotion;
+pub mod tax;
+pub mod shipping;
+pub mod shipment;
+pub mod order_notification;
+pub mod email_outbox;
+pub mod email_preferences;
+pub mod back_in_stock;
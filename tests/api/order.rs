@@ -1,6 +1,6 @@
 use chrono::Utc;
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
-use ecommerce::{models::{InventoryItem, Order, OrderQuery}, db_interaction::OrderWithItems, schema::{inventory, orders}};
+use ecommerce::{models::{Order, OrderQuery, ProductVariant}, db_interaction::OrderWithItems, pagination::Page, schema::{inventory, orders, product_variants}};
 use uuid::Uuid;
 
 use crate::helpers::{create_user_and_login, TestApp};
@@ -9,54 +9,29 @@ use crate::helpers::{create_user_and_login, TestApp};
 pub async fn post_order_creates_order(){
     let app = TestApp::spawn_app().await;
 
-    let inventory_items = vec![
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 1".to_string(),
-            amount: Some(50_i32),
-            price: Some(47_f64)
-        },
-
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 2".to_string(),
-            amount: Some(75_i32),
-            price: Some(100_f64)
-        },
-
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 3".to_string(),
-            amount: Some(28_i32),
-            price: Some(60_f64)
-        }
+    let variants = [
+        app.insert_inventory_item("item 1", 50, 47_f64),
+        app.insert_inventory_item("item 2", 75, 100_f64),
+        app.insert_inventory_item("item 3", 28, 60_f64)
     ];
 
-    let mut conn = app.pool.get().unwrap();
-    for item in inventory_items.iter(){
-        diesel::insert_into(inventory::table)
-            .values(item)
-            .execute(&mut conn)
-            .unwrap();
-    }
-
     let access_token = create_user_and_login(&app).await;
 
     let order_data = serde_json::json!([
         {
-            "item_id": inventory_items[0].item_id,
+            "variant_id": variants[0].variant_id,
             "amount": 5_i32
         },
 
 
         {
-            "item_id": inventory_items[1].item_id,
+            "variant_id": variants[1].variant_id,
             "amount": 8_i32
         },
 
 
         {
-            "item_id": inventory_items[2].item_id,
+            "variant_id": variants[2].variant_id,
             "amount": 40_i32
         }
     ]);
@@ -74,9 +49,9 @@ pub async fn post_order_creates_order(){
 
     assert_eq!(body.len(), 2);
     
-    let ideal = vec![inventory_items[0].item_id, inventory_items[1].item_id];
-    for item_id in body.iter(){
-        assert!(ideal.contains(item_id))
+    let ideal = vec![variants[0].variant_id, variants[1].variant_id];
+    for variant_id in body.iter(){
+        assert!(ideal.contains(variant_id))
     }
 }
 
@@ -84,54 +59,29 @@ pub async fn post_order_creates_order(){
 pub async fn get_order_returns_orders(){
     let app = TestApp::spawn_app().await;
 
-    let inventory_items = vec![
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 1".to_string(),
-            amount: Some(50_i32),
-            price: Some(47_f64)
-        },
-
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 2".to_string(),
-            amount: Some(75_i32),
-            price: Some(100_f64)
-        },
-
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 3".to_string(),
-            amount: Some(28_i32),
-            price: Some(60_f64)
-        }
+    let variants = [
+        app.insert_inventory_item("item 1", 50, 47_f64),
+        app.insert_inventory_item("item 2", 75, 100_f64),
+        app.insert_inventory_item("item 3", 28, 60_f64)
     ];
 
-    let mut conn = app.pool.get().unwrap();
-    for item in inventory_items.iter(){
-        diesel::insert_into(inventory::table)
-            .values(item)
-            .execute(&mut conn)
-            .unwrap();
-    }
-
     let access_token = create_user_and_login(&app).await;
 
     let order_data = serde_json::json!([
         {
-            "item_id": inventory_items[0].item_id,
+            "variant_id": variants[0].variant_id,
             "amount": 5_i32
         },
 
 
         {
-            "item_id": inventory_items[1].item_id,
+            "variant_id": variants[1].variant_id,
             "amount": 8_i32
         },
 
 
         {
-            "item_id": inventory_items[2].item_id,
+            "variant_id": variants[2].variant_id,
             "amount": 40_i32
         }
     ]);
@@ -149,17 +99,18 @@ pub async fn get_order_returns_orders(){
 
     assert_eq!(body.len(), 2);
     
-    let ideal = vec![inventory_items[0].item_id, inventory_items[1].item_id];
-    for item_id in body.iter(){
-        assert!(ideal.contains(item_id))
+    let ideal = vec![variants[0].variant_id, variants[1].variant_id];
+    for variant_id in body.iter(){
+        assert!(ideal.contains(variant_id))
     }
 
     
-    let get_orders = app.get_orders(1, 10, &access_token)
+    let get_orders = app.get_orders(10, None, &access_token)
                         .await
-                        .json::<Vec<OrderWithItems>>()
+                        .json::<Page<OrderWithItems>>()
                         .await
-                        .unwrap();
+                        .unwrap()
+                        .items;
 
     assert_eq!(get_orders.len(), 1);
     assert_eq!(get_orders[0].items.len(), 2);
@@ -169,54 +120,35 @@ pub async fn get_order_returns_orders(){
 async fn concurrent_orders_is_consistent(){
     let app = TestApp::spawn_app().await;
 
-    let inventory_items = vec![
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 1".to_string(),
-            amount: Some(50_i32),
-            price: Some(47_f64)
-        },
-
-        InventoryItem{
-            item_id: Uuid::new_v4(),
-            name: "item 2".to_string(),
-            amount: Some(2_i32),
-            price: Some(100_f64)
-        },
+    let variants = [
+        app.insert_inventory_item("item 1", 50, 47_f64),
+        app.insert_inventory_item("item 2", 2, 100_f64),
     ];
 
-    let mut conn = app.pool.get().unwrap();
-    for item in inventory_items.iter(){
-        diesel::insert_into(inventory::table)
-            .values(item)
-            .execute(&mut conn)
-            .unwrap();
-    }
-
     let access_token = create_user_and_login(&app).await;
 
     let order_data = serde_json::json!([
         {
-            "item_id": inventory_items[0].item_id,
+            "variant_id": variants[0].variant_id,
             "amount": 5_i32
         },
 
 
         {
-            "item_id": inventory_items[1].item_id,
+            "variant_id": variants[1].variant_id,
             "amount": 2_i32
         },
     ]);
 
     let order_data2 = serde_json::json!([
         {
-            "item_id": inventory_items[0].item_id,
+            "variant_id": variants[0].variant_id,
             "amount": 5_i32
         },
 
 
         {
-            "item_id": inventory_items[1].item_id,
+            "variant_id": variants[1].variant_id,
             "amount": 1_i32
         },
     ]);
@@ -239,31 +171,114 @@ async fn concurrent_orders_is_consistent(){
 }
 
 #[actix_web::test]
-async fn update_order_status(){
+async fn post_order_decrements_stock_of_ordered_variant(){
     let app = TestApp::spawn_app().await;
+
+    let default_variant = app.insert_inventory_item("t-shirt", 10, 20_f64);
+    let access_token = app.login_admin().await;
+
+    let large: ProductVariant = app.post_variant(
+        default_variant.item_id,
+        serde_json::json!({
+            "sku": "TSHIRT-L",
+            "name": "Large",
+            "price": 25.0,
+            "amount": 4,
+            "options": { "size": "L" }
+        }),
+        &access_token
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    let user_token = create_user_and_login(&app).await;
+
+    let order_data = serde_json::json!([
+        {
+            "variant_id": large.variant_id,
+            "amount": 3_i32
+        }
+    ]);
+
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&order_data)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+
     let mut conn = app.pool.get().unwrap();
 
-    let order_id = Uuid::new_v4();
-    let test_order = Order{
-        order_id: order_id.clone(),
-        user_id: app.user.user_id,
-        order_date: Utc::now(),
-        status: "pending".to_string()
-    };
-    
-    diesel::insert_into(orders::table)
-        .values(&test_order)
-        .execute(&mut conn)
+    let variant_amounts: Vec<(String, i32)> = product_variants::table
+        .filter(product_variants::item_id.eq(default_variant.item_id))
+        .select((product_variants::sku, product_variants::amount))
+        .order(product_variants::sku)
+        .load(&mut conn)
         .unwrap();
 
+    assert!(variant_amounts.contains(&(default_variant.sku.clone(), 10)));
+    assert!(variant_amounts.contains(&("TSHIRT-L".to_string(), 1)));
+
+    let item_amount: Option<i32> = inventory::table
+        .filter(inventory::item_id.eq(default_variant.item_id))
+        .select(inventory::amount)
+        .get_result(&mut conn)
+        .unwrap();
+
+    assert_eq!(item_amount, Some(11));
+
+    let get_orders = app.get_orders(10, None, &user_token)
+                        .await
+                        .json::<Page<OrderWithItems>>()
+                        .await
+                        .unwrap()
+                        .items;
+
+    assert_eq!(get_orders[0].items[0].variant_id, large.variant_id);
+    assert_eq!(get_orders[0].items[0].item_id, default_variant.item_id);
+}
+
+#[actix_web::test]
+async fn update_order_status(){
+    let app = TestApp::spawn_app().await;
+    let mut conn = app.pool.get().unwrap();
     let access_token = app.login_admin().await;
 
-    let put_order_request = serde_json::json!({
-        "order_id": order_id,
-        "status": "shipped"
-    });
+    let variant = app.insert_inventory_item("Delivered item", 5, 10_f64);
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&access_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
 
-    let response = app.put_orders(put_order_request, &access_token).await;
+    let order_id: Uuid = orders::table
+                            .filter(orders::user_id.eq(app.admin.user_id))
+                            .select(orders::order_id)
+                            .get_result(&mut conn)
+                            .unwrap();
+
+    // Statuses other than delivered follow shipments
+    for status in ["pending", "shipped"] {
+        let response = app.put_orders(serde_json::json!({ "order_id": order_id, "status": status }), &access_token).await;
+        assert_eq!(response.status().as_u16(), 409, "Order was set to {}", status);
+    }
+
+    let response = app.put_orders(serde_json::json!({ "order_id": order_id, "status": "delivered" }), &access_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.post_shipment(order_id, serde_json::json!({
+        "carrier": "DHL",
+        "tracking_number": "DHL-1"
+    }), &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.put_orders(serde_json::json!({ "order_id": order_id, "status": "delivered" }), &access_token).await;
     assert_eq!(response.status().as_u16(), 200);
 
     let updated_order: OrderQuery = orders::table
@@ -271,11 +286,9 @@ async fn update_order_status(){
                             .get_result::<OrderQuery>(&mut conn)
                             .unwrap();
 
-    assert_eq!(updated_order.status, "shipped")
+    assert_eq!(updated_order.status, "delivered")
 }
 
-
-
 #[actix_web::test]
 async fn delete_order_works_for_admin(){
     let app = TestApp::spawn_app().await;
@@ -311,3 +324,106 @@ async fn delete_order_works_for_admin(){
 
     assert_eq!(orders.len(), 0)
 }
+
+#[actix_web::test]
+async fn delete_order_with_shipments_is_refused(){
+    let app = TestApp::spawn_app().await;
+    let mut conn = app.pool.get().unwrap();
+    let access_token = app.login_admin().await;
+
+    let variant = app.insert_inventory_item("Half shipped item", 5, 10_f64);
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&access_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 3 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = app.get_orders(1, None, &access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0);
+    let response = app.post_shipment(order.order_id, serde_json::json!({
+        "carrier": "DHL",
+        "tracking_number": "DHL-2",
+        "lines": [{ "order_item_id": order.items[0].order_item_id, "quantity": 1 }]
+    }), &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_orders_admin(serde_json::json!({ "order_id": order.order_id }), &access_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    // Unshipped stock stays allocated to the order
+    let amount: i32 = product_variants::table
+                            .find(variant.variant_id)
+                            .select(product_variants::amount)
+                            .get_result(&mut conn)
+                            .unwrap();
+    assert_eq!(amount, 2);
+}
+
+#[actix_web::test]
+async fn get_order_pages_from_newest_to_oldest(){
+    let app = TestApp::spawn_app().await;
+
+    let variant = app.insert_inventory_item("item 1", 50, 47_f64);
+
+    let access_token = create_user_and_login(&app).await;
+
+    for amount in 1..=3 {
+        let order_data = serde_json::json!([
+            {
+                "variant_id": variant.variant_id,
+                "amount": amount
+            }
+        ]);
+
+        let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+            .bearer_auth(&access_token)
+            .json(&order_data)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app.get_orders(2, None, &access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let link = response.headers()[reqwest::header::LINK].to_str().unwrap().to_string();
+    let first_page = response.json::<Page<OrderWithItems>>().await.unwrap();
+
+    assert_eq!(first_page.total, 3);
+    assert_eq!(first_page.items.len(), 2);
+    assert_eq!(first_page.items[0].items[0].quantity, 3);
+    assert_eq!(first_page.items[1].items[0].quantity, 2);
+
+    let next_cursor = first_page.next_cursor.unwrap();
+    assert!(link.contains(&format!("cursor={}>; rel=\"next\"", next_cursor)));
+
+    let second_page = app.get_orders(2, Some(&next_cursor), &access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap();
+
+    assert_eq!(second_page.items.len(), 1);
+    assert_eq!(second_page.items[0].items[0].quantity, 1);
+    assert!(second_page.next_cursor.is_none());
+}
+
+#[actix_web::test]
+async fn get_order_with_invalid_limit_is_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let access_token = create_user_and_login(&app).await;
+
+    for limit in [0, -1, 100_000] {
+        let response = app.get_orders(limit, None, &access_token).await;
+        assert_eq!(response.status().as_u16(), 400, "limit {} was accepted", limit);
+    }
+}
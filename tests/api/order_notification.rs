@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use ecommerce::{db_interaction::OrderWithItems, pagination::Page};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::TestApp;
+
+async fn post_order(app: &TestApp, variant_id: Uuid, amount: i32, access_token: &String) -> Uuid {
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": amount }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+        .order_id
+}
+
+async fn mount_email(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+}
+
+#[actix_web::test]
+async fn placing_order_sends_confirmation(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Confirmed lamp", 5, 12.5);
+    let order_id = post_order(&app, variant.variant_id, 2, &admin_token).await;
+
+    let emails = app.wait_for_emails(1, |email| email.subject == "We received your order").await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, app.admin.email);
+    assert!(emails[0].text_body.contains(&order_id.to_string()));
+    assert!(emails[0].text_body.contains(&format!("2 x Confirmed lamp ({}): 25.00 INR", variant.sku)));
+    assert!(emails[0].text_body.contains("Total: 25.00 INR"));
+}
+
+#[actix_web::test]
+async fn status_changes_notify_customer_once(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Notified lamp", 5, 10.0);
+    let order_id = post_order(&app, variant.variant_id, 1, &admin_token).await;
+
+    let response = app.post_shipment(order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "DHL-777" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.wait_for_emails(1, |email| email.subject == "Your order is on its way").await;
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].text_body.contains("Tracking number (DHL): DHL-777"));
+
+    for _ in 0..2 {
+        let response = app.put_orders(serde_json::json!({ "order_id": order_id, "status": "delivered" }), &admin_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Setting the same status again doesn't email customer twice
+    actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+    let emails = app.wait_for_emails(1, |email| email.subject == "Your order was delivered").await;
+    assert_eq!(emails.len(), 1);
+}
+
+#[actix_web::test]
+async fn cancelling_order_notifies_customer(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Cancelled lamp", 5, 10.0);
+    let order_id = post_order(&app, variant.variant_id, 3, &admin_token).await;
+
+    let response = app.api_client.delete(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "order_id": order_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.wait_for_emails(1, |email| email.subject == "Your order was cancelled").await;
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].text_body.contains(&order_id.to_string()));
+    assert!(emails[0].text_body.contains("3 x Cancelled lamp"));
+}
@@ -0,0 +1,219 @@
+use ecommerce::{db_interaction::OrderWithItems, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn place_order(app: &TestApp, variant_id: Uuid, amount: i32, access_token: &String) -> OrderWithItems {
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": amount }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    latest_order(app, access_token).await
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+async fn send_event(app: &TestApp, event_type: &str, provider_reference: &str) -> reqwest::Response {
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": event_type,
+        "provider_reference": provider_reference
+    }))
+    .unwrap();
+
+    app.post_payment_webhook(&body, &app.payment_provider.sign(&body)).await
+}
+
+#[actix_web::test]
+async fn checkout_payment_creates_intent_for_order_total(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Paid item", 10, 12.5);
+
+    let order = place_order(&app, variant.variant_id, 3, &user_token).await;
+    assert!(order.paid_at.is_none());
+
+    let response = app.post_checkout_payment(order.order_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let intent = response.json::<CheckoutPaymentResponse>().await.unwrap();
+    assert_eq!(intent.payment.amount_cents, 3750);
+    assert_eq!(intent.payment.currency, "INR");
+    assert_eq!(intent.payment.status, "requires_payment");
+    assert!(intent.client_secret.is_some());
+
+    // Asking again returns the payment already in flight
+    let again = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_eq!(again.payment.payment_id, intent.payment.payment_id);
+    assert!(again.client_secret.is_none());
+}
+
+#[actix_web::test]
+async fn checkout_payment_is_only_for_own_unpaid_orders(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+    let admin_token = app.login_admin().await;
+    let variant = app.insert_inventory_item("Owned item", 10, 5.0);
+
+    let order = place_order(&app, variant.variant_id, 1, &user_token).await;
+
+    let response = app.post_checkout_payment(order.order_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_checkout_payment(Uuid::new_v4(), &user_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    let response = send_event(&app, "payment.captured", &intent.payment.provider_reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_checkout_payment(order.order_id, &user_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn captured_webhook_marks_order_paid(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Captured item", 10, 5.0);
+
+    let order = place_order(&app, variant.variant_id, 2, &user_token).await;
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    let reference = intent.payment.provider_reference;
+
+    let response = send_event(&app, "payment.authorized", &reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(latest_order(&app, &user_token).await.paid_at.is_none());
+
+    let response = send_event(&app, "payment.captured", &reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(latest_order(&app, &user_token).await.paid_at.is_some());
+
+    // Late failure after capture is ignored
+    let response = send_event(&app, "payment.failed", &reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(latest_order(&app, &user_token).await.paid_at.is_some());
+}
+
+#[actix_web::test]
+async fn paid_order_cant_be_deleted(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Kept item", 10, 5.0);
+
+    let order = place_order(&app, variant.variant_id, 2, &user_token).await;
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+
+    let response = send_event(&app, "payment.captured", &intent.payment.provider_reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_orders_admin(serde_json::json!({ "order_id": order.order_id }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let order = latest_order(&app, &user_token).await;
+    assert!(order.paid_at.is_some());
+    assert_eq!(order.items[0].quantity, 2);
+
+    // Orders with only a failed attempt can still be deleted
+    let unpaid = place_order(&app, variant.variant_id, 1, &user_token).await;
+    let intent = app.post_checkout_payment(unpaid.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+
+    let response = send_event(&app, "payment.failed", &intent.payment.provider_reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_orders_admin(serde_json::json!({ "order_id": unpaid.order_id }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(latest_order(&app, &user_token).await.order_id, order.order_id);
+}
+
+#[actix_web::test]
+async fn failed_payment_can_be_retried(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Retried item", 10, 5.0);
+
+    let order = place_order(&app, variant.variant_id, 1, &user_token).await;
+    let first = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+
+    let response = send_event(&app, "payment.failed", &first.payment.provider_reference).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_ne!(second.payment.payment_id, first.payment.payment_id);
+    assert!(second.client_secret.is_some());
+}
+
+#[actix_web::test]
+async fn webhook_with_bad_signature_or_body_is_rejected(){
+    let app = TestApp::spawn_app().await;
+    let user_token = create_user_and_login(&app).await;
+    let variant = app.insert_inventory_item("Forged item", 10, 5.0);
+
+    let order = place_order(&app, variant.variant_id, 1, &user_token).await;
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": "payment.captured",
+        "provider_reference": intent.payment.provider_reference
+    }))
+    .unwrap();
+
+    let response = app.post_payment_webhook(&body, "deadbeef").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let forged = app.payment_provider.sign(b"some other body");
+    let response = app.post_payment_webhook(&body, &forged).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(latest_order(&app, &user_token).await.paid_at.is_none());
+
+    let malformed = b"{\"type\":\"payment.refunded\"}";
+    let response = app.post_payment_webhook(malformed, &app.payment_provider.sign(malformed)).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = send_event(&app, "payment.captured", "fake_pi_unknown").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
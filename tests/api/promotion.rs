@@ -0,0 +1,227 @@
+use chrono::{Duration, Utc};
+use ecommerce::{db_interaction::{OrderWithItems, PlacedOrder, PromotionWithRule}, pagination::Page, routes::checkout::{payment::CheckoutPaymentResponse, post::CheckoutResponse}};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, body: serde_json::Value, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+async fn create_promotion(app: &TestApp, body: serde_json::Value, access_token: &String) -> PromotionWithRule {
+    let response = app.post_promotion(body, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<PromotionWithRule>().await.unwrap()
+}
+
+#[actix_web::test]
+async fn promotion_settings_are_validated_and_managed(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let item = app.insert_inventory_item("Promoted item", 10, 5.0);
+
+    let test_cases = vec![
+        (serde_json::json!({ "name": " ", "rule": { "type": "tiered", "item_ids": [item.item_id], "tiers": [{ "min_quantity": 2, "percent_off": 10 }] } }), 400, "empty name"),
+        (serde_json::json!({ "name": "Free", "rule": { "type": "buy_x_get_y", "item_ids": [item.item_id], "buy_quantity": 0, "get_quantity": 1, "percent_off": 100 } }), 400, "zero buy quantity"),
+        (serde_json::json!({ "name": "Tiers", "rule": { "type": "tiered", "item_ids": [item.item_id], "tiers": [] } }), 400, "no tiers"),
+        (serde_json::json!({ "name": "Kit", "rule": { "type": "bundle", "items": [], "price_cents": 100 } }), 400, "empty bundle"),
+        (serde_json::json!({ "name": "Ghost", "rule": { "type": "tiered", "item_ids": [Uuid::new_v4()], "tiers": [{ "min_quantity": 2, "percent_off": 10 }] } }), 400, "unknown item"),
+        (serde_json::json!({
+            "name": "Backwards",
+            "starts_at": Utc::now(),
+            "ends_at": Utc::now() - Duration::days(1),
+            "rule": { "type": "tiered", "item_ids": [item.item_id], "tiers": [{ "min_quantity": 2, "percent_off": 10 }] }
+        }), 400, "window ending before it starts")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.post_promotion(body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Promotion wasn't rejected for {}", description);
+    }
+
+    let promotion = create_promotion(&app, serde_json::json!({
+        "name": "Bulk buy",
+        "priority": 5,
+        "rule": { "type": "tiered", "item_ids": [item.item_id], "tiers": [{ "min_quantity": 3, "percent_off": 10 }] }
+    }), &admin_token).await;
+    create_promotion(&app, serde_json::json!({
+        "name": "Two for one",
+        "rule": { "type": "buy_x_get_y", "item_ids": [item.item_id], "buy_quantity": 1, "get_quantity": 1, "percent_off": 100 }
+    }), &admin_token).await;
+
+    let response = app.put_promotion(promotion.promotion_id, serde_json::json!({
+        "name": "Bulk buy",
+        "priority": 5,
+        "active": false,
+        "rule": { "type": "bundle", "items": [{ "item_id": item.item_id, "quantity": 3 }], "price_cents": 1200 }
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let updated = response.json::<PromotionWithRule>().await.unwrap();
+    assert!(!updated.active);
+    assert!(matches!(updated.rule, ecommerce::domain::promotion::PromotionRule::Bundle { price_cents: 1200, .. }));
+
+    // Promotions are listed in the order they are evaluated
+    let page = app.get_promotions(&admin_token)
+        .await
+        .json::<Page<PromotionWithRule>>()
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.items[0].name, "Two for one");
+
+    let response = app.delete_promotion(promotion.promotion_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_promotion(promotion.promotion_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.get_promotions(&user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn promotions_are_applied_to_order_and_charged(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let shirt = app.insert_inventory_item("Promo shirt", 20, 10.0);
+    let mug = app.insert_inventory_item("Promo mug", 20, 4.0);
+
+    create_promotion(&app, serde_json::json!({
+        "name": "Shirt two for one",
+        "rule": { "type": "buy_x_get_y", "item_ids": [shirt.item_id], "buy_quantity": 1, "get_quantity": 1, "percent_off": 100 }
+    }), &admin_token).await;
+    create_promotion(&app, serde_json::json!({
+        "name": "Mug tiers",
+        "priority": 1,
+        "rule": { "type": "tiered", "item_ids": [mug.item_id], "tiers": [{ "min_quantity": 2, "percent_off": 10 }, { "min_quantity": 4, "percent_off": 25 }] }
+    }), &admin_token).await;
+    create_promotion(&app, serde_json::json!({
+        "name": "Finished sale",
+        "starts_at": Utc::now() - Duration::days(2),
+        "ends_at": Utc::now() - Duration::days(1),
+        "rule": { "type": "tiered", "item_ids": [shirt.item_id, mug.item_id], "tiers": [{ "min_quantity": 1, "percent_off": 50 }] }
+    }), &admin_token).await;
+
+    let response = post_order(&app, serde_json::json!({
+        "items": [
+            { "variant_id": shirt.variant_id, "amount": 3 },
+            { "variant_id": mug.variant_id, "amount": 4 }
+        ]
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.subtotal_cents, 4600);
+    assert_eq!(placed.pricing.promotions.len(), 2);
+    assert_eq!(placed.pricing.promotions.iter().find(|applied| applied.name == "Shirt two for one").unwrap().discount_cents, 1000);
+    assert_eq!(placed.pricing.promotions.iter().find(|applied| applied.name == "Mug tiers").unwrap().discount_cents, 400);
+    assert_eq!(placed.pricing.total_cents, 3200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.order_id, placed.order_id);
+    assert_eq!(order.pricing.promotion_discount_cents, 1400);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_eq!(intent.payment.amount_cents, 3200);
+}
+
+#[actix_web::test]
+async fn coupon_discounts_price_after_bundle(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let camera = app.insert_inventory_item("Kit camera", 10, 100.0);
+    let case = app.insert_inventory_item("Kit case", 10, 20.0);
+
+    create_promotion(&app, serde_json::json!({
+        "name": "Camera kit",
+        "rule": {
+            "type": "bundle",
+            "items": [{ "item_id": camera.item_id, "quantity": 1 }, { "item_id": case.item_id, "quantity": 1 }],
+            "price_cents": 11000
+        }
+    }), &admin_token).await;
+    let response = app.post_coupon(serde_json::json!({ "code": "TENOFF", "discount_type": "percentage", "discount_value": 10 }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let items = serde_json::json!([
+        { "variant_id": camera.variant_id, "amount": 1 },
+        { "variant_id": case.variant_id, "amount": 2 }
+    ]);
+
+    // Checkout quotes promotions on held items
+    let checkout = app.post_checkout(items.clone(), &user_token)
+        .await
+        .json::<CheckoutResponse>()
+        .await
+        .unwrap();
+    assert_eq!(checkout.pricing.subtotal_cents, 14000);
+    assert_eq!(checkout.pricing.promotion_discount_cents, 1000);
+    assert_eq!(checkout.pricing.total_cents, 13000);
+
+    let response = post_order(&app, serde_json::json!({ "items": items, "coupon_code": "TENOFF" }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.promotions[0].name, "Camera kit");
+    assert_eq!(placed.pricing.promotion_discount_cents, 1000);
+    assert_eq!(placed.pricing.coupon_discount_cents, 1300);
+    assert_eq!(placed.pricing.total_cents, 11700);
+
+    // Plain list of items still gets back ordered variant ids
+    let response = post_order(&app, serde_json::json!([{ "variant_id": case.variant_id, "amount": 1 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<Vec<Uuid>>().await.unwrap(), vec![case.variant_id]);
+}
+
+#[actix_web::test]
+async fn huge_backordered_quantity_is_priced_with_promotions(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let mug = app.insert_inventory_item("Endless mug", 0, 4.0);
+    let response = app.put_backorder_policy(mug.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    create_promotion(&app, serde_json::json!({
+        "name": "Mug two for one",
+        "rule": { "type": "buy_x_get_y", "item_ids": [mug.item_id], "buy_quantity": 1, "get_quantity": 1, "percent_off": 100 }
+    }), &admin_token).await;
+
+    let response = post_order(&app, serde_json::json!({
+        "items": [{ "variant_id": mug.variant_id, "amount": i32::MAX }]
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.subtotal_cents, i32::MAX as i64 * 400);
+    assert_eq!(placed.pricing.promotion_discount_cents, (i32::MAX / 2) as i64 * 400);
+
+    let response = reqwest::get(format!("{}/health", app.get_app_url())).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,307 @@
+use diesel::{QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::{OrderWithItems, RefundWithLines}, models::Payment, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse, schema::{orders, payments, product_variants}};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+// Place an order for given lines and capture its payment
+async fn paid_order(app: &TestApp, lines: serde_json::Value, access_token: &String) -> OrderWithItems {
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&lines)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(app, access_token).await;
+    let intent = app.post_checkout_payment(order.order_id, access_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": "payment.captured",
+        "provider_reference": intent.payment.provider_reference
+    }))
+    .unwrap();
+    let response = app.post_payment_webhook(&body, &app.payment_provider.sign(&body)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    latest_order(app, access_token).await
+}
+
+fn payment(app: &TestApp, payment_id: Uuid) -> Payment {
+    let mut conn = app.pool.get().unwrap();
+    payments::table
+        .find(payment_id)
+        .get_result::<Payment>(&mut conn)
+        .unwrap()
+}
+
+fn variant_amount(app: &TestApp, variant_id: Uuid) -> i32 {
+    let mut conn = app.pool.get().unwrap();
+    product_variants::table
+        .find(variant_id)
+        .select(product_variants::amount)
+        .get_result::<i32>(&mut conn)
+        .unwrap()
+}
+
+async fn mount_email(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+}
+
+#[actix_web::test]
+async fn partial_refund_restocks_refunded_quantity(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let shirt = app.insert_inventory_item("Refunded shirt", 10, 10.0);
+    let mug = app.insert_inventory_item("Kept mug", 10, 4.0);
+    let order = paid_order(&app, serde_json::json!([
+        { "variant_id": shirt.variant_id, "amount": 3 },
+        { "variant_id": mug.variant_id, "amount": 1 }
+    ]), &user_token).await;
+    let shirt_line = order.items.iter().find(|item| item.variant_id == shirt.variant_id).unwrap();
+    assert_eq!(shirt_line.unit_price_cents, 1000);
+    assert_eq!(variant_amount(&app, shirt.variant_id), 7);
+
+    let response = app.post_refund(order.order_id, serde_json::json!({
+        "lines": [{ "order_item_id": shirt_line.order_item_id, "quantity": 2 }],
+        "restock": true,
+        "reason": "damaged"
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refund = response.json::<RefundWithLines>().await.unwrap();
+    assert_eq!(refund.refund.amount_cents, 2000);
+    assert_eq!(refund.refund.status, "succeeded");
+    assert!(refund.refund.provider_reference.is_some());
+    assert_eq!(refund.lines.len(), 1);
+
+    assert_eq!(variant_amount(&app, shirt.variant_id), 9);
+    assert_eq!(variant_amount(&app, mug.variant_id), 9);
+
+    let payment = payment(&app, refund.refund.payment_id);
+    assert_eq!(payment.status, "partially_refunded");
+    assert_eq!(payment.refunded_cents, 2000);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.status, "pending");
+    assert_eq!(order.items.iter().find(|item| item.variant_id == shirt.variant_id).unwrap().refunded_quantity, 2);
+
+    let refunds = app.get_refunds(order.order_id, 20, None, &admin_token)
+        .await
+        .json::<Page<RefundWithLines>>()
+        .await
+        .unwrap();
+    assert_eq!(refunds.total, 1);
+    assert_eq!(refunds.items[0].refund.reason.as_deref(), Some("damaged"));
+    assert!(refunds.next_cursor.is_none());
+
+    // Refunds are kept on record so neither the route nor the db lets the order go
+    let response = app.delete_orders_admin(serde_json::json!({ "order_id": order.order_id }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let mut conn = app.pool.get().unwrap();
+    assert!(diesel::delete(orders::table.find(order.order_id)).execute(&mut conn).is_err());
+}
+
+#[actix_web::test]
+async fn full_refund_marks_order_and_payment_refunded(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Fully refunded item", 5, 2.5);
+    let order = paid_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]), &user_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let response = app.post_refund(order.order_id, serde_json::json!({
+        "lines": [{ "order_item_id": order_item_id, "quantity": 1 }]
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Without lines the rest of the order is refunded, without restock stock stays as it is
+    let response = app.post_refund(order.order_id, serde_json::json!({}), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let refund = response.json::<RefundWithLines>().await.unwrap();
+    assert_eq!(refund.refund.amount_cents, 250);
+    assert_eq!(variant_amount(&app, variant.variant_id), 3);
+
+    let payment = payment(&app, refund.refund.payment_id);
+    assert_eq!(payment.status, "refunded");
+    assert_eq!(payment.refunded_cents, payment.amount_cents);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.status, "refunded");
+
+    let response = app.post_refund(order.order_id, serde_json::json!({}), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn invalid_refunds_are_rejected(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Guarded item", 5, 1.0);
+    let order = paid_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]), &user_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let test_cases = vec![
+        (serde_json::json!({ "lines": [{ "order_item_id": order_item_id, "quantity": 3 }] }), 400, "more than ordered"),
+        (serde_json::json!({ "lines": [
+            { "order_item_id": order_item_id, "quantity": 1 },
+            { "order_item_id": order_item_id, "quantity": 2 }
+        ] }), 400, "repeated lines adding up to more than ordered"),
+        (serde_json::json!({ "lines": [{ "order_item_id": order_item_id, "quantity": 0 }] }), 400, "zero quantity"),
+        (serde_json::json!({ "lines": [{ "order_item_id": Uuid::new_v4(), "quantity": 1 }] }), 400, "line of another order")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.post_refund(order.order_id, body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Refund wasn't rejected for {}", description);
+    }
+
+    let response = app.post_refund(Uuid::new_v4(), serde_json::json!({}), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_refund(order.order_id, serde_json::json!({}), &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let refunds = app.get_refunds(order.order_id, 20, None, &admin_token)
+        .await
+        .json::<Page<RefundWithLines>>()
+        .await
+        .unwrap();
+    assert!(refunds.items.is_empty());
+
+    let response = app.get_refunds(order.order_id, 0, None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn unpaid_order_cant_be_refunded(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Unpaid item", 5, 1.0);
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+
+    let response = app.post_refund(order.order_id, serde_json::json!({}), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn customer_receives_refund_receipt(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let variant = app.insert_inventory_item("Receipt item", 5, 12.5);
+    let order = paid_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]), &user_token).await;
+
+    let response = app.post_refund(order.order_id, serde_json::json!({ "restock": true }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let receipts = app.wait_for_emails(1, |email| email.subject == "Your refund has been issued" && email.text_body.contains(&order.order_id.to_string())).await;
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].to, "amanrao032@gmail.com");
+    assert!(receipts[0].text_body.starts_with("25.00 INR was refunded"));
+    assert!(receipts[0].text_body.contains(&format!("2 x Receipt item ({}): 25.00 INR", variant.sku)));
+}
+
+#[actix_web::test]
+async fn discounted_order_is_refunded_what_was_paid(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let response = app.post_coupon(serde_json::json!({ "code": "THIRD", "discount_type": "fixed", "discount_value": 100 }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let variant = app.insert_inventory_item("Discounted refund item", 5, 1.0);
+    let order = paid_order(&app, serde_json::json!({
+        "items": [{ "variant_id": variant.variant_id, "amount": 3 }],
+        "coupon_code": "THIRD"
+    }), &user_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let response = app.post_refund(order.order_id, serde_json::json!({
+        "lines": [{ "order_item_id": order_item_id, "quantity": 1 }]
+    }), &admin_token).await;
+    let first = response.json::<RefundWithLines>().await.unwrap();
+    assert_eq!(first.refund.amount_cents, 66);
+
+    let response = app.post_refund(order.order_id, serde_json::json!({}), &admin_token).await;
+    let rest = response.json::<RefundWithLines>().await.unwrap();
+    assert_eq!(rest.refund.amount_cents, 134);
+
+    let payment = payment(&app, rest.refund.payment_id);
+    assert_eq!(payment.amount_cents, 200);
+    assert_eq!(payment.status, "refunded");
+}
+
+#[actix_web::test]
+async fn restocked_refund_fills_waiting_backorders(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    mount_email(&app).await;
+
+    let shirt = app.insert_inventory_item("Scarce shirt", 2, 10.0);
+    let response = app.put_backorder_policy(shirt.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = paid_order(&app, serde_json::json!([{ "variant_id": shirt.variant_id, "amount": 2 }]), &user_token).await;
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!([{ "variant_id": shirt.variant_id, "amount": 1 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(latest_order(&app, &admin_token).await.items[0].backordered_quantity, 1);
+
+    let response = app.post_refund(order.order_id, serde_json::json!({
+        "lines": [{ "order_item_id": order.items[0].order_item_id, "quantity": 1 }],
+        "restock": true
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(latest_order(&app, &admin_token).await.items[0].backordered_quantity, 0);
+    assert_eq!(variant_amount(&app, shirt.variant_id), 0);
+}
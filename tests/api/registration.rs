@@ -47,6 +47,9 @@ async fn post_registration_with_valid_form_data(){
                     .expect("Failed to send request to register endpoint");
 
     assert_eq!(response.status().as_u16(), 200);
+
+    // Confirmation email is delivered by the outbox worker after the response
+    app.wait_for_emails(1, |email| email.subject == "Confirmation email").await;
 }
 
 #[actix_web::test]
@@ -108,7 +111,9 @@ async fn post_registration_adds_user_to_db(){
     .unwrap()
     .unwrap();
 
-    assert_eq!(rows, 1)
+    assert_eq!(rows, 1);
+
+    app.wait_for_emails(1, |email| email.subject == "Confirmation email").await;
 }
 
 #[actix_web::test]
@@ -137,6 +142,10 @@ async fn post_registration_sends_confirmation_mail(){
                     .expect("Failed to send request to register endpoint");
 
     assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.wait_for_emails(1, |email| email.subject == "Confirmation email").await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "amanrao032@gmail.com");
 }
 
 #[actix_web::test]
@@ -149,7 +158,7 @@ async fn get_confirm_confirms_subscription(){
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -165,8 +174,9 @@ async fn get_confirm_confirms_subscription(){
 
     assert_eq!(response.status().as_u16(), 200);
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -201,6 +211,42 @@ async fn get_confirm_confirms_subscription(){
     assert_eq!(status, "confirmed")
 }
 
+#[actix_web::test]
+async fn confirmation_mail_is_sent_in_preferred_language(){
+    let app = TestApp::spawn_app().await;
+    let body = serde_json::json!({
+        "email" : "amanrao032@gmail.com",
+        "name" : "Aman <Rao>",
+        "password" : "testpassword",
+        "confirm_password" : "testpassword"
+    });
+
+    Mock::given(path("/email"))
+        .and(header_exists("X-Postmark-Server-Token"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_api)
+        .await;
+
+    let response = app.api_client.post(format!("http://{}:{}/register", app.host, app.port))
+                    .header("Accept-Language", "es-MX,es;q=0.9,en;q=0.8")
+                    .form(&body)
+                    .send()
+                    .await
+                    .expect("Failed to send request to register endpoint");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.wait_for_emails(1, |email| email.to == "amanrao032@gmail.com").await;
+    assert_eq!(emails[0].subject, "Confirma tu cuenta");
+    assert!(emails[0].text_body.starts_with("Hola Aman <Rao>,"));
+    assert!(emails[0].html_body.contains("Hola Aman &lt;Rao&gt;,"));
+
+    // Link is the same whatever language the email is in
+    let link = app.get_confirmation_link(&emails[0].text_body);
+    assert_eq!(reqwest::get(link).await.unwrap().status().as_u16(), 200);
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ReceiveEmailRequest{
@@ -209,4 +255,13 @@ pub struct ReceiveEmailRequest{
     pub subject: String,
     pub html_body: String,
     pub text_body: String,
+    #[serde(default)]
+    pub headers: Vec<ReceiveEmailHeader>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReceiveEmailHeader{
+    pub name: String,
+    pub value: String
 }
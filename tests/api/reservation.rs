@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::InventoryItemWithDetails, models::StockReservation, pagination::Page, routes::checkout::post::CheckoutResponse, schema::{product_variants, stock_reservations}};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, variant_id: Uuid, amount: i32, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": amount }]))
+        .send()
+        .await
+        .unwrap()
+}
+
+// Available to sell quantities of first listed item and its first variant
+async fn available_to_sell(app: &TestApp) -> (i32, i32) {
+    let page = app.get_inventory(5, None)
+        .await
+        .json::<Page<InventoryItemWithDetails>>()
+        .await
+        .unwrap();
+
+    let item = &page.items[0];
+    (item.available_to_sell, item.variants[0].available_to_sell)
+}
+
+fn reservation(app: &TestApp, reservation_id: Uuid) -> StockReservation {
+    let mut conn = app.pool.get().unwrap();
+    stock_reservations::table
+        .find(reservation_id)
+        .get_result::<StockReservation>(&mut conn)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn checkout_holds_stock_without_changing_stock_on_hand(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Reserved item", 10, 5.0);
+    let user_token = create_user_and_login(&app).await;
+
+    let response = app.post_checkout(serde_json::json!([
+        { "variant_id": variant.variant_id, "amount": 3 },
+        { "variant_id": variant.variant_id, "amount": 1 }
+    ]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let checkout = response.json::<CheckoutResponse>().await.unwrap();
+    assert_eq!(checkout.reservations.len(), 1);
+    assert_eq!(checkout.reservations[0].quantity, 4);
+    assert_eq!(checkout.reservations[0].status, "active");
+    assert!(checkout.expires_at > Utc::now());
+
+    assert_eq!(available_to_sell(&app).await, (6, 6));
+
+    let mut conn = app.pool.get().unwrap();
+    let amount = product_variants::table
+        .find(variant.variant_id)
+        .select(product_variants::amount)
+        .get_result::<i32>(&mut conn)
+        .unwrap();
+    assert_eq!(amount, 10);
+}
+
+#[actix_web::test]
+async fn stock_reserved_by_another_user_cant_be_reserved_or_ordered(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Contested item", 10, 5.0);
+
+    let first_token = create_user_and_login(&app).await;
+    let second_token = app.login_admin().await;
+
+    let response = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 8 }]), &first_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 3 }]), &second_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = post_order(&app, variant.variant_id, 3, &second_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = post_order(&app, variant.variant_id, 2, &second_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(available_to_sell(&app).await, (0, 0));
+}
+
+#[actix_web::test]
+async fn ordering_reserved_stock_commits_the_reservation(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Committed item", 5, 5.0);
+    let user_token = create_user_and_login(&app).await;
+
+    let checkout = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 5 }]), &user_token)
+        .await
+        .json::<CheckoutResponse>()
+        .await
+        .unwrap();
+
+    let response = post_order(&app, variant.variant_id, 5, &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let committed = reservation(&app, checkout.reservations[0].reservation_id);
+    assert_eq!(committed.status, "committed");
+    assert!(committed.order_id.is_some());
+
+    assert_eq!(available_to_sell(&app).await, (0, 0));
+}
+
+#[actix_web::test]
+async fn new_checkout_or_release_frees_previous_reservations(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Released item", 10, 5.0);
+    let user_token = create_user_and_login(&app).await;
+
+    let first = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 7 }]), &user_token)
+        .await
+        .json::<CheckoutResponse>()
+        .await
+        .unwrap();
+
+    // Replacing the checkout doesn't count own earlier hold against available stock
+    let response = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 9 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(reservation(&app, first.reservations[0].reservation_id).status, "released");
+    assert_eq!(available_to_sell(&app).await, (1, 1));
+
+    let response = app.delete_checkout(&user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(available_to_sell(&app).await, (10, 10));
+}
+
+#[actix_web::test]
+async fn expired_reservations_are_released_by_background_task(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Expiring item", 10, 5.0);
+    let user_token = create_user_and_login(&app).await;
+
+    let checkout = app.post_checkout(serde_json::json!([{ "variant_id": variant.variant_id, "amount": 10 }]), &user_token)
+        .await
+        .json::<CheckoutResponse>()
+        .await
+        .unwrap();
+    let reservation_id = checkout.reservations[0].reservation_id;
+
+    let mut conn = app.pool.get().unwrap();
+    diesel::update(stock_reservations::table.find(reservation_id))
+        .set(stock_reservations::expires_at.eq(Utc::now() - chrono::Duration::seconds(1)))
+        .execute(&mut conn)
+        .unwrap();
+
+    // Expired hold stops counting against stock even before it is swept
+    assert_eq!(available_to_sell(&app).await, (10, 10));
+
+    let mut status = reservation(&app, reservation_id).status;
+    for _ in 0..50 {
+        if status == "expired" {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+        status = reservation(&app, reservation_id).status;
+    }
+    assert_eq!(status, "expired");
+}
+
+#[actix_web::test]
+async fn checkout_with_invalid_items_is_rejected(){
+    let app = TestApp::spawn_app().await;
+    let variant = app.insert_inventory_item("Invalid checkout item", 10, 5.0);
+    let user_token = create_user_and_login(&app).await;
+
+    let test_cases = vec![
+        (serde_json::json!([]), "empty checkout"),
+        (serde_json::json!([{ "variant_id": variant.variant_id, "amount": 0 }]), "zero amount"),
+        (serde_json::json!([{ "variant_id": Uuid::new_v4(), "amount": 1 }]), "unknown variant"),
+        (serde_json::json!([{ "variant_id": variant.variant_id, "amount": 11 }]), "more than stock"),
+        (serde_json::json!([
+            { "variant_id": variant.variant_id, "amount": i32::MAX },
+            { "variant_id": variant.variant_id, "amount": i32::MAX }
+        ]), "overflowing amount")
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_checkout(body, &user_token).await;
+        assert_eq!(response.status().as_u16(), 400, "Checkout didn't fail for {}", description);
+    }
+
+    assert_eq!(available_to_sell(&app).await, (10, 10));
+}
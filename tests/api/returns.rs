@@ -0,0 +1,307 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::{OrderWithItems, ReturnWithLines}, models::Payment, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse, schema::{payments, product_variants}};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+// Place an order, capture its payment, ship it and mark it delivered
+async fn delivered_order(app: &TestApp, variant_id: Uuid, amount: i32, access_token: &String, admin_token: &String) -> OrderWithItems {
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&serde_json::json!([{ "variant_id": variant_id, "amount": amount }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(app, access_token).await;
+
+    let intent = app.post_checkout_payment(order.order_id, access_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": "payment.captured",
+        "provider_reference": intent.payment.provider_reference
+    }))
+    .unwrap();
+    let response = app.post_payment_webhook(&body, &app.payment_provider.sign(&body)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "DHL-1" }), admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.put_orders(serde_json::json!({
+        "order_id": order.order_id,
+        "status": "delivered"
+    }), admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    latest_order(app, access_token).await
+}
+
+fn variant_amount(app: &TestApp, variant_id: Uuid) -> i32 {
+    let mut conn = app.pool.get().unwrap();
+    product_variants::table
+        .find(variant_id)
+        .select(product_variants::amount)
+        .get_result::<i32>(&mut conn)
+        .unwrap()
+}
+
+fn return_body(order_item_id: Uuid, quantity: i32) -> serde_json::Value {
+    serde_json::json!({
+        "lines": [{ "order_item_id": order_item_id, "quantity": quantity, "reason": "damaged" }],
+        "note": "Box was crushed"
+    })
+}
+
+#[actix_web::test]
+async fn return_is_only_requested_for_delivered_lines(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Returned item", 10, 5.0);
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let pending = latest_order(&app, &user_token).await;
+
+    let response = app.post_return(pending.order_id, return_body(pending.items[0].order_item_id, 1), &user_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let order = delivered_order(&app, variant.variant_id, 2, &user_token, &admin_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let test_cases = vec![
+        (serde_json::json!({ "lines": [] }), 400, "no lines"),
+        (return_body(order_item_id, 0), 400, "zero quantity"),
+        (return_body(order_item_id, 3), 400, "more than ordered"),
+        (return_body(pending.items[0].order_item_id, 1), 400, "line of another order"),
+        (serde_json::json!({ "lines": [{ "order_item_id": order_item_id, "quantity": 1, "reason": "bored" }] }), 400, "unknown reason")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.post_return(order.order_id, body, &user_token).await;
+        assert_eq!(response.status().as_u16(), status, "Return wasn't rejected for {}", description);
+    }
+
+    let response = app.post_return(order.order_id, return_body(order_item_id, 1), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_return(order.order_id, return_body(order_item_id, 2), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order_return = response.json::<ReturnWithLines>().await.unwrap();
+    assert_eq!(order_return.order_return.status, "requested");
+    assert_eq!(order_return.lines[0].reason, "damaged");
+
+    // Lines held by an open return can't be returned again
+    let response = app.post_return(order.order_id, return_body(order_item_id, 1), &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let returns = app.get_order_returns(order.order_id, 20, None, &user_token)
+        .await
+        .json::<Page<ReturnWithLines>>()
+        .await
+        .unwrap();
+    assert_eq!(returns.total, 1);
+    assert_eq!(returns.items.len(), 1);
+}
+
+#[actix_web::test]
+async fn received_return_is_restocked_and_refunded(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+
+    let variant = app.insert_inventory_item("Restocked return", 10, 7.5);
+    let order = delivered_order(&app, variant.variant_id, 3, &user_token, &admin_token).await;
+    assert_eq!(variant_amount(&app, variant.variant_id), 7);
+
+    let order_return = app.post_return(order.order_id, return_body(order.items[0].order_item_id, 2), &user_token)
+        .await
+        .json::<ReturnWithLines>()
+        .await
+        .unwrap();
+    let return_id = order_return.order_return.return_id;
+
+    let queue = app.get_returns_queue("requested", &admin_token)
+        .await
+        .json::<Page<ReturnWithLines>>()
+        .await
+        .unwrap();
+    assert_eq!(queue.total, 1);
+    assert_eq!(queue.items[0].order_return.return_id, return_id);
+
+    // Nothing can be refunded before goods are back
+    let response = app.post_return_refund(return_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.put_return_status(return_id, "approved", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(variant_amount(&app, variant.variant_id), 7);
+
+    let response = app.put_return_status(return_id, "received", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(variant_amount(&app, variant.variant_id), 9);
+
+    let response = app.post_return_refund(return_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let refunded = response.json::<ReturnWithLines>().await.unwrap();
+    assert!(refunded.order_return.refund_id.is_some());
+
+    // Refund doesn't restock goods a second time
+    assert_eq!(variant_amount(&app, variant.variant_id), 9);
+
+    let mut conn = app.pool.get().unwrap();
+    let payment = payments::table
+        .filter(payments::order_id.eq(order.order_id))
+        .get_result::<Payment>(&mut conn)
+        .unwrap();
+    assert_eq!(payment.status, "partially_refunded");
+    assert_eq!(payment.refunded_cents, 1500);
+
+    let response = app.post_return_refund(return_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn return_status_moves_only_forward(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Rejected return", 10, 5.0);
+    let order = delivered_order(&app, variant.variant_id, 1, &user_token, &admin_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let order_return = app.post_return(order.order_id, return_body(order_item_id, 1), &user_token)
+        .await
+        .json::<ReturnWithLines>()
+        .await
+        .unwrap();
+    let return_id = order_return.order_return.return_id;
+
+    let response = app.put_return_status(return_id, "approved", &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.put_return_status(return_id, "received", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.put_return_status(return_id, "rejected", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.put_return_status(return_id, "approved", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.put_return_status(Uuid::new_v4(), "approved", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Rejected return gives its lines back
+    let response = app.post_return(order.order_id, return_body(order_item_id, 1), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn received_return_fills_waiting_backorders(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+
+    let variant = app.insert_inventory_item("Scarce return", 2, 7.5);
+    let response = app.put_backorder_policy(variant.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = delivered_order(&app, variant.variant_id, 2, &user_token, &admin_token).await;
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(latest_order(&app, &admin_token).await.items[0].backordered_quantity, 1);
+
+    let order_return = app.post_return(order.order_id, return_body(order.items[0].order_item_id, 1), &user_token)
+        .await
+        .json::<ReturnWithLines>()
+        .await
+        .unwrap();
+    let return_id = order_return.order_return.return_id;
+
+    for status in ["approved", "received"] {
+        let response = app.put_return_status(return_id, status, &admin_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    assert_eq!(latest_order(&app, &admin_token).await.items[0].backordered_quantity, 0);
+    assert_eq!(variant_amount(&app, variant.variant_id), 0);
+}
+
+#[actix_web::test]
+async fn concurrent_refunds_of_return_pay_out_once(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+
+    let variant = app.insert_inventory_item("Twice refunded return", 10, 5.0);
+    let order = delivered_order(&app, variant.variant_id, 3, &user_token, &admin_token).await;
+
+    let order_return = app.post_return(order.order_id, return_body(order.items[0].order_item_id, 1), &user_token)
+        .await
+        .json::<ReturnWithLines>()
+        .await
+        .unwrap();
+    let return_id = order_return.order_return.return_id;
+
+    for status in ["approved", "received"] {
+        let response = app.put_return_status(return_id, status, &admin_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let (first, second) = tokio::join!(
+        app.post_return_refund(return_id, &admin_token),
+        app.post_return_refund(return_id, &admin_token)
+    );
+    let mut statuses = vec![first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, vec![200, 409]);
+
+    let mut conn = app.pool.get().unwrap();
+    let payment = payments::table
+        .filter(payments::order_id.eq(order.order_id))
+        .get_result::<Payment>(&mut conn)
+        .unwrap();
+    assert_eq!(payment.refunded_cents, 500);
+}
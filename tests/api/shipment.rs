@@ -0,0 +1,174 @@
+use ecommerce::{db_interaction::{OrderWithItems, ShipmentWithLines}, pagination::Page};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, body: serde_json::Value, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+#[actix_web::test]
+async fn shipments_are_validated(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Validated shipment item", 10, 10.0);
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 2 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    let test_cases = vec![
+        (serde_json::json!({ "carrier": " ", "tracking_number": "TRK1" }), 400, "empty carrier"),
+        (serde_json::json!({ "carrier": "DHL", "tracking_number": "" }), 400, "empty tracking number"),
+        (serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1", "lines": [{ "order_item_id": order_item_id, "quantity": 0 }] }), 400, "zero quantity"),
+        (serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1", "lines": [{ "order_item_id": order_item_id, "quantity": 3 }] }), 400, "excess quantity"),
+        (serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1", "lines": [{ "order_item_id": Uuid::new_v4(), "quantity": 1 }] }), 400, "foreign line")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.post_shipment(order.order_id, body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Shipment wasn't rejected for {}", description);
+    }
+
+    let response = app.post_shipment(Uuid::new_v4(), serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1" }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Without lines everything ready is shipped, after which nothing is left to ship
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK1" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<ShipmentWithLines>().await.unwrap().lines[0].quantity, 2);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "TRK2" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn order_status_follows_shipment_coverage(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let lamp = app.insert_inventory_item("Shipped lamp", 10, 10.0);
+    let rug = app.insert_inventory_item("Shipped rug", 10, 20.0);
+    let response = post_order(&app, serde_json::json!([
+        { "variant_id": lamp.variant_id, "amount": 3 },
+        { "variant_id": rug.variant_id, "amount": 1 }
+    ]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+    let lamp_line = order.items.iter().find(|item| item.variant_id == lamp.variant_id).unwrap().order_item_id;
+    let rug_line = order.items.iter().find(|item| item.variant_id == rug.variant_id).unwrap().order_item_id;
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({
+        "carrier": "DHL",
+        "tracking_number": "DHL-001",
+        "lines": [{ "order_item_id": lamp_line, "quantity": 2 }]
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.status, "partially_shipped");
+    assert_eq!(order.shipments.len(), 1);
+    assert_eq!(order.shipments[0].shipment.tracking_number, "DHL-001");
+    assert_eq!(order.items.iter().find(|item| item.order_item_id == lamp_line).unwrap().shipped_quantity, 2);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({
+        "carrier": "UPS",
+        "tracking_number": "UPS-002",
+        "lines": [{ "order_item_id": lamp_line, "quantity": 1 }, { "order_item_id": rug_line, "quantity": 1 }]
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.status, "shipped");
+    assert!(order.items.iter().all(|item| item.shipped_quantity == item.quantity));
+
+    let first_page = app.get_shipments(order.order_id, 1, None, &admin_token)
+        .await
+        .json::<Page<ShipmentWithLines>>()
+        .await
+        .unwrap();
+    assert_eq!(first_page.total, 2);
+    assert_eq!(first_page.items.len(), 1);
+    assert_eq!(first_page.items[0].shipment.carrier, "DHL");
+
+    let second_page = app.get_shipments(order.order_id, 1, first_page.next_cursor.as_deref(), &admin_token)
+        .await
+        .json::<Page<ShipmentWithLines>>()
+        .await
+        .unwrap();
+    assert_eq!(second_page.items.len(), 1);
+    assert_eq!(second_page.items[0].shipment.carrier, "UPS");
+    assert_eq!(second_page.items[0].lines.len(), 2);
+    assert!(second_page.next_cursor.is_none());
+}
+
+#[actix_web::test]
+async fn backordered_quantity_ships_once_restocked(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+
+    let variant = app.insert_inventory_item("Partly backordered item", 1, 10.0);
+    let response = app.put_backorder_policy(variant.item_id, "backorder", None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 3 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+    let order_item_id = order.items[0].order_item_id;
+
+    // Only the picked unit is ready to ship
+    let response = app.post_shipment(order.order_id, serde_json::json!({
+        "carrier": "DHL",
+        "tracking_number": "DHL-100",
+        "lines": [{ "order_item_id": order_item_id, "quantity": 2 }]
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "DHL-100" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(latest_order(&app, &user_token).await.status, "partially_shipped");
+
+    // Partially shipped orders still get restocked units
+    let response = app.post_stock_movement(variant.item_id, serde_json::json!({
+        "variant_id": variant.variant_id,
+        "quantity": 2,
+        "reason": "restock"
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_shipment(order.order_id, serde_json::json!({ "carrier": "DHL", "tracking_number": "DHL-101" }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<ShipmentWithLines>().await.unwrap().lines[0].quantity, 2);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.status, "shipped");
+    assert_eq!(order.shipments.len(), 2);
+}
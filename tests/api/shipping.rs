@@ -0,0 +1,223 @@
+use ecommerce::{db_interaction::{OrderWithItems, PlacedOrder, ShippingMethodWithZones, ShippingQuote}, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, body: serde_json::Value, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn create_method(app: &TestApp, body: serde_json::Value, access_token: &String) -> ShippingMethodWithZones {
+    let response = app.post_shipping_method(body, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<ShippingMethodWithZones>().await.unwrap()
+}
+
+#[actix_web::test]
+async fn shipping_methods_are_validated_and_managed(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let test_cases = vec![
+        (serde_json::json!({ "name": " ", "rate": { "type": "flat_rate", "rate_cents": 500 } }), "empty name"),
+        (serde_json::json!({ "name": "Standard", "rate": { "type": "flat_rate", "rate_cents": -1 } }), "negative rate"),
+        (serde_json::json!({ "name": "Standard", "rate": { "type": "express", "rate_cents": 500 } }), "unknown rate type"),
+        (serde_json::json!({ "name": "Standard", "rate": { "type": "flat_rate", "rate_cents": 500 }, "zones": [{ "country": "India" }] }), "invalid zone")
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_shipping_method(body, &admin_token).await;
+        assert_eq!(response.status().as_u16() / 100, 4, "Shipping method wasn't rejected for {}", description);
+    }
+
+    let method = create_method(&app, serde_json::json!({
+        "name": "Standard",
+        "rate": { "type": "weight_based", "base_cents": 300, "per_kg_cents": 100 },
+        "zones": [{ "country": "in", "region": "ka" }, { "country": "DE" }]
+    }), &admin_token).await;
+    assert!(method.active);
+    assert_eq!(method.zones.len(), 2);
+
+    // Replacing a method replaces its zones too
+    let response = app.put_shipping_method(method.shipping_method_id, serde_json::json!({
+        "name": "Economy",
+        "active": false,
+        "rate": { "type": "flat_rate", "rate_cents": 250 }
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    create_method(&app, serde_json::json!({
+        "name": "Express",
+        "rate": { "type": "flat_rate", "rate_cents": 900 },
+        "zones": [{ "country": "IN" }]
+    }), &admin_token).await;
+
+    let first_page = app.get_shipping_methods(1, None, &admin_token)
+        .await
+        .json::<Page<ShippingMethodWithZones>>()
+        .await
+        .unwrap();
+    assert_eq!(first_page.total, 2);
+    assert_eq!(first_page.items.len(), 1);
+    assert_eq!(first_page.items[0].name, "Economy");
+    assert!(!first_page.items[0].active);
+    assert!(first_page.items[0].zones.is_empty());
+
+    let second_page = app.get_shipping_methods(1, first_page.next_cursor.as_deref(), &admin_token)
+        .await
+        .json::<Page<ShippingMethodWithZones>>()
+        .await
+        .unwrap();
+    assert_eq!(second_page.items.len(), 1);
+    assert_eq!(second_page.items[0].name, "Express");
+    assert_eq!(second_page.items[0].zones.len(), 1);
+    assert!(second_page.next_cursor.is_none());
+
+    let response = app.delete_shipping_method(method.shipping_method_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_shipping_method(method.shipping_method_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.put_item_weight(Uuid::new_v4(), 500, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.get_shipping_methods(1, None, &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn quote_lists_methods_shipping_to_address_cheapest_first(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let kettle = app.insert_inventory_item("Shipped kettle", 20, 30.0);
+    let response = app.put_item_weight(kettle.item_id, 1200, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    create_method(&app, serde_json::json!({
+        "name": "Courier",
+        "rate": { "type": "weight_based", "base_cents": 200, "per_kg_cents": 150 }
+    }), &admin_token).await;
+    create_method(&app, serde_json::json!({
+        "name": "Post",
+        "rate": { "type": "free_over", "rate_cents": 499, "threshold_cents": 5000 },
+        "zones": [{ "country": "IN" }]
+    }), &admin_token).await;
+    create_method(&app, serde_json::json!({
+        "name": "Local",
+        "rate": { "type": "flat_rate", "rate_cents": 100 },
+        "zones": [{ "country": "IN", "region": "KA" }]
+    }), &admin_token).await;
+    create_method(&app, serde_json::json!({
+        "name": "Retired",
+        "active": false,
+        "rate": { "type": "flat_rate", "rate_cents": 0 }
+    }), &admin_token).await;
+
+    let quote = |amount: i32, address: serde_json::Value| serde_json::json!({
+        "items": [{ "variant_id": kettle.variant_id, "amount": amount }],
+        "address": address
+    });
+
+    let quotes = app.post_shipping_quote(quote(1, serde_json::json!({ "country": "IN", "region": "KA" })), &user_token)
+        .await
+        .json::<Vec<ShippingQuote>>()
+        .await
+        .unwrap();
+    let quoted: Vec<(&str, i64)> = quotes.iter().map(|quote| (quote.name.as_str(), quote.cost_cents)).collect();
+    assert_eq!(quoted, vec![("Local", 100), ("Post", 499), ("Courier", 500)]);
+
+    // Two kettles weigh 2.4kg and cross the free shipping threshold
+    let quotes = app.post_shipping_quote(quote(2, serde_json::json!({ "country": "IN", "region": "MH" })), &user_token)
+        .await
+        .json::<Vec<ShippingQuote>>()
+        .await
+        .unwrap();
+    let quoted: Vec<(&str, i64)> = quotes.iter().map(|quote| (quote.name.as_str(), quote.cost_cents)).collect();
+    assert_eq!(quoted, vec![("Post", 0), ("Courier", 650)]);
+
+    let quotes = app.post_shipping_quote(quote(1, serde_json::json!({ "country": "US" })), &user_token)
+        .await
+        .json::<Vec<ShippingQuote>>()
+        .await
+        .unwrap();
+    assert_eq!(quotes.len(), 1);
+
+    let response = app.post_shipping_quote(quote(0, serde_json::json!({ "country": "US" })), &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_shipping_quote(serde_json::json!({
+        "items": [{ "variant_id": Uuid::new_v4(), "amount": 1 }],
+        "address": { "country": "US" }
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn order_is_charged_for_chosen_shipping_method(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Shipped chair", 20, 25.0);
+    let method = create_method(&app, serde_json::json!({
+        "name": "Standard",
+        "rate": { "type": "flat_rate", "rate_cents": 700 },
+        "zones": [{ "country": "DE" }]
+    }), &admin_token).await;
+    let inactive = create_method(&app, serde_json::json!({
+        "name": "Paused",
+        "active": false,
+        "rate": { "type": "flat_rate", "rate_cents": 100 }
+    }), &admin_token).await;
+
+    let items = serde_json::json!([{ "variant_id": variant.variant_id, "amount": 1 }]);
+
+    let test_cases = vec![
+        (serde_json::json!({ "items": items, "address": { "country": "DE" }, "shipping_method_id": Uuid::new_v4() }), "unknown method"),
+        (serde_json::json!({ "items": items, "address": { "country": "DE" }, "shipping_method_id": inactive.shipping_method_id }), "inactive method"),
+        (serde_json::json!({ "items": items, "shipping_method_id": method.shipping_method_id }), "missing address"),
+        (serde_json::json!({ "items": items, "address": { "country": "FR" }, "shipping_method_id": method.shipping_method_id }), "address outside zones")
+    ];
+
+    for (body, description) in test_cases {
+        let response = post_order(&app, body, &user_token).await;
+        assert_eq!(response.status().as_u16(), 400, "Order wasn't rejected for {}", description);
+    }
+
+    let response = post_order(&app, serde_json::json!({
+        "items": items,
+        "address": { "country": "DE" },
+        "shipping_method_id": method.shipping_method_id
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.shipping_cents, 700);
+    assert_eq!(placed.pricing.total_cents, 3200);
+
+    let order = app.get_orders(1, None, &user_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0);
+    assert_eq!(order.order_id, placed.order_id);
+    assert_eq!(order.pricing.shipping_cents, 700);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_eq!(intent.payment.amount_cents, 3200);
+}
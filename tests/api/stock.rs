@@ -0,0 +1,213 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use ecommerce::{db_interaction::StockReconciliation, models::{ProductVariant, StockMovement}, pagination::Page, schema::{inventory, product_variants}};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+// Post an item through the api so its initial stock is recorded, returning its default variant
+async fn post_item(app: &TestApp, access_token: &str, sku: &str, amount: i32) -> ProductVariant {
+    let item = serde_json::json!({
+        "name" : format!("Item {}", sku),
+        "amount" : amount.to_string(),
+        "price" : "10",
+        "sku" : sku
+    });
+
+    let response = app.post_inventory(item, access_token.to_string()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut conn = app.pool.get().unwrap();
+    product_variants::table
+        .filter(product_variants::sku.eq(sku))
+        .get_result::<ProductVariant>(&mut conn)
+        .unwrap()
+}
+
+async fn movements(app: &TestApp, item_id: Uuid, access_token: &String) -> Vec<StockMovement> {
+    let response = app.get_stock_movements(item_id, 50, None, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Page<StockMovement>>().await.unwrap().items
+}
+
+async fn reconciliation(app: &TestApp, item_id: Uuid, access_token: &String) -> StockReconciliation {
+    let response = app.get_stock_reconciliation(item_id, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json().await.unwrap()
+}
+
+#[actix_web::test]
+async fn creating_item_and_ordering_it_are_recorded_in_ledger(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 4 }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let history = movements(&app, variant.item_id, &admin_token).await;
+    let entries: Vec<(i32, &str)> = history.iter()
+        .map(|movement| (movement.quantity, movement.reason.as_str()))
+        .collect();
+    assert_eq!(entries, vec![(-4, "order"), (10, "restock")]);
+    assert_eq!(history[1].actor_id, Some(app.admin.user_id));
+    assert!(history[0].reference_id.is_some());
+
+    let report = reconciliation(&app, variant.item_id, &admin_token).await;
+    assert!(report.is_consistent);
+    assert_eq!(report.recorded_amount, Some(6));
+    assert_eq!(report.ledger_amount, 6);
+}
+
+#[actix_web::test]
+async fn manual_movements_change_stock(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+
+    let response = app.post_stock_movement(
+        variant.item_id,
+        serde_json::json!({ "variant_id": variant.variant_id, "quantity": 5, "reason": "restock", "note": "Supplier delivery" }),
+        &admin_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_stock_movement(
+        variant.item_id,
+        serde_json::json!({ "variant_id": variant.variant_id, "quantity": -3, "reason": "adjustment", "note": "Damaged" }),
+        &admin_token
+    ).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut conn = app.pool.get().unwrap();
+    let amount: Option<i32> = inventory::table
+        .filter(inventory::item_id.eq(variant.item_id))
+        .select(inventory::amount)
+        .get_result(&mut conn)
+        .unwrap();
+    assert_eq!(amount, Some(12));
+
+    let report = reconciliation(&app, variant.item_id, &admin_token).await;
+    assert!(report.is_consistent);
+    assert_eq!(report.ledger_amount, 12);
+}
+
+#[actix_web::test]
+async fn invalid_manual_movements_are_rejected(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+    let other = post_item(&app, &admin_token, "LEDGER-2", 10).await;
+
+    let cases = [
+        (variant.item_id, serde_json::json!({ "variant_id": variant.variant_id, "quantity": -11, "reason": "adjustment" }), 400),
+        (variant.item_id, serde_json::json!({ "variant_id": variant.variant_id, "quantity": -1, "reason": "restock" }), 400),
+        (variant.item_id, serde_json::json!({ "variant_id": variant.variant_id, "quantity": i32::MIN, "reason": "adjustment" }), 400),
+        (variant.item_id, serde_json::json!({ "variant_id": variant.variant_id, "quantity": 1, "reason": "order" }), 400),
+        (variant.item_id, serde_json::json!({ "variant_id": other.variant_id, "quantity": 1, "reason": "restock" }), 400),
+        (Uuid::new_v4(), serde_json::json!({ "variant_id": variant.variant_id, "quantity": 1, "reason": "restock" }), 404)
+    ];
+
+    for (item_id, body, status) in cases {
+        let response = app.post_stock_movement(item_id, &body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "{} was not rejected", body);
+    }
+
+    assert_eq!(movements(&app, variant.item_id, &admin_token).await.len(), 1);
+}
+
+#[actix_web::test]
+async fn deleting_pending_order_returns_stock_as_cancellation(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+
+    let user_token = create_user_and_login(&app).await;
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!([{ "variant_id": variant.variant_id, "amount": 4 }]))
+        .send()
+        .await
+        .unwrap();
+
+    let order_id = movements(&app, variant.item_id, &admin_token).await[0]
+        .reference_id
+        .unwrap();
+
+    let response = app.delete_orders_admin(serde_json::json!({ "order_id": order_id }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let history = movements(&app, variant.item_id, &admin_token).await;
+    assert_eq!(history[0].reason, "cancellation");
+    assert_eq!(history[0].quantity, 4);
+    assert_eq!(history[0].reference_id, Some(order_id));
+
+    let report = reconciliation(&app, variant.item_id, &admin_token).await;
+    assert!(report.is_consistent);
+    assert_eq!(report.recorded_amount, Some(10));
+}
+
+#[actix_web::test]
+async fn reconciliation_reports_stock_changed_outside_ledger(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+
+    let mut conn = app.pool.get().unwrap();
+    diesel::update(product_variants::table)
+        .filter(product_variants::variant_id.eq(variant.variant_id))
+        .set(product_variants::amount.eq(7))
+        .execute(&mut conn)
+        .unwrap();
+
+    let report = reconciliation(&app, variant.item_id, &admin_token).await;
+    assert!(!report.is_consistent);
+    assert_eq!(report.variants[0].ledger_amount, 10);
+    assert_eq!(report.variants[0].discrepancy, -3);
+}
+
+#[actix_web::test]
+async fn stock_movements_are_paginated_newest_first(){
+    let app = TestApp::spawn_app().await;
+
+    let admin_token = app.login_admin().await;
+    let variant = post_item(&app, &admin_token, "LEDGER-1", 10).await;
+
+    for quantity in 1..=3 {
+        app.post_stock_movement(
+            variant.item_id,
+            serde_json::json!({ "variant_id": variant.variant_id, "quantity": quantity, "reason": "restock" }),
+            &admin_token
+        ).await;
+    }
+
+    let first_page = app.get_stock_movements(variant.item_id, 2, None, &admin_token)
+        .await
+        .json::<Page<StockMovement>>()
+        .await
+        .unwrap();
+    assert_eq!(first_page.total, 4);
+    let quantities: Vec<i32> = first_page.items.iter().map(|movement| movement.quantity).collect();
+    assert_eq!(quantities, vec![3, 2]);
+
+    let second_page = app.get_stock_movements(variant.item_id, 2, first_page.next_cursor.as_deref(), &admin_token)
+        .await
+        .json::<Page<StockMovement>>()
+        .await
+        .unwrap();
+    let quantities: Vec<i32> = second_page.items.iter().map(|movement| movement.quantity).collect();
+    assert_eq!(quantities, vec![1, 10]);
+    assert!(second_page.next_cursor.is_none());
+}
@@ -0,0 +1,199 @@
+use ecommerce::{db_interaction::{OrderWithItems, PlacedOrder, RefundWithLines}, models::TaxRate, pagination::Page, routes::checkout::payment::CheckoutPaymentResponse};
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn post_order(app: &TestApp, body: serde_json::Value, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+async fn set_rate(app: &TestApp, body: serde_json::Value, access_token: &String) -> TaxRate {
+    let response = app.put_tax_rate(body, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<TaxRate>().await.unwrap()
+}
+
+#[actix_web::test]
+async fn tax_rates_are_validated_and_replaced(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let test_cases = vec![
+        (serde_json::json!({ "country": "IND", "tax_class": "standard", "rate_basis_points": 1800 }), 400, "three letter country"),
+        (serde_json::json!({ "country": "IN", "region": "KA-1", "tax_class": "standard", "rate_basis_points": 1800 }), 400, "invalid region"),
+        (serde_json::json!({ "country": "IN", "tax_class": "food and drink", "rate_basis_points": 500 }), 400, "spaced tax class"),
+        (serde_json::json!({ "country": "IN", "tax_class": "standard", "rate_basis_points": 10001 }), 400, "rate over 100%"),
+        (serde_json::json!({ "country": "IN", "tax_class": "standard", "rate_basis_points": -1 }), 400, "negative rate")
+    ];
+
+    for (body, status, description) in test_cases {
+        let response = app.put_tax_rate(body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), status, "Tax rate wasn't rejected for {}", description);
+    }
+
+    let rate = set_rate(&app, serde_json::json!({ "country": "in", "tax_class": "Standard", "rate_basis_points": 1800 }), &admin_token).await;
+    assert_eq!(rate.country, "IN");
+    assert_eq!(rate.tax_class, "standard");
+
+    // Same destination and class replaces the rate
+    let replaced = set_rate(&app, serde_json::json!({ "country": "IN", "tax_class": "standard", "rate_basis_points": 1200 }), &admin_token).await;
+    assert_eq!(replaced.tax_rate_id, rate.tax_rate_id);
+    assert_eq!(replaced.rate_basis_points, 1200);
+
+    set_rate(&app, serde_json::json!({ "country": "IN", "region": "KA", "tax_class": "standard", "rate_basis_points": 1000 }), &admin_token).await;
+    set_rate(&app, serde_json::json!({ "country": "IN", "region": "KA", "tax_class": "books", "rate_basis_points": 500 }), &admin_token).await;
+
+    let first_page = app.get_tax_rates(2, None, &admin_token)
+        .await
+        .json::<Page<TaxRate>>()
+        .await
+        .unwrap();
+    assert_eq!(first_page.total, 3);
+    let rates: Vec<(Option<String>, String)> = first_page.items.iter().map(|rate| (rate.region.clone(), rate.tax_class.clone())).collect();
+    assert_eq!(rates, vec![(None, "standard".to_string()), (Some("KA".to_string()), "books".to_string())]);
+
+    let second_page = app.get_tax_rates(2, first_page.next_cursor.as_deref(), &admin_token)
+        .await
+        .json::<Page<TaxRate>>()
+        .await
+        .unwrap();
+    let rates: Vec<(Option<String>, String)> = second_page.items.iter().map(|rate| (rate.region.clone(), rate.tax_class.clone())).collect();
+    assert_eq!(rates, vec![(Some("KA".to_string()), "standard".to_string())]);
+    assert!(second_page.next_cursor.is_none());
+
+    let response = app.delete_tax_rate(rate.tax_rate_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_tax_rate(rate.tax_rate_id, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.put_item_tax_class(Uuid::new_v4(), "books", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.get_tax_rates(2, None, &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn order_is_taxed_by_destination_and_class(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let lamp = app.insert_inventory_item("Taxed lamp", 20, 10.0);
+    let book = app.insert_inventory_item("Taxed book", 20, 4.0);
+    let response = app.put_item_tax_class(book.item_id, "books", &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_rate(&app, serde_json::json!({ "country": "IN", "tax_class": "standard", "rate_basis_points": 1800 }), &admin_token).await;
+    set_rate(&app, serde_json::json!({ "country": "IN", "region": "KA", "tax_class": "standard", "rate_basis_points": 1000 }), &admin_token).await;
+    set_rate(&app, serde_json::json!({ "country": "IN", "tax_class": "books", "rate_basis_points": 500 }), &admin_token).await;
+
+    let items = serde_json::json!([
+        { "variant_id": lamp.variant_id, "amount": 2 },
+        { "variant_id": book.variant_id, "amount": 1 }
+    ]);
+
+    // Region rate takes precedence, classes without a region rate fall back to the country's
+    let response = post_order(&app, serde_json::json!({ "items": items, "address": { "country": "in", "region": "ka" } }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.tax_cents, 220);
+    assert_eq!(placed.pricing.total_cents, 2620);
+
+    let order = latest_order(&app, &user_token).await;
+    let lamp_line = order.items.iter().find(|item| item.variant_id == lamp.variant_id).unwrap();
+    assert_eq!(lamp_line.tax_rate_basis_points, 1000);
+    assert_eq!(lamp_line.tax_cents, 200);
+    let book_line = order.items.iter().find(|item| item.variant_id == book.variant_id).unwrap();
+    assert_eq!(book_line.tax_rate_basis_points, 500);
+    assert_eq!(book_line.tax_cents, 20);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    assert_eq!(intent.payment.amount_cents, 2620);
+
+    let response = post_order(&app, serde_json::json!({ "items": items, "address": { "country": "IN", "region": "MH" } }), &user_token).await;
+    assert_eq!(response.json::<PlacedOrder>().await.unwrap().pricing.tax_cents, 380);
+
+    // Destinations without rates and orders without an address aren't taxed
+    let response = post_order(&app, serde_json::json!({ "items": items, "address": { "country": "US" } }), &user_token).await;
+    assert_eq!(response.json::<PlacedOrder>().await.unwrap().pricing.tax_cents, 0);
+
+    let response = post_order(&app, serde_json::json!({ "items": items }), &user_token).await;
+    assert_eq!(response.json::<PlacedOrder>().await.unwrap().pricing.tax_cents, 0);
+
+    let response = post_order(&app, serde_json::json!({ "items": items, "address": { "country": "India" } }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn tax_is_charged_after_discount_and_refunded(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_api)
+        .await;
+
+    set_rate(&app, serde_json::json!({ "country": "DE", "tax_class": "standard", "rate_basis_points": 1000 }), &admin_token).await;
+    let response = app.post_coupon(serde_json::json!({ "code": "TWOOFF", "discount_type": "fixed", "discount_value": 200 }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let variant = app.insert_inventory_item("Taxed discounted item", 10, 10.0);
+    let response = post_order(&app, serde_json::json!({
+        "items": [{ "variant_id": variant.variant_id, "amount": 2 }],
+        "coupon_code": "TWOOFF",
+        "address": { "country": "DE" }
+    }), &user_token).await;
+    let placed = response.json::<PlacedOrder>().await.unwrap();
+    assert_eq!(placed.pricing.coupon_discount_cents, 200);
+    assert_eq!(placed.pricing.tax_cents, 180);
+    assert_eq!(placed.pricing.total_cents, 1980);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.items[0].discount_cents, 200);
+
+    let intent = app.post_checkout_payment(order.order_id, &user_token)
+        .await
+        .json::<CheckoutPaymentResponse>()
+        .await
+        .unwrap();
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": "payment.captured",
+        "provider_reference": intent.payment.provider_reference
+    }))
+    .unwrap();
+    let response = app.post_payment_webhook(&body, &app.payment_provider.sign(&body)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A unit gives back its share of the discounted price along with its tax
+    let response = app.post_refund(order.order_id, serde_json::json!({
+        "lines": [{ "order_item_id": order.items[0].order_item_id, "quantity": 1 }]
+    }), &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<RefundWithLines>().await.unwrap().refund.amount_cents, 990);
+}
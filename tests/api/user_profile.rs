@@ -1,7 +1,7 @@
 use ecommerce::models::UserProfileInfo;
 use wiremock::{matchers::{header_exists, path}, Mock, ResponseTemplate};
 
-use crate::{helpers::{LoginResponse, TestApp}, registration::ReceiveEmailRequest};
+use crate::helpers::{create_user_and_login, LoginResponse, TestApp};
 
 #[actix_web::test]
 async fn get_profile_without_logged_in_user(){
@@ -27,7 +27,7 @@ async fn get_profile_with_logged_in_user(){
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -42,8 +42,9 @@ async fn get_profile_with_logged_in_user(){
 
     assert_eq!(response.status().as_u16(), 200);
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -98,7 +99,7 @@ async fn post_profile_with_logged_in_user(){
         "confirm_password" : "testpassword"
     });
 
-    let guard = Mock::given(path("/email"))
+    let _guard = Mock::given(path("/email"))
         .and(header_exists("X-Postmark-Server-Token"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
@@ -112,8 +113,9 @@ async fn post_profile_with_logged_in_user(){
                     .expect("Failed to send request to register endpoint");
 
 
-    let requests = guard.received_requests().await;
-    let body_json: ReceiveEmailRequest = requests[0].body_json().unwrap();
+    let body_json = app.wait_for_emails(1, |email| email.subject == "Confirmation email")
+        .await
+        .remove(0);
 
     let link = app.get_confirmation_link(&body_json.text_body);
     
@@ -165,6 +167,58 @@ async fn post_profile_with_logged_in_user(){
 
     let body: UserProfileInfo = response.json().await.unwrap();
 
-    assert_eq!(body.phone_number, Some("8927401349".to_string()));
+    assert_eq!(body.phone_number, Some("+918927401349".to_string()));
+    assert_eq!(body.phone_country, Some("IN".to_string()));
     assert_eq!(body.address, Some("test, address, 45585, India".to_string()))
 }
+
+#[actix_web::test]
+async fn post_profile_normalizes_international_phone_number(){
+    let app = TestApp::spawn_app().await;
+    let access_token = create_user_and_login(&app).await;
+
+    let profile_body = serde_json::json!({
+        "phone_number": "+1 (201) 555-0123",
+    });
+
+    let post_profile_response = app.api_client.post(format!("http://{}:{}/user/profile", app.host, app.port))
+        .bearer_auth(&access_token)
+        .form(&profile_body)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(post_profile_response.status().as_u16(), 200);
+
+    let body: UserProfileInfo = app.api_client.get(format!("http://{}:{}/user/profile", app.host, app.port))
+                    .bearer_auth(&access_token)
+                    .send()
+                    .await
+                    .expect("Failed to send request to user profile endpoint")
+                    .json()
+                    .await
+                    .unwrap();
+
+    assert_eq!(body.phone_number, Some("+12015550123".to_string()));
+    assert_eq!(body.phone_country, Some("US".to_string()));
+}
+
+#[actix_web::test]
+async fn post_profile_rejects_invalid_phone_number(){
+    let app = TestApp::spawn_app().await;
+    let access_token = create_user_and_login(&app).await;
+
+    let profile_body = serde_json::json!({
+        "phone_number": "12345",
+    });
+
+    let post_profile_response = app.api_client.post(format!("http://{}:{}/user/profile", app.host, app.port))
+        .bearer_auth(&access_token)
+        .form(&profile_body)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(post_profile_response.status().as_u16(), 500);
+    assert!(post_profile_response.text().await.unwrap().contains("not a valid phone number"));
+}
@@ -0,0 +1,220 @@
+use ecommerce::{db_interaction::{OrderWithItems, WarehouseStockLevel}, domain::allocation::AllocationStrategy, models::{ProductVariant, Warehouse}, pagination::Page};
+use uuid::Uuid;
+
+use crate::helpers::{create_user_and_login, TestApp};
+
+async fn create_warehouse(app: &TestApp, body: serde_json::Value, access_token: &String) -> Warehouse {
+    let response = app.post_warehouse(body, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Warehouse>().await.unwrap()
+}
+
+async fn restock(app: &TestApp, variant: &ProductVariant, warehouse_id: Uuid, quantity: i32, access_token: &String) {
+    let response = app.post_stock_movement(variant.item_id, serde_json::json!({
+        "variant_id": variant.variant_id,
+        "quantity": quantity,
+        "reason": "restock",
+        "warehouse_id": warehouse_id
+    }), access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Stock of first variant of item keyed by warehouse code
+async fn stock_by_warehouse(app: &TestApp, item_id: Uuid, access_token: &String) -> Vec<(String, i32)> {
+    let response = app.get_warehouse_stock(item_id, access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<Vec<WarehouseStockLevel>>()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|level| (level.warehouse_code, level.amount))
+        .collect()
+}
+
+async fn latest_order(app: &TestApp, access_token: &String) -> OrderWithItems {
+    app.get_orders(1, None, access_token)
+        .await
+        .json::<Page<OrderWithItems>>()
+        .await
+        .unwrap()
+        .items
+        .remove(0)
+}
+
+async fn post_order(app: &TestApp, body: serde_json::Value, access_token: &String) -> reqwest::Response {
+    app.api_client.post(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn admin_can_create_and_list_warehouses(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let east = create_warehouse(&app, serde_json::json!({
+        "code": "EAST-1",
+        "name": "East warehouse",
+        "priority": 1,
+        "latitude": 22.57,
+        "longitude": 88.36
+    }), &admin_token).await;
+    assert!(!east.is_default);
+
+    create_warehouse(&app, serde_json::json!({
+        "code": "WEST-1",
+        "name": "West warehouse",
+        "priority": 1
+    }), &admin_token).await;
+
+    let response = app.get_warehouses(2, None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let first_page = response.json::<Page<Warehouse>>().await.unwrap();
+    assert_eq!(first_page.total, 3);
+    let codes: Vec<String> = first_page.items.into_iter().map(|warehouse| warehouse.code).collect();
+    assert_eq!(codes, vec!["MAIN".to_string(), "EAST-1".to_string()]);
+
+    let second_page = app.get_warehouses(2, first_page.next_cursor.as_deref(), &admin_token)
+        .await
+        .json::<Page<Warehouse>>()
+        .await
+        .unwrap();
+    let codes: Vec<String> = second_page.items.into_iter().map(|warehouse| warehouse.code).collect();
+    assert_eq!(codes, vec!["WEST-1".to_string()]);
+    assert!(second_page.next_cursor.is_none());
+
+    let response = app.get_warehouses(0, None, &admin_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn invalid_warehouses_are_rejected(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+
+    let test_cases = vec![
+        (serde_json::json!({ "code": "MAIN", "name": "Duplicate" }), "duplicate code"),
+        (serde_json::json!({ "code": "east", "name": "Lowercase" }), "lowercase code"),
+        (serde_json::json!({ "code": "EAST", "name": " " }), "empty name"),
+        (serde_json::json!({ "code": "EAST", "name": "East", "latitude": 95.0, "longitude": 10.0 }), "invalid location"),
+        (serde_json::json!({ "code": "EAST", "name": "East", "latitude": 10.0 }), "partial location")
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_warehouse(body, &admin_token).await;
+        assert_eq!(response.status().as_u16(), 400, "Warehouse wasn't rejected for {}", description);
+    }
+
+    let user_token = create_user_and_login(&app).await;
+    let response = app.post_warehouse(serde_json::json!({ "code": "EAST", "name": "East" }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn orders_prefer_single_warehouse_and_split_otherwise(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Warehoused item", 2, 10.0);
+    let east = create_warehouse(&app, serde_json::json!({ "code": "EAST", "name": "East", "priority": 1 }), &admin_token).await;
+    restock(&app, &variant, east.warehouse_id, 5, &admin_token).await;
+
+    // Only EAST holds the whole order
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 4 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    let picks: Vec<(String, i32)> = order.items[0].allocations.iter()
+        .map(|pick| (pick.warehouse_code.clone(), pick.quantity))
+        .collect();
+    assert_eq!(picks, vec![("EAST".to_string(), 4)]);
+    assert_eq!(
+        stock_by_warehouse(&app, variant.item_id, &admin_token).await,
+        vec![("MAIN".to_string(), 2), ("EAST".to_string(), 1)]
+    );
+
+    // No warehouse holds 3 so the line is split in priority order
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 3 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    let picks: Vec<(String, i32)> = order.items[0].allocations.iter()
+        .map(|pick| (pick.warehouse_code.clone(), pick.quantity))
+        .collect();
+    assert_eq!(picks, vec![("MAIN".to_string(), 2), ("EAST".to_string(), 1)]);
+    assert_eq!(
+        stock_by_warehouse(&app, variant.item_id, &admin_token).await,
+        vec![("MAIN".to_string(), 0), ("EAST".to_string(), 0)]
+    );
+}
+
+#[actix_web::test]
+async fn nearest_strategy_ships_from_closest_warehouse(){
+    let app = TestApp::spawn_app_with(|settings| {
+        settings.fulfillment.allocation_strategy = AllocationStrategy::Nearest;
+    }).await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Nearby item", 0, 10.0);
+    let delhi = create_warehouse(&app, serde_json::json!({
+        "code": "DEL", "name": "Delhi", "latitude": 28.61, "longitude": 77.21
+    }), &admin_token).await;
+    let bengaluru = create_warehouse(&app, serde_json::json!({
+        "code": "BLR", "name": "Bengaluru", "priority": 5, "latitude": 12.97, "longitude": 77.59
+    }), &admin_token).await;
+    restock(&app, &variant, delhi.warehouse_id, 5, &admin_token).await;
+    restock(&app, &variant, bengaluru.warehouse_id, 5, &admin_token).await;
+
+    // Chennai is closest to Bengaluru even though Delhi has higher priority
+    let response = post_order(&app, serde_json::json!({
+        "items": [{ "variant_id": variant.variant_id, "amount": 1 }],
+        "ship_to": { "latitude": 13.08, "longitude": 80.27 }
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let order = latest_order(&app, &user_token).await;
+    assert_eq!(order.items[0].allocations.len(), 1);
+    assert_eq!(order.items[0].allocations[0].warehouse_id, bengaluru.warehouse_id);
+
+    let response = post_order(&app, serde_json::json!({
+        "items": [{ "variant_id": variant.variant_id, "amount": 1 }],
+        "ship_to": { "latitude": 100.0, "longitude": 80.27 }
+    }), &user_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn cancelled_order_restocks_allocated_warehouse(){
+    let app = TestApp::spawn_app().await;
+    let admin_token = app.login_admin().await;
+    let user_token = create_user_and_login(&app).await;
+
+    let variant = app.insert_inventory_item("Cancelled item", 0, 10.0);
+    let east = create_warehouse(&app, serde_json::json!({ "code": "EAST", "name": "East" }), &admin_token).await;
+    restock(&app, &variant, east.warehouse_id, 3, &admin_token).await;
+
+    let response = post_order(&app, serde_json::json!([{ "variant_id": variant.variant_id, "amount": 3 }]), &user_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let order = latest_order(&app, &user_token).await;
+
+    let response = app.api_client.delete(format!("http://{}:{}/user/order", app.host, app.port))
+        .bearer_auth(&user_token)
+        .json(&serde_json::json!({ "order_id": order.order_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        stock_by_warehouse(&app, variant.item_id, &admin_token).await,
+        vec![("EAST".to_string(), 3), ("MAIN".to_string(), 0)]
+    );
+}